@@ -0,0 +1,449 @@
+use {
+    crate::{condop, AwsPrincipal, ConditionOp, ConditionVariant, Effect, Policy, Principal, Statement},
+    serde::Serialize,
+    std::fmt::{Display, Formatter, Result as FmtResult},
+};
+
+/// Global condition keys that narrow who can use a grant to an external principal.
+const RESTRICTING_KEYS: &[&str] = &[
+    "aws:principalaccount",
+    "aws:principalarn",
+    "aws:principalorgid",
+    "aws:principalorgpaths",
+    "aws:principalservicename",
+    "aws:sourceaccount",
+    "aws:sourcearn",
+    "aws:sourceip",
+    "aws:sourceorgid",
+    "aws:sourceorgpaths",
+    "aws:sourcevpc",
+    "aws:sourcevpce",
+    "aws:userid",
+    "aws:username",
+    "kms:calleraccount",
+    "sts:externalid",
+];
+
+/// The kind of external access granted by a resource policy.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
+pub enum ExternalAccessKind {
+    /// Access is granted to any principal (`"*"`, `{"AWS": "*"}`, or an Allow statement with `NotPrincipal`).
+    Public,
+
+    /// Access is granted to a principal in another AWS account.
+    CrossAccount,
+
+    /// Access is granted to users authenticated by an identity provider.
+    Federated,
+
+    /// Access is granted to an AWS service.
+    Service,
+
+    /// Access is granted to a canonical user, which may belong to another account.
+    CanonicalUser,
+}
+
+impl Display for ExternalAccessKind {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        match self {
+            Self::Public => f.write_str("Public"),
+            Self::CrossAccount => f.write_str("CrossAccount"),
+            Self::Federated => f.write_str("Federated"),
+            Self::Service => f.write_str("Service"),
+            Self::CanonicalUser => f.write_str("CanonicalUser"),
+        }
+    }
+}
+
+/// A condition clause that narrows an external grant, such as `StringEquals` on `aws:PrincipalOrgID`.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct AccessRestriction {
+    operator: ConditionOp,
+    key: String,
+    values: Vec<String>,
+}
+
+impl AccessRestriction {
+    /// Returns the condition operator.
+    #[inline]
+    pub fn operator(&self) -> ConditionOp {
+        self.operator
+    }
+
+    /// Returns the condition key, as written in the policy.
+    #[inline]
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    /// Returns the values the condition key is compared against.
+    #[inline]
+    pub fn values(&self) -> &[String] {
+        &self.values
+    }
+}
+
+impl Display for AccessRestriction {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        write!(f, "{} {} {}", self.operator, self.key, self.values.join(", "))
+    }
+}
+
+/// A grant of access to a principal outside the account that owns the resource.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct AccessFinding {
+    kind: ExternalAccessKind,
+    principal: String,
+    statement_index: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sid: Option<String>,
+    restrictions: Vec<AccessRestriction>,
+}
+
+impl AccessFinding {
+    /// Returns the kind of external access.
+    #[inline]
+    pub fn kind(&self) -> ExternalAccessKind {
+        self.kind
+    }
+
+    /// Returns the principal the access is granted to: an account id, ARN, identity provider, service, or canonical
+    /// user id. Public grants return `*`, or `* except ...` for `NotPrincipal`.
+    #[inline]
+    pub fn principal(&self) -> &str {
+        &self.principal
+    }
+
+    /// Returns the index of the granting statement within the policy.
+    #[inline]
+    pub fn statement_index(&self) -> usize {
+        self.statement_index
+    }
+
+    /// Returns the statement id of the granting statement, if any.
+    #[inline]
+    pub fn sid(&self) -> Option<&str> {
+        self.sid.as_deref()
+    }
+
+    /// Returns the conditions on the granting statement that narrow who can use it.
+    #[inline]
+    pub fn restrictions(&self) -> &[AccessRestriction] {
+        &self.restrictions
+    }
+
+    /// Indicates whether the grant is narrowed by at least one condition.
+    #[inline]
+    pub fn is_restricted(&self) -> bool {
+        !self.restrictions.is_empty()
+    }
+}
+
+impl Display for AccessFinding {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        write!(f, "{} access for {} (statement {}", self.kind, self.principal, self.statement_index)?;
+        if let Some(sid) = &self.sid {
+            write!(f, " {sid:?}")?;
+        }
+        f.write_str(")")?;
+
+        for (i, restriction) in self.restrictions.iter().enumerate() {
+            f.write_str(if i == 0 {
+                " when "
+            } else {
+                "; "
+            })?;
+            write!(f, "{restriction}")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// The external access granted by a resource-based policy, in the style of IAM Access Analyzer.
+///
+/// Each Allow statement with a `Principal` or `NotPrincipal` element is examined. A finding is reported for each
+/// principal that is public, in an account other than the owning account, federated, a service, or a canonical user.
+/// Conditions on keys such as `aws:SourceVpc`, `aws:PrincipalOrgID`, and `aws:SourceAccount` are reported as
+/// restrictions; negated and `IfExists` operators, and values of `*`, do not restrict access and are ignored.
+///
+/// Deny statements are not taken into account, so a finding may be narrowed further by them.
+///
+/// # Example
+///
+/// ```
+/// # use scratchstack_aspen::{ExternalAccess, ExternalAccessKind, Policy};
+/// # use std::str::FromStr;
+/// let policy = Policy::from_str(r#"{"Version": "2012-10-17", "Statement": [
+///     {"Effect": "Allow", "Principal": "*", "Action": "s3:GetObject", "Resource": "arn:aws:s3:::bucket/*",
+///      "Condition": {"StringEquals": {"aws:PrincipalOrgID": "o-1234567890"}}},
+///     {"Effect": "Allow", "Principal": {"AWS": "210987654321"}, "Action": "s3:ListBucket",
+///      "Resource": "arn:aws:s3:::bucket"}]}"#).unwrap();
+///
+/// let access = ExternalAccess::analyze(&policy, "123456789012");
+/// assert!(!access.is_public());
+/// assert_eq!(access.findings()[0].restrictions()[0].key(), "aws:PrincipalOrgID");
+/// assert_eq!(access.findings()[1].kind(), ExternalAccessKind::CrossAccount);
+/// ```
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ExternalAccess {
+    findings: Vec<AccessFinding>,
+}
+
+impl ExternalAccess {
+    /// Analyzes a resource-based policy attached to a resource owned by `account_id`.
+    pub fn analyze(policy: &Policy, account_id: &str) -> Self {
+        let mut findings = Vec::new();
+
+        for (statement_index, statement) in policy.statement().iter().enumerate() {
+            if statement.effect() != &Effect::Allow {
+                continue;
+            }
+
+            let grantees = grantees(statement, account_id);
+            if grantees.is_empty() {
+                continue;
+            }
+
+            let restrictions = restrictions(statement);
+            for (kind, principal) in grantees {
+                // Federated grants are also narrowed by the identity provider's own keys, e.g. `accounts.google.com:aud`.
+                let provider_prefix = match kind {
+                    ExternalAccessKind::Federated => Some(format!("{}:", principal.to_lowercase())),
+                    _ => None,
+                };
+                let restrictions = restrictions
+                    .iter()
+                    .filter(|r| {
+                        let key = r.key.to_lowercase();
+                        RESTRICTING_KEYS.contains(&key.as_str())
+                            || provider_prefix.as_ref().map(|prefix| key.starts_with(prefix)).unwrap_or(false)
+                    })
+                    .cloned()
+                    .collect();
+
+                findings.push(AccessFinding {
+                    kind,
+                    principal,
+                    statement_index,
+                    sid: statement.sid().map(str::to_string),
+                    restrictions,
+                });
+            }
+        }
+
+        Self {
+            findings,
+        }
+    }
+
+    /// Returns every finding, in statement order.
+    #[inline]
+    pub fn findings(&self) -> &[AccessFinding] {
+        &self.findings
+    }
+
+    /// Returns the findings of the given kind.
+    pub fn of_kind(&self, kind: ExternalAccessKind) -> impl Iterator<Item = &AccessFinding> {
+        self.findings.iter().filter(move |f| f.kind == kind)
+    }
+
+    /// Indicates whether any principal can access the resource without a restricting condition.
+    pub fn is_public(&self) -> bool {
+        self.of_kind(ExternalAccessKind::Public).any(|f| !f.is_restricted())
+    }
+
+    /// Indicates whether the policy grants no external access.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.findings.is_empty()
+    }
+}
+
+impl Display for ExternalAccess {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        for finding in self.findings.iter() {
+            writeln!(f, "{finding}")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Returns the external principals a statement grants access to.
+fn grantees(statement: &Statement, account_id: &str) -> Vec<(ExternalAccessKind, String)> {
+    if let Some(not_principal) = statement.not_principal() {
+        let excluded = match not_principal {
+            Principal::Any => return Vec::new(),
+            Principal::Specified(sp) => sp.to_string(),
+        };
+        return vec![(ExternalAccessKind::Public, format!("* except {excluded}"))];
+    }
+
+    let specified = match statement.principal() {
+        None => return Vec::new(),
+        Some(Principal::Any) => return vec![(ExternalAccessKind::Public, "*".to_string())],
+        Some(Principal::Specified(sp)) => sp,
+    };
+
+    let mut result = Vec::new();
+
+    for aws in specified.aws().map(|l| l.as_slice()).unwrap_or_default() {
+        match aws {
+            AwsPrincipal::Any => result.push((ExternalAccessKind::Public, "*".to_string())),
+            AwsPrincipal::Account(account) if account != account_id => {
+                result.push((ExternalAccessKind::CrossAccount, account.clone()))
+            }
+            AwsPrincipal::Arn(arn) if arn.account_id() != account_id => {
+                result.push((ExternalAccessKind::CrossAccount, arn.to_string()))
+            }
+            _ => (),
+        }
+    }
+
+    let others = [
+        (ExternalAccessKind::Federated, specified.federated()),
+        (ExternalAccessKind::Service, specified.service()),
+        (ExternalAccessKind::CanonicalUser, specified.canonical_user()),
+    ];
+
+    for (kind, list) in others {
+        for principal in list.map(|l| l.as_slice()).unwrap_or_default() {
+            result.push((kind, principal.clone()));
+        }
+    }
+
+    result
+}
+
+/// Returns the condition clauses of a statement that can narrow access: non-negated, non-`IfExists` operators
+/// (other than `Null` and `Bool`) whose values are not all `*`.
+fn restrictions(statement: &Statement) -> Vec<AccessRestriction> {
+    let mut result = Vec::new();
+    let condition = match statement.condition() {
+        Some(condition) => condition,
+        None => return result,
+    };
+
+    for (op, map) in condition.iter() {
+        let variant = match op {
+            ConditionOp::Arn(_, variant)
+            | ConditionOp::Date(_, variant)
+            | ConditionOp::IpAddress(variant)
+            | ConditionOp::Numeric(_, variant)
+            | ConditionOp::String(_, variant)
+            | ConditionOp::Binary(variant) => *variant,
+            ConditionOp::Bool(_) | ConditionOp::Null => continue,
+        };
+
+        if variant != ConditionVariant::None {
+            continue;
+        }
+
+        let wildcard_ok = *op == condop::StringLike || *op == condop::ArnLike;
+        for (key, values) in map.iter() {
+            if wildcard_ok && values.iter().all(|v| v == "*") {
+                continue;
+            }
+
+            result.push(AccessRestriction {
+                operator: *op,
+                key: key.clone(),
+                values: values.iter().cloned().collect(),
+            });
+        }
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        crate::{condop, ExternalAccess, ExternalAccessKind, Policy},
+        pretty_assertions::assert_eq,
+        std::str::FromStr,
+    };
+
+    #[test_log::test]
+    fn test_principal_kinds() {
+        let policy = Policy::from_str(
+            r#"{"Version": "2012-10-17", "Statement": [
+                {"Sid": "Own", "Effect": "Allow", "Principal": {"AWS": ["123456789012", "arn:aws:iam::123456789012:role/r"]},
+                 "Action": "sqs:*", "Resource": "*"},
+                {"Sid": "Other", "Effect": "Allow", "Principal": {"AWS": "arn:aws:iam::210987654321:role/r"},
+                 "Action": "sqs:SendMessage", "Resource": "*"},
+                {"Effect": "Allow", "Principal": {"Service": "sns.amazonaws.com"}, "Action": "sqs:SendMessage",
+                 "Resource": "*", "Condition": {"ArnEquals": {"aws:SourceArn": "arn:aws:sns:us-east-1:123456789012:t"}}},
+                {"Effect": "Allow", "Principal": {"Federated": "cognito-identity.amazonaws.com"}, "Action": "sqs:*",
+                 "Resource": "*", "Condition": {"StringEquals": {"cognito-identity.amazonaws.com:aud": "pool"}}},
+                {"Effect": "Allow", "Principal": {"CanonicalUser": "abcdef"}, "Action": "sqs:*", "Resource": "*"},
+                {"Effect": "Deny", "Principal": "*", "Action": "sqs:*", "Resource": "*"}
+            ]}"#,
+        )
+        .unwrap();
+
+        let access = ExternalAccess::analyze(&policy, "123456789012");
+        let kinds: Vec<_> = access.findings().iter().map(|f| (f.kind(), f.statement_index())).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                (ExternalAccessKind::CrossAccount, 1),
+                (ExternalAccessKind::Service, 2),
+                (ExternalAccessKind::Federated, 3),
+                (ExternalAccessKind::CanonicalUser, 4),
+            ]
+        );
+        assert!(!access.is_public());
+
+        let findings = access.findings();
+        assert_eq!(findings[0].sid(), Some("Other"));
+        assert_eq!(findings[0].principal(), "arn:aws:iam::210987654321:role/r");
+        assert!(!findings[0].is_restricted());
+        assert_eq!(findings[1].restrictions()[0].operator(), condop::ArnEquals);
+        assert_eq!(findings[2].restrictions()[0].key(), "cognito-identity.amazonaws.com:aud");
+        assert_eq!(
+            findings[1].to_string(),
+            "Service access for sns.amazonaws.com (statement 2) when ArnEquals aws:SourceArn arn:aws:sns:us-east-1:123456789012:t"
+        );
+    }
+
+    #[test_log::test]
+    fn test_public_restrictions() {
+        let policy = Policy::from_str(
+            r#"{"Version": "2012-10-17", "Statement": [
+                {"Effect": "Allow", "Principal": {"AWS": "*"}, "Action": "s3:GetObject", "Resource": "*",
+                 "Condition": {
+                    "StringNotEquals": {"aws:SourceVpc": "vpc-1"},
+                    "StringEqualsIfExists": {"aws:PrincipalOrgID": "o-1"},
+                    "StringLike": {"aws:PrincipalArn": "*"},
+                    "Bool": {"aws:SecureTransport": "true"}
+                 }},
+                {"Effect": "Allow", "NotPrincipal": {"AWS": "arn:aws:iam::123456789012:root"}, "Action": "s3:*",
+                 "Resource": "*", "Condition": {"StringEquals": {"aws:SourceVpce": ["vpce-1", "vpce-2"]}}}
+            ]}"#,
+        )
+        .unwrap();
+
+        let access = ExternalAccess::analyze(&policy, "123456789012");
+        assert_eq!(access.findings().len(), 2);
+        assert!(access.of_kind(ExternalAccessKind::Public).count() == 2);
+        assert!(!access.findings()[0].is_restricted());
+        assert!(access.is_public());
+
+        let not_principal = &access.findings()[1];
+        assert!(not_principal.principal().starts_with("* except "));
+        assert_eq!(not_principal.restrictions()[0].values(), &["vpce-1".to_string(), "vpce-2".to_string()]);
+
+        // Identity policies have no principals, so there's nothing to report.
+        let identity =
+            Policy::from_str(r#"{"Statement": {"Effect": "Allow", "Action": "s3:*", "Resource": "*"}}"#).unwrap();
+        let empty = ExternalAccess::analyze(&identity, "123456789012");
+        assert!(empty.is_empty());
+        assert!(!empty.is_public());
+        assert_eq!(empty.to_string(), "");
+    }
+}
@@ -0,0 +1,214 @@
+use {
+    super::space::{describe_request, RequestSpace},
+    crate::{AspenError, Context, Decision},
+    std::fmt::{Display, Formatter, Result as FmtResult},
+};
+
+/// A request that refutes an equivalence or subset check.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Counterexample {
+    context: Context,
+    left: Decision,
+    right: Decision,
+}
+
+impl Counterexample {
+    /// Returns the request context.
+    #[inline]
+    pub fn context(&self) -> &Context {
+        &self.context
+    }
+
+    /// Returns the decision of the policy (or policy set) the check was invoked on.
+    #[inline]
+    pub fn left(&self) -> Decision {
+        self.left
+    }
+
+    /// Returns the decision of the policy (or policy set) passed as the argument to the check.
+    #[inline]
+    pub fn right(&self) -> Decision {
+        self.right
+    }
+}
+
+impl Display for Counterexample {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        write!(f, "{}: {} vs. {}", describe_request(&self.context), self.left, self.right)
+    }
+}
+
+/// The outcome of an equivalence or subset check.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Verdict {
+    /// The relationship holds for every request.
+    Holds,
+
+    /// No counterexample was found, but some condition values had to be sampled, so the relationship is not proven.
+    Inconclusive,
+
+    /// The relationship does not hold; the counterexample demonstrates this.
+    Refuted(Counterexample),
+}
+
+impl Verdict {
+    /// Indicates whether the relationship was proven to hold.
+    #[inline]
+    pub fn holds(&self) -> bool {
+        matches!(self, Self::Holds)
+    }
+
+    /// If the relationship was refuted, returns the counterexample. Otherwise returns `None`.
+    #[inline]
+    pub fn counterexample(&self) -> Option<&Counterexample> {
+        match self {
+            Self::Refuted(counterexample) => Some(counterexample),
+            _ => None,
+        }
+    }
+}
+
+/// The relationship between two policies being checked.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) enum Relation {
+    /// Both allow exactly the same requests.
+    Equivalent,
+
+    /// Every request allowed by the left is allowed by the right.
+    Subset,
+}
+
+/// Checks the relationship between two evaluators over the given request space.
+pub(crate) fn check<L, R>(space: &RequestSpace, relation: Relation, left: L, right: R) -> Result<Verdict, AspenError>
+where
+    L: Fn(&Context) -> Result<Decision, AspenError>,
+    R: Fn(&Context) -> Result<Decision, AspenError>,
+{
+    for context in space.contexts() {
+        let left_decision = left(context)?;
+        let right_decision = right(context)?;
+        let left_allowed = left_decision == Decision::Allow;
+        let right_allowed = right_decision == Decision::Allow;
+
+        let refuted = match relation {
+            Relation::Equivalent => left_allowed != right_allowed,
+            Relation::Subset => left_allowed && !right_allowed,
+        };
+
+        if refuted {
+            return Ok(Verdict::Refuted(Counterexample {
+                context: context.clone(),
+                left: left_decision,
+                right: right_decision,
+            }));
+        }
+    }
+
+    if space.is_exact() {
+        Ok(Verdict::Holds)
+    } else {
+        Ok(Verdict::Inconclusive)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        crate::{Decision, Policy, PolicySet, PolicySource, Verdict},
+        pretty_assertions::assert_eq,
+        std::str::FromStr,
+    };
+
+    #[test_log::test]
+    fn test_policy_equivalence() {
+        let original = Policy::from_str(
+            r#"{"Version": "2012-10-17", "Statement": [
+                {"Effect": "Allow", "Action": ["s3:GetObject", "s3:GetObjectAcl"], "Resource": "arn:aws:s3:::b/*"},
+                {"Effect": "Allow", "Action": "s3:ListBucket", "Resource": "arn:aws:s3:::b"}
+            ]}"#,
+        )
+        .unwrap();
+        let refactored = Policy::from_str(
+            r#"{"Version": "2012-10-17", "Statement": [
+                {"Effect": "Allow", "Action": "s3:GetObject*", "Resource": "arn:aws:s3:::b/*"},
+                {"Effect": "Deny", "Action": "s3:GetObject?*", "NotResource": "arn:aws:s3:::nothing"},
+                {"Effect": "Allow", "Action": "s3:ListBucket", "Resource": "arn:aws:s3:::b"}
+            ]}"#,
+        )
+        .unwrap();
+
+        // s3:GetObjectTagging is allowed by the refactored policy's first statement.
+        let verdict = original.is_equivalent(&refactored).unwrap();
+        let counterexample = verdict.counterexample().unwrap();
+        assert!(counterexample.context().api().starts_with("GetObject"));
+        assert_eq!(counterexample.left(), Decision::DefaultDeny);
+        assert_eq!(counterexample.right(), Decision::Allow);
+        assert!(original.is_subset_of(&refactored).unwrap().holds());
+        assert!(counterexample.to_string().ends_with(": DefaultDeny vs. Allow"));
+
+        // Reordering statements that don't overlap is safe.
+        let reordered = Policy::from_str(
+            r#"{"Version": "2012-10-17", "Statement": [
+                {"Effect": "Allow", "Action": "s3:GetObjectAcl", "Resource": "arn:aws:s3:::b/*"},
+                {"Effect": "Allow", "Action": "s3:ListBucket", "Resource": "arn:aws:s3:::b"},
+                {"Effect": "Allow", "Action": "s3:GetObject", "Resource": "arn:aws:s3:::b/*"}
+            ]}"#,
+        )
+        .unwrap();
+        assert_eq!(original.is_equivalent(&reordered).unwrap(), Verdict::Holds);
+    }
+
+    #[test_log::test]
+    fn test_policy_set_subset_and_inconclusive() {
+        let boundary = Policy::from_str(
+            r#"{"Statement": {"Effect": "Allow", "Action": ["iam:Get*", "iam:List*"], "Resource": "*"}}"#,
+        )
+        .unwrap();
+        let candidate = Policy::from_str(
+            r#"{"Statement": {"Effect": "Allow", "Action": "iam:GetUser", "Resource": "arn:aws:iam::*:user/*"}}"#,
+        )
+        .unwrap();
+        let source = PolicySource::new_entity_inline("arn:aws:iam::123456789012:user/u", "AIDA", "p");
+
+        let candidate_set = PolicySet::from(vec![(source.clone(), candidate)]);
+        let boundary_set = PolicySet::from(vec![(source, boundary)]);
+        assert!(candidate_set.is_subset_of(&boundary_set).unwrap().holds());
+        let verdict = boundary_set.is_subset_of(&candidate_set).unwrap();
+        assert_eq!(verdict.counterexample().unwrap().left(), Decision::Allow);
+
+        // Case-insensitive comparisons are sampled, so the best we can say is that no counterexample was found.
+        let p1 = Policy::from_str(
+            r#"{"Statement": {"Effect": "Allow", "Action": "s3:GetObject", "Resource": "*",
+                "Condition": {"StringEqualsIgnoreCase": {"aws:username": "Alice"}}}}"#,
+        )
+        .unwrap();
+        assert_eq!(p1.is_equivalent(&p1).unwrap(), Verdict::Inconclusive);
+    }
+
+    #[test_log::test]
+    fn test_role_principals() {
+        let role = |name: &str| {
+            Policy::from_str(&format!(
+                r#"{{"Statement": {{"Effect": "Allow", "Action": "s3:GetObject", "Resource": "*",
+                    "Principal": {{"AWS": "arn:aws:iam::111111111111:role/{name}"}}}}}}"#
+            ))
+            .unwrap()
+        };
+
+        // Role ARNs match the role's sessions, so the policies differ on sessions of either role.
+        let verdict = role("A").is_equivalent(&role("B")).unwrap();
+        let counterexample = verdict.counterexample().unwrap();
+        assert!(counterexample.context().actor().to_string().contains(":assumed-role/"));
+        assert!(!role("A").is_subset_of(&role("B")).unwrap().holds());
+        assert_eq!(role("A").is_equivalent(&role("A")).unwrap(), Verdict::Holds);
+
+        // The account root matches sessions of every role in the account.
+        let root = Policy::from_str(
+            r#"{"Statement": {"Effect": "Allow", "Action": "s3:GetObject", "Resource": "*",
+                "Principal": {"AWS": "arn:aws:iam::111111111111:root"}}}"#,
+        )
+        .unwrap();
+        assert!(role("A").is_subset_of(&root).unwrap().holds());
+        assert!(!root.is_subset_of(&role("A")).unwrap().holds());
+    }
+}
@@ -0,0 +1,329 @@
+use {
+    super::space::{describe_request, RequestSpace},
+    crate::{AspenError, Context, Decision, Policy, PolicySet},
+    serde::Serialize,
+    std::fmt::{Display, Formatter, Result as FmtResult},
+};
+
+/// How access to a request changed between two versions of a policy.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, Serialize)]
+pub enum AccessChange {
+    /// The request was not allowed before the change and is allowed after it.
+    Gained,
+
+    /// The request was allowed before the change and is not allowed after it.
+    Lost,
+
+    /// The request is not allowed either before or after the change, but it switched between an explicit
+    /// [Decision::Deny] and a [Decision::DefaultDeny]. This matters when the policy is combined with others.
+    DenialChanged,
+}
+
+/// A request whose decision differs between two versions of a policy.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct DecisionChange {
+    context: Context,
+    before: Decision,
+    after: Decision,
+}
+
+impl DecisionChange {
+    /// Returns the request context. This is a representative of every request the policies cannot distinguish from
+    /// it.
+    #[inline]
+    pub fn context(&self) -> &Context {
+        &self.context
+    }
+
+    /// Returns the decision before the change.
+    #[inline]
+    pub fn before(&self) -> Decision {
+        self.before
+    }
+
+    /// Returns the decision after the change.
+    #[inline]
+    pub fn after(&self) -> Decision {
+        self.after
+    }
+
+    /// Returns how access to the request changed.
+    pub fn change(&self) -> AccessChange {
+        match (self.before, self.after) {
+            (Decision::Allow, _) => AccessChange::Lost,
+            (_, Decision::Allow) => AccessChange::Gained,
+            _ => AccessChange::DenialChanged,
+        }
+    }
+}
+
+impl Display for DecisionChange {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        let marker = match self.change() {
+            AccessChange::Gained => '+',
+            AccessChange::Lost => '-',
+            AccessChange::DenialChanged => '~',
+        };
+
+        write!(f, "{marker} {}: {} -> {}", describe_request(&self.context), self.before, self.after)
+    }
+}
+
+/// The semantic difference between two versions of a policy or policy set.
+///
+/// Rather than comparing the JSON text, the diff evaluates both versions against a request space derived from the
+/// glob patterns in their actions, resources, principals, and conditions. Each reported [DecisionChange] is a
+/// representative request for a class of requests whose decision changed.
+///
+/// Each request carries at most one resource and a single actor identity. Condition values are enumerated exactly
+/// where the operators allow it and sampled otherwise; [PolicyDiff::is_exact] reports whether any sampling was
+/// needed.
+///
+/// # Example
+///
+/// ```
+/// # use scratchstack_aspen::{AccessChange, Policy, PolicyDiff};
+/// # use std::str::FromStr;
+/// let before = Policy::from_str(r#"{"Version": "2012-10-17", "Statement": {
+///     "Effect": "Allow", "Action": "s3:GetObject", "Resource": "arn:aws:s3:::bucket/*"}}"#).unwrap();
+/// let after = Policy::from_str(r#"{"Version": "2012-10-17", "Statement": {
+///     "Effect": "Allow", "Action": "s3:Get*", "Resource": "arn:aws:s3:::bucket/public/*"}}"#).unwrap();
+///
+/// let diff = PolicyDiff::between_policies(&before, &after).unwrap();
+/// assert!(diff.is_exact());
+/// assert!(diff.gained().any(|c| c.context().api() != "GetObject"));
+/// assert!(diff.lost().all(|c| c.context().api() == "GetObject"));
+/// ```
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct PolicyDiff {
+    changes: Vec<DecisionChange>,
+    requests_evaluated: usize,
+    exact: bool,
+}
+
+impl PolicyDiff {
+    /// Computes the difference between two versions of a single policy.
+    ///
+    /// # Errors
+    ///
+    /// If either policy fails to evaluate (for example, because of a malformed variable reference), the error is
+    /// returned.
+    pub fn between_policies(before: &Policy, after: &Policy) -> Result<Self, AspenError> {
+        let space = RequestSpace::new([before, after]);
+        Self::compute(&space, |context| before.evaluate(context), |context| after.evaluate(context))
+    }
+
+    /// Computes the difference between two versions of a policy set.
+    ///
+    /// # Errors
+    ///
+    /// If either policy set fails to evaluate (for example, because of a malformed variable reference), the error is
+    /// returned.
+    pub fn between_policy_sets(before: &PolicySet, after: &PolicySet) -> Result<Self, AspenError> {
+        let policies = before.policies().iter().chain(after.policies().iter()).map(|(_, policy)| policy);
+        let space = RequestSpace::new(policies);
+        Self::compute(
+            &space,
+            |context| before.evaluate(context).map(|(decision, _)| decision),
+            |context| after.evaluate(context).map(|(decision, _)| decision),
+        )
+    }
+
+    fn compute<B, A>(space: &RequestSpace, eval_before: B, eval_after: A) -> Result<Self, AspenError>
+    where
+        B: Fn(&Context) -> Result<Decision, AspenError>,
+        A: Fn(&Context) -> Result<Decision, AspenError>,
+    {
+        let mut changes = Vec::new();
+
+        for context in space.contexts() {
+            let before = eval_before(context)?;
+            let after = eval_after(context)?;
+            if before != after {
+                changes.push(DecisionChange {
+                    context: context.clone(),
+                    before,
+                    after,
+                });
+            }
+        }
+
+        Ok(Self {
+            changes,
+            requests_evaluated: space.contexts().len(),
+            exact: space.is_exact(),
+        })
+    }
+
+    /// Returns every request whose decision changed.
+    #[inline]
+    pub fn changes(&self) -> &[DecisionChange] {
+        &self.changes
+    }
+
+    /// Returns the requests that were not allowed before the change and are allowed after it.
+    pub fn gained(&self) -> impl Iterator<Item = &DecisionChange> {
+        self.changes.iter().filter(|c| c.change() == AccessChange::Gained)
+    }
+
+    /// Returns the requests that were allowed before the change and are not allowed after it.
+    pub fn lost(&self) -> impl Iterator<Item = &DecisionChange> {
+        self.changes.iter().filter(|c| c.change() == AccessChange::Lost)
+    }
+
+    /// Indicates whether the two versions make the same decision for every request.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    /// Indicates whether the request space was enumerated exactly. If `false`, some condition values or
+    /// combinations were sampled and an empty diff does not prove the versions are equivalent.
+    #[inline]
+    pub fn is_exact(&self) -> bool {
+        self.exact
+    }
+
+    /// Returns the number of representative requests both versions were evaluated against.
+    #[inline]
+    pub fn requests_evaluated(&self) -> usize {
+        self.requests_evaluated
+    }
+}
+
+impl Display for PolicyDiff {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        for change in self.changes.iter() {
+            writeln!(f, "{change}")?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        crate::{AccessChange, Decision, DecisionChange, Policy, PolicyDiff, PolicySet, PolicySource},
+        pretty_assertions::assert_eq,
+        std::{collections::BTreeSet, str::FromStr},
+    };
+
+    #[test_log::test]
+    fn test_identical_and_reordered() {
+        let p1 = Policy::from_str(include_str!("../test-policy-1.json")).unwrap();
+        let diff = PolicyDiff::between_policies(&p1, &p1).unwrap();
+        assert!(diff.is_empty());
+        assert!(diff.requests_evaluated() > 0);
+        assert_eq!(diff.to_string(), "");
+
+        // Splitting an action list across statements doesn't change anything.
+        let before = Policy::from_str(
+            r#"{"Statement": {"Effect": "Allow", "Action": ["ec2:Describe*", "ec2:RunInstances"], "Resource": "*"}}"#,
+        )
+        .unwrap();
+        let after = Policy::from_str(
+            r#"{"Statement": [
+                {"Effect": "Allow", "Action": "ec2:RunInstances", "Resource": "*"},
+                {"Effect": "Allow", "Action": "ec2:Describe*", "Resource": "*"}
+            ]}"#,
+        )
+        .unwrap();
+        let diff = PolicyDiff::between_policies(&before, &after).unwrap();
+        assert!(diff.is_exact());
+        assert!(diff.is_empty(), "{diff}");
+    }
+
+    #[test_log::test]
+    fn test_role_principal_change() {
+        let role = |name: &str| {
+            Policy::from_str(&format!(
+                r#"{{"Statement": {{"Effect": "Allow", "Action": "s3:GetObject", "Resource": "*",
+                    "Principal": {{"AWS": "arn:aws:iam::111111111111:role/{name}"}}}}}}"#
+            ))
+            .unwrap()
+        };
+
+        let diff = PolicyDiff::between_policies(&role("A"), &role("B")).unwrap();
+        assert!(diff.is_exact());
+        let identities = |changes: Vec<&DecisionChange>| -> BTreeSet<String> {
+            changes.iter().flat_map(|c| c.context().actor().iter().map(|i| i.to_string())).collect()
+        };
+        assert_eq!(
+            identities(diff.gained().collect()),
+            BTreeSet::from(["arn:aws:sts::111111111111:assumed-role/B/aspen-analysis".to_string()])
+        );
+        assert_eq!(
+            identities(diff.lost().collect()),
+            BTreeSet::from(["arn:aws:sts::111111111111:assumed-role/A/aspen-analysis".to_string()])
+        );
+    }
+
+    #[test_log::test]
+    fn test_condition_and_deny_changes() {
+        let before = Policy::from_str(
+            r#"{"Version": "2012-10-17", "Statement": [
+                {"Effect": "Allow", "Action": "s3:GetObject", "Resource": "*",
+                 "Condition": {"IpAddress": {"aws:SourceIp": "10.0.0.0/8"}}}
+            ]}"#,
+        )
+        .unwrap();
+        let after = Policy::from_str(
+            r#"{"Version": "2012-10-17", "Statement": [
+                {"Effect": "Deny", "Action": "s3:GetObject", "Resource": "arn:aws:s3:::secret/*"},
+                {"Effect": "Allow", "Action": "s3:GetObject", "Resource": "*",
+                 "Condition": {"IpAddress": {"aws:SourceIp": "10.0.0.0/7"}}}
+            ]}"#,
+        )
+        .unwrap();
+
+        let diff = PolicyDiff::between_policies(&before, &after).unwrap();
+        assert!(diff.is_exact());
+
+        // 11.0.0.0 is in the widened range but not the original one.
+        let gained: Vec<_> = diff.gained().collect();
+        assert!(!gained.is_empty());
+        for change in gained.iter() {
+            let ip = change.context().session_data().get("aws:SourceIp").unwrap().to_string();
+            assert_eq!(ip, "11.0.0.0");
+            assert_eq!(change.before(), Decision::DefaultDeny);
+            assert_eq!(change.after(), Decision::Allow);
+        }
+
+        // Secret objects are now denied from inside the original range.
+        assert!(diff.lost().all(|c| c.context().resources()[0].resource().starts_with("secret/")));
+        assert!(diff.lost().any(|c| c.context().session_data().get("aws:SourceIp").unwrap().to_string() == "10.0.0.0"));
+        assert!(diff.changes().iter().any(|c| c.change() == AccessChange::DenialChanged));
+
+        let text = diff.to_string();
+        assert!(text
+            .lines()
+            .any(|line| line.starts_with("+ s3:GetObject ") && line.ends_with(": DefaultDeny -> Allow")));
+        assert!(text
+            .lines()
+            .any(|line| line.starts_with("- s3:GetObject on arn:") && line.ends_with(": Allow -> Deny")));
+    }
+
+    #[test_log::test]
+    fn test_policy_sets() {
+        let identity =
+            Policy::from_str(r#"{"Statement": {"Effect": "Allow", "Action": "iam:*", "Resource": "*"}}"#).unwrap();
+        let boundary = Policy::from_str(
+            r#"{"Statement": {"Effect": "Allow", "Action": ["iam:Get*", "iam:List*"], "Resource": "*"}}"#,
+        )
+        .unwrap();
+
+        let mut before = PolicySet::new();
+        before.add_policy(PolicySource::new_entity_inline("arn:aws:iam::123456789012:user/u", "AIDA", "p"), identity);
+        let mut after = before.clone();
+        after.add_policy(PolicySource::new_permission_boundary("arn:aws:iam::aws:policy/b", "ANPA", "v1"), boundary);
+
+        let diff = PolicyDiff::between_policy_sets(&before, &after).unwrap();
+        assert!(diff.gained().next().is_none());
+        let lost: Vec<_> = diff.lost().map(|c| c.context().api().to_string()).collect();
+        assert!(!lost.is_empty());
+        assert!(lost.iter().all(|api| !api.starts_with("Get") && !api.starts_with("List")), "{lost:?}");
+    }
+}
@@ -0,0 +1,841 @@
+use {
+    crate::{AspenError, Context, Decision, Policy, PolicySet, PolicySource},
+    scratchstack_arn::Arn,
+    scratchstack_aws_principal::{AssumedRole, Principal as PrincipalActor, Service, SessionData, SessionValue, User},
+    std::{
+        collections::{BTreeSet, HashMap, VecDeque},
+        fmt::{Display, Formatter, Result as FmtResult},
+        str::FromStr,
+    },
+};
+
+/// The session name used for role sessions and the name of placeholder resources.
+const ANALYSIS_NAME: &str = "aspen-analysis";
+
+/// The region used for placeholder resources created during an escalation.
+const PLACEHOLDER_REGION: &str = "us-east-1";
+
+/// A known technique for gaining permissions beyond those granted to a principal.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum EscalationMethod {
+    /// `sts:AssumeRole` on a role whose trust policy allows the principal.
+    AssumeRole,
+
+    /// `iam:UpdateAssumeRolePolicy` and `sts:AssumeRole` on a role: rewrite the trust policy, then assume the role.
+    UpdateAssumeRolePolicy,
+
+    /// `iam:PassRole`, `lambda:CreateFunction`, and `lambda:InvokeFunction`: run code as a role trusted by Lambda.
+    PassRoleToLambda,
+
+    /// `iam:PassRole` and `ec2:RunInstances`: launch an instance with a role trusted by EC2.
+    PassRoleToEc2,
+
+    /// `iam:PassRole` and `cloudformation:CreateStack`: create resources as a role trusted by CloudFormation.
+    PassRoleToCloudFormation,
+
+    /// `iam:PassRole` and `glue:CreateDevEndpoint`: run code as a role trusted by Glue.
+    PassRoleToGlue,
+
+    /// `iam:CreateAccessKey` on another user.
+    CreateAccessKey,
+
+    /// `iam:CreateLoginProfile` on another user.
+    CreateLoginProfile,
+
+    /// `iam:UpdateLoginProfile` on another user.
+    UpdateLoginProfile,
+
+    /// `iam:CreatePolicyVersion` on a customer managed policy attached to the principal.
+    CreatePolicyVersion,
+
+    /// `iam:SetDefaultPolicyVersion` on a customer managed policy attached to the principal.
+    SetDefaultPolicyVersion,
+
+    /// `iam:AttachUserPolicy` on the principal itself.
+    AttachUserPolicy,
+
+    /// `iam:AttachGroupPolicy` on a group the principal is a member of.
+    AttachGroupPolicy,
+
+    /// `iam:AttachRolePolicy` on the principal's own role.
+    AttachRolePolicy,
+
+    /// `iam:PutUserPolicy` on the principal itself.
+    PutUserPolicy,
+
+    /// `iam:PutGroupPolicy` on a group the principal is a member of.
+    PutGroupPolicy,
+
+    /// `iam:PutRolePolicy` on the principal's own role.
+    PutRolePolicy,
+
+    /// `iam:AddUserToGroup` to join another group.
+    AddUserToGroup,
+}
+
+impl EscalationMethod {
+    /// Indicates whether this method changes the principal's own permissions, as opposed to obtaining the
+    /// permissions of another principal.
+    pub fn is_self_escalation(&self) -> bool {
+        matches!(
+            self,
+            Self::CreatePolicyVersion
+                | Self::SetDefaultPolicyVersion
+                | Self::AttachUserPolicy
+                | Self::AttachGroupPolicy
+                | Self::AttachRolePolicy
+                | Self::PutUserPolicy
+                | Self::PutGroupPolicy
+                | Self::PutRolePolicy
+                | Self::AddUserToGroup
+        )
+    }
+}
+
+impl Display for EscalationMethod {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        write!(f, "{self:?}")
+    }
+}
+
+/// A statement that grants one of the permissions an escalation step relies on.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Evidence {
+    action: String,
+    resource: String,
+    source: PolicySource,
+    statement_index: usize,
+    sid: Option<String>,
+}
+
+impl Evidence {
+    /// Returns the action granted, e.g. `iam:PassRole`.
+    #[inline]
+    pub fn action(&self) -> &str {
+        &self.action
+    }
+
+    /// Returns the resource the action was evaluated against.
+    #[inline]
+    pub fn resource(&self) -> &str {
+        &self.resource
+    }
+
+    /// Returns the source of the policy containing the statement. Role trust policies are reported as
+    /// [PolicySource::Resource] policies of the role.
+    #[inline]
+    pub fn source(&self) -> &PolicySource {
+        &self.source
+    }
+
+    /// Returns the index of the statement within the policy.
+    #[inline]
+    pub fn statement_index(&self) -> usize {
+        self.statement_index
+    }
+
+    /// Returns the statement id, if any.
+    #[inline]
+    pub fn sid(&self) -> Option<&str> {
+        self.sid.as_deref()
+    }
+}
+
+impl Display for Evidence {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        write!(f, "{} on {}: statement {}", self.action, self.resource, self.statement_index)?;
+        if let Some(sid) = &self.sid {
+            write!(f, " {sid:?}")?;
+        }
+        write!(f, " of {}", self.source)
+    }
+}
+
+/// A single step of an escalation.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct EscalationStep {
+    method: EscalationMethod,
+    principal: String,
+    target: String,
+    evidence: Vec<Evidence>,
+}
+
+impl EscalationStep {
+    /// Returns the technique used.
+    #[inline]
+    pub fn method(&self) -> EscalationMethod {
+        self.method
+    }
+
+    /// Returns the ARN of the principal performing the step.
+    #[inline]
+    pub fn principal(&self) -> &str {
+        &self.principal
+    }
+
+    /// Returns the ARN of the user, role, group, or policy the step acts on.
+    #[inline]
+    pub fn target(&self) -> &str {
+        &self.target
+    }
+
+    /// Returns the statements granting the permissions the step needs.
+    #[inline]
+    pub fn evidence(&self) -> &[Evidence] {
+        &self.evidence
+    }
+}
+
+impl Display for EscalationStep {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        write!(f, "{} on {}", self.method, self.target)
+    }
+}
+
+/// A way for a principal to obtain permissions it was not granted.
+///
+/// The last step either changes the permissions of the principal reached so far (see
+/// [EscalationMethod::is_self_escalation]) or reaches a principal whose permissions are not a subset of the
+/// starting principal's.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct EscalationFinding {
+    principal: String,
+    steps: Vec<EscalationStep>,
+}
+
+impl EscalationFinding {
+    /// Returns the ARN of the principal that can escalate.
+    #[inline]
+    pub fn principal(&self) -> &str {
+        &self.principal
+    }
+
+    /// Returns the steps of the escalation, in order.
+    #[inline]
+    pub fn steps(&self) -> &[EscalationStep] {
+        &self.steps
+    }
+
+    /// Indicates whether the escalation takes more than one step.
+    #[inline]
+    pub fn is_multi_hop(&self) -> bool {
+        self.steps.len() > 1
+    }
+}
+
+impl Display for EscalationFinding {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        write!(f, "{}: ", self.principal)?;
+        for (i, step) in self.steps.iter().enumerate() {
+            if i > 0 {
+                f.write_str(", then ")?;
+            }
+            write!(f, "{step}")?;
+        }
+        Ok(())
+    }
+}
+
+/// An IAM user or role in an [IamModel].
+#[derive(Clone, Debug)]
+struct Entity {
+    arn: Arn,
+    policies: PolicySet,
+    trust_policy: Option<Policy>,
+    actor: PrincipalActor,
+    session_data: SessionData,
+    groups: BTreeSet<String>,
+}
+
+impl Entity {
+    fn is_user(&self) -> bool {
+        self.trust_policy.is_none()
+    }
+}
+
+/// The IAM users and roles of one or more accounts, with the policies that apply to each.
+///
+/// The model is used to find privilege escalations: see [IamModel::find_escalations].
+///
+/// # Example
+///
+/// ```
+/// # use scratchstack_aspen::{EscalationMethod, IamModel, Policy, PolicySet, PolicySource};
+/// # use std::str::FromStr;
+/// let dev = Policy::from_str(r#"{"Statement": {"Effect": "Allow", "Action": ["iam:PassRole", "lambda:*"],
+///     "Resource": "*"}}"#).unwrap();
+/// let admin = Policy::from_str(r#"{"Statement": {"Effect": "Allow", "Action": "*", "Resource": "*"}}"#).unwrap();
+/// let trust = Policy::from_str(r#"{"Statement": {"Effect": "Allow", "Action": "sts:AssumeRole",
+///     "Resource": "*", "Principal": {"Service": "lambda.amazonaws.com"}}}"#).unwrap();
+///
+/// let user = "arn:aws:iam::123456789012:user/dev";
+/// let role = "arn:aws:iam::123456789012:role/admin";
+/// let mut model = IamModel::new();
+/// model.add_user(user, PolicySet::from(vec![(PolicySource::new_entity_inline(user, "AIDA", "dev"), dev)])).unwrap();
+/// model.add_role(role, PolicySet::from(vec![(PolicySource::new_entity_inline(role, "AROA", "admin"), admin)]),
+///     trust).unwrap();
+///
+/// let findings = model.find_escalations().unwrap();
+/// assert_eq!(findings[0].principal(), user);
+/// assert_eq!(findings[0].steps()[0].method(), EscalationMethod::PassRoleToLambda);
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct IamModel {
+    entities: Vec<Entity>,
+}
+
+impl IamModel {
+    /// Create a new, empty model.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add an IAM user and the policies that apply to it, including those of its groups.
+    ///
+    /// # Errors
+    ///
+    /// If `arn` is not an IAM user ARN, [AspenError::InvalidPrincipal] is returned.
+    pub fn add_user(&mut self, arn: &str, policies: PolicySet) -> Result<(), AspenError> {
+        let invalid = || AspenError::InvalidPrincipal(arn.to_string());
+        let parsed = Arn::from_str(arn).map_err(|_| invalid())?;
+        let user = User::from_str(arn).map_err(|_| invalid())?;
+
+        let mut session_data = session_data(&parsed);
+        session_data.insert("aws:username", SessionValue::String(user.user_name().to_string()));
+        let groups = policies
+            .policies()
+            .iter()
+            .filter_map(|(source, _)| match source {
+                PolicySource::GroupInline {
+                    group_arn,
+                    ..
+                }
+                | PolicySource::GroupAttachedPolicy {
+                    group_arn,
+                    ..
+                } => Some(group_arn.clone()),
+                _ => None,
+            })
+            .collect();
+
+        self.entities.push(Entity {
+            arn: parsed,
+            policies,
+            trust_policy: None,
+            actor: user.into(),
+            session_data,
+            groups,
+        });
+        Ok(())
+    }
+
+    /// Add an IAM role, the policies that apply to it, and its trust (assume role) policy.
+    ///
+    /// # Errors
+    ///
+    /// If `arn` is not an IAM role ARN, [AspenError::InvalidPrincipal] is returned.
+    pub fn add_role(&mut self, arn: &str, policies: PolicySet, trust_policy: Policy) -> Result<(), AspenError> {
+        let invalid = || AspenError::InvalidPrincipal(arn.to_string());
+        let parsed = Arn::from_str(arn).map_err(|_| invalid())?;
+        if parsed.service() != "iam" || !parsed.resource().starts_with("role/") {
+            return Err(invalid());
+        }
+
+        let role_name = parsed.resource().rsplit('/').next().unwrap_or_default();
+        let session = AssumedRole::new(parsed.partition(), parsed.account_id(), role_name, ANALYSIS_NAME)
+            .map_err(|_| invalid())?;
+
+        self.entities.push(Entity {
+            session_data: session_data(&parsed),
+            arn: parsed,
+            policies,
+            trust_policy: Some(trust_policy),
+            actor: session.into(),
+            groups: BTreeSet::new(),
+        });
+        Ok(())
+    }
+
+    /// Find the ways each principal in the model can obtain permissions it was not granted.
+    ///
+    /// Each principal is checked for the known escalation techniques in [EscalationMethod]. Techniques that reach
+    /// another user or role are followed transitively, so a finding may chain role assumptions and passed roles
+    /// before the final step. A path is reported when it reaches a principal whose permissions are not provably a
+    /// subset of the starting principal's, or when the principal reached can change its own permissions. Only the
+    /// shortest path to each principal is reported.
+    ///
+    /// Permissions are evaluated against the actual role, user, group, and policy ARNs in the model. Actions that
+    /// create resources (such as `lambda:CreateFunction`) are evaluated against a placeholder resource in the
+    /// principal's account in `us-east-1`. The identity policy of the caller must allow `sts:AssumeRole` even when the
+    /// role is in the same account.
+    ///
+    /// # Errors
+    ///
+    /// If a policy fails to evaluate (for example, because of a malformed variable reference), the error is
+    /// returned.
+    pub fn find_escalations(&self) -> Result<Vec<EscalationFinding>, AspenError> {
+        let mut hops = Vec::with_capacity(self.entities.len());
+        let mut self_steps = Vec::with_capacity(self.entities.len());
+        for index in 0..self.entities.len() {
+            hops.push(self.hops(index)?);
+            self_steps.push(self.self_escalations(index)?);
+        }
+
+        let mut findings = Vec::new();
+        let mut gains: HashMap<(usize, usize), bool> = HashMap::new();
+
+        for start in 0..self.entities.len() {
+            // Breadth-first search for the shortest path to each reachable principal.
+            let mut paths: Vec<Option<Vec<&EscalationStep>>> = vec![None; self.entities.len()];
+            let mut order = Vec::new();
+            let mut queue = VecDeque::from([start]);
+            paths[start] = Some(Vec::new());
+
+            while let Some(current) = queue.pop_front() {
+                order.push(current);
+                for (next, step) in hops[current].iter() {
+                    if paths[*next].is_none() {
+                        let mut path = paths[current].clone().unwrap_or_default();
+                        path.push(step);
+                        paths[*next] = Some(path);
+                        queue.push_back(*next);
+                    }
+                }
+            }
+
+            for reached in order {
+                let path = paths[reached].as_ref().expect("reached principals have paths");
+
+                if reached != start {
+                    let gain = match gains.get(&(start, reached)) {
+                        Some(gain) => *gain,
+                        None => {
+                            let verdict =
+                                self.entities[reached].policies.is_subset_of(&self.entities[start].policies)?;
+                            let gain = !verdict.holds();
+                            gains.insert((start, reached), gain);
+                            gain
+                        }
+                    };
+
+                    if gain {
+                        findings.push(self.finding(start, path.iter().copied()));
+                    }
+                }
+
+                for step in self_steps[reached].iter() {
+                    findings.push(self.finding(start, path.iter().copied().chain([step])));
+                }
+            }
+        }
+
+        Ok(findings)
+    }
+
+    fn finding<'a, I: IntoIterator<Item = &'a EscalationStep>>(&self, start: usize, steps: I) -> EscalationFinding {
+        EscalationFinding {
+            principal: self.entities[start].arn.to_string(),
+            steps: steps.into_iter().cloned().collect(),
+        }
+    }
+
+    /// Returns the steps that give the principal at `index` the permissions of another principal.
+    fn hops(&self, index: usize) -> Result<Vec<(usize, EscalationStep)>, AspenError> {
+        let entity = &self.entities[index];
+        let mut result = Vec::new();
+
+        for (target_index, target) in self.entities.iter().enumerate() {
+            if target_index == index {
+                continue;
+            }
+
+            let target_arn = target.arn.to_string();
+            let mut push = |method, evidence: Vec<Vec<Evidence>>| {
+                result.push((
+                    target_index,
+                    EscalationStep {
+                        method,
+                        principal: entity.arn.to_string(),
+                        target: target_arn.clone(),
+                        evidence: evidence.into_iter().flatten().collect(),
+                    },
+                ));
+            };
+
+            match &target.trust_policy {
+                None => {
+                    for (method, action) in [
+                        (EscalationMethod::CreateAccessKey, "iam:CreateAccessKey"),
+                        (EscalationMethod::CreateLoginProfile, "iam:CreateLoginProfile"),
+                        (EscalationMethod::UpdateLoginProfile, "iam:UpdateLoginProfile"),
+                    ] {
+                        if let Some(evidence) = self.allowed(entity, action, &target_arn)? {
+                            push(method, vec![evidence]);
+                        }
+                    }
+                }
+                Some(trust_policy) => {
+                    let assume = self.allowed(entity, "sts:AssumeRole", &target_arn)?;
+                    if let Some(assume) = &assume {
+                        if let Some(trust) = trust_allows(target, trust_policy, &entity.actor, &entity.session_data)? {
+                            push(EscalationMethod::AssumeRole, vec![assume.clone(), vec![trust]]);
+                        }
+
+                        if let Some(update) = self.allowed(entity, "iam:UpdateAssumeRolePolicy", &target_arn)? {
+                            push(EscalationMethod::UpdateAssumeRolePolicy, vec![update, assume.clone()]);
+                        }
+                    }
+
+                    let pass_role = match self.allowed(entity, "iam:PassRole", &target_arn)? {
+                        Some(pass_role) => pass_role,
+                        None => continue,
+                    };
+
+                    for (method, service, actions) in [
+                        (
+                            EscalationMethod::PassRoleToLambda,
+                            "lambda",
+                            &["lambda:CreateFunction", "lambda:InvokeFunction"][..],
+                        ),
+                        (EscalationMethod::PassRoleToEc2, "ec2", &["ec2:RunInstances"][..]),
+                        (
+                            EscalationMethod::PassRoleToCloudFormation,
+                            "cloudformation",
+                            &["cloudformation:CreateStack"][..],
+                        ),
+                        (EscalationMethod::PassRoleToGlue, "glue", &["glue:CreateDevEndpoint"][..]),
+                    ] {
+                        let actor =
+                            Service::new(service, None, "amazonaws.com").expect("service names are valid").into();
+                        let trust = match trust_allows(target, trust_policy, &actor, &SessionData::new())? {
+                            Some(trust) => trust,
+                            None => continue,
+                        };
+
+                        let mut evidence = vec![pass_role.clone(), vec![trust]];
+                        for action in actions {
+                            match self.allowed(entity, action, &placeholder(&entity.arn, service))? {
+                                Some(granted) => evidence.push(granted),
+                                None => {
+                                    evidence.clear();
+                                    break;
+                                }
+                            }
+                        }
+
+                        if !evidence.is_empty() {
+                            push(method, evidence);
+                        }
+                    }
+                }
+            }
+        }
+
+        Ok(result)
+    }
+
+    /// Returns the steps that let the principal at `index` change its own permissions.
+    fn self_escalations(&self, index: usize) -> Result<Vec<EscalationStep>, AspenError> {
+        let entity = &self.entities[index];
+        let own_arn = entity.arn.to_string();
+        let mut candidates = Vec::new();
+
+        if entity.is_user() {
+            candidates.push((EscalationMethod::AttachUserPolicy, "iam:AttachUserPolicy", own_arn.clone()));
+            candidates.push((EscalationMethod::PutUserPolicy, "iam:PutUserPolicy", own_arn));
+        } else {
+            candidates.push((EscalationMethod::AttachRolePolicy, "iam:AttachRolePolicy", own_arn.clone()));
+            candidates.push((EscalationMethod::PutRolePolicy, "iam:PutRolePolicy", own_arn));
+        }
+
+        for group in entity.groups.iter() {
+            candidates.push((EscalationMethod::AttachGroupPolicy, "iam:AttachGroupPolicy", group.clone()));
+            candidates.push((EscalationMethod::PutGroupPolicy, "iam:PutGroupPolicy", group.clone()));
+        }
+
+        if entity.is_user() {
+            let other_groups: BTreeSet<&String> =
+                self.entities.iter().flat_map(|e| e.groups.iter()).filter(|g| !entity.groups.contains(*g)).collect();
+            for group in other_groups {
+                candidates.push((EscalationMethod::AddUserToGroup, "iam:AddUserToGroup", group.clone()));
+            }
+        }
+
+        let managed: BTreeSet<&String> = entity
+            .policies
+            .policies()
+            .iter()
+            .filter_map(|(source, _)| match source {
+                PolicySource::EntityAttachedPolicy {
+                    policy_arn,
+                    ..
+                }
+                | PolicySource::GroupAttachedPolicy {
+                    policy_arn,
+                    ..
+                }
+                | PolicySource::PermissionBoundary {
+                    policy_arn,
+                    ..
+                } => Some(policy_arn),
+                _ => None,
+            })
+            .filter(|policy_arn| Arn::from_str(policy_arn).map(|arn| arn.account_id() != "aws").unwrap_or(false))
+            .collect();
+
+        for policy_arn in managed {
+            candidates.push((EscalationMethod::CreatePolicyVersion, "iam:CreatePolicyVersion", policy_arn.clone()));
+            candidates.push((
+                EscalationMethod::SetDefaultPolicyVersion,
+                "iam:SetDefaultPolicyVersion",
+                policy_arn.clone(),
+            ));
+        }
+
+        let mut result = Vec::new();
+        for (method, action, target) in candidates {
+            if let Some(evidence) = self.allowed(entity, action, &target)? {
+                result.push(EscalationStep {
+                    method,
+                    principal: entity.arn.to_string(),
+                    target,
+                    evidence,
+                });
+            }
+        }
+
+        Ok(result)
+    }
+
+    /// If the entity's policies allow `action` on `resource`, returns the allowing statements.
+    fn allowed(&self, entity: &Entity, action: &str, resource: &str) -> Result<Option<Vec<Evidence>>, AspenError> {
+        let (service, api) = action.split_once(':').expect("actions are qualified");
+        let resource_arn = Arn::from_str(resource).map_err(|_| AspenError::InvalidResource(resource.to_string()))?;
+        let context = Context::builder()
+            .service(service)
+            .api(api)
+            .actor(entity.actor.clone())
+            .resources(vec![resource_arn])
+            .session_data(entity.session_data.clone())
+            .build()
+            .expect("all context fields are set");
+
+        let (decision, statements) = entity.policies.evaluate_statements(&context)?;
+        if decision != Decision::Allow {
+            return Ok(None);
+        }
+
+        let policies = entity.policies.policies();
+        Ok(Some(
+            statements
+                .into_iter()
+                .filter_map(|(policy_index, statement_index)| {
+                    let (source, policy) = &policies[policy_index];
+                    statement_index.map(|statement_index| Evidence {
+                        action: action.to_string(),
+                        resource: resource.to_string(),
+                        source: source.clone(),
+                        statement_index,
+                        sid: policy.statement()[statement_index].sid().map(str::to_string),
+                    })
+                })
+                .collect(),
+        ))
+    }
+}
+
+/// If the role's trust policy allows `actor` to assume it, returns the allowing statement.
+fn trust_allows(
+    role: &Entity,
+    trust_policy: &Policy,
+    actor: &PrincipalActor,
+    session_data: &SessionData,
+) -> Result<Option<Evidence>, AspenError> {
+    let context = Context::builder()
+        .service("sts")
+        .api("AssumeRole")
+        .actor(actor.clone())
+        .resources(vec![role.arn.clone()])
+        .session_data(session_data.clone())
+        .build()
+        .expect("all context fields are set");
+
+    match trust_policy.evaluate_with_statement(&context)? {
+        (Decision::Allow, Some(statement_index)) => Ok(Some(Evidence {
+            action: "sts:AssumeRole".to_string(),
+            resource: role.arn.to_string(),
+            source: PolicySource::new_resource(role.arn.to_string(), Some("AssumeRolePolicyDocument")),
+            statement_index,
+            sid: trust_policy.statement()[statement_index].sid().map(str::to_string),
+        })),
+        _ => Ok(None),
+    }
+}
+
+/// Returns the session data describing the given principal.
+fn session_data(arn: &Arn) -> SessionData {
+    let mut session_data = SessionData::new();
+    session_data.insert("aws:PrincipalArn", SessionValue::String(arn.to_string()));
+    session_data.insert("aws:PrincipalAccount", SessionValue::String(arn.account_id().to_string()));
+    session_data
+}
+
+/// Returns the ARN of a placeholder resource created in the principal's account by the given service.
+fn placeholder(principal: &Arn, service: &str) -> String {
+    let resource = match service {
+        "lambda" => format!("function:{ANALYSIS_NAME}"),
+        "ec2" => format!("instance/{ANALYSIS_NAME}"),
+        "cloudformation" => format!("stack/{ANALYSIS_NAME}/{ANALYSIS_NAME}"),
+        _ => format!("devEndpoint/{ANALYSIS_NAME}"),
+    };
+    format!("arn:{}:{service}:{PLACEHOLDER_REGION}:{}:{resource}", principal.partition(), principal.account_id())
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        crate::{EscalationMethod, IamModel, Policy, PolicySet, PolicySource},
+        pretty_assertions::assert_eq,
+        std::str::FromStr,
+    };
+
+    fn inline(arn: &str, policy: &str) -> PolicySet {
+        PolicySet::from(vec![(
+            PolicySource::new_entity_inline(arn, "AIDA", "inline"),
+            Policy::from_str(policy).unwrap(),
+        )])
+    }
+
+    const ALICE: &str = "arn:aws:iam::123456789012:user/alice";
+    const BOB: &str = "arn:aws:iam::123456789012:user/bob";
+    const CAROL: &str = "arn:aws:iam::123456789012:user/carol";
+    const OPS: &str = "arn:aws:iam::123456789012:role/ops";
+    const ADMIN: &str = "arn:aws:iam::123456789012:role/admin";
+
+    fn model() -> IamModel {
+        let mut model = IamModel::new();
+        model
+            .add_user(
+                ALICE,
+                inline(
+                    ALICE,
+                    r#"{"Statement": {"Sid": "AssumeOps", "Effect": "Allow", "Action": "sts:AssumeRole",
+                        "Resource": "arn:aws:iam::123456789012:role/*"}}"#,
+                ),
+            )
+            .unwrap();
+
+        let mut bob =
+            inline(BOB, r#"{"Statement": {"Effect": "Allow", "Action": "iam:*PolicyVersion", "Resource": "*"}}"#);
+        for policy_arn in ["arn:aws:iam::123456789012:policy/bob", "arn:aws:iam::aws:policy/ReadOnlyAccess"] {
+            bob.add_policy(
+                PolicySource::new_entity_attached_policy(policy_arn, "ANPA", "v1"),
+                Policy::from_str(r#"{"Statement": {"Effect": "Allow", "Action": "s3:Get*", "Resource": "*"}}"#)
+                    .unwrap(),
+            );
+        }
+        model.add_user(BOB, bob).unwrap();
+
+        model
+            .add_user(
+                CAROL,
+                inline(
+                    CAROL,
+                    r#"{"Statement": [
+                        {"Effect": "Deny", "Action": "iam:PassRole", "Resource": "arn:aws:iam::123456789012:role/admin"},
+                        {"Effect": "Allow", "Action": ["iam:PassRole", "ec2:*"], "Resource": "*"}
+                    ]}"#,
+                ),
+            )
+            .unwrap();
+
+        let ops_trust = Policy::from_str(
+            r#"{"Statement": {"Effect": "Allow", "Action": "sts:AssumeRole",
+                "Resource": "*",
+                "Principal": {"AWS": "arn:aws:iam::123456789012:user/alice"}}}"#,
+        )
+        .unwrap();
+        model
+            .add_role(
+                OPS,
+                inline(
+                    OPS,
+                    r#"{"Statement": [
+                        {"Sid": "Pass", "Effect": "Allow", "Action": "iam:PassRole", "Resource": "arn:aws:iam::123456789012:role/admin"},
+                        {"Sid": "Launch", "Effect": "Allow", "Action": "ec2:RunInstances", "Resource": "*"}
+                    ]}"#,
+                ),
+                ops_trust,
+            )
+            .unwrap();
+
+        let admin_trust = Policy::from_str(
+            r#"{"Statement": {"Effect": "Allow", "Action": "sts:AssumeRole", "Resource": "*",
+                "Principal": {"Service": "ec2.amazonaws.com"}}}"#,
+        )
+        .unwrap();
+        model
+            .add_role(
+                ADMIN,
+                inline(ADMIN, r#"{"Statement": {"Effect": "Allow", "Action": "*", "Resource": "*"}}"#),
+                admin_trust,
+            )
+            .unwrap();
+        model
+    }
+
+    #[test_log::test]
+    fn test_multi_hop() {
+        let findings = model().find_escalations().unwrap();
+        let alice: Vec<_> = findings.iter().filter(|f| f.principal() == ALICE).collect();
+
+        let to_admin = alice
+            .iter()
+            .find(|f| f.steps().len() == 2 && f.steps()[1].target() == ADMIN)
+            .expect("alice reaches admin through ops");
+        assert!(to_admin.is_multi_hop());
+        assert_eq!(to_admin.steps()[0].method(), EscalationMethod::AssumeRole);
+        assert_eq!(to_admin.steps()[1].method(), EscalationMethod::PassRoleToEc2);
+        assert_eq!(to_admin.steps()[1].principal(), OPS);
+        assert_eq!(to_admin.to_string(), format!("{ALICE}: AssumeRole on {OPS}, then PassRoleToEc2 on {ADMIN}"));
+
+        let evidence = to_admin.steps()[1].evidence();
+        let actions: Vec<_> = evidence.iter().map(|e| (e.action(), e.sid())).collect();
+        assert_eq!(
+            actions,
+            vec![("iam:PassRole", Some("Pass")), ("sts:AssumeRole", None), ("ec2:RunInstances", Some("Launch"))]
+        );
+        assert_eq!(evidence[1].source(), &PolicySource::new_resource(ADMIN, Some("AssumeRolePolicyDocument")));
+        assert!(to_admin.steps()[0].evidence().iter().any(|e| e.sid() == Some("AssumeOps")));
+
+        // Once admin is reached, it can also rewrite its own policies.
+        assert!(alice.iter().any(|f| f.steps().len() == 3
+            && f.steps()[2].method().is_self_escalation()
+            && f.steps()[2].target() == ADMIN));
+
+        // Nobody can assume ops except alice.
+        assert!(findings.iter().all(|f| f.principal() == ALICE || f.steps()[0].target() != OPS));
+    }
+
+    #[test_log::test]
+    fn test_self_escalation_and_deny() {
+        let findings = model().find_escalations().unwrap();
+
+        let bob: Vec<_> = findings.iter().filter(|f| f.principal() == BOB).map(|f| f.to_string()).collect();
+        assert_eq!(
+            bob,
+            vec![
+                format!("{BOB}: CreatePolicyVersion on arn:aws:iam::123456789012:policy/bob"),
+                format!("{BOB}: SetDefaultPolicyVersion on arn:aws:iam::123456789012:policy/bob"),
+            ]
+        );
+
+        // Carol's PassRole permission excludes admin, and no other role trusts EC2.
+        assert!(findings.iter().all(|f| f.principal() != CAROL), "{findings:?}");
+
+        assert!(IamModel::new()
+            .add_role(ALICE, PolicySet::new(), Policy::from_str(r#"{"Statement": []}"#).unwrap())
+            .is_err());
+        assert!(IamModel::new().add_user(OPS, PolicySet::new()).is_err());
+    }
+}
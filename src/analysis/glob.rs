@@ -0,0 +1,446 @@
+use {
+    scratchstack_aws_principal::SessionData,
+    std::collections::{BTreeSet, HashSet, VecDeque},
+};
+
+/// The default bound on the number of automaton states explored when partitioning a set of glob patterns.
+pub(crate) const MAX_GLOB_STATES: usize = 16384;
+
+/// A single element of a glob pattern.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub(crate) enum GlobToken {
+    /// A character that must match exactly.
+    Literal(char),
+
+    /// The `?` wildcard: any single character.
+    AnyChar,
+
+    /// The `*` wildcard: any sequence of characters, including none.
+    AnyString,
+}
+
+/// Converts a glob pattern into tokens without performing variable substitution.
+pub(crate) fn glob_tokens(pattern: &str) -> Vec<GlobToken> {
+    pattern
+        .chars()
+        .map(|c| match c {
+            '*' => GlobToken::AnyString,
+            '?' => GlobToken::AnyChar,
+            c => GlobToken::Literal(c),
+        })
+        .collect()
+}
+
+/// Converts a string into tokens that match only that exact string.
+pub(crate) fn literal_tokens(value: &str) -> Vec<GlobToken> {
+    value.chars().map(GlobToken::Literal).collect()
+}
+
+/// Converts a glob pattern into tokens, substituting `${...}` variables from the session data the same way
+/// [Context::matcher][crate::Context::matcher] does for [PolicyVersion::V2012_10_17][crate::PolicyVersion].
+///
+/// Returns `None` if the pattern contains a malformed variable reference.
+pub(crate) fn glob_tokens_subst(pattern: &str, session_data: &SessionData) -> Option<Vec<GlobToken>> {
+    let mut result = Vec::with_capacity(pattern.len());
+    let mut i = pattern.chars();
+
+    while let Some(c) = i.next() {
+        match c {
+            '$' => {
+                if i.next()? != '{' {
+                    return None;
+                }
+
+                let mut var = String::new();
+                loop {
+                    let c = i.next()?;
+                    if c == '}' {
+                        break;
+                    }
+                    var.push(c);
+                }
+
+                match var.as_str() {
+                    "*" => result.push(GlobToken::Literal('*')),
+                    "$" => result.push(GlobToken::Literal('$')),
+                    "?" => result.push(GlobToken::Literal('?')),
+                    var => {
+                        if let Some(value) = session_data.get(var) {
+                            result.extend(value.as_variable_value().chars().map(GlobToken::Literal));
+                        }
+                    }
+                }
+            }
+            '*' => result.push(GlobToken::AnyString),
+            '?' => result.push(GlobToken::AnyChar),
+            c => result.push(GlobToken::Literal(c)),
+        }
+    }
+
+    Some(result)
+}
+
+/// Returns the names of the variables referenced by a pattern, excluding the escapes `${*}`, `${$}`, and `${?}`.
+pub(crate) fn pattern_variables(pattern: &str) -> Vec<String> {
+    let mut result = Vec::new();
+    let mut rest = pattern;
+
+    while let Some(start) = rest.find("${") {
+        rest = &rest[start + 2..];
+        match rest.find('}') {
+            None => break,
+            Some(end) => {
+                let var = &rest[..end];
+                if !matches!(var, "*" | "$" | "?") {
+                    result.push(var.to_string());
+                }
+                rest = &rest[end + 1..];
+            }
+        }
+    }
+
+    result
+}
+
+/// The set of strings a witness must be drawn from.
+///
+/// Most glob patterns in a policy are matched against free-form strings, but the leading segments of an ARN must
+/// satisfy the ARN syntax rules or the request could never be constructed. Each domain is a small deterministic
+/// automaton that runs alongside the glob patterns.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub(crate) enum Domain {
+    /// Any string, including the empty string.
+    Any,
+
+    /// Any non-empty string.
+    NonEmpty,
+
+    /// An ARN partition.
+    Partition,
+
+    /// An ARN service.
+    Service,
+
+    /// An ARN region (possibly empty).
+    Region,
+
+    /// An ARN account id (empty, twelve digits, or `aws`).
+    AccountId,
+}
+
+/// The state of a [Domain] automaton. The meaning of each byte depends on the domain.
+type DomainState = [u8; 3];
+
+const DEAD: u8 = u8::MAX;
+const LOCAL: [char; 5] = ['l', 'o', 'c', 'a', 'l'];
+
+const FRESH_ANY: &str = "xqzjkvwy";
+const FRESH_ALPHA: &str = "xqzjkvwyabcdefghilmnoprstu";
+const FRESH_DIGIT: &str = "7358642910";
+
+impl Domain {
+    fn start(self) -> DomainState {
+        match self {
+            Self::Partition | Self::Service => [0, 1, 0],
+            _ => [0, 0, 0],
+        }
+    }
+
+    fn step(self, state: DomainState, c: char) -> Option<DomainState> {
+        let lower_alpha = c.is_alphabetic() && !c.is_uppercase();
+
+        match self {
+            Self::Any => Some(state),
+            Self::NonEmpty => Some([1, 0, 0]),
+            Self::Partition | Self::Service => {
+                // Mirrors scratchstack_arn::utils::validate_partition and validate_service: state[0] is the length
+                // (saturating at the maximum that matters), and state[1] indicates whether the last character was a
+                // dash.
+                let [len, last_was_dash, _] = state;
+                let (valid, max_len) = match self {
+                    Self::Partition => (lower_alpha || c.is_ascii_digit(), 32),
+                    _ => (c.is_alphanumeric() && !c.is_uppercase(), 1),
+                };
+
+                if self == Self::Partition && len == max_len {
+                    None
+                } else if valid {
+                    Some([(len + 1).min(max_len), 0, 0])
+                } else if c == '-' && last_was_dash == 0 {
+                    Some([(len + 1).min(max_len), 1, 0])
+                } else {
+                    None
+                }
+            }
+            Self::Region => {
+                // Mirrors scratchstack_arn::utils::validate_region: state[0] is the parse state (0 = start,
+                // 1 = last was alpha, 2 = last was dash, 3 = last was digit), state[1] is the section (0 = region,
+                // 1 = local region), and state[2] tracks progress through the special region "local".
+                let [parse, section, local] = state;
+                let parse_next = match (parse, c) {
+                    (DEAD, _) => None,
+                    (1, '-') => Some((2, section)),
+                    (3, '-') if section == 0 => Some((2, 1)),
+                    (parse, _) if parse <= 2 && lower_alpha => Some((1, section)),
+                    (parse, _) if parse >= 2 && c.is_ascii_digit() => Some((3, section)),
+                    _ => None,
+                };
+                let local_next = if local < LOCAL.len() as u8 && LOCAL[local as usize] == c {
+                    local + 1
+                } else {
+                    DEAD
+                };
+
+                match parse_next {
+                    Some((parse, section)) => Some([parse, section, local_next]),
+                    None if local_next != DEAD => Some([DEAD, section, local_next]),
+                    None => None,
+                }
+            }
+            Self::AccountId => {
+                let [n, _, _] = state;
+                match (n, c) {
+                    (0..=11, c) if c.is_ascii_digit() => Some([n + 1, 0, 0]),
+                    (0, 'a') => Some([13, 0, 0]),
+                    (13, 'w') => Some([14, 0, 0]),
+                    (14, 's') => Some([15, 0, 0]),
+                    _ => None,
+                }
+            }
+        }
+    }
+
+    fn accepts(self, state: DomainState) -> bool {
+        match self {
+            Self::Any => true,
+            Self::NonEmpty => state[0] == 1,
+            Self::Partition | Self::Service => state[0] > 0 && state[1] == 0,
+            Self::Region => state == [0, 0, 0] || state[0] == 3 || state[2] == LOCAL.len() as u8,
+            Self::AccountId => matches!(state[0], 0 | 12 | 15),
+        }
+    }
+
+    /// Candidate characters for each class of characters the domain treats differently. One unused character is
+    /// drawn from each class to stand in for every character that does not appear in a pattern.
+    fn fresh_classes(self) -> &'static [&'static str] {
+        match self {
+            Self::Any | Self::NonEmpty => &[FRESH_ANY],
+            Self::Partition | Self::Service | Self::Region => &[FRESH_ALPHA, FRESH_DIGIT],
+            Self::AccountId => &[FRESH_DIGIT],
+        }
+    }
+}
+
+/// A partition of a [Domain] into equivalence classes, where two strings are equivalent if they are matched by
+/// exactly the same glob patterns.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) struct GlobPartition {
+    classes: Vec<(String, Vec<bool>)>,
+    exact: bool,
+}
+
+impl GlobPartition {
+    /// Partitions `domain` by the given patterns.
+    ///
+    /// The patterns are compiled into a single product automaton that is determinized lazily in breadth-first
+    /// order, so each class is represented by its shortest witness. If more than `max_states` states would have to
+    /// be explored, the search stops early and the partition is marked as inexact.
+    pub(crate) fn new(patterns: &[Vec<GlobToken>], domain: Domain, max_states: usize) -> Self {
+        let mut bases = Vec::with_capacity(patterns.len());
+        let mut n_bits = 0;
+        for pattern in patterns {
+            bases.push(n_bits);
+            n_bits += pattern.len() + 1;
+        }
+        let n_words = n_bits.div_ceil(64);
+
+        let closure = |bits: &mut Vec<u64>, i: usize, mut pos: usize| {
+            let pattern = &patterns[i];
+            loop {
+                let bit = bases[i] + pos;
+                bits[bit / 64] |= 1 << (bit % 64);
+                if pos < pattern.len() && pattern[pos] == GlobToken::AnyString {
+                    pos += 1;
+                } else {
+                    break;
+                }
+            }
+        };
+        let is_set = |bits: &[u64], bit: usize| bits[bit / 64] & (1 << (bit % 64)) != 0;
+
+        let mut start = vec![0u64; n_words];
+        for i in 0..patterns.len() {
+            closure(&mut start, i, 0);
+        }
+
+        let mut literals = BTreeSet::new();
+        for token in patterns.iter().flatten() {
+            if let GlobToken::Literal(c) = token {
+                literals.insert(*c);
+            }
+        }
+        let mut alphabet = literals.clone();
+        for class in domain.fresh_classes() {
+            if let Some(c) = class.chars().find(|c| !literals.contains(c)) {
+                alphabet.insert(c);
+            }
+        }
+
+        let mut result = Self {
+            classes: Vec::new(),
+            exact: true,
+        };
+        let mut seen_vectors = HashSet::new();
+        let mut visited = HashSet::new();
+        let mut queue = VecDeque::new();
+        let start = (start, domain.start());
+        visited.insert(start.clone());
+        queue.push_back((start, String::new()));
+
+        while let Some(((bits, dstate), witness)) = queue.pop_front() {
+            let live = bits.iter().any(|w| *w != 0);
+            let vector: Vec<bool> =
+                patterns.iter().enumerate().map(|(i, p)| is_set(&bits, bases[i] + p.len())).collect();
+
+            if domain.accepts(dstate) && seen_vectors.insert(vector.clone()) {
+                result.classes.push((witness.clone(), vector));
+            }
+
+            if !live && seen_vectors.contains(&vec![false; patterns.len()]) {
+                continue;
+            }
+
+            for &c in alphabet.iter() {
+                let next_dstate = match domain.step(dstate, c) {
+                    Some(next_dstate) => next_dstate,
+                    None => continue,
+                };
+
+                let mut next = vec![0u64; n_words];
+                for (i, pattern) in patterns.iter().enumerate() {
+                    for (pos, token) in pattern.iter().enumerate() {
+                        if !is_set(&bits, bases[i] + pos) {
+                            continue;
+                        }
+
+                        match token {
+                            GlobToken::Literal(l) if *l == c => closure(&mut next, i, pos + 1),
+                            GlobToken::AnyChar => closure(&mut next, i, pos + 1),
+                            GlobToken::AnyString => closure(&mut next, i, pos),
+                            _ => (),
+                        }
+                    }
+                }
+
+                let key = (next, next_dstate);
+                if visited.contains(&key) {
+                    continue;
+                }
+
+                if visited.len() >= max_states {
+                    result.exact = false;
+                    return result;
+                }
+
+                visited.insert(key.clone());
+                let mut next_witness = witness.clone();
+                next_witness.push(c);
+                queue.push_back((key, next_witness));
+            }
+        }
+
+        result
+    }
+
+    /// Returns a witness string and acceptance vector for each class. Element `i` of the vector indicates whether
+    /// the `i`th pattern matches the witness.
+    #[inline]
+    pub(crate) fn classes(&self) -> &[(String, Vec<bool>)] {
+        &self.classes
+    }
+
+    /// Indicates whether every class of the domain was found.
+    #[inline]
+    pub(crate) fn is_exact(&self) -> bool {
+        self.exact
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::{glob_tokens, glob_tokens_subst, pattern_variables, Domain, GlobPartition, MAX_GLOB_STATES},
+        crate::eval::regex_from_glob,
+        pretty_assertions::assert_eq,
+        scratchstack_arn::Arn,
+        scratchstack_aws_principal::{SessionData, SessionValue},
+    };
+
+    fn check_partition(patterns: &[&str], domain: Domain) -> GlobPartition {
+        let tokens: Vec<_> = patterns.iter().map(|p| glob_tokens(p)).collect();
+        let partition = GlobPartition::new(&tokens, domain, MAX_GLOB_STATES);
+        assert!(partition.is_exact());
+
+        for (witness, vector) in partition.classes() {
+            for (pattern, expected) in patterns.iter().zip(vector) {
+                assert_eq!(regex_from_glob(pattern, false).is_match(witness), *expected, "{pattern} vs {witness}");
+            }
+        }
+
+        partition
+    }
+
+    #[test_log::test]
+    fn test_partition_overlap() {
+        // a*, *b, and their intersection a*b all need a witness, as does the complement.
+        let partition = check_partition(&["a*", "*b", "a?c"], Domain::Any);
+        let vectors: Vec<_> = partition.classes().iter().map(|(_, v)| v.clone()).collect();
+        assert_eq!(vectors.len(), 5);
+        assert!(vectors.contains(&vec![true, true, false]));
+        assert!(vectors.contains(&vec![true, false, true]));
+        assert!(vectors.contains(&vec![false, false, false]));
+        assert_eq!(partition.classes()[0].0, "");
+
+        let partition = check_partition(&["*"], Domain::NonEmpty);
+        assert_eq!(partition.classes().len(), 1);
+        assert_eq!(partition.classes()[0].0.len(), 1);
+    }
+
+    #[test_log::test]
+    fn test_partition_domains() {
+        let partition = check_partition(&["us-*-1", "*-2"], Domain::Region);
+        for (witness, _) in partition.classes() {
+            assert!(Arn::new("aws", "ec2", witness, "", "x").is_ok(), "{witness}");
+        }
+        assert_eq!(partition.classes().len(), 3);
+
+        let partition = check_partition(&["1234*", "123456789012"], Domain::AccountId);
+        let witnesses: Vec<_> = partition.classes().iter().map(|(w, _)| w.as_str()).collect();
+        assert_eq!(witnesses.len(), 3);
+        for witness in witnesses {
+            assert!(Arn::new("aws", "ec2", "", witness, "x").is_ok(), "{witness}");
+        }
+
+        let partition = check_partition(&["aws*", "?"], Domain::Partition);
+        for (witness, _) in partition.classes() {
+            assert!(Arn::new(witness, "ec2", "", "", "x").is_ok(), "{witness}");
+        }
+        assert_eq!(partition.classes().len(), 3);
+
+        let partition = check_partition(&["local"], Domain::Region);
+        assert_eq!(partition.classes().len(), 2);
+    }
+
+    #[test_log::test]
+    fn test_substitution() {
+        let session_data = SessionData::from([("aws:username", SessionValue::from("bob*"))]);
+        let tokens = glob_tokens_subst("home/${aws:username}/${*}${missing}*", &session_data).unwrap();
+        let partition = GlobPartition::new(&[tokens], Domain::Any, MAX_GLOB_STATES);
+        let witnesses: Vec<_> = partition.classes().iter().map(|(w, v)| (w.as_str(), v[0])).collect();
+        assert_eq!(witnesses, vec![("", false), ("home/bob*/*", true)]);
+
+        assert!(glob_tokens_subst("${aws:username", &session_data).is_none());
+        assert!(glob_tokens_subst("$aws", &session_data).is_none());
+        assert_eq!(pattern_variables("a/${aws:username}/${*}/${x}"), vec!["aws:username", "x"]);
+    }
+}
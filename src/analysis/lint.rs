@@ -0,0 +1,461 @@
+use {
+    crate::{
+        ActionCatalog, ActionList, Effect, ExternalAccess, ExternalAccessKind, Policy, PolicyVersion, Resource,
+        Statement,
+    },
+    serde::Serialize,
+    std::{
+        collections::HashSet,
+        fmt::{Display, Formatter, Result as FmtResult},
+    },
+};
+
+/// How serious a [LintFinding] is.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
+pub enum LintSeverity {
+    /// The policy is valid, but probably does not do what the author intended.
+    Warning,
+
+    /// The policy will be rejected by IAM.
+    Error,
+}
+
+impl Display for LintSeverity {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        match self {
+            Self::Warning => f.write_str("warning"),
+            Self::Error => f.write_str("error"),
+        }
+    }
+}
+
+/// The kind of problem reported by a [LintFinding].
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
+pub enum LintKind {
+    /// The policy has no `Version`, so policy variables are treated as literal text.
+    MissingVersion,
+
+    /// The policy uses version `2008-10-17`, which does not support the policy variables it contains.
+    LegacyVersionVariables,
+
+    /// The policy has no statements.
+    EmptyPolicy,
+
+    /// Two statements have the same `Sid`.
+    DuplicateSid,
+
+    /// An action is listed more than once in a statement.
+    DuplicateAction,
+
+    /// An Allow statement uses `NotAction`, which grants every action not listed, including future ones.
+    AllowNotAction,
+
+    /// An Allow statement uses `NotResource`, which grants access to every resource not listed.
+    AllowNotResource,
+
+    /// An Allow statement uses `NotPrincipal`, which grants access to every principal not listed.
+    AllowNotPrincipal,
+
+    /// An Allow statement grants every action on every resource without a condition.
+    AdminAccess,
+
+    /// An Allow statement grants access to any principal without a restricting condition.
+    PublicAccess,
+
+    /// An action does not match any API in the supplied [ActionCatalog].
+    UnknownAction,
+}
+
+impl LintKind {
+    /// Returns the severity of this kind of finding.
+    pub fn severity(&self) -> LintSeverity {
+        match self {
+            Self::DuplicateSid => LintSeverity::Error,
+            _ => LintSeverity::Warning,
+        }
+    }
+}
+
+impl Display for LintKind {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        Display::fmt(&format!("{self:?}"), f)
+    }
+}
+
+/// A single problem found in a policy.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct LintFinding {
+    kind: LintKind,
+    severity: LintSeverity,
+    statement_index: Option<usize>,
+    sid: Option<String>,
+    message: String,
+}
+
+impl LintFinding {
+    fn new(kind: LintKind, statement: Option<(usize, &Statement)>, message: String) -> Self {
+        Self {
+            kind,
+            severity: kind.severity(),
+            statement_index: statement.map(|(i, _)| i),
+            sid: statement.and_then(|(_, s)| s.sid().map(str::to_string)),
+            message,
+        }
+    }
+
+    /// Returns the kind of problem.
+    #[inline]
+    pub fn kind(&self) -> LintKind {
+        self.kind
+    }
+
+    /// Returns the severity of the problem.
+    #[inline]
+    pub fn severity(&self) -> LintSeverity {
+        self.severity
+    }
+
+    /// Returns the index of the statement the problem was found in, or `None` if it applies to the whole policy.
+    #[inline]
+    pub fn statement_index(&self) -> Option<usize> {
+        self.statement_index
+    }
+
+    /// Returns the statement id of the statement the problem was found in, if any.
+    #[inline]
+    pub fn sid(&self) -> Option<&str> {
+        self.sid.as_deref()
+    }
+
+    /// Returns a description of the problem.
+    #[inline]
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+impl Display for LintFinding {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        write!(f, "{}[{}]", self.severity, self.kind)?;
+        if let Some(statement_index) = self.statement_index {
+            write!(f, " statement {statement_index}")?;
+            if let Some(sid) = &self.sid {
+                write!(f, " {sid:?}")?;
+            }
+        }
+        write!(f, ": {}", self.message)
+    }
+}
+
+/// Problems found in a policy that parses but is likely to be rejected by IAM or to grant more than intended.
+///
+/// # Example
+///
+/// ```
+/// # use scratchstack_aspen::{LintKind, Policy, PolicyLint};
+/// # use std::str::FromStr;
+/// let policy = Policy::from_str(r#"{"Statement": [
+///     {"Sid": "Read", "Effect": "Allow", "Action": ["s3:GetObject", "s3:GetObject"], "Resource": "*"},
+///     {"Sid": "Read", "Effect": "Allow", "NotAction": "iam:*", "Resource": "*"}]}"#).unwrap();
+///
+/// let lint = PolicyLint::check(&policy);
+/// assert!(lint.has_errors());
+/// let kinds: Vec<_> = lint.findings().iter().map(|f| f.kind()).collect();
+/// assert_eq!(kinds, vec![LintKind::MissingVersion, LintKind::DuplicateAction, LintKind::DuplicateSid,
+///                        LintKind::AllowNotAction]);
+/// ```
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct PolicyLint {
+    findings: Vec<LintFinding>,
+}
+
+impl PolicyLint {
+    /// Checks a policy.
+    pub fn check(policy: &Policy) -> Self {
+        Self::check_inner(policy, None)
+    }
+
+    /// Checks a policy, additionally reporting actions that match nothing in the catalog.
+    pub fn check_with_catalog(policy: &Policy, catalog: &ActionCatalog) -> Self {
+        Self::check_inner(policy, Some(catalog))
+    }
+
+    fn check_inner(policy: &Policy, catalog: Option<&ActionCatalog>) -> Self {
+        let mut findings = Vec::new();
+        let statements = policy.statement();
+
+        match policy.version() {
+            PolicyVersion::None => {
+                let message = if statements.iter().any(uses_variables) {
+                    "no Version is specified; policy variables will not be substituted"
+                } else {
+                    "no Version is specified"
+                };
+                findings.push(LintFinding::new(LintKind::MissingVersion, None, message.to_string()))
+            }
+            PolicyVersion::V2008_10_17 if statements.iter().any(uses_variables) => findings.push(LintFinding::new(
+                LintKind::LegacyVersionVariables,
+                None,
+                "policy variables require Version 2012-10-17".to_string(),
+            )),
+            _ => (),
+        }
+
+        if statements.is_empty() {
+            findings.push(LintFinding::new(LintKind::EmptyPolicy, None, "the policy has no statements".to_string()));
+        }
+
+        // Public grants are found the same way as for external access analysis; the account doesn't matter.
+        let public: HashSet<usize> = ExternalAccess::analyze(policy, "")
+            .of_kind(ExternalAccessKind::Public)
+            .filter(|finding| !finding.is_restricted())
+            .map(|finding| finding.statement_index())
+            .collect();
+
+        let mut sids = HashSet::new();
+        for (index, statement) in statements.iter().enumerate() {
+            let at = Some((index, statement));
+
+            for list in [statement.action(), statement.not_action()].into_iter().flatten() {
+                for action in duplicates(list) {
+                    findings.push(LintFinding::new(LintKind::DuplicateAction, at, format!("{action} is listed twice")));
+                }
+            }
+
+            if let Some(sid) = statement.sid() {
+                if !sids.insert(sid) {
+                    findings.push(LintFinding::new(LintKind::DuplicateSid, at, format!("Sid {sid:?} is not unique")));
+                }
+            }
+
+            if statement.effect() == &Effect::Allow {
+                if statement.not_action().is_some() {
+                    findings.push(LintFinding::new(
+                        LintKind::AllowNotAction,
+                        at,
+                        "Allow with NotAction grants every action not listed".to_string(),
+                    ));
+                }
+
+                if statement.not_resource().is_some() {
+                    findings.push(LintFinding::new(
+                        LintKind::AllowNotResource,
+                        at,
+                        "Allow with NotResource grants every resource not listed".to_string(),
+                    ));
+                }
+
+                if statement.not_principal().is_some() {
+                    findings.push(LintFinding::new(
+                        LintKind::AllowNotPrincipal,
+                        at,
+                        "Allow with NotPrincipal grants every principal not listed".to_string(),
+                    ));
+                }
+
+                if is_admin(statement) {
+                    findings.push(LintFinding::new(
+                        LintKind::AdminAccess,
+                        at,
+                        "grants every action on every resource without a condition".to_string(),
+                    ));
+                }
+
+                if public.contains(&index) {
+                    findings.push(LintFinding::new(
+                        LintKind::PublicAccess,
+                        at,
+                        "grants access to any principal without a restricting condition".to_string(),
+                    ));
+                }
+            }
+
+            if let (Some(catalog), Some(actions)) = (catalog, statement.action()) {
+                for action in actions.iter() {
+                    if catalog.expand(action).is_empty() {
+                        findings.push(LintFinding::new(
+                            LintKind::UnknownAction,
+                            at,
+                            format!("{action} does not match any known action"),
+                        ));
+                    }
+                }
+            }
+        }
+
+        Self {
+            findings,
+        }
+    }
+
+    /// Returns every finding, policy-level findings first and then in statement order.
+    #[inline]
+    pub fn findings(&self) -> &[LintFinding] {
+        &self.findings
+    }
+
+    /// Indicates whether any finding has [LintSeverity::Error].
+    pub fn has_errors(&self) -> bool {
+        self.findings.iter().any(|f| f.severity == LintSeverity::Error)
+    }
+
+    /// Indicates whether no problems were found.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.findings.is_empty()
+    }
+}
+
+impl Display for PolicyLint {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        for finding in self.findings.iter() {
+            writeln!(f, "{finding}")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Returns the actions that appear more than once in a list, in order of their second appearance.
+fn duplicates(list: &ActionList) -> Vec<String> {
+    let mut seen = HashSet::new();
+    list.iter().map(ToString::to_string).filter(|action| !seen.insert(action.clone())).collect()
+}
+
+fn uses_variables(statement: &Statement) -> bool {
+    let in_resources = [statement.resource(), statement.not_resource()]
+        .into_iter()
+        .flatten()
+        .flat_map(|list| list.iter())
+        .any(|resource| resource.to_string().contains("${"));
+    let in_conditions = statement
+        .condition()
+        .map(|condition| condition.values().flat_map(|map| map.values()).flat_map(|values| values.iter()))
+        .into_iter()
+        .flatten()
+        .any(|value| value.contains("${"));
+
+    in_resources || in_conditions
+}
+
+/// Indicates whether the statement applies to every action on every resource without a condition. `NotAction` and
+/// `NotResource` only count as "every" when nothing is excluded.
+fn is_admin(statement: &Statement) -> bool {
+    let all_actions = statement.not_action().map(|actions| actions.is_empty()).unwrap_or(false)
+        || statement.action().map(|actions| actions.iter().any(|a| a.is_any())).unwrap_or(false);
+    let all_resources = statement.not_resource().map(|resources| resources.is_empty()).unwrap_or(false)
+        || statement.resource().map(|resources| resources.iter().any(Resource::is_any)).unwrap_or(false);
+
+    all_actions && all_resources && statement.condition().map(|c| c.is_empty()).unwrap_or(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        crate::{ActionCatalog, LintKind, LintSeverity, Policy, PolicyLint},
+        pretty_assertions::assert_eq,
+        std::str::FromStr,
+    };
+
+    fn kinds(lint: &PolicyLint) -> Vec<LintKind> {
+        lint.findings().iter().map(|f| f.kind()).collect()
+    }
+
+    #[test_log::test]
+    fn test_clean_and_versions() {
+        let clean = Policy::from_str(
+            r#"{"Version": "2012-10-17", "Statement": {"Sid": "Home", "Effect": "Allow", "Action": "s3:GetObject",
+                "Resource": "arn:aws:s3:::bucket/${aws:username}/*"}}"#,
+        )
+        .unwrap();
+        let lint = PolicyLint::check(&clean);
+        assert!(lint.is_empty());
+        assert_eq!(lint.to_string(), "");
+
+        let legacy = Policy::from_str(
+            r#"{"Version": "2008-10-17", "Statement": {"Effect": "Allow", "Action": "s3:GetObject",
+                "Resource": "*", "Condition": {"StringEquals": {"s3:prefix": "${aws:username}/"}}}}"#,
+        )
+        .unwrap();
+        assert_eq!(kinds(&PolicyLint::check(&legacy)), vec![LintKind::LegacyVersionVariables]);
+
+        // Only mention variables when the policy uses them.
+        let unversioned = |resource: &str| {
+            let policy = Policy::from_str(&format!(
+                r#"{{"Statement": {{"Effect": "Allow", "Action": "s3:GetObject", "Resource": "{resource}"}}}}"#
+            ))
+            .unwrap();
+            PolicyLint::check(&policy).findings()[0].message().to_string()
+        };
+        assert_eq!(unversioned("arn:aws:s3:::bucket/*"), "no Version is specified");
+        assert_eq!(
+            unversioned("arn:aws:s3:::bucket/${aws:username}/*"),
+            "no Version is specified; policy variables will not be substituted"
+        );
+
+        let empty = Policy::from_str(r#"{"Version": "2012-10-17", "Statement": []}"#).unwrap();
+        let lint = PolicyLint::check(&empty);
+        assert_eq!(kinds(&lint), vec![LintKind::EmptyPolicy]);
+        assert!(!lint.has_errors());
+        assert_eq!(lint.to_string(), "warning[EmptyPolicy]: the policy has no statements\n");
+    }
+
+    #[test_log::test]
+    fn test_statement_findings() {
+        let policy = Policy::from_str(
+            r#"{"Version": "2012-10-17", "Statement": [
+                {"Sid": "Public", "Effect": "Allow", "Principal": "*", "Action": "s3:GetObject",
+                 "Resource": "arn:aws:s3:::bucket/*"},
+                {"Sid": "OrgOnly", "Effect": "Allow", "Principal": "*", "Action": "s3:GetObject",
+                 "Resource": "arn:aws:s3:::bucket/*", "Condition": {"StringEquals": {"aws:PrincipalOrgID": "o-1"}}},
+                {"Sid": "Public", "Effect": "Allow", "NotPrincipal": {"AWS": "arn:aws:iam::123456789012:root"},
+                 "Action": "s3:ListBucket", "NotResource": "arn:aws:s3:::secret"},
+                {"Effect": "Deny", "NotAction": "s3:*", "Resource": "*"},
+                {"Effect": "Allow", "Action": ["ec2:RunInstance", "ec2:Describe*"], "Resource": "*"}
+            ]}"#,
+        )
+        .unwrap();
+
+        let lint = PolicyLint::check(&policy);
+        assert_eq!(
+            kinds(&lint),
+            vec![
+                LintKind::PublicAccess,
+                LintKind::DuplicateSid,
+                LintKind::AllowNotResource,
+                LintKind::AllowNotPrincipal,
+                LintKind::PublicAccess,
+            ]
+        );
+        assert_eq!(lint.findings()[1].severity(), LintSeverity::Error);
+        assert_eq!(lint.findings()[1].statement_index(), Some(2));
+        assert_eq!(
+            lint.findings()[1].to_string(),
+            r#"error[DuplicateSid] statement 2 "Public": Sid "Public" is not unique"#
+        );
+
+        // NotAction and NotResource only grant everything when nothing is excluded.
+        let not_action = |not_action: &str| {
+            let policy = Policy::from_str(&format!(
+                r#"{{"Version": "2012-10-17", "Statement": {{"Effect": "Allow", "NotAction": {not_action},
+                    "Resource": "*"}}}}"#
+            ))
+            .unwrap();
+            kinds(&PolicyLint::check(&policy))
+        };
+        assert_eq!(not_action(r#""iam:*""#), vec![LintKind::AllowNotAction]);
+        assert_eq!(not_action("[]"), vec![LintKind::AllowNotAction, LintKind::AdminAccess]);
+
+        let mut catalog = ActionCatalog::new();
+        catalog.insert("ec2", "RunInstances");
+        catalog.insert("ec2", "DescribeInstances");
+        catalog.insert("s3", "GetObject");
+        catalog.insert("s3", "ListBucket");
+        let lint = PolicyLint::check_with_catalog(&policy, &catalog);
+        let unknown: Vec<_> = lint.findings().iter().filter(|f| f.kind() == LintKind::UnknownAction).collect();
+        assert_eq!(unknown.len(), 1);
+        assert_eq!(unknown[0].message(), "ec2:RunInstance does not match any known action");
+    }
+}
@@ -0,0 +1,22 @@
+//! Static analysis of Aspen policies.
+//!
+//! Most of the analyses here reason about policies by enumerating a finite request space derived from the patterns in the
+//! policies themselves (see `space::RequestSpace` for the details and limitations).
+
+mod access;
+pub(crate) mod compare;
+mod diff;
+mod escalation;
+pub(crate) mod glob;
+mod lint;
+pub(crate) mod space;
+mod usage;
+
+pub use {
+    access::{AccessFinding, AccessRestriction, ExternalAccess, ExternalAccessKind},
+    compare::{Counterexample, Verdict},
+    diff::{AccessChange, DecisionChange, PolicyDiff},
+    escalation::{EscalationFinding, EscalationMethod, EscalationStep, Evidence, IamModel},
+    lint::{LintFinding, LintKind, LintSeverity, PolicyLint},
+    usage::{StatementUsage, UsageReport},
+};
@@ -0,0 +1,834 @@
+use {
+    super::glob::{
+        glob_tokens, glob_tokens_subst, literal_tokens, pattern_variables, Domain, GlobPartition, GlobToken,
+        MAX_GLOB_STATES,
+    },
+    crate::{
+        condition::string::StringCmp, AwsPrincipal, ConditionOp, Context, Policy, PolicyVersion, Principal, Resource,
+        Statement,
+    },
+    chrono::{DateTime, Duration, SecondsFormat, Utc},
+    ipnet::IpNet,
+    scratchstack_arn::Arn,
+    scratchstack_aws_principal::{
+        AssumedRole, CanonicalUser, FederatedUser, Principal as PrincipalActor, PrincipalIdentity, RootUser, Service,
+        SessionData, SessionValue, User,
+    },
+    std::{
+        collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+        net::{IpAddr, Ipv4Addr, Ipv6Addr},
+        str::FromStr,
+    },
+};
+
+/// The maximum number of condition value combinations enumerated for a single action before sampling.
+const MAX_SESSION_COMBINATIONS: usize = 1024;
+
+/// The maximum number of requests generated for a single action before sampling.
+const MAX_REQUESTS_PER_ACTION: usize = 8192;
+
+/// The account used for principals that are not constrained by any policy.
+const DEFAULT_ACCOUNT: &str = "123456789012";
+
+/// The name used for fresh services, users, and values that do not appear in any policy.
+const FRESH_NAME: &str = "aspen-analysis";
+
+/// The actor, resources, and session data of a request; the action is supplied separately.
+type RequestWitness = (PrincipalActor, Vec<Arn>, SessionData);
+
+/// A finite set of request contexts that exercises the behavior of a group of policies.
+///
+/// The space is built from the patterns that appear in the policies rather than from a catalog: every action,
+/// resource, principal, and condition value is a witness for one equivalence class of requests that the policies
+/// cannot tell apart. Evaluating the policies against each context in the space is therefore equivalent to evaluating
+/// them against every possible request, with the following caveats:
+///
+/// * Requests carry at most one resource, and actors carry a single identity.
+/// * Condition values are only enumerated exactly when each condition key is used with a single family of operators
+///   and without variable substitution; otherwise, representative values are sampled.
+/// * Very large spaces are sampled rather than enumerated.
+///
+/// If any of these approximations were needed, [RequestSpace::is_exact] returns `false`.
+#[derive(Clone, Debug)]
+pub(crate) struct RequestSpace {
+    contexts: Vec<Context>,
+    exact: bool,
+}
+
+impl RequestSpace {
+    /// Builds the request space for the given policies.
+    pub(crate) fn new<'a, I: IntoIterator<Item = &'a Policy>>(policies: I) -> Self {
+        let statements: Vec<(&Statement, PolicyVersion)> = policies
+            .into_iter()
+            .flat_map(|policy| policy.statement().iter().map(move |statement| (statement, policy.version())))
+            .collect();
+
+        let (actions, mut exact) = action_witnesses(&statements);
+        let mut cache: HashMap<Vec<usize>, Vec<RequestWitness>> = HashMap::new();
+        let mut contexts = Vec::new();
+
+        for (service, api) in actions {
+            let relevant: Vec<usize> = statements
+                .iter()
+                .enumerate()
+                .filter(|(_, (statement, _))| action_applies(statement, &service, &api))
+                .map(|(i, _)| i)
+                .collect();
+
+            let requests = cache.entry(relevant).or_insert_with_key(|relevant| {
+                let relevant: Vec<_> = relevant.iter().map(|i| statements[*i]).collect();
+                let (requests, requests_exact) = request_witnesses(&relevant);
+                exact &= requests_exact;
+                requests
+            });
+
+            for (actor, resources, session_data) in requests.iter() {
+                contexts.push(
+                    Context::builder()
+                        .service(service.as_str())
+                        .api(api.as_str())
+                        .actor(actor.clone())
+                        .resources(resources.clone())
+                        .session_data(session_data.clone())
+                        .build()
+                        .expect("all context fields are set"),
+                );
+            }
+        }
+
+        Self {
+            contexts,
+            exact,
+        }
+    }
+
+    /// Returns the contexts in the request space.
+    #[inline]
+    pub(crate) fn contexts(&self) -> &[Context] {
+        &self.contexts
+    }
+
+    /// Indicates whether the space covers every equivalence class of requests, subject to the single-resource and
+    /// single-identity restrictions.
+    #[inline]
+    pub(crate) fn is_exact(&self) -> bool {
+        self.exact
+    }
+}
+
+/// Describes a request context on a single line, e.g. for reports.
+pub(crate) fn describe_request(context: &Context) -> String {
+    let mut result = format!("{}:{}", context.service(), context.api());
+
+    match context.resources().as_slice() {
+        [] => result.push_str(" without a resource"),
+        resources => {
+            let resources: Vec<String> = resources.iter().map(Arn::to_string).collect();
+            result.push_str(" on ");
+            result.push_str(&resources.join(", "));
+        }
+    }
+
+    let identities: Vec<String> = context.actor().iter().map(PrincipalIdentity::to_string).collect();
+    result.push_str(" by ");
+    result.push_str(&identities.join(", "));
+
+    let mut session: Vec<(&String, &SessionValue)> = context.session_data().iter().collect();
+    if !session.is_empty() {
+        session.sort();
+        let values: Vec<String> = session
+            .into_iter()
+            .map(|(key, value)| match value {
+                SessionValue::Timestamp(t) => format!("{key}={}", t.to_rfc3339_opts(SecondsFormat::AutoSi, true)),
+                value => format!("{key}={value}"),
+            })
+            .collect();
+        result.push_str(" with ");
+        result.push_str(&values.join(", "));
+    }
+
+    result
+}
+
+/// Indicates whether the statement's `Action` or `NotAction` element applies to the given service and API.
+fn action_applies(statement: &Statement, service: &str, api: &str) -> bool {
+    if let Some(actions) = statement.action() {
+        actions.iter().any(|action| action.matches(service, api))
+    } else if let Some(actions) = statement.not_action() {
+        !actions.iter().any(|action| action.matches(service, api))
+    } else {
+        false
+    }
+}
+
+/// Returns one `(service, api)` pair for each class of actions the statements distinguish.
+fn action_witnesses(statements: &[(&Statement, PolicyVersion)]) -> (Vec<(String, String)>, bool) {
+    let mut by_service: BTreeMap<&str, Vec<Vec<GlobToken>>> = BTreeMap::new();
+    let mut needs_fresh = false;
+
+    for (statement, _) in statements {
+        let actions = match (statement.action(), statement.not_action()) {
+            (Some(actions), _) => actions,
+            (None, Some(actions)) => {
+                needs_fresh = true;
+                actions
+            }
+            (None, None) => continue,
+        };
+
+        for action in actions.iter() {
+            match action.specific() {
+                None => needs_fresh = true,
+                Some((service, api)) => by_service.entry(service).or_default().push(glob_tokens(api)),
+            }
+        }
+    }
+
+    let mut result = Vec::new();
+    let mut exact = true;
+
+    for (service, patterns) in by_service.iter() {
+        let partition = GlobPartition::new(patterns, Domain::NonEmpty, MAX_GLOB_STATES);
+        exact &= partition.is_exact();
+        for (api, _) in partition.classes() {
+            result.push((service.to_string(), api.clone()));
+        }
+    }
+
+    if needs_fresh {
+        let service = fresh_name(|name| by_service.contains_key(name));
+        result.push((service, "Action".to_string()));
+    }
+
+    (result, exact)
+}
+
+/// Returns `FRESH_NAME`, or `FRESH_NAME` with a numeric suffix, such that `used` returns `false`.
+fn fresh_name<F: Fn(&str) -> bool>(used: F) -> String {
+    let mut name = FRESH_NAME.to_string();
+    let mut suffix = 2;
+    while used(&name) {
+        name = format!("{FRESH_NAME}-{suffix}");
+        suffix += 1;
+    }
+    name
+}
+
+/// Returns the actor, resources, and session data for each class of requests the statements distinguish. All of the
+/// statements are assumed to apply to the requested action.
+fn request_witnesses(statements: &[(&Statement, PolicyVersion)]) -> (Vec<RequestWitness>, bool) {
+    let actors = actor_witnesses(statements);
+    let (sessions, mut exact) = session_witnesses(statements);
+
+    // Resource patterns may refer to session variables, so the resources have to be partitioned once for each
+    // distinct substitution.
+    let mut resource_cache: HashMap<Vec<[Vec<GlobToken>; 5]>, Vec<Vec<Arn>>> = HashMap::new();
+    let mut pairs = Vec::new();
+    let mut resources_per_session = Vec::with_capacity(sessions.len());
+
+    for (i, session_data) in sessions.iter().enumerate() {
+        let patterns = resource_patterns(statements, session_data);
+        let resources = resource_cache.entry(patterns).or_insert_with_key(|patterns| {
+            let (arns, arns_exact) = arn_witnesses(patterns);
+            exact &= arns_exact;
+            let mut resources = vec![vec![]];
+            resources.extend(arns.into_iter().map(|arn| vec![arn]));
+            resources
+        });
+
+        for j in 0..resources.len() {
+            pairs.push((i, j));
+        }
+        resources_per_session.push(resources.clone());
+    }
+
+    let (selected, selected_exact) = combinations(&[pairs.len(), actors.len()], MAX_REQUESTS_PER_ACTION);
+    exact &= selected_exact;
+
+    let result = selected
+        .into_iter()
+        .map(|indices| {
+            let (session, resource) = pairs[indices[0]];
+            (actors[indices[1]].clone(), resources_per_session[session][resource].clone(), sessions[session].clone())
+        })
+        .collect();
+
+    (result, exact)
+}
+
+/// Returns the tokenized segments of every ARN pattern in the statements' `Resource` and `NotResource` elements.
+fn resource_patterns(
+    statements: &[(&Statement, PolicyVersion)],
+    session_data: &SessionData,
+) -> Vec<[Vec<GlobToken>; 5]> {
+    let mut result = Vec::new();
+
+    for (statement, pv) in statements {
+        let resources = statement.resource().or_else(|| statement.not_resource());
+        for resource in resources.iter().flat_map(|resources| resources.iter()) {
+            if let Resource::Arn(arn) = resource {
+                let resource_tokens = match pv {
+                    PolicyVersion::None | PolicyVersion::V2008_10_17 => Some(glob_tokens(arn.resource_pattern())),
+                    PolicyVersion::V2012_10_17 => glob_tokens_subst(arn.resource_pattern(), session_data),
+                };
+
+                // Malformed substitutions cause evaluation to fail regardless of the request.
+                if let Some(resource_tokens) = resource_tokens {
+                    result.push([
+                        glob_tokens(arn.partition_pattern()),
+                        glob_tokens(arn.service_pattern()),
+                        glob_tokens(arn.region_pattern()),
+                        glob_tokens(arn.account_id_pattern()),
+                        resource_tokens,
+                    ]);
+                }
+            }
+        }
+    }
+
+    result
+}
+
+/// Returns one valid ARN for each class of ARNs the given segmented patterns distinguish.
+///
+/// Each segment is partitioned separately. The segments are then combined left to right, keeping only one prefix for
+/// each distinct set of patterns that still match; since an ARN pattern matches only if every segment matches, this
+/// is exact without enumerating the full product of the segment partitions.
+fn arn_witnesses(patterns: &[[Vec<GlobToken>; 5]]) -> (Vec<Arn>, bool) {
+    if patterns.is_empty() {
+        let arn = Arn::new("aws", FRESH_NAME, "us-east-1", DEFAULT_ACCOUNT, FRESH_NAME).expect("ARN is valid");
+        return (vec![arn], true);
+    }
+
+    const DOMAINS: [Domain; 5] = [Domain::Partition, Domain::Service, Domain::Region, Domain::AccountId, Domain::Any];
+    let mut exact = true;
+    let mut frontier: Vec<(Vec<&str>, Vec<bool>)> = vec![(vec![], vec![true; patterns.len()])];
+    let partitions: Vec<GlobPartition> = DOMAINS
+        .iter()
+        .enumerate()
+        .map(|(segment, domain)| {
+            let segment_patterns: Vec<_> = patterns.iter().map(|p| p[segment].clone()).collect();
+            let partition = GlobPartition::new(&segment_patterns, *domain, MAX_GLOB_STATES);
+            exact &= partition.is_exact();
+            partition
+        })
+        .collect();
+
+    for partition in partitions.iter() {
+        let mut next = Vec::new();
+        let mut seen = HashSet::new();
+
+        for (prefix, alive) in frontier.iter() {
+            for (witness, matched) in partition.classes() {
+                let alive: Vec<bool> = alive.iter().zip(matched).map(|(a, m)| *a && *m).collect();
+                if seen.insert(alive.clone()) {
+                    let mut prefix = prefix.clone();
+                    prefix.push(witness.as_str());
+                    next.push((prefix, alive));
+                }
+            }
+        }
+
+        frontier = next;
+    }
+
+    let arns = frontier.into_iter().filter_map(|(s, _)| Arn::new(s[0], s[1], s[2], s[3], s[4]).ok()).collect();
+    (arns, exact)
+}
+
+/// Returns one actor for each class of principals the statements' `Principal` and `NotPrincipal` elements
+/// distinguish.
+fn actor_witnesses(statements: &[(&Statement, PolicyVersion)]) -> Vec<PrincipalActor> {
+    let default_user = User::new("aws", DEFAULT_ACCOUNT, "/", FRESH_NAME).expect("user is valid");
+    let principals: Vec<&Principal> =
+        statements.iter().filter_map(|(statement, _)| statement.principal().or(statement.not_principal())).collect();
+
+    if principals.is_empty() {
+        return vec![PrincipalActor::from(vec![default_user.into()])];
+    }
+
+    let mut identities: BTreeSet<PrincipalIdentity> = BTreeSet::new();
+    let mut accounts: BTreeSet<(String, String)> = BTreeSet::new();
+    let mut canonical_users: BTreeSet<&str> = BTreeSet::new();
+    let mut federated_users: BTreeSet<&str> = BTreeSet::new();
+    let mut role_names: BTreeSet<&str> = BTreeSet::new();
+
+    for principal in principals.iter().filter_map(|p| p.specified()) {
+        for aws in principal.aws().iter().flat_map(|aws| aws.iter()) {
+            match aws {
+                AwsPrincipal::Any => (),
+                AwsPrincipal::Account(account_id) => {
+                    accounts.insert(("aws".to_string(), account_id.clone()));
+                }
+                AwsPrincipal::Arn(arn) => {
+                    accounts.insert((arn.partition().to_string(), arn.account_id().to_string()));
+                    let identity = if arn.service() == "iam" && arn.resource() == "root" {
+                        RootUser::new(arn.partition(), arn.account_id()).ok().map(PrincipalIdentity::from)
+                    } else if let Some(user_name) = arn.resource().strip_prefix("federated-user/") {
+                        FederatedUser::new(arn.partition(), arn.account_id(), user_name)
+                            .ok()
+                            .map(PrincipalIdentity::from)
+                    } else if let Some(role) = arn.resource().strip_prefix("role/").filter(|_| arn.service() == "iam") {
+                        // A role ARN matches the sessions of the role, which have no IAM identity of their own.
+                        let role_name = role.rsplit('/').next().unwrap_or(role);
+                        role_names.insert(role_name);
+                        AssumedRole::new(arn.partition(), arn.account_id(), role_name, FRESH_NAME)
+                            .ok()
+                            .map(PrincipalIdentity::from)
+                    } else {
+                        PrincipalIdentity::parse_arn(&arn.to_string()).ok()
+                    };
+                    identities.extend(identity);
+                }
+            }
+        }
+
+        for canonical_user in principal.canonical_user().iter().flat_map(|c| c.iter()) {
+            canonical_users.insert(canonical_user);
+            identities.extend(CanonicalUser::new(canonical_user).ok().map(PrincipalIdentity::from));
+        }
+
+        for federated in principal.federated().iter().flat_map(|f| f.iter()) {
+            federated_users.insert(federated);
+            identities.extend(FederatedUser::new("aws", DEFAULT_ACCOUNT, federated).ok().map(PrincipalIdentity::from));
+        }
+
+        for service in principal.service().iter().flat_map(|s| s.iter()) {
+            if let Some((name, dns_suffix)) = service.split_once('.') {
+                identities.extend(Service::new(name, None, dns_suffix).ok().map(PrincipalIdentity::from));
+            }
+        }
+    }
+
+    // A session of a role that no policy names, in each account.
+    let fresh_role = fresh_name(|name| role_names.contains(name));
+    for (partition, account_id) in accounts.iter() {
+        identities.extend(RootUser::new(partition, account_id).ok().map(PrincipalIdentity::from));
+        identities.extend(User::new(partition, account_id, "/", FRESH_NAME).ok().map(PrincipalIdentity::from));
+        identities
+            .extend(AssumedRole::new(partition, account_id, &fresh_role, FRESH_NAME).ok().map(PrincipalIdentity::from));
+    }
+
+    // Fresh identities of each kind that no policy names.
+    let fresh_account = (0..=9)
+        .map(|d| d.to_string().repeat(12))
+        .find(|account_id| !accounts.iter().any(|(_, a)| a == account_id))
+        .expect("at most ten accounts can be excluded");
+    identities.insert(User::new("aws", &fresh_account, "/", FRESH_NAME).expect("user is valid").into());
+
+    let fresh_canonical_user = (0..=9)
+        .map(|d| d.to_string().repeat(64))
+        .find(|id| !canonical_users.contains(id.as_str()))
+        .expect("at most ten canonical users can be excluded");
+    identities.insert(CanonicalUser::new(&fresh_canonical_user).expect("canonical user is valid").into());
+
+    let fresh_federated_user = fresh_name(|name| federated_users.contains(name));
+    identities
+        .extend(FederatedUser::new("aws", DEFAULT_ACCOUNT, &fresh_federated_user).ok().map(PrincipalIdentity::from));
+    identities.insert(Service::new(FRESH_NAME, None, "amazonaws.com").expect("service is valid").into());
+
+    identities.into_iter().map(|identity| PrincipalActor::from(vec![identity])).collect()
+}
+
+/// The condition clauses that refer to a single (case-insensitive) condition key.
+#[derive(Default)]
+struct KeyUsage<'a> {
+    key: String,
+    clauses: Vec<(ConditionOp, Vec<&'a str>, PolicyVersion)>,
+}
+
+/// Returns one set of session data for each class of condition values the statements distinguish.
+fn session_witnesses(statements: &[(&Statement, PolicyVersion)]) -> (Vec<SessionData>, bool) {
+    let mut usages: BTreeMap<String, KeyUsage> = BTreeMap::new();
+    let mut variables: BTreeMap<String, String> = BTreeMap::new();
+
+    for (statement, pv) in statements {
+        if let Some(condition) = statement.condition() {
+            for (op, map) in condition.iter() {
+                for (key, values) in map.iter() {
+                    let usage = usages.entry(key.to_lowercase()).or_default();
+                    if usage.key.is_empty() {
+                        usage.key = key.clone();
+                    }
+                    usage.clauses.push((*op, values.iter().map(String::as_str).collect(), *pv));
+
+                    if *pv == PolicyVersion::V2012_10_17 {
+                        for var in values.iter().flat_map(|v| pattern_variables(v)) {
+                            variables.entry(var.to_lowercase()).or_insert(var);
+                        }
+                    }
+                }
+            }
+        }
+
+        if *pv == PolicyVersion::V2012_10_17 {
+            let resources = statement.resource().or_else(|| statement.not_resource());
+            for resource in resources.iter().flat_map(|resources| resources.iter()) {
+                if let Resource::Arn(arn) = resource {
+                    for var in pattern_variables(arn.resource_pattern()) {
+                        variables.entry(var.to_lowercase()).or_insert(var);
+                    }
+                }
+            }
+        }
+    }
+
+    let mut exact = variables.is_empty();
+    let mut keys = Vec::with_capacity(usages.len() + variables.len());
+    let mut candidates = Vec::with_capacity(usages.len() + variables.len());
+
+    for (_, usage) in usages.iter() {
+        let (values, values_exact) = key_candidates(usage);
+        exact &= values_exact;
+        keys.push(usage.key.as_str());
+        candidates.push(values);
+    }
+
+    // Variables that are only substituted, never tested directly, are either absent or set to a fresh value.
+    for (lower, var) in variables.iter() {
+        if !usages.contains_key(lower) {
+            keys.push(var.as_str());
+            candidates.push(vec![SessionValue::Null, SessionValue::from(FRESH_NAME)]);
+        }
+    }
+
+    let radices: Vec<usize> = candidates.iter().map(Vec::len).collect();
+    let (selected, selected_exact) = combinations(&radices, MAX_SESSION_COMBINATIONS);
+    exact &= selected_exact;
+
+    let sessions = selected
+        .into_iter()
+        .map(|indices| {
+            let mut session_data = SessionData::new();
+            for (k, i) in indices.into_iter().enumerate() {
+                let value = &candidates[k][i];
+                if !value.is_null() {
+                    session_data.insert(keys[k], value.clone());
+                }
+            }
+            session_data
+        })
+        .collect();
+
+    (sessions, exact)
+}
+
+/// Returns candidate values for a single condition key, starting with [SessionValue::Null] (the key is absent).
+fn key_candidates(usage: &KeyUsage) -> (Vec<SessionValue>, bool) {
+    let mut values = vec![SessionValue::Null];
+    let mut families = BTreeSet::new();
+    let mut exact = true;
+    let mut string_patterns = Vec::new();
+    let mut arn_patterns = Vec::new();
+    let empty = SessionData::new();
+
+    for (op, allowed, pv) in usage.clauses.iter() {
+        for value in allowed.iter() {
+            // Variables are resolved against an empty session; the values they take at evaluation time are not
+            // known here.
+            let resolved = match pv {
+                PolicyVersion::V2012_10_17 if !pattern_variables(value).is_empty() => {
+                    exact = false;
+                    glob_tokens_subst(value, &empty)
+                        .map(|tokens| {
+                            tokens
+                                .iter()
+                                .map(|t| match t {
+                                    GlobToken::Literal(c) => *c,
+                                    GlobToken::AnyChar => '?',
+                                    GlobToken::AnyString => '*',
+                                })
+                                .collect::<String>()
+                        })
+                        .unwrap_or_default()
+                }
+                _ => value.to_string(),
+            };
+
+            match op {
+                ConditionOp::String(cmp, _) => {
+                    families.insert("String");
+                    match cmp {
+                        StringCmp::Like => string_patterns.push(glob_tokens(&resolved)),
+                        StringCmp::Equals => string_patterns.push(literal_tokens(&resolved)),
+                        StringCmp::EqualsIgnoreCase => {
+                            exact = false;
+                            string_patterns.push(literal_tokens(&resolved));
+                            string_patterns.push(literal_tokens(&resolved.to_lowercase()));
+                            string_patterns.push(literal_tokens(&resolved.to_uppercase()));
+                        }
+                    }
+                }
+                ConditionOp::Numeric(_, _) => {
+                    families.insert("Numeric");
+                    if let Ok(n) = i64::from_str(&resolved) {
+                        values.push(SessionValue::Integer(n.saturating_sub(1)));
+                        values.push(SessionValue::Integer(n));
+                        values.push(SessionValue::Integer(n.saturating_add(1)));
+                    }
+                }
+                ConditionOp::Date(_, _) => {
+                    families.insert("Date");
+                    let parsed = match DateTime::parse_from_rfc3339(&resolved) {
+                        Ok(parsed) => Some(parsed.with_timezone(&Utc)),
+                        Err(_) => i64::from_str(&resolved).ok().and_then(|secs| DateTime::from_timestamp(secs, 0)),
+                    };
+                    if let Some(parsed) = parsed {
+                        let epsilon = Duration::nanoseconds(1);
+                        values.push(SessionValue::Timestamp(parsed - epsilon));
+                        values.push(SessionValue::Timestamp(parsed));
+                        values.push(SessionValue::Timestamp(parsed + epsilon));
+                    }
+                }
+                ConditionOp::IpAddress(_) => {
+                    families.insert("IpAddress");
+                    let parsed =
+                        resolved.parse::<IpNet>().ok().or_else(|| resolved.parse::<IpAddr>().ok().map(IpNet::from));
+                    if let Some(net) = parsed {
+                        // Every interval between network boundaries starts either at the first address of a network,
+                        // just after the last address of a network, or at the zero address.
+                        values.push(SessionValue::IpAddr(net.network()));
+                        let after = match net.broadcast() {
+                            IpAddr::V4(last) => u32::from(last).checked_add(1).map(|a| IpAddr::V4(Ipv4Addr::from(a))),
+                            IpAddr::V6(last) => u128::from(last).checked_add(1).map(|a| IpAddr::V6(Ipv6Addr::from(a))),
+                        };
+                        values.extend(after.map(SessionValue::IpAddr));
+                        values.push(SessionValue::IpAddr(match net {
+                            IpNet::V4(_) => IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+                            IpNet::V6(_) => IpAddr::V6(Ipv6Addr::UNSPECIFIED),
+                        }));
+                    }
+                }
+                ConditionOp::Bool(_) => {
+                    families.insert("Bool");
+                    values.push(SessionValue::Bool(true));
+                    values.push(SessionValue::Bool(false));
+                }
+                ConditionOp::Binary(_) => {
+                    families.insert("Binary");
+                    if let Ok(decoded) = base64::decode(&resolved) {
+                        values.push(SessionValue::Binary(decoded));
+                    }
+                    values.push(SessionValue::Binary(FRESH_NAME.as_bytes().to_vec()));
+                }
+                ConditionOp::Arn(_, _) => {
+                    families.insert("Arn");
+                    let parts: Vec<&str> = resolved.splitn(6, ':').collect();
+                    if parts.len() == 6 && parts[0] == "arn" {
+                        arn_patterns.push([
+                            glob_tokens(parts[1]),
+                            glob_tokens(parts[2]),
+                            glob_tokens(parts[3]),
+                            glob_tokens(parts[4]),
+                            glob_tokens(parts[5]),
+                        ]);
+                    }
+                }
+                ConditionOp::Null => (),
+            }
+        }
+    }
+
+    if !string_patterns.is_empty() {
+        let partition = GlobPartition::new(&string_patterns, Domain::Any, MAX_GLOB_STATES);
+        exact &= partition.is_exact();
+        values.extend(partition.classes().iter().map(|(witness, _)| SessionValue::String(witness.clone())));
+    }
+
+    if families.contains("Arn") {
+        let (arns, arns_exact) = arn_witnesses(&arn_patterns);
+        exact &= arns_exact;
+        values.extend(arns.iter().map(|arn| SessionValue::String(arn.to_string())));
+    }
+
+    // Values of the wrong type (or that fail to parse) behave differently from every well-formed value. The Null
+    // operator only tests for presence, so it also needs some non-null value.
+    if families.iter().any(|f| matches!(*f, "Numeric" | "Date" | "IpAddress" | "Arn")) || families.is_empty() {
+        values.push(SessionValue::from(FRESH_NAME));
+    }
+
+    // The same value can be interpreted differently by operators of different families (e.g. "5" as a string and as
+    // a number), so the per-family candidates are only exact in isolation.
+    if families.len() > 1 {
+        exact = false;
+    }
+
+    let mut seen = HashSet::new();
+    values.retain(|value| seen.insert(value.clone()));
+    (values, exact)
+}
+
+/// Selects index tuples from lists with the given lengths.
+///
+/// If the cartesian product has at most `max` elements, the entire product is returned and the result is exact.
+/// Otherwise, `max` tuples are sampled deterministically; the first tuples cycle through every index of every list so
+/// that each value is exercised at least once where possible.
+fn combinations(radices: &[usize], max: usize) -> (Vec<Vec<usize>>, bool) {
+    if radices.contains(&0) {
+        return (vec![], true);
+    }
+
+    let total = radices.iter().try_fold(1usize, |acc, r| acc.checked_mul(*r));
+    match total {
+        Some(total) if total <= max => {
+            let mut result = Vec::with_capacity(total);
+            let mut indices = vec![0; radices.len()];
+            for _ in 0..total {
+                result.push(indices.clone());
+                for (index, radix) in indices.iter_mut().zip(radices).rev() {
+                    *index += 1;
+                    if *index < *radix {
+                        break;
+                    }
+                    *index = 0;
+                }
+            }
+            (result, true)
+        }
+        _ => {
+            let longest = radices.iter().copied().max().unwrap_or(0);
+            let mut sampler = Sampler::new();
+            let result = (0..max)
+                .map(|row| {
+                    radices
+                        .iter()
+                        .map(|radix| {
+                            if row < longest {
+                                row % radix
+                            } else {
+                                sampler.next(*radix)
+                            }
+                        })
+                        .collect()
+                })
+                .collect();
+            (result, false)
+        }
+    }
+}
+
+/// A small deterministic pseudo-random number generator (xorshift64*) for sampling oversized request spaces.
+struct Sampler(u64);
+
+impl Sampler {
+    fn new() -> Self {
+        Self(0x9e37_79b9_7f4a_7c15)
+    }
+
+    fn next(&mut self, bound: usize) -> usize {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        (self.0.wrapping_mul(0x2545_f491_4f6c_dd1d) % bound as u64) as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::{combinations, RequestSpace},
+        crate::{Decision, Policy},
+        pretty_assertions::assert_eq,
+        scratchstack_arn::Arn,
+        scratchstack_aws_principal::{PrincipalIdentity, SessionValue},
+        std::{collections::BTreeSet, str::FromStr},
+    };
+
+    #[test_log::test]
+    fn test_combinations() {
+        let (all, exact) = combinations(&[2, 3], 6);
+        assert!(exact);
+        assert_eq!(all, vec![vec![0, 0], vec![0, 1], vec![0, 2], vec![1, 0], vec![1, 1], vec![1, 2]]);
+
+        let (sampled, exact) = combinations(&[2, 3], 4);
+        assert!(!exact);
+        assert_eq!(sampled.len(), 4);
+        assert_eq!(&sampled[..3], &[vec![0, 0], vec![1, 1], vec![0, 2]]);
+
+        assert_eq!(combinations(&[], 4), (vec![vec![]], true));
+        assert_eq!(combinations(&[3, 0], 4), (vec![], true));
+    }
+
+    #[test_log::test]
+    fn test_space_covers_policy() {
+        let policy = Policy::from_str(
+            r#"{
+                "Version": "2012-10-17",
+                "Statement": [
+                    {
+                        "Effect": "Deny",
+                        "NotAction": "ec2:*",
+                        "Resource": "arn:aws:s3:::bucket/secret*"
+                    },
+                    {
+                        "Effect": "Allow",
+                        "Action": ["s3:Get*", "s3:*Object"],
+                        "Resource": "arn:aws:s3:::bucket/*",
+                        "Condition": {"NumericLessThan": {"s3:max-keys": "10"}}
+                    }
+                ]
+            }"#,
+        )
+        .unwrap();
+
+        let space = RequestSpace::new([&policy]);
+        assert!(space.is_exact());
+
+        let mut outcomes = Vec::new();
+        for context in space.contexts() {
+            let decision = policy.evaluate(context).unwrap();
+            let resource = context.resources().first().map(|r| r.to_string());
+            let max_keys = context.session_data().get("s3:max-keys").cloned();
+            outcomes.push((decision, format!("{}:{}", context.service(), context.api()), resource, max_keys));
+        }
+
+        // s3:GetObject on a non-secret object with a small max-keys is allowed...
+        assert!(outcomes.iter().any(|(d, api, r, m)| *d == Decision::Allow
+            && api.starts_with("s3:Get")
+            && api.ends_with("Object")
+            && r.as_deref().map(|r| r.starts_with("arn:aws:s3:::bucket/") && !r.contains("secret")).unwrap_or(false)
+            && *m == Some(SessionValue::Integer(9))));
+
+        // ... secret objects are always denied outside of ec2...
+        assert!(outcomes
+            .iter()
+            .filter(|(_, _, r, _)| r.as_deref().map(|r| r.starts_with("arn:aws:s3:::bucket/secret")).unwrap_or(false))
+            .all(|(d, api, _, _)| (*d == Decision::Deny) != api.starts_with("ec2:")));
+
+        // ... and the boundary value of the condition is exercised.
+        assert!(outcomes
+            .iter()
+            .any(|(d, _, _, m)| *d == Decision::DefaultDeny && *m == Some(SessionValue::Integer(10))));
+
+        // ec2 actions and a fresh service are both represented.
+        let services: std::collections::BTreeSet<_> = space.contexts().iter().map(|c| c.service()).collect();
+        assert_eq!(services.into_iter().collect::<Vec<_>>(), vec!["aspen-analysis", "ec2", "s3"]);
+    }
+
+    #[test_log::test]
+    fn test_space_covers_role_sessions() {
+        let policy = Policy::from_str(
+            r#"{"Statement": {"Effect": "Allow", "Action": "s3:GetObject", "Resource": "*",
+                "Principal": {"AWS": "arn:aws:iam::111111111111:role/ops/A"}}}"#,
+        )
+        .unwrap();
+        let space = RequestSpace::new([&policy]);
+        assert!(space.is_exact());
+
+        let sessions: BTreeSet<_> = space
+            .contexts()
+            .iter()
+            .flat_map(|c| c.actor().iter())
+            .filter(|identity| matches!(identity, PrincipalIdentity::AssumedRole(_)))
+            .map(|identity| Arn::try_from(identity).unwrap().to_string())
+            .collect();
+        assert_eq!(
+            sessions.into_iter().collect::<Vec<_>>(),
+            vec![
+                "arn:aws:sts::111111111111:assumed-role/A/aspen-analysis",
+                "arn:aws:sts::111111111111:assumed-role/aspen-analysis/aspen-analysis",
+            ]
+        );
+        assert!(space.contexts().iter().any(|c| policy.evaluate(c).unwrap() == Decision::Allow));
+    }
+}
@@ -0,0 +1,487 @@
+use {
+    crate::{
+        AccessRecord, Action, ActionCatalog, AspenError, Decision, Effect, Policy, PolicySet, Resource, Statement,
+        StatementList,
+    },
+    std::{
+        collections::BTreeSet,
+        fmt::{Display, Formatter, Result as FmtResult},
+    },
+};
+
+/// How often a statement was exercised by recorded requests.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct StatementUsage {
+    policy_index: usize,
+    statement_index: usize,
+    sid: Option<String>,
+    effect: Effect,
+    hits: usize,
+    used_actions: BTreeSet<String>,
+    unused_actions: Vec<String>,
+    unused_expansions: Vec<String>,
+    unused_resources: Vec<String>,
+}
+
+impl StatementUsage {
+    /// Returns the index of the policy in [PolicySet::policies].
+    #[inline]
+    pub fn policy_index(&self) -> usize {
+        self.policy_index
+    }
+
+    /// Returns the index of the statement within the policy.
+    #[inline]
+    pub fn statement_index(&self) -> usize {
+        self.statement_index
+    }
+
+    /// Returns the statement id, if any.
+    #[inline]
+    pub fn sid(&self) -> Option<&str> {
+        self.sid.as_deref()
+    }
+
+    /// Returns the effect of the statement.
+    #[inline]
+    pub fn effect(&self) -> &Effect {
+        &self.effect
+    }
+
+    /// Returns the number of recorded requests this statement allowed (or, for a Deny statement, denied).
+    #[inline]
+    pub fn hits(&self) -> usize {
+        self.hits
+    }
+
+    /// Returns the actions (in `service:Api` form) this statement allowed.
+    #[inline]
+    pub fn used_actions(&self) -> &BTreeSet<String> {
+        &self.used_actions
+    }
+
+    /// Returns the action patterns in the statement's `Action` element that no recorded request used.
+    #[inline]
+    pub fn unused_actions(&self) -> &[String] {
+        &self.unused_actions
+    }
+
+    /// Returns the actions from the [ActionCatalog] covered by a wildcard action pattern that was used, but which
+    /// were not used themselves.
+    #[inline]
+    pub fn unused_expansions(&self) -> &[String] {
+        &self.unused_expansions
+    }
+
+    /// Returns the resource patterns in the statement's `Resource` element that no recorded request used.
+    #[inline]
+    pub fn unused_resources(&self) -> &[String] {
+        &self.unused_resources
+    }
+
+    /// Indicates whether this is an Allow statement that no recorded request used.
+    #[inline]
+    pub fn is_unused(&self) -> bool {
+        self.effect == Effect::Allow && self.hits == 0
+    }
+}
+
+impl Display for StatementUsage {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        write!(f, "policy {}, statement {}", self.policy_index, self.statement_index)?;
+        if let Some(sid) = &self.sid {
+            write!(f, " {sid:?}")?;
+        }
+        write!(f, ": {} hits", self.hits)?;
+
+        for (label, items) in [
+            ("unused actions", &self.unused_actions),
+            ("unused wildcard actions", &self.unused_expansions),
+            ("unused resources", &self.unused_resources),
+        ] {
+            if !items.is_empty() {
+                write!(f, "; {label}: {}", items.join(", "))?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Bookkeeping for a statement while records are evaluated.
+#[derive(Default)]
+struct Tally {
+    hits: usize,
+    used_actions: BTreeSet<String>,
+    used_action_patterns: BTreeSet<usize>,
+    used_resource_patterns: BTreeSet<usize>,
+}
+
+/// The permissions in a [PolicySet] that were and were not exercised by a window of recorded requests.
+///
+/// Every recorded request is evaluated against the policy set. When it is allowed, it is attributed to every Allow
+/// statement that matches it, not just the first. When it is denied, it is attributed to the matching Deny
+/// statements. Requests that are not allowed do not count towards Allow statements.
+///
+/// # Example
+///
+/// ```
+/// # use scratchstack_aspen::{AccessRecord, ActionCatalog, Policy, PolicySet, PolicySource, UsageReport};
+/// # use std::str::FromStr;
+/// let user = "arn:aws:iam::123456789012:user/alice";
+/// let policy = Policy::from_str(r#"{"Statement": [
+///     {"Sid": "Read", "Effect": "Allow", "Action": "s3:Get*", "Resource": ["arn:aws:s3:::a/*", "arn:aws:s3:::b/*"]},
+///     {"Sid": "Admin", "Effect": "Allow", "Action": "iam:*", "Resource": "*"}]}"#).unwrap();
+/// let policy_set = PolicySet::from(vec![(PolicySource::new_entity_inline(user, "AIDA", "p"), policy)]);
+/// let records = [AccessRecord::new(Some(user.to_string()), "s3", "GetObject", vec!["arn:aws:s3:::a/k".to_string()])];
+///
+/// let report = UsageReport::new(&policy_set, &records, &ActionCatalog::new()).unwrap();
+/// assert_eq!(report.unused_statements().next().unwrap().sid(), Some("Admin"));
+/// assert_eq!(report.statements()[0].unused_resources(), &["arn:aws:s3:::b/*".to_string()]);
+///
+/// let trimmed = report.trimmed_policy(&policy_set, 0).unwrap();
+/// assert_eq!(trimmed.to_string().replace([' ', '\n'], ""),
+///     r#"{"Statement":[{"Sid":"Read","Effect":"Allow","Action":["s3:GetObject"],"Resource":["arn:aws:s3:::a/*"]}]}"#);
+/// ```
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct UsageReport {
+    statements: Vec<StatementUsage>,
+    records_evaluated: usize,
+    records_allowed: usize,
+}
+
+impl UsageReport {
+    /// Evaluates the recorded requests against the policy set. The catalog is used to list the actions covered by
+    /// wildcard patterns; it may be empty.
+    ///
+    /// # Errors
+    ///
+    /// If a record cannot be converted to a request (see [AccessRecord::to_context]) or a policy fails to evaluate,
+    /// the error is returned.
+    pub fn new<'a, I>(policy_set: &PolicySet, records: I, catalog: &ActionCatalog) -> Result<Self, AspenError>
+    where
+        I: IntoIterator<Item = &'a AccessRecord>,
+    {
+        let policies = policy_set.policies();
+        let mut tallies: Vec<Vec<Tally>> =
+            policies.iter().map(|(_, policy)| policy.statement().iter().map(|_| Tally::default()).collect()).collect();
+        let mut records_evaluated = 0;
+        let mut records_allowed = 0;
+
+        for record in records {
+            let context = record.to_context()?;
+            let (decision, _) = policy_set.evaluate(&context)?;
+            records_evaluated += 1;
+            if decision == Decision::Allow {
+                records_allowed += 1;
+            }
+
+            for (policy_index, (_, policy)) in policies.iter().enumerate() {
+                for (statement_index, statement_decision) in policy.matching_statements(&context)? {
+                    if statement_decision != decision {
+                        continue;
+                    }
+
+                    let statement = &policy.statement()[statement_index];
+                    let tally = &mut tallies[policy_index][statement_index];
+                    tally.hits += 1;
+                    tally.used_actions.insert(format!("{}:{}", record.service(), record.api()));
+
+                    if let Some(actions) = statement.action() {
+                        for (i, action) in actions.iter().enumerate() {
+                            if action.matches(context.service(), context.api()) {
+                                tally.used_action_patterns.insert(i);
+                            }
+                        }
+                    }
+
+                    if let Some(resources) = statement.resource() {
+                        for (i, resource) in resources.iter().enumerate() {
+                            let used = if context.resources().is_empty() {
+                                resource.is_any()
+                            } else {
+                                let mut used = false;
+                                for candidate in context.resources() {
+                                    used |= resource.matches(&context, policy.version(), candidate)?;
+                                }
+                                used
+                            };
+
+                            if used {
+                                tally.used_resource_patterns.insert(i);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        let mut statements = Vec::new();
+        for (policy_index, ((_, policy), policy_tallies)) in policies.iter().zip(tallies).enumerate() {
+            for (statement_index, (statement, tally)) in policy.statement().iter().zip(policy_tallies).enumerate() {
+                let mut unused_actions = Vec::new();
+                let mut unused_expansions = BTreeSet::new();
+                for (i, action) in
+                    statement.action().map(|a| a.iter().collect()).unwrap_or_else(Vec::new).into_iter().enumerate()
+                {
+                    if !tally.used_action_patterns.contains(&i) {
+                        unused_actions.push(action.to_string());
+                    } else if is_wildcard(action) {
+                        for (service, api) in catalog.expand(action) {
+                            let name = format!("{service}:{api}");
+                            if !tally.used_actions.contains(&name) {
+                                unused_expansions.insert(name);
+                            }
+                        }
+                    }
+                }
+
+                let unused_resources = statement
+                    .resource()
+                    .map(|r| r.iter().collect())
+                    .unwrap_or_else(Vec::new)
+                    .into_iter()
+                    .enumerate()
+                    .filter(|(i, _)| !tally.used_resource_patterns.contains(i))
+                    .map(|(_, resource)| resource.to_string())
+                    .collect();
+
+                statements.push(StatementUsage {
+                    policy_index,
+                    statement_index,
+                    sid: statement.sid().map(str::to_string),
+                    effect: *statement.effect(),
+                    hits: tally.hits,
+                    used_actions: tally.used_actions,
+                    unused_actions,
+                    unused_expansions: unused_expansions.into_iter().collect(),
+                    unused_resources,
+                });
+            }
+        }
+
+        Ok(Self {
+            statements,
+            records_evaluated,
+            records_allowed,
+        })
+    }
+
+    /// Returns the usage of every statement, ordered by policy and then statement.
+    #[inline]
+    pub fn statements(&self) -> &[StatementUsage] {
+        &self.statements
+    }
+
+    /// Returns the Allow statements that no recorded request used.
+    pub fn unused_statements(&self) -> impl Iterator<Item = &StatementUsage> {
+        self.statements.iter().filter(|s| s.is_unused())
+    }
+
+    /// Returns the number of records evaluated.
+    #[inline]
+    pub fn records_evaluated(&self) -> usize {
+        self.records_evaluated
+    }
+
+    /// Returns the number of records the policy set allowed.
+    #[inline]
+    pub fn records_allowed(&self) -> usize {
+        self.records_allowed
+    }
+
+    /// Returns a candidate replacement for the policy at `policy_index`, trimmed to the recorded usage.
+    ///
+    /// Unused Allow statements are removed. The `Action` element of the remaining Allow statements is replaced by the
+    /// actions actually used, and the `Resource` element is reduced to the patterns actually used. `NotAction` and
+    /// `NotResource` elements, principals, conditions, and Deny statements are kept unchanged.
+    ///
+    /// `policy_set` must be the policy set this report was created from. Returns `None` if `policy_index` is out of
+    /// range.
+    pub fn trimmed_policy(&self, policy_set: &PolicySet, policy_index: usize) -> Option<Policy> {
+        let (_, policy) = policy_set.policies().get(policy_index)?;
+        let mut statements = Vec::new();
+
+        for (statement, usage) in
+            policy.statement().iter().zip(self.statements.iter().filter(|s| s.policy_index == policy_index))
+        {
+            if usage.is_unused() {
+                continue;
+            }
+
+            if statement.effect() == &Effect::Deny {
+                statements.push(statement.clone());
+                continue;
+            }
+
+            statements.push(trim_statement(statement, usage));
+        }
+
+        let mut builder = Policy::builder();
+        builder.version(policy.version()).statement(StatementList::from(statements));
+        if let Some(id) = policy.id() {
+            builder.id(id);
+        }
+        Some(builder.build().expect("all policy fields are set"))
+    }
+}
+
+impl Display for UsageReport {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        writeln!(f, "{} of {} records allowed", self.records_allowed, self.records_evaluated)?;
+        for statement in self.statements.iter() {
+            writeln!(f, "{statement}")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Indicates whether an action pattern covers more than one action.
+fn is_wildcard(action: &Action) -> bool {
+    match action {
+        Action::Any => true,
+        Action::Specific(_) => action.api().contains(['*', '?']),
+    }
+}
+
+/// Rebuilds an Allow statement with only the actions and resource patterns that were used.
+fn trim_statement(statement: &Statement, usage: &StatementUsage) -> Statement {
+    let mut builder = Statement::builder();
+    builder.effect(*statement.effect());
+
+    if let Some(sid) = statement.sid() {
+        builder.sid(sid);
+    }
+
+    if let Some(not_action) = statement.not_action() {
+        builder.not_action(not_action.clone());
+    } else {
+        let actions: Vec<Action> = usage
+            .used_actions
+            .iter()
+            .map(|action| {
+                let (service, api) = action.split_once(':').expect("used actions are qualified");
+                Action::new(service, api).expect("used actions were matched by a valid pattern")
+            })
+            .collect();
+        builder.action(actions);
+    }
+
+    if let Some(not_resource) = statement.not_resource() {
+        builder.not_resource(not_resource.clone());
+    } else if let Some(resources) = statement.resource() {
+        let used: Vec<Resource> =
+            resources.iter().filter(|r| !usage.unused_resources.contains(&r.to_string())).cloned().collect();
+        builder.resource(used);
+    }
+
+    if let Some(principal) = statement.principal() {
+        builder.principal(principal.clone());
+    }
+
+    if let Some(not_principal) = statement.not_principal() {
+        builder.not_principal(not_principal.clone());
+    }
+
+    if let Some(condition) = statement.condition() {
+        builder.condition(condition.clone());
+    }
+
+    builder.build().expect("trimmed statement keeps the required elements")
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        crate::{AccessRecord, ActionCatalog, Policy, PolicySet, PolicySource, UsageReport},
+        pretty_assertions::assert_eq,
+        std::str::FromStr,
+    };
+
+    const USER: &str = "arn:aws:iam::123456789012:user/alice";
+
+    fn record(action: &str, resource: Option<&str>) -> AccessRecord {
+        let (service, api) = action.split_once(':').unwrap();
+        AccessRecord::new(Some(USER.to_string()), service, api, resource.into_iter().map(str::to_string).collect())
+    }
+
+    #[test_log::test]
+    fn test_usage_attribution() {
+        let identity = Policy::from_str(
+            r#"{"Version": "2012-10-17", "Id": "dev", "Statement": [
+                {"Sid": "S3", "Effect": "Allow", "Action": ["s3:Get*", "s3:List*", "s3:PutObject"],
+                 "Resource": ["arn:aws:s3:::data", "arn:aws:s3:::data/*", "arn:aws:s3:::other/*"]},
+                {"Sid": "Overlap", "Effect": "Allow", "Action": "s3:GetObject", "Resource": "*"},
+                {"Sid": "Ec2", "Effect": "Allow", "Action": "ec2:*", "Resource": "*"},
+                {"Sid": "NoDelete", "Effect": "Deny", "Action": "s3:DeleteObject", "Resource": "*"}
+            ]}"#,
+        )
+        .unwrap();
+        let policy_set =
+            PolicySet::from(vec![(PolicySource::new_entity_inline(USER, "AIDA", "dev"), identity.clone())]);
+
+        let mut catalog = ActionCatalog::new();
+        for api in ["GetObject", "GetObjectAcl", "GetBucketPolicy", "ListBucket", "PutObject"] {
+            catalog.insert("s3", api);
+        }
+
+        let records = [
+            record("s3:GetObject", Some("arn:aws:s3:::data/a")),
+            record("s3:GetObject", Some("arn:aws:s3:::data/b")),
+            record("s3:ListBucket", Some("arn:aws:s3:::data")),
+            record("s3:DeleteObject", Some("arn:aws:s3:::data/a")),
+            record("iam:ListUsers", None),
+        ];
+        let report = UsageReport::new(&policy_set, &records, &catalog).unwrap();
+        assert_eq!(report.records_evaluated(), 5);
+        assert_eq!(report.records_allowed(), 3);
+
+        let statements = report.statements();
+        assert_eq!(statements[0].hits(), 3);
+        assert_eq!(statements[0].unused_actions(), &["s3:PutObject".to_string()]);
+        assert_eq!(
+            statements[0].unused_expansions(),
+            &["s3:GetBucketPolicy".to_string(), "s3:GetObjectAcl".to_string()]
+        );
+        assert_eq!(statements[0].unused_resources(), &["arn:aws:s3:::other/*".to_string()]);
+
+        // Both matching statements get credit for the GetObject calls.
+        assert_eq!(statements[1].hits(), 2);
+        assert_eq!(statements[2].hits(), 0);
+        assert_eq!(statements[3].hits(), 1);
+
+        let unused: Vec<_> = report.unused_statements().map(|s| s.sid().unwrap()).collect();
+        assert_eq!(unused, vec!["Ec2"]);
+        assert!(report.to_string().starts_with("3 of 5 records allowed\npolicy 0, statement 0 \"S3\": 3 hits; "));
+
+        let trimmed = report.trimmed_policy(&policy_set, 0).unwrap();
+        let expected = Policy::from_str(
+            r#"{"Version": "2012-10-17", "Id": "dev", "Statement": [
+                {"Sid": "S3", "Effect": "Allow", "Action": ["s3:GetObject", "s3:ListBucket"],
+                 "Resource": ["arn:aws:s3:::data", "arn:aws:s3:::data/*"]},
+                {"Sid": "Overlap", "Effect": "Allow", "Action": "s3:GetObject", "Resource": "*"},
+                {"Sid": "NoDelete", "Effect": "Deny", "Action": "s3:DeleteObject", "Resource": "*"}
+            ]}"#,
+        )
+        .unwrap();
+        assert_eq!(trimmed, expected);
+        assert!(report.trimmed_policy(&policy_set, 1).is_none());
+
+        // The trimmed policy still allows every recorded request that was allowed before.
+        for record in records.iter() {
+            let context = record.to_context().unwrap();
+            assert_eq!(trimmed.evaluate(&context).unwrap(), identity.evaluate(&context).unwrap());
+        }
+    }
+
+    #[test_log::test]
+    fn test_invalid_record() {
+        let policy_set = PolicySet::new();
+        let records = [AccessRecord::new(None, "s3", "GetObject", vec!["bucket".to_string()])];
+        assert!(UsageReport::new(&policy_set, &records, &ActionCatalog::new()).is_err());
+
+        let report = UsageReport::new(&policy_set, &[], &ActionCatalog::new()).unwrap();
+        assert_eq!(report.to_string(), "0 of 0 records allowed\n");
+    }
+}
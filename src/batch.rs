@@ -0,0 +1,584 @@
+use {
+    crate::{AspenError, Context, Decision, PolicySet, PolicySource},
+    derive_builder::Builder,
+    scratchstack_arn::Arn,
+    scratchstack_aws_principal::{Principal, SessionData},
+    std::{
+        collections::{HashMap, HashSet},
+        num::NonZeroUsize,
+        thread,
+    },
+};
+
+/// An actor whose access is evaluated as part of a [MatrixRequest], along with the policies that apply to it.
+#[derive(Clone, Debug)]
+pub struct MatrixSubject<'a> {
+    actor: Principal,
+    policy_set: &'a PolicySet,
+    session_data: SessionData,
+}
+
+impl<'a> MatrixSubject<'a> {
+    /// Create a new [MatrixSubject] for the given actor, evaluated against `policy_set` with `session_data`.
+    pub fn new(actor: Principal, policy_set: &'a PolicySet, session_data: SessionData) -> Self {
+        Self {
+            actor,
+            policy_set,
+            session_data,
+        }
+    }
+
+    /// Returns the [Principal] actor making the requests.
+    #[inline]
+    pub fn actor(&self) -> &Principal {
+        &self.actor
+    }
+
+    /// Returns the [PolicySet] the actor's requests are evaluated against.
+    #[inline]
+    pub fn policy_set(&self) -> &'a PolicySet {
+        self.policy_set
+    }
+
+    /// Returns the session data associated with the actor's requests.
+    #[inline]
+    pub fn session_data(&self) -> &SessionData {
+        &self.session_data
+    }
+}
+
+/// The position of a cell in an [EvaluationMatrix].
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct MatrixIndex {
+    subject: usize,
+    action: usize,
+    resource: usize,
+}
+
+impl MatrixIndex {
+    /// Create a new [MatrixIndex] from indices into the subjects, actions, and resources of a [MatrixRequest].
+    pub fn new(subject: usize, action: usize, resource: usize) -> Self {
+        Self {
+            subject,
+            action,
+            resource,
+        }
+    }
+
+    /// Returns the index of the subject.
+    #[inline]
+    pub fn subject(&self) -> usize {
+        self.subject
+    }
+
+    /// Returns the index of the action.
+    #[inline]
+    pub fn action(&self) -> usize {
+        self.action
+    }
+
+    /// Returns the index of the resource set.
+    #[inline]
+    pub fn resource(&self) -> usize {
+        self.resource
+    }
+}
+
+/// The cells of an [EvaluationMatrix] to evaluate.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub enum MatrixCells {
+    /// Evaluate the cartesian product of all subjects, actions, and resource sets.
+    #[default]
+    Product,
+
+    /// Evaluate only the listed cells, in the order given. Each cell may be listed at most once.
+    Explicit(Vec<MatrixIndex>),
+}
+
+/// A bulk evaluation request.
+///
+/// Each cell of the request is a combination of a [MatrixSubject], an action (a `(service, api)` pair), and a set of
+/// resource ARNs (which may be empty). The request is evaluated with [MatrixRequest::evaluate], which reuses request
+/// contexts and compiled patterns across cells and spreads the work across multiple threads where available.
+///
+/// # Example
+///
+/// ```
+/// # use scratchstack_aspen::{Decision, MatrixRequest, MatrixSubject, Policy, PolicySet, PolicySource};
+/// # use scratchstack_aws_principal::{Principal, SessionData, User};
+/// # use std::str::FromStr;
+/// let policy = Policy::from_str(r#"{"Statement": {"Effect": "Allow", "Action": "s3:Get*", "Resource": "*"}}"#).unwrap();
+/// let mut policy_set = PolicySet::new();
+/// policy_set.add_policy(PolicySource::new_entity_inline("arn:aws:iam::123456789012:user/alice", "AIDAEXAMPLE", "p"), policy);
+///
+/// let actor = Principal::from(User::from_str("arn:aws:iam::123456789012:user/alice").unwrap());
+/// let request = MatrixRequest::builder()
+///     .subjects(vec![MatrixSubject::new(actor, &policy_set, SessionData::new())])
+///     .actions(vec![("s3".to_string(), "GetObject".to_string()), ("s3".to_string(), "PutObject".to_string())])
+///     .build()
+///     .unwrap();
+/// let matrix = request.evaluate().unwrap();
+/// assert_eq!(matrix.decision(0, 0, 0), Some(Decision::Allow));
+/// assert_eq!(matrix.decision(0, 1, 0), Some(Decision::DefaultDeny));
+/// ```
+#[derive(Builder, Clone, Debug)]
+#[builder(build_fn(validate = "Self::validate"))]
+pub struct MatrixRequest<'a> {
+    /// The actors (and their policies) to evaluate.
+    subjects: Vec<MatrixSubject<'a>>,
+
+    /// The actions to evaluate, as `(service, api)` pairs.
+    actions: Vec<(String, String)>,
+
+    /// The resource sets to evaluate. Each entry is the full list of resources for a request. If not specified, a
+    /// single empty resource set is used.
+    #[builder(default = "vec![vec![]]")]
+    resources: Vec<Vec<Arn>>,
+
+    /// The cells to evaluate. Defaults to [MatrixCells::Product].
+    #[builder(default)]
+    cells: MatrixCells,
+
+    /// If true, use [PolicySet::evaluate_all] semantics for each cell instead of [PolicySet::evaluate].
+    #[builder(default)]
+    eval_all: bool,
+
+    /// The maximum number of threads to use. Defaults to the available parallelism of the system.
+    #[builder(setter(strip_option), default)]
+    threads: Option<NonZeroUsize>,
+}
+
+impl<'a> MatrixRequest<'a> {
+    /// Returns a new [MatrixRequestBuilder] for building a [MatrixRequest].
+    pub fn builder() -> MatrixRequestBuilder<'a> {
+        MatrixRequestBuilder::default()
+    }
+
+    /// Returns the subjects being evaluated.
+    #[inline]
+    pub fn subjects(&self) -> &[MatrixSubject<'a>] {
+        &self.subjects
+    }
+
+    /// Returns the actions being evaluated as `(service, api)` pairs.
+    #[inline]
+    pub fn actions(&self) -> &[(String, String)] {
+        &self.actions
+    }
+
+    /// Returns the resource sets being evaluated.
+    #[inline]
+    pub fn resources(&self) -> &[Vec<Arn>] {
+        &self.resources
+    }
+
+    /// Returns the cells being evaluated.
+    #[inline]
+    pub fn cells(&self) -> &MatrixCells {
+        &self.cells
+    }
+
+    /// Returns the number of cells that will be evaluated.
+    pub fn len(&self) -> usize {
+        match &self.cells {
+            MatrixCells::Product => self.subjects.len() * self.actions.len() * self.resources.len(),
+            MatrixCells::Explicit(indices) => indices.len(),
+        }
+    }
+
+    /// Returns `true` if the request has no cells to evaluate.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the [MatrixIndex] of the `n`th cell to evaluate.
+    fn index(&self, n: usize) -> MatrixIndex {
+        match &self.cells {
+            MatrixCells::Product => {
+                let n_resources = self.resources.len();
+                let n_actions = self.actions.len();
+                MatrixIndex::new(n / (n_actions * n_resources), (n / n_resources) % n_actions, n % n_resources)
+            }
+            MatrixCells::Explicit(indices) => indices[n],
+        }
+    }
+
+    /// Evaluate every cell of the request.
+    ///
+    /// # Errors
+    ///
+    /// If evaluating any cell fails (for example, a policy contains a malformed variable reference), the error for
+    /// the first such cell (in cell order) is returned.
+    pub fn evaluate(&self) -> Result<EvaluationMatrix<'a>, AspenError> {
+        let n_cells = self.len();
+        let n_threads = self
+            .threads
+            .or_else(|| thread::available_parallelism().ok())
+            .map(NonZeroUsize::get)
+            .unwrap_or(1)
+            .clamp(1, n_cells.max(1));
+        let chunk_size = n_cells.div_ceil(n_threads);
+
+        let cells = if n_threads == 1 {
+            self.evaluate_range(0, n_cells)?
+        } else {
+            let results: Vec<Result<Vec<MatrixCell>, AspenError>> = thread::scope(|scope| {
+                let handles: Vec<_> = (0..n_cells)
+                    .step_by(chunk_size)
+                    .map(|start| scope.spawn(move || self.evaluate_range(start, (start + chunk_size).min(n_cells))))
+                    .collect();
+                handles.into_iter().map(|h| h.join().expect("matrix evaluation thread panicked")).collect()
+            });
+
+            let mut cells = Vec::with_capacity(n_cells);
+            for result in results {
+                cells.extend(result?);
+            }
+            cells
+        };
+
+        let indices = match &self.cells {
+            MatrixCells::Product => None,
+            MatrixCells::Explicit(indices) => Some(indices.clone()),
+        };
+        let positions = indices.as_ref().map(|indices| indices.iter().enumerate().map(|(n, i)| (*i, n)).collect());
+
+        Ok(EvaluationMatrix {
+            policy_sets: self.subjects.iter().map(|s| s.policy_set).collect(),
+            shape: (self.subjects.len(), self.actions.len(), self.resources.len()),
+            indices,
+            positions,
+            cells,
+        })
+    }
+
+    /// Evaluate cells `start..end`, reusing a single [Context] for runs of cells with the same subject.
+    fn evaluate_range(&self, start: usize, end: usize) -> Result<Vec<MatrixCell>, AspenError> {
+        let mut result = Vec::with_capacity(end - start);
+        let mut current: Option<(usize, Context)> = None;
+
+        for n in start..end {
+            let index = self.index(n);
+            let (service, api) = &self.actions[index.action];
+            let resources = &self.resources[index.resource];
+
+            let context = match &mut current {
+                Some((subject, context)) if *subject == index.subject => context,
+                _ => {
+                    let subject = &self.subjects[index.subject];
+                    let context = Context::builder()
+                        .api(api)
+                        .actor(subject.actor.clone())
+                        .session_data(subject.session_data.clone())
+                        .service(service)
+                        .build()
+                        .expect("all context fields are set");
+                    &mut current.insert((index.subject, context)).1
+                }
+            };
+            context.set_request(service, api, resources);
+
+            let (decision, sources) =
+                self.subjects[index.subject].policy_set.evaluate_indices(context, self.eval_all)?;
+            result.push(MatrixCell {
+                decision,
+                sources,
+            });
+        }
+
+        Ok(result)
+    }
+}
+
+impl<'a> MatrixRequestBuilder<'a> {
+    fn validate(&self) -> Result<(), MatrixRequestBuilderError> {
+        if let Some(MatrixCells::Explicit(indices)) = &self.cells {
+            let n_subjects = self.subjects.as_ref().map(Vec::len).unwrap_or(0);
+            let n_actions = self.actions.as_ref().map(Vec::len).unwrap_or(0);
+            let n_resources = self.resources.as_ref().map(Vec::len).unwrap_or(1);
+
+            let mut seen = HashSet::with_capacity(indices.len());
+            for index in indices {
+                if index.subject >= n_subjects || index.action >= n_actions || index.resource >= n_resources {
+                    return Err(MatrixRequestBuilderError::ValidationError(format!(
+                        "Cell index {index:?} is out of bounds."
+                    )));
+                }
+
+                if !seen.insert(index) {
+                    return Err(MatrixRequestBuilderError::ValidationError(format!(
+                        "Cell index {index:?} is listed more than once."
+                    )));
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// The result of evaluating a single cell of a [MatrixRequest].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct MatrixCell {
+    decision: Decision,
+    sources: Vec<usize>,
+}
+
+impl MatrixCell {
+    /// Returns the decision for this cell.
+    #[inline]
+    pub fn decision(&self) -> Decision {
+        self.decision
+    }
+
+    /// Returns the indices (into [PolicySet::policies] of the subject's policy set) of the policies responsible for
+    /// the decision.
+    #[inline]
+    pub fn source_indices(&self) -> &[usize] {
+        &self.sources
+    }
+}
+
+/// The results of evaluating a [MatrixRequest].
+///
+/// Cells store their decision and the indices of the policies responsible for it; use [EvaluationMatrix::sources]
+/// to resolve these to [PolicySource] references.
+#[derive(Clone, Debug)]
+pub struct EvaluationMatrix<'a> {
+    policy_sets: Vec<&'a PolicySet>,
+    shape: (usize, usize, usize),
+    indices: Option<Vec<MatrixIndex>>,
+    positions: Option<HashMap<MatrixIndex, usize>>,
+    cells: Vec<MatrixCell>,
+}
+
+impl<'a> EvaluationMatrix<'a> {
+    /// Returns the number of subjects, actions, and resource sets in the matrix.
+    #[inline]
+    pub fn shape(&self) -> (usize, usize, usize) {
+        self.shape
+    }
+
+    /// Returns the number of evaluated cells.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.cells.len()
+    }
+
+    /// Returns `true` if no cells were evaluated.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.cells.is_empty()
+    }
+
+    /// Returns the evaluated cell at the given index, or `None` if that cell was not evaluated.
+    pub fn get(&self, index: MatrixIndex) -> Option<&MatrixCell> {
+        match &self.positions {
+            None => {
+                let (n_subjects, n_actions, n_resources) = self.shape;
+                if index.subject >= n_subjects || index.action >= n_actions || index.resource >= n_resources {
+                    None
+                } else {
+                    self.cells.get((index.subject * n_actions + index.action) * n_resources + index.resource)
+                }
+            }
+            Some(positions) => positions.get(&index).map(|n| &self.cells[*n]),
+        }
+    }
+
+    /// Returns the decision for the given subject, action, and resource set indices, or `None` if that cell was not
+    /// evaluated.
+    pub fn decision(&self, subject: usize, action: usize, resource: usize) -> Option<Decision> {
+        self.get(MatrixIndex::new(subject, action, resource)).map(MatrixCell::decision)
+    }
+
+    /// Returns the [PolicySource]s responsible for the decision in the given cell, or `None` if that cell was not
+    /// evaluated.
+    pub fn sources(&self, index: MatrixIndex) -> Option<Vec<&'a PolicySource>> {
+        self.get(index).map(|cell| {
+            let policy_set = self.policy_sets[index.subject];
+            cell.sources.iter().map(|i| &policy_set.policies()[*i].0).collect()
+        })
+    }
+
+    /// Returns an iterator over the evaluated cells and their indices, in evaluation order.
+    pub fn iter(&self) -> impl Iterator<Item = (MatrixIndex, &MatrixCell)> + '_ {
+        let (_, n_actions, n_resources) = self.shape;
+        self.cells.iter().enumerate().map(move |(n, cell)| {
+            let index = match &self.indices {
+                None => MatrixIndex::new(n / (n_actions * n_resources), (n / n_resources) % n_actions, n % n_resources),
+                Some(indices) => indices[n],
+            };
+            (index, cell)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        crate::{Decision, MatrixCells, MatrixIndex, MatrixRequest, MatrixSubject, Policy, PolicySet, PolicySource},
+        indoc::indoc,
+        pretty_assertions::assert_eq,
+        scratchstack_arn::Arn,
+        scratchstack_aws_principal::{Principal, SessionData, SessionValue, User},
+        std::{num::NonZeroUsize, str::FromStr},
+    };
+
+    fn policy_set() -> PolicySet {
+        let policy = Policy::from_str(indoc! {r#"
+        {
+            "Version": "2012-10-17",
+            "Statement": [
+                {
+                    "Effect": "Allow",
+                    "Action": ["s3:Get*", "s3:List*"],
+                    "Resource": ["arn:aws:s3:::bucket", "arn:aws:s3:::bucket/${aws:username}/*"]
+                },
+                {
+                    "Effect": "Deny",
+                    "Action": "s3:GetObject",
+                    "Resource": "arn:aws:s3:::bucket/secret/*"
+                }
+            ]
+        }"#})
+        .unwrap();
+        let deny = Policy::from_str(indoc! {r#"
+        {
+            "Statement": {
+                "Effect": "Deny",
+                "Action": "s3:GetObject",
+                "Resource": "arn:aws:s3:::bucket/secret/*"
+            }
+        }"#})
+        .unwrap();
+
+        PolicySet::from(vec![
+            (PolicySource::new_entity_inline("arn:aws:iam::123456789012:user/alice", "AIDAALICE", "Main"), policy),
+            (PolicySource::new_entity_inline("arn:aws:iam::123456789012:user/alice", "AIDAALICE", "Deny"), deny),
+        ])
+    }
+
+    fn subject<'a>(user_name: &str, policy_set: &'a PolicySet) -> MatrixSubject<'a> {
+        let actor = Principal::from(User::new("aws", "123456789012", "/", user_name).unwrap());
+        let session_data = SessionData::from([("aws:username", SessionValue::from(user_name))]);
+        MatrixSubject::new(actor, policy_set, session_data)
+    }
+
+    fn actions() -> Vec<(String, String)> {
+        vec![("s3".into(), "GetObject".into()), ("s3".into(), "ListBucket".into()), ("s3".into(), "PutObject".into())]
+    }
+
+    fn resources() -> Vec<Vec<Arn>> {
+        vec![
+            vec![Arn::from_str("arn:aws:s3:::bucket").unwrap()],
+            vec![Arn::from_str("arn:aws:s3:::bucket/alice/file").unwrap()],
+            vec![Arn::from_str("arn:aws:s3:::bucket/secret/file").unwrap()],
+        ]
+    }
+
+    #[test_log::test]
+    fn test_product_matches_individual_evaluation() {
+        let ps = policy_set();
+        let subjects = vec![subject("alice", &ps), subject("bob", &ps)];
+
+        for threads in [1, 4] {
+            let request = MatrixRequest::builder()
+                .subjects(subjects.clone())
+                .actions(actions())
+                .resources(resources())
+                .threads(NonZeroUsize::new(threads).unwrap())
+                .build()
+                .unwrap();
+            let matrix = request.evaluate().unwrap();
+            assert_eq!(matrix.shape(), (2, 3, 3));
+            assert_eq!(matrix.len(), 18);
+
+            for (index, cell) in matrix.iter() {
+                let subject = &subjects[index.subject()];
+                let (service, api) = &actions()[index.action()];
+                let context = crate::Context::builder()
+                    .service(service)
+                    .api(api)
+                    .actor(subject.actor().clone())
+                    .session_data(subject.session_data().clone())
+                    .resources(resources()[index.resource()].clone())
+                    .build()
+                    .unwrap();
+                let (decision, sources) = ps.evaluate(&context).unwrap();
+                assert_eq!(cell.decision(), decision);
+                assert_eq!(matrix.sources(index).unwrap(), sources);
+            }
+
+            assert_eq!(matrix.decision(0, 0, 1), Some(Decision::Allow));
+            assert_eq!(matrix.decision(1, 0, 1), Some(Decision::DefaultDeny));
+            assert_eq!(matrix.decision(0, 0, 2), Some(Decision::Deny));
+            assert_eq!(matrix.decision(0, 2, 0), Some(Decision::DefaultDeny));
+            assert_eq!(matrix.decision(2, 0, 0), None);
+            assert_eq!(matrix.sources(MatrixIndex::new(2, 0, 0)), None);
+        }
+    }
+
+    #[test_log::test]
+    fn test_explicit_cells() {
+        let ps = policy_set();
+        let cells = vec![MatrixIndex::new(0, 1, 0), MatrixIndex::new(0, 0, 2)];
+        let request = MatrixRequest::builder()
+            .subjects(vec![subject("alice", &ps)])
+            .actions(actions())
+            .resources(resources())
+            .cells(MatrixCells::Explicit(cells.clone()))
+            .eval_all(true)
+            .build()
+            .unwrap();
+        assert_eq!(request.len(), 2);
+
+        let matrix = request.evaluate().unwrap();
+        assert_eq!(matrix.iter().map(|(i, _)| i).collect::<Vec<_>>(), cells);
+        assert_eq!(matrix.decision(0, 1, 0), Some(Decision::Allow));
+        assert_eq!(matrix.get(cells[1]).unwrap().source_indices(), &[0, 1]);
+        assert_eq!(matrix.decision(0, 0, 0), None);
+
+        let e = MatrixRequest::builder()
+            .subjects(vec![subject("alice", &ps)])
+            .actions(actions())
+            .cells(MatrixCells::Explicit(vec![MatrixIndex::new(0, 0, 1)]))
+            .build()
+            .unwrap_err();
+        assert_eq!(e.to_string(), "Cell index MatrixIndex { subject: 0, action: 0, resource: 1 } is out of bounds.");
+
+        let e = MatrixRequest::builder()
+            .subjects(vec![subject("alice", &ps)])
+            .actions(actions())
+            .cells(MatrixCells::Explicit(vec![cells[0], MatrixIndex::new(0, 0, 0), cells[0]]))
+            .build()
+            .unwrap_err();
+        assert_eq!(
+            e.to_string(),
+            "Cell index MatrixIndex { subject: 0, action: 1, resource: 0 } is listed more than once."
+        );
+    }
+
+    #[test_log::test]
+    fn test_evaluation_error() {
+        let policy = Policy::from_str(indoc! {r#"
+        {
+            "Version": "2012-10-17",
+            "Statement": {"Effect": "Allow", "Action": "*", "Resource": "arn:aws:s3:::${"}
+        }"#})
+        .unwrap();
+        let ps = PolicySet::from(vec![(PolicySource::new_session(), policy)]);
+        let request = MatrixRequest::builder()
+            .subjects(vec![subject("alice", &ps)])
+            .actions(actions())
+            .resources(resources())
+            .build()
+            .unwrap();
+        assert_eq!(request.evaluate().unwrap_err().to_string(), "Invalid variable substitution: ${");
+
+        let request = MatrixRequest::builder().subjects(vec![]).actions(actions()).build().unwrap();
+        assert!(request.is_empty());
+        assert!(request.evaluate().unwrap().is_empty());
+    }
+}
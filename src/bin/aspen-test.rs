@@ -0,0 +1,73 @@
+//! Runs Aspen policy test suites.
+//!
+//! Usage: `aspen-test [--failures-only] <path>...`
+//!
+//! Each path is either a test suite file or a directory that is searched recursively for files ending in
+//! `.test.json`. The exit status is 0 if every test case passes, 1 if any test case fails, and 2 if a suite cannot
+//! be loaded or the arguments are invalid.
+
+use {
+    clap::Parser,
+    scratchstack_aspen::TestSuite,
+    std::{path::PathBuf, process::ExitCode},
+};
+
+#[derive(Parser)]
+#[command(name = "aspen-test", version, about = "Run Aspen policy test suites")]
+struct Cli {
+    /// Only report failing test cases.
+    #[arg(long)]
+    failures_only: bool,
+
+    /// Test suite files, or directories searched recursively for `.test.json` files.
+    #[arg(required = true)]
+    paths: Vec<PathBuf>,
+}
+
+fn main() -> ExitCode {
+    let Cli {
+        failures_only,
+        paths,
+    } = Cli::parse();
+
+    let mut suites = Vec::new();
+    for path in paths {
+        let loaded = if path.is_dir() {
+            TestSuite::load_dir(&path)
+        } else {
+            TestSuite::from_file(&path).map(|suite| vec![suite])
+        };
+
+        match loaded {
+            Ok(loaded) => suites.extend(loaded),
+            Err(e) => {
+                eprintln!("{e}");
+                return ExitCode::from(2);
+            }
+        }
+    }
+
+    let (mut passed, mut failed) = (0, 0);
+    for suite in suites.iter() {
+        let report = suite.run();
+        let suite_failed = report.failures().count();
+        passed += report.results().len() - suite_failed;
+        failed += suite_failed;
+
+        if !failures_only {
+            println!("{report}");
+        } else if suite_failed > 0 {
+            println!("{}: {suite_failed} failed", report.suite());
+            for failure in report.failures() {
+                println!("  {failure}");
+            }
+        }
+    }
+
+    println!("{} suites: {passed} passed, {failed} failed", suites.len());
+    if failed == 0 {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    }
+}
@@ -0,0 +1,370 @@
+//! Validates, formats, evaluates, explains, and diffs Aspen policies.
+//!
+//! Policy files contain either a single policy document or, if the top-level JSON value is a list, a serialized
+//! `PolicySet`. Single policies are evaluated as inline policies of a synthetic standalone entity (see
+//! `PolicySource::new_standalone`), as in `aspen-test`.
+//!
+//! With the `server` feature, `aspen serve` exposes the policy sets in a directory as an HTTP decision service; see
+//! `DecisionService` for the endpoints.
+//!
+//! The exit status is 0 on success, 1 if a check fails (a policy is invalid or has lint errors, `fmt --check` finds
+//! unformatted files, `eval` does not allow the request, or `diff` finds a change), and 2 on usage or I/O errors.
+
+use {
+    clap::{Parser, Subcommand},
+    scratchstack_aspen::{ActionCatalog, Context, Decision, Policy, PolicyDiff, PolicyLint, PolicySet, PolicySource},
+    serde_json::{json, Value},
+    std::{
+        fs,
+        path::{Path, PathBuf},
+        process::ExitCode,
+        str::FromStr,
+    },
+};
+
+const EXIT_CHECK_FAILED: u8 = 1;
+const EXIT_ERROR: u8 = 2;
+
+#[derive(Parser)]
+#[command(name = "aspen", version, about = "Validate, format, evaluate, explain, and diff AWS IAM policies")]
+struct Cli {
+    /// Write machine-readable JSON instead of text.
+    #[arg(long, global = true)]
+    json: bool,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Parse and lint policies.
+    Validate {
+        /// Treat lint warnings as failures.
+        #[arg(long)]
+        strict: bool,
+
+        /// An action catalog (JSON) used to report unknown actions.
+        #[arg(long)]
+        catalog: Option<PathBuf>,
+
+        /// Policy files to validate.
+        #[arg(required = true)]
+        policies: Vec<PathBuf>,
+    },
+
+    /// Print policies in canonical form.
+    Fmt {
+        /// Report files that are not in canonical form instead of printing them.
+        #[arg(long, conflicts_with = "write")]
+        check: bool,
+
+        /// Rewrite files in canonical form.
+        #[arg(long)]
+        write: bool,
+
+        /// Policy files to format.
+        #[arg(required = true)]
+        policies: Vec<PathBuf>,
+    },
+
+    /// Evaluate a request context against policies.
+    Eval {
+        /// The request context (JSON).
+        #[arg(long)]
+        context: PathBuf,
+
+        /// Policy or policy set files.
+        #[arg(required = true)]
+        policies: Vec<PathBuf>,
+    },
+
+    /// Evaluate a request context and explain which statements decided it.
+    Explain {
+        /// The request context (JSON).
+        #[arg(long)]
+        context: PathBuf,
+
+        /// Policy or policy set files.
+        #[arg(required = true)]
+        policies: Vec<PathBuf>,
+    },
+
+    /// Show requests whose decision differs between two versions of a policy.
+    Diff {
+        /// The policy before the change.
+        before: PathBuf,
+
+        /// The policy after the change.
+        after: PathBuf,
+    },
+
+    /// Serve authorization decisions over HTTP for the policy sets in a directory.
+    #[cfg(feature = "server")]
+    Serve {
+        /// The address to listen on.
+        #[arg(long, default_value = "127.0.0.1:8080")]
+        listen: String,
+
+        /// Poll the directory for changes every this many seconds and reload the policies when it changes.
+        #[arg(long, value_name = "SECONDS")]
+        watch: Option<u64>,
+
+        /// The policy store directory: policy and policy set files, with managed policies under `policies`.
+        dir: PathBuf,
+    },
+}
+
+/// A failure that stops a command, with the exit status to report.
+struct Failure {
+    message: String,
+    status: u8,
+}
+
+impl Failure {
+    fn error<S: Into<String>>(message: S) -> Self {
+        Self {
+            message: message.into(),
+            status: EXIT_ERROR,
+        }
+    }
+}
+
+type CommandResult = Result<u8, Failure>;
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    let result = match cli.command {
+        Command::Validate {
+            strict,
+            catalog,
+            policies,
+        } => validate(cli.json, strict, catalog.as_deref(), &policies),
+        Command::Fmt {
+            check,
+            write,
+            policies,
+        } => fmt(cli.json, check, write, &policies),
+        Command::Eval {
+            context,
+            policies,
+        } => eval(cli.json, &context, &policies, false),
+        Command::Explain {
+            context,
+            policies,
+        } => eval(cli.json, &context, &policies, true),
+        Command::Diff {
+            before,
+            after,
+        } => diff(cli.json, &before, &after),
+        #[cfg(feature = "server")]
+        Command::Serve {
+            listen,
+            watch,
+            dir,
+        } => serve(&listen, watch, &dir),
+    };
+
+    match result {
+        Ok(status) => ExitCode::from(status),
+        Err(failure) => {
+            if cli.json {
+                println!("{}", json!({ "Error": failure.message }));
+            } else {
+                eprintln!("aspen: {}", failure.message);
+            }
+            ExitCode::from(failure.status)
+        }
+    }
+}
+
+fn read(path: &Path) -> Result<String, Failure> {
+    fs::read_to_string(path).map_err(|e| Failure::error(format!("{}: {e}", path.display())))
+}
+
+fn parse_policy(path: &Path) -> Result<Policy, Failure> {
+    Policy::from_str(&read(path)?).map_err(|e| Failure::error(format!("{}: {e}", path.display())))
+}
+
+/// Loads policy files into a single policy set, attributing single policies to the standalone entity.
+fn load_policy_set(paths: &[PathBuf]) -> Result<PolicySet, Failure> {
+    let mut policy_set = PolicySet::new();
+
+    for path in paths {
+        let text = read(path)?;
+        let value: Value =
+            serde_json::from_str(&text).map_err(|e| Failure::error(format!("{}: {e}", path.display())))?;
+        if value.is_array() {
+            let loaded = PolicySet::from_str(&text).map_err(|e| Failure::error(format!("{}: {e}", path.display())))?;
+            for (source, policy) in loaded.policies() {
+                policy_set.add_policy(source.clone(), policy.clone());
+            }
+        } else {
+            let policy = parse_policy(path)?;
+            policy_set.add_policy(PolicySource::new_standalone(path.display().to_string()), policy);
+        }
+    }
+
+    Ok(policy_set)
+}
+
+fn validate(json: bool, strict: bool, catalog: Option<&Path>, paths: &[PathBuf]) -> CommandResult {
+    let catalog = match catalog {
+        Some(path) => Some(
+            ActionCatalog::from_str(&read(path)?).map_err(|e| Failure::error(format!("{}: {e}", path.display())))?,
+        ),
+        None => None,
+    };
+
+    let mut status = 0;
+    let mut results = Vec::new();
+
+    for path in paths {
+        let lint = Policy::parse(&read(path)?).map(|policy| match &catalog {
+            Some(catalog) => PolicyLint::check_with_catalog(&policy, catalog),
+            None => PolicyLint::check(&policy),
+        });
+
+        let failed = match &lint {
+            Ok(lint) => lint.has_errors() || (strict && !lint.is_empty()),
+            Err(_) => true,
+        };
+        if failed {
+            status = EXIT_CHECK_FAILED;
+        }
+
+        match (json, &lint) {
+            (true, Ok(lint)) => results.push(json!({
+                "File": path.display().to_string(),
+                "Valid": !failed,
+                "Findings": lint.findings(),
+            })),
+            (true, Err(errors)) => results.push(json!({
+                "File": path.display().to_string(),
+                "Valid": false,
+                "Errors": errors,
+            })),
+            (false, Ok(lint)) if lint.is_empty() => println!("{}: ok", path.display()),
+            (false, Ok(lint)) => {
+                for finding in lint.findings() {
+                    println!("{}: {finding}", path.display());
+                }
+            }
+            (false, Err(errors)) => {
+                for error in errors {
+                    println!("{}: invalid policy: {error}", path.display());
+                }
+            }
+        }
+    }
+
+    if json {
+        println!("{}", Value::Array(results));
+    }
+
+    Ok(status)
+}
+
+fn fmt(json: bool, check: bool, write: bool, paths: &[PathBuf]) -> CommandResult {
+    let mut status = 0;
+    let mut results = Vec::new();
+
+    for path in paths {
+        let text = read(path)?;
+        let formatted = format!("{}\n", parse_policy(path)?);
+        let unchanged = text == formatted;
+
+        if check {
+            if !unchanged {
+                status = EXIT_CHECK_FAILED;
+                if !json {
+                    println!("{}: not formatted", path.display());
+                }
+            }
+        } else if write {
+            if !unchanged {
+                fs::write(path, &formatted).map_err(|e| Failure::error(format!("{}: {e}", path.display())))?;
+            }
+        } else if !json {
+            print!("{formatted}");
+        }
+
+        if json {
+            let mut result = json!({ "File": path.display().to_string(), "Formatted": unchanged });
+            if !check && !write {
+                result["Policy"] = serde_json::from_str(&formatted).expect("formatted policies are valid JSON");
+            }
+            results.push(result);
+        }
+    }
+
+    if json {
+        println!("{}", Value::Array(results));
+    }
+
+    Ok(status)
+}
+
+fn eval(json: bool, context: &Path, paths: &[PathBuf], explain: bool) -> CommandResult {
+    let context =
+        Context::from_str(&read(context)?).map_err(|e| Failure::error(format!("{}: {e}", context.display())))?;
+    let policy_set = load_policy_set(paths)?;
+    let (decision, reasons) = policy_set.explain(&context).map_err(|e| Failure::error(e.to_string()))?;
+    let status = if decision == Decision::Allow {
+        0
+    } else {
+        EXIT_CHECK_FAILED
+    };
+
+    match (json, explain) {
+        (true, false) => println!("{}", json!({ "Decision": decision })),
+        (true, true) => println!("{}", json!({ "Decision": decision, "Reasons": reasons })),
+        (false, false) => println!("{decision}"),
+        (false, true) => {
+            println!("{decision}");
+            if decision == Decision::DefaultDeny {
+                println!("  no statement allows {}:{}", context.service(), context.api());
+            }
+            for reason in reasons.iter() {
+                println!("  {reason}");
+            }
+        }
+    }
+
+    Ok(status)
+}
+
+fn diff(json: bool, before: &Path, after: &Path) -> CommandResult {
+    let diff = PolicyDiff::between_policies(&parse_policy(before)?, &parse_policy(after)?)
+        .map_err(|e| Failure::error(e.to_string()))?;
+
+    if json {
+        println!("{}", serde_json::to_string(&diff).expect("diffs serialize"));
+    } else {
+        print!("{diff}");
+        if !diff.is_exact() {
+            println!("(condition values were sampled; the diff may be incomplete)");
+        }
+    }
+
+    Ok(if diff.is_empty() {
+        0
+    } else {
+        EXIT_CHECK_FAILED
+    })
+}
+
+#[cfg(feature = "server")]
+fn serve(listen: &str, watch: Option<u64>, dir: &Path) -> CommandResult {
+    use {
+        scratchstack_aspen::DecisionService,
+        std::{net::TcpListener, sync::Arc, time::Duration},
+    };
+
+    let service = Arc::new(DecisionService::load(dir).map_err(|e| Failure::error(e.to_string()))?);
+    let _watcher = watch.map(|seconds| service.store().watch(Duration::from_secs(seconds.max(1))));
+    let listener = TcpListener::bind(listen).map_err(|e| Failure::error(format!("{listen}: {e}")))?;
+    eprintln!("aspen: serving {} policy sets from {} on {listen}", service.policy_set_names().len(), dir.display());
+    service.serve(listener).map_err(|e| Failure::error(format!("{listen}: {e}")))?;
+    Ok(0)
+}
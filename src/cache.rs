@@ -0,0 +1,390 @@
+use {
+    crate::{analysis::glob::pattern_variables, AspenError, Context, Decision, PolicySet, PolicySource, Resource},
+    scratchstack_arn::Arn,
+    scratchstack_aws_principal::{Principal, SessionValue},
+    std::{
+        collections::{BTreeSet, HashMap, VecDeque},
+        sync::{Arc, Mutex},
+        time::{Duration, Instant},
+    },
+};
+
+/// The parts of a request context that can affect the decision of a policy set.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+struct CacheKey {
+    /// The actor, if any statement has a `Principal` or `NotPrincipal` element.
+    actor: Option<Principal>,
+    service: String,
+    api: String,
+    resources: Vec<Arn>,
+
+    /// The values of the referenced session keys, in the order of [KeyUsage::session_keys].
+    session: Vec<Option<SessionValue>>,
+}
+
+/// The parts of a request context referenced by a policy set, found by static analysis.
+#[derive(Debug, Default)]
+struct KeyUsage {
+    uses_actor: bool,
+
+    /// The session keys referenced by conditions and policy variables, lowercased.
+    session_keys: Vec<String>,
+}
+
+impl KeyUsage {
+    fn new(policy_set: &PolicySet) -> Self {
+        let mut uses_actor = false;
+        let mut session_keys = BTreeSet::new();
+
+        for (_, policy) in policy_set.policies() {
+            for statement in policy.statement().iter() {
+                uses_actor |= statement.principal().is_some() || statement.not_principal().is_some();
+
+                if let Some(condition) = statement.condition() {
+                    for map in condition.values() {
+                        for (key, values) in map.iter() {
+                            session_keys.insert(key.to_lowercase());
+                            for value in values.iter() {
+                                session_keys.extend(pattern_variables(value).iter().map(|var| var.to_lowercase()));
+                            }
+                        }
+                    }
+                }
+
+                let resources = statement.resource().or_else(|| statement.not_resource());
+                for resource in resources.iter().flat_map(|resources| resources.iter()) {
+                    if let Resource::Arn(pattern) = resource {
+                        let pattern = pattern.to_string();
+                        session_keys.extend(pattern_variables(&pattern).iter().map(|var| var.to_lowercase()));
+                    }
+                }
+            }
+        }
+
+        Self {
+            uses_actor,
+            session_keys: session_keys.into_iter().collect(),
+        }
+    }
+
+    fn key(&self, context: &Context) -> CacheKey {
+        CacheKey {
+            actor: self.uses_actor.then(|| context.actor().clone()),
+            service: context.service().to_string(),
+            api: context.api().to_string(),
+            resources: context.resources().clone(),
+            session: self.session_keys.iter().map(|key| context.session_data().get(key).cloned()).collect(),
+        }
+    }
+}
+
+/// A cached decision and the indices (into [PolicySet::policies]) of the policies responsible for it.
+#[derive(Debug)]
+struct CacheEntry {
+    decision: Decision,
+    indices: Vec<usize>,
+    inserted: Instant,
+
+    /// The insertion sequence number, used to recognize stale entries in [CacheState::order].
+    sequence: u64,
+}
+
+#[derive(Debug, Default)]
+struct CacheState {
+    /// The policy set the entries were computed for.
+    policy_set: Option<Arc<PolicySet>>,
+    usage: Arc<KeyUsage>,
+    entries: HashMap<CacheKey, CacheEntry>,
+
+    /// Keys in insertion order, for evicting the oldest entries.
+    order: VecDeque<(CacheKey, u64)>,
+    next_sequence: u64,
+    hits: u64,
+    misses: u64,
+}
+
+impl CacheState {
+    /// Discards the entries if they were computed for a different policy set.
+    fn validate(&mut self, policy_set: &Arc<PolicySet>) {
+        if !self.policy_set.as_ref().map(|current| Arc::ptr_eq(current, policy_set)).unwrap_or(false) {
+            self.policy_set = Some(Arc::clone(policy_set));
+            self.usage = Arc::new(KeyUsage::new(policy_set));
+            self.entries.clear();
+            self.order.clear();
+        }
+    }
+}
+
+/// A bounded cache of policy set decisions.
+///
+/// The cache key is built from the request's service, API, and resources, the actor (only if a statement has a
+/// `Principal` or `NotPrincipal` element), and the values of only those session keys referenced by the policies'
+/// `Condition` blocks and policy variables. Requests that differ only in unreferenced session keys share an entry.
+///
+/// Policy sets are passed as an [Arc] to each evaluation. When a different policy set is passed (for example, after
+/// the policies are reloaded), the cache is cleared and the referenced keys are recomputed, so stale decisions are
+/// never returned. The cache holds at most `capacity` entries, evicting the oldest first, and entries expire after
+/// the time-to-live, if one is given. Evaluation errors are not cached.
+///
+/// # Example
+///
+/// ```
+/// # use scratchstack_aspen::{Context, Decision, DecisionCache, PolicySet};
+/// # use std::{str::FromStr, sync::Arc, time::Duration};
+/// let policy_set = Arc::new(PolicySet::from_str(r#"[
+///     {"Source": {"Type": "EntityInline", "EntityArn": "arn:aws:iam::123456789012:user/alice",
+///                 "EntityId": "AIDAEXAMPLE", "PolicyName": "s3-read"},
+///      "Policy": {"Statement": {"Effect": "Allow", "Action": "s3:GetObject", "Resource": "*"}}}
+/// ]"#).unwrap());
+/// let cache = DecisionCache::new(1000, Some(Duration::from_secs(60)));
+///
+/// let context = Context::from_str(r#"{"Service": "s3", "Api": "GetObject"}"#).unwrap();
+/// assert_eq!(cache.evaluate(&policy_set, &context).unwrap().0, Decision::Allow);
+/// assert_eq!(cache.evaluate(&policy_set, &context).unwrap().0, Decision::Allow);
+/// assert_eq!((cache.hits(), cache.misses()), (1, 1));
+/// ```
+#[derive(Debug)]
+pub struct DecisionCache {
+    capacity: usize,
+    ttl: Option<Duration>,
+    state: Mutex<CacheState>,
+}
+
+impl DecisionCache {
+    /// Creates a cache holding at most `capacity` decisions, each valid for `ttl` (or until evicted, if `None`).
+    pub fn new(capacity: usize, ttl: Option<Duration>) -> Self {
+        Self {
+            capacity,
+            ttl,
+            state: Mutex::new(CacheState::default()),
+        }
+    }
+
+    /// Returns the maximum number of decisions held.
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Returns the time-to-live of cached decisions, if any.
+    #[inline]
+    pub fn ttl(&self) -> Option<Duration> {
+        self.ttl
+    }
+
+    /// Returns the number of cached decisions, including any that have expired but not yet been removed.
+    pub fn len(&self) -> usize {
+        self.lock().entries.len()
+    }
+
+    /// Indicates whether the cache is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the number of evaluations answered from the cache.
+    pub fn hits(&self) -> u64 {
+        self.lock().hits
+    }
+
+    /// Returns the number of evaluations that were not answered from the cache.
+    pub fn misses(&self) -> u64 {
+        self.lock().misses
+    }
+
+    /// Discards every cached decision.
+    pub fn clear(&self) {
+        let mut state = self.lock();
+        state.entries.clear();
+        state.order.clear();
+    }
+
+    /// Evaluate the policy set, using a cached decision if available; see [PolicySet::evaluate].
+    ///
+    /// # Errors
+    ///
+    /// If the policy set fails to evaluate (for example, because of a malformed variable reference), the error is
+    /// returned.
+    pub fn evaluate<'a>(
+        &self,
+        policy_set: &'a Arc<PolicySet>,
+        context: &Context,
+    ) -> Result<(Decision, Vec<&'a PolicySource>), AspenError> {
+        let sources = |indices: &[usize]| indices.iter().map(|&i| &policy_set.policies()[i].0).collect();
+
+        let (key, usage) = {
+            let mut state = self.lock();
+            state.validate(policy_set);
+            let key = state.usage.key(context);
+
+            let expired = match state.entries.get(&key) {
+                Some(entry) if !self.is_expired(entry) => {
+                    let result = (entry.decision, sources(&entry.indices));
+                    state.hits += 1;
+                    return Ok(result);
+                }
+                Some(_) => true,
+                None => false,
+            };
+            if expired {
+                state.entries.remove(&key);
+            }
+
+            state.misses += 1;
+            (key, Arc::clone(&state.usage))
+        };
+
+        // Evaluate without holding the lock so other requests are not blocked.
+        let (decision, indices) = policy_set.evaluate_indices(context, false)?;
+        let result = (decision, sources(&indices));
+
+        let mut state = self.lock();
+        // Only cache the decision if the policy set has not changed in the meantime.
+        if self.capacity > 0 && Arc::ptr_eq(&state.usage, &usage) {
+            let sequence = state.next_sequence;
+            state.next_sequence += 1;
+            state.order.push_back((key.clone(), sequence));
+            state.entries.insert(
+                key,
+                CacheEntry {
+                    decision,
+                    indices,
+                    inserted: Instant::now(),
+                    sequence,
+                },
+            );
+            self.evict(&mut state);
+        }
+
+        Ok(result)
+    }
+
+    fn evict(&self, state: &mut CacheState) {
+        while state.entries.len() > self.capacity {
+            match state.order.pop_front() {
+                Some((key, sequence)) => {
+                    if state.entries.get(&key).map(|entry| entry.sequence == sequence).unwrap_or(false) {
+                        state.entries.remove(&key);
+                    }
+                }
+                None => break,
+            }
+        }
+
+        // Drop the order records of entries that expired and were replaced.
+        if state.order.len() > 2 * self.capacity {
+            let CacheState {
+                entries,
+                order,
+                ..
+            } = state;
+            order.retain(|(key, sequence)| entries.get(key).map(|entry| entry.sequence == *sequence).unwrap_or(false));
+        }
+    }
+
+    fn is_expired(&self, entry: &CacheEntry) -> bool {
+        self.ttl.map(|ttl| entry.inserted.elapsed() >= ttl).unwrap_or(false)
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, CacheState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        crate::{Context, Decision, DecisionCache, PolicySet},
+        pretty_assertions::assert_eq,
+        std::{str::FromStr, sync::Arc, thread, time::Duration},
+    };
+
+    fn policy_set(json: &str) -> Arc<PolicySet> {
+        Arc::new(
+            PolicySet::from_str(&format!(
+                r#"[{{"Source": {{"Type": "EntityInline", "EntityArn": "arn:aws:iam::123456789012:user/alice",
+                                  "EntityId": "AIDAEXAMPLE", "PolicyName": "test"}},
+                     "Policy": {json}}}]"#
+            ))
+            .unwrap(),
+        )
+    }
+
+    fn context(resource: &str, session: &str) -> Context {
+        Context::from_str(&format!(
+            r#"{{"Service": "s3", "Api": "GetObject", "Resources": ["{resource}"], "SessionData": {{{session}}}}}"#
+        ))
+        .unwrap()
+    }
+
+    #[test_log::test]
+    fn test_key_uses_referenced_session_keys() {
+        let policies = policy_set(
+            r#"{"Version": "2012-10-17", "Statement": {"Effect": "Allow", "Action": "s3:GetObject",
+                "Resource": "arn:aws:s3:::bucket/${aws:username}/*",
+                "Condition": {"Bool": {"aws:SecureTransport": "true"}}}}"#,
+        );
+        let cache = DecisionCache::new(10, None);
+
+        let alice = context(
+            "arn:aws:s3:::bucket/alice/key",
+            r#""aws:username": {"String": "alice"}, "aws:SecureTransport": {"Bool": true}, "aws:EpochTime": {"Integer": 1}"#,
+        );
+        assert_eq!(cache.evaluate(&policies, &alice).unwrap(), policies.evaluate(&alice).unwrap());
+        assert_eq!(cache.evaluate(&policies, &alice).unwrap().0, Decision::Allow);
+        assert_eq!((cache.hits(), cache.misses()), (1, 1));
+
+        // An unreferenced session key does not affect the key.
+        let later = context(
+            "arn:aws:s3:::bucket/alice/key",
+            r#""aws:username": {"String": "alice"}, "aws:SecureTransport": {"Bool": true}, "aws:EpochTime": {"Integer": 2}"#,
+        );
+        assert_eq!(cache.evaluate(&policies, &later).unwrap().0, Decision::Allow);
+        assert_eq!((cache.hits(), cache.misses()), (2, 1));
+
+        // Referenced keys (in conditions and variables) do.
+        let insecure = context(
+            "arn:aws:s3:::bucket/alice/key",
+            r#""aws:username": {"String": "alice"}, "aws:SecureTransport": {"Bool": false}"#,
+        );
+        assert_eq!(cache.evaluate(&policies, &insecure).unwrap().0, Decision::DefaultDeny);
+        let bob = context(
+            "arn:aws:s3:::bucket/alice/key",
+            r#""aws:username": {"String": "bob"}, "aws:SecureTransport": {"Bool": true}"#,
+        );
+        assert_eq!(cache.evaluate(&policies, &bob).unwrap().0, Decision::DefaultDeny);
+        assert_eq!((cache.hits(), cache.misses()), (2, 3));
+        assert_eq!(cache.len(), 3);
+
+        // A new policy set invalidates the cache.
+        let updated = policy_set(r#"{"Statement": {"Effect": "Deny", "Action": "s3:*", "Resource": "*"}}"#);
+        assert_eq!(cache.evaluate(&updated, &alice).unwrap().0, Decision::Deny);
+        assert_eq!(cache.len(), 1);
+        assert_eq!(cache.evaluate(&policies, &alice).unwrap().0, Decision::Allow);
+        assert_eq!((cache.hits(), cache.misses()), (2, 5));
+    }
+
+    #[test_log::test]
+    fn test_capacity_and_ttl() {
+        let policies = policy_set(r#"{"Statement": {"Effect": "Allow", "Action": "s3:GetObject", "Resource": "*"}}"#);
+        let cache = DecisionCache::new(2, None);
+        for key in ["a", "b", "c", "a"] {
+            cache.evaluate(&policies, &context(&format!("arn:aws:s3:::bucket/{key}"), "")).unwrap();
+        }
+        assert_eq!(cache.len(), 2);
+        assert_eq!((cache.hits(), cache.misses()), (0, 4));
+
+        cache.clear();
+        assert!(cache.is_empty());
+
+        let cache = DecisionCache::new(10, Some(Duration::from_millis(20)));
+        let request = context("arn:aws:s3:::bucket/a", "");
+        cache.evaluate(&policies, &request).unwrap();
+        cache.evaluate(&policies, &request).unwrap();
+        thread::sleep(Duration::from_millis(30));
+        cache.evaluate(&policies, &request).unwrap();
+        assert_eq!((cache.hits(), cache.misses()), (1, 2));
+        assert_eq!(cache.ttl(), Some(Duration::from_millis(20)));
+        assert_eq!(cache.capacity(), 10);
+    }
+}
@@ -0,0 +1,142 @@
+use {
+    crate::{from_str_json, Action},
+    serde::{Deserialize, Serialize},
+    std::collections::{BTreeMap, BTreeSet},
+};
+
+/// A catalog of known actions, grouped by service.
+///
+/// Aspen policies frequently refer to actions using wildcards (`s3:Get*`, `*`) or by exclusion (`NotAction`). An
+/// action catalog supplies the universe of concrete actions these patterns are expanded against. The crate does not
+/// ship with a catalog; callers typically load one from the AWS service authorization reference or from their own
+/// service definitions.
+///
+/// In JSON, a catalog is represented as a map of service names to lists of API names:
+///
+/// ```
+/// # use scratchstack_aspen::{Action, ActionCatalog};
+/// # use std::str::FromStr;
+/// let catalog = ActionCatalog::from_str(r#"{"s3": ["GetObject", "PutObject"], "ec2": ["RunInstances"]}"#).unwrap();
+/// let expanded = catalog.expand(&Action::from_str("s3:Get*").unwrap());
+/// assert_eq!(expanded, vec![("s3", "GetObject")]);
+/// ```
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(transparent)]
+pub struct ActionCatalog {
+    services: BTreeMap<String, BTreeSet<String>>,
+}
+
+from_str_json!(ActionCatalog);
+
+impl ActionCatalog {
+    /// Create a new, empty action catalog.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add an action to the catalog. Returns `true` if the action was not already present.
+    pub fn insert<S: Into<String>, A: Into<String>>(&mut self, service: S, api: A) -> bool {
+        self.services.entry(service.into()).or_default().insert(api.into())
+    }
+
+    /// Indicates whether the catalog contains the given action.
+    pub fn contains(&self, service: &str, api: &str) -> bool {
+        self.services.get(service).map(|apis| apis.contains(api)).unwrap_or(false)
+    }
+
+    /// Returns an iterator over the services in the catalog, in sorted order.
+    pub fn services(&self) -> impl Iterator<Item = &str> {
+        self.services.keys().map(String::as_str)
+    }
+
+    /// Returns the APIs known for the given service, in sorted order, or `None` if the service is not in the
+    /// catalog.
+    pub fn apis(&self, service: &str) -> Option<impl Iterator<Item = &str>> {
+        self.services.get(service).map(|apis| apis.iter().map(String::as_str))
+    }
+
+    /// Returns an iterator over every `(service, api)` pair in the catalog, in sorted order.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.services.iter().flat_map(|(service, apis)| apis.iter().map(move |api| (service.as_str(), api.as_str())))
+    }
+
+    /// Returns the number of actions in the catalog.
+    pub fn len(&self) -> usize {
+        self.services.values().map(BTreeSet::len).sum()
+    }
+
+    /// Returns `true` if the catalog contains no actions.
+    pub fn is_empty(&self) -> bool {
+        self.services.values().all(BTreeSet::is_empty)
+    }
+
+    /// Returns every action in the catalog matched by the given action pattern, in sorted order.
+    pub fn expand(&self, action: &Action) -> Vec<(&str, &str)> {
+        match action {
+            Action::Any => self.iter().collect(),
+            Action::Specific(_) => match self.services.get_key_value(action.service()) {
+                None => vec![],
+                Some((service, apis)) => apis
+                    .iter()
+                    .filter(|api| action.matches(service, api))
+                    .map(|api| (service.as_str(), api.as_str()))
+                    .collect(),
+            },
+        }
+    }
+}
+
+impl<S: Into<String>, A: Into<String>> Extend<(S, A)> for ActionCatalog {
+    fn extend<I: IntoIterator<Item = (S, A)>>(&mut self, iter: I) {
+        for (service, api) in iter {
+            self.insert(service, api);
+        }
+    }
+}
+
+impl<S: Into<String>, A: Into<String>> FromIterator<(S, A)> for ActionCatalog {
+    fn from_iter<I: IntoIterator<Item = (S, A)>>(iter: I) -> Self {
+        let mut result = Self::new();
+        result.extend(iter);
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        crate::{Action, ActionCatalog},
+        pretty_assertions::assert_eq,
+        std::str::FromStr,
+    };
+
+    #[test_log::test]
+    fn test_basic_ops() {
+        let mut catalog = ActionCatalog::from_iter([("s3", "GetObject"), ("s3", "PutObject"), ("ec2", "RunInstances")]);
+        assert_eq!(catalog.len(), 3);
+        assert!(!catalog.is_empty());
+        assert!(catalog.contains("s3", "GetObject"));
+        assert!(!catalog.contains("s3", "DeleteObject"));
+        assert!(!catalog.contains("iam", "GetUser"));
+        assert!(!catalog.insert("s3", "GetObject"));
+        assert!(catalog.insert("s3", "GetObjectAcl"));
+        assert_eq!(catalog.services().collect::<Vec<_>>(), vec!["ec2", "s3"]);
+        assert_eq!(catalog.apis("s3").unwrap().collect::<Vec<_>>(), vec!["GetObject", "GetObjectAcl", "PutObject"]);
+        assert!(catalog.apis("iam").is_none());
+        assert!(ActionCatalog::new().is_empty());
+
+        assert_eq!(
+            catalog.expand(&Action::from_str("s3:Get*").unwrap()),
+            vec![("s3", "GetObject"), ("s3", "GetObjectAcl")]
+        );
+        assert_eq!(catalog.expand(&Action::from_str("iam:*").unwrap()), vec![]);
+        assert_eq!(catalog.expand(&Action::Any).len(), 4);
+    }
+
+    #[test_log::test]
+    fn test_serialization() {
+        let catalog = ActionCatalog::from_str(r#"{"s3": ["PutObject", "GetObject"]}"#).unwrap();
+        assert_eq!(serde_json::to_string(&catalog).unwrap(), r#"{"s3":["GetObject","PutObject"]}"#);
+        assert!(ActionCatalog::from_str(r#"["s3"]"#).is_err());
+    }
+}
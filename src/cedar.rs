@@ -0,0 +1,1026 @@
+use {
+    crate::{
+        analysis::glob::GlobToken,
+        condition::{numeric::NumericCmp, string::StringCmp},
+        Action, ActionList, AwsPrincipal, ConditionOp, Effect, Policy, PolicyVersion, Principal, Resource,
+        ResourceList, Statement, StringLikeList,
+    },
+    ipnet::IpNet,
+    serde::Serialize,
+    serde_json::{json, Map, Value},
+    std::{
+        collections::{BTreeMap, BTreeSet},
+        fmt::{Display, Formatter, Result as FmtResult},
+        net::IpAddr,
+        str::FromStr,
+    },
+};
+
+/// The Cedar namespace that translated policies and the generated schema use.
+pub const CEDAR_NAMESPACE: &str = "Aws";
+
+/// Why part of a statement could not be translated to Cedar exactly.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
+pub enum CedarDiagnosticKind {
+    /// A condition operator has no Cedar equivalent (date, binary, and case-insensitive string operators).
+    UnsupportedOperator,
+
+    /// A pattern or condition value uses a policy variable such as `${aws:username}`.
+    PolicyVariable,
+
+    /// A pattern uses the `?` wildcard; Cedar `like` patterns only support `*`.
+    SingleCharacterWildcard,
+
+    /// An ARN condition pattern has a wildcard outside of the resource segment, where a Cedar `like` pattern would
+    /// also match across `:` separators.
+    ArnSegmentWildcard,
+
+    /// A condition key is used with operators of different types; Cedar context attributes have a single type.
+    ConflictingKeyType,
+}
+
+impl Display for CedarDiagnosticKind {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        Display::fmt(&format!("{self:?}"), f)
+    }
+}
+
+/// What the translator did with a construct it could not translate exactly.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
+pub enum CedarResolution {
+    /// The `Allow` statement was left out of the Cedar policies, so Cedar never grants more than the original.
+    StatementOmitted,
+
+    /// The constraint was left out of the `Deny` statement's `forbid` policy, so Cedar denies at least as much as the
+    /// original.
+    ConstraintDropped,
+}
+
+impl Display for CedarResolution {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        match self {
+            Self::StatementOmitted => f.write_str("omitted"),
+            Self::ConstraintDropped => f.write_str("approximated"),
+        }
+    }
+}
+
+/// A construct in a statement that has no faithful Cedar equivalent.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct CedarDiagnostic {
+    kind: CedarDiagnosticKind,
+    resolution: CedarResolution,
+    statement_index: usize,
+    sid: Option<String>,
+    message: String,
+}
+
+impl CedarDiagnostic {
+    /// Returns the kind of construct that could not be translated.
+    #[inline]
+    pub fn kind(&self) -> CedarDiagnosticKind {
+        self.kind
+    }
+
+    /// Returns what the translator did instead.
+    #[inline]
+    pub fn resolution(&self) -> CedarResolution {
+        self.resolution
+    }
+
+    /// Returns the index of the statement in the policy.
+    #[inline]
+    pub fn statement_index(&self) -> usize {
+        self.statement_index
+    }
+
+    /// Returns the statement id of the statement, if any.
+    #[inline]
+    pub fn sid(&self) -> Option<&str> {
+        self.sid.as_deref()
+    }
+
+    /// Returns a description of the problem.
+    #[inline]
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+impl Display for CedarDiagnostic {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        write!(f, "{}[{}] statement {}", self.resolution, self.kind, self.statement_index)?;
+        if let Some(sid) = &self.sid {
+            write!(f, " {sid:?}")?;
+        }
+        write!(f, ": {}", self.message)
+    }
+}
+
+/// An Aspen [Policy] translated into Cedar policy text and a Cedar schema.
+///
+/// Each statement becomes a `permit` (for `Allow`) or `forbid` (for `Deny`) policy annotated with
+/// `@id("<Sid>")`. Literal actions are placed in the policy scope; everything else becomes a `when` clause. The
+/// translation assumes Cedar requests are built from Aspen requests as follows; the generated schema (in Cedar's JSON
+/// schema format, under the [CEDAR_NAMESPACE] namespace) describes the same model:
+///
+/// * The principal is an `AwsPrincipal` (attributes `arn`, `partition`, `account`, and `roleName` for assumed role
+///   sessions), `CanonicalUser` (`id`), `FederatedUser` (`name`), or `ServicePrincipal` (`globalName`,
+///   `regionalName`) entity, or an `Anonymous` entity for unauthenticated requests. Cedar has a single principal, so
+///   an actor with several identities must be authorized once per identity.
+/// * The action is the `Action` entity named `service:Api`, and the context attribute `action` holds the same string.
+/// * The resource is a `Resource` entity with the ARN and its parts as attributes (`arn`, `partition`, `service`,
+///   `region`, `account`, `resourceId`), or a `NoResource` entity if the request has no resources. Requests with
+///   several resources must be authorized once per resource.
+/// * Condition keys are context attributes named by the lowercased key, with the type implied by the operators that
+///   use them.
+///
+/// The output needs Cedar 3 or later for the `is` operator.
+///
+/// Constructs without a faithful Cedar equivalent produce a [CedarDiagnostic]. The translation stays conservative:
+/// an `Allow` statement containing one is omitted entirely, and a `Deny` statement keeps its other constraints and
+/// drops the one that could not be translated.
+///
+/// # Example
+///
+/// ```
+/// # use scratchstack_aspen::{CedarTranslation, Policy};
+/// # use std::str::FromStr;
+/// let policy = Policy::from_str(r#"{"Version": "2012-10-17", "Statement": [
+///     {"Sid": "Read", "Effect": "Allow", "Action": "s3:GetObject", "Resource": "arn:aws:s3:::bucket/*"},
+///     {"Sid": "Home", "Effect": "Allow", "Action": "s3:PutObject", "Resource": "arn:aws:s3:::bucket/${aws:username}/*"}
+/// ]}"#).unwrap();
+/// let translation = CedarTranslation::from_policy(&policy);
+/// assert!(translation.policies().contains(r#"action == Aws::Action::"s3:GetObject""#));
+/// assert!(translation.policies().contains(r#"resource.resourceId like "bucket/*""#));
+/// assert_eq!(translation.diagnostics().len(), 1);
+/// assert_eq!(translation.diagnostics()[0].sid(), Some("Home"));
+/// ```
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct CedarTranslation {
+    policies: String,
+    schema: Value,
+    diagnostics: Vec<CedarDiagnostic>,
+}
+
+impl CedarTranslation {
+    /// Translates the statements of a policy.
+    pub fn from_policy(policy: &Policy) -> Self {
+        let mut translator = Translator::new(policy);
+        let mut texts = Vec::with_capacity(policy.statement().len());
+
+        for (index, statement) in policy.statement().iter().enumerate() {
+            if let Some(text) = translator.statement(index, statement, policy.version()) {
+                texts.push(text);
+            }
+        }
+
+        Self {
+            policies: texts.join("\n"),
+            schema: translator.schema(),
+            diagnostics: translator.diagnostics,
+        }
+    }
+
+    /// Returns the Cedar policies, one per translated statement.
+    #[inline]
+    pub fn policies(&self) -> &str {
+        &self.policies
+    }
+
+    /// Returns the Cedar schema in JSON schema format.
+    #[inline]
+    pub fn schema(&self) -> &Value {
+        &self.schema
+    }
+
+    /// Returns the constructs that could not be translated exactly.
+    #[inline]
+    pub fn diagnostics(&self) -> &[CedarDiagnostic] {
+        &self.diagnostics
+    }
+
+    /// Indicates whether every statement was translated exactly.
+    #[inline]
+    pub fn is_exact(&self) -> bool {
+        self.diagnostics.is_empty()
+    }
+}
+
+/// The Cedar type of a context attribute.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum KeyType {
+    String,
+    Long,
+    Boolean,
+    IpAddr,
+}
+
+impl KeyType {
+    fn of(op: &ConditionOp) -> Option<Self> {
+        match op {
+            ConditionOp::Arn(..) | ConditionOp::String(..) => Some(Self::String),
+            ConditionOp::Numeric(..) => Some(Self::Long),
+            ConditionOp::Bool(_) => Some(Self::Boolean),
+            ConditionOp::IpAddress(_) => Some(Self::IpAddr),
+            ConditionOp::Binary(_) | ConditionOp::Date(..) | ConditionOp::Null => None,
+        }
+    }
+
+    fn schema(self) -> Value {
+        match self {
+            Self::String => json!({"type": "String", "required": false}),
+            Self::Long => json!({"type": "Long", "required": false}),
+            Self::Boolean => json!({"type": "Boolean", "required": false}),
+            Self::IpAddr => json!({"type": "Extension", "name": "ipaddr", "required": false}),
+        }
+    }
+}
+
+impl Display for KeyType {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        match self {
+            Self::String => f.write_str("String"),
+            Self::Long => f.write_str("Long"),
+            Self::Boolean => f.write_str("Boolean"),
+            Self::IpAddr => f.write_str("ipaddr"),
+        }
+    }
+}
+
+/// A constraint that could not be translated exactly.
+struct Inexact {
+    kind: CedarDiagnosticKind,
+    message: String,
+}
+
+impl Inexact {
+    fn new(kind: CedarDiagnosticKind, message: String) -> Self {
+        Self {
+            kind,
+            message,
+        }
+    }
+}
+
+/// A translated constraint: `None` if it matches every request.
+type Constraint = Result<Option<String>, Inexact>;
+
+/// How the actions of a statement are expressed.
+enum ActionConstraint {
+    /// An `action == ...` or `action in [...]` scope constraint.
+    Scope(String),
+
+    /// A `when` clause expression.
+    When(String),
+}
+
+struct Translator {
+    key_types: BTreeMap<String, KeyType>,
+    actions: BTreeSet<String>,
+    diagnostics: Vec<CedarDiagnostic>,
+}
+
+impl Translator {
+    /// Creates a translator, assigning each condition key the type of the first typed operator that uses it.
+    fn new(policy: &Policy) -> Self {
+        let mut key_types = BTreeMap::new();
+        let mut untyped = BTreeSet::new();
+
+        for statement in policy.statement().iter() {
+            for (op, map) in statement.condition().into_iter().flat_map(|c| c.iter()) {
+                for key in map.keys() {
+                    match KeyType::of(op) {
+                        Some(key_type) => {
+                            key_types.entry(key.to_lowercase()).or_insert(key_type);
+                        }
+                        None => {
+                            untyped.insert(key.to_lowercase());
+                        }
+                    }
+                }
+            }
+        }
+
+        for key in untyped {
+            key_types.entry(key).or_insert(KeyType::String);
+        }
+
+        Self {
+            key_types,
+            actions: BTreeSet::new(),
+            diagnostics: Vec::new(),
+        }
+    }
+
+    /// Translates a statement, returning `None` if it was omitted.
+    fn statement(&mut self, index: usize, statement: &Statement, pv: PolicyVersion) -> Option<String> {
+        let mut scope_action = None;
+        let mut constraints = Vec::new();
+
+        if let Some(actions) = statement.action() {
+            match self.action_constraint(actions) {
+                Ok(Some(ActionConstraint::Scope(scope))) => scope_action = Some(scope),
+                Ok(Some(ActionConstraint::When(expr))) => constraints.push(Ok(Some(expr))),
+                Ok(None) => (),
+                Err(e) => constraints.push(Err(e)),
+            }
+        } else if let Some(actions) = statement.not_action() {
+            constraints.push(self.action_constraint(actions).map(|constraint| match constraint {
+                Some(ActionConstraint::Scope(expr) | ActionConstraint::When(expr)) => Some(format!("!({expr})")),
+                None => Some("false".to_string()),
+            }));
+        }
+
+        if let Some(resources) = statement.resource() {
+            constraints.push(resource_constraint(resources, pv));
+        } else if let Some(resources) = statement.not_resource() {
+            constraints.push(resource_constraint(resources, pv).map(|expr| match expr {
+                Some(expr) => Some(format!("!({expr})")),
+                None => Some("false".to_string()),
+            }));
+        }
+
+        if let Some(principal) = statement.principal() {
+            constraints.push(Ok(principal_constraint(principal)));
+        } else if let Some(principal) = statement.not_principal() {
+            constraints.push(Ok(Some(match principal_constraint(principal) {
+                Some(expr) => format!("!({expr})"),
+                None => "false".to_string(),
+            })));
+        }
+
+        for (op, map) in statement.condition().into_iter().flat_map(|c| c.iter()) {
+            for (key, allowed) in map.iter() {
+                constraints.push(self.condition_constraint(op, key, allowed, pv));
+            }
+        }
+
+        let resolution = match statement.effect() {
+            Effect::Allow => CedarResolution::StatementOmitted,
+            Effect::Deny => CedarResolution::ConstraintDropped,
+        };
+        let mut exprs = Vec::with_capacity(constraints.len());
+        let mut exact = true;
+
+        for constraint in constraints {
+            match constraint {
+                Ok(Some(expr)) => exprs.push(expr),
+                Ok(None) => (),
+                Err(inexact) => {
+                    exact = false;
+                    self.diagnostics.push(CedarDiagnostic {
+                        kind: inexact.kind,
+                        resolution,
+                        statement_index: index,
+                        sid: statement.sid().map(str::to_string),
+                        message: inexact.message,
+                    });
+                }
+            }
+        }
+
+        if !exact && resolution == CedarResolution::StatementOmitted {
+            return None;
+        }
+
+        let id = match statement.sid() {
+            Some(sid) => sid.to_string(),
+            None => format!("statement-{index}"),
+        };
+        let effect = match statement.effect() {
+            Effect::Allow => "permit",
+            Effect::Deny => "forbid",
+        };
+        let action = scope_action.unwrap_or_else(|| "action".to_string());
+        let mut text = format!("@id({})\n{effect} (\n    principal,\n    {action},\n    resource\n)", quote(&id));
+
+        if !exprs.is_empty() {
+            text.push_str("\nwhen {\n    ");
+            text.push_str(&exprs.join(" &&\n    "));
+            text.push_str("\n}");
+        }
+
+        text.push_str(";\n");
+        Some(text)
+    }
+
+    /// Translates an action list into the expression matching any of its actions.
+    fn action_constraint(&mut self, actions: &ActionList) -> Result<Option<ActionConstraint>, Inexact> {
+        if actions.iter().any(Action::is_any) {
+            return Ok(None);
+        }
+
+        if actions.iter().all(|action| !action.api().contains(['*', '?'])) {
+            let mut entities = Vec::with_capacity(actions.len());
+            for action in actions.iter() {
+                let name = format!("{}:{}", action.service(), action.api());
+                entities.push(format!("{CEDAR_NAMESPACE}::Action::{}", quote(&name)));
+                self.actions.insert(name);
+            }
+
+            return Ok(Some(ActionConstraint::Scope(if entities.len() == 1 {
+                format!("action == {}", entities[0])
+            } else {
+                format!("action in [{}]", entities.join(", "))
+            })));
+        }
+
+        let mut terms = Vec::with_capacity(actions.len());
+        for action in actions.iter() {
+            let mut tokens = literal(&format!("{}:", action.service()));
+            tokens.extend(pattern_tokens(action.api(), true, false)?);
+            match glob_expr("context.action", &tokens, action.api())? {
+                Some(term) => terms.push(term),
+                None => return Ok(None),
+            }
+        }
+
+        Ok(Some(ActionConstraint::When(any_of(terms))))
+    }
+
+    /// Translates the values of one condition key for one operator.
+    fn condition_constraint(
+        &self,
+        op: &ConditionOp,
+        key: &str,
+        allowed: &StringLikeList<String>,
+        pv: PolicyVersion,
+    ) -> Constraint {
+        let key = key.to_lowercase();
+        let attr = format!("context[{}]", quote(&key));
+        let has = format!("context has {}", quote(&key));
+        let variables = pv == PolicyVersion::V2012_10_17;
+
+        if let Some(key_type) = KeyType::of(op) {
+            let declared = self.key_types[&key];
+            if declared != key_type {
+                return Err(Inexact::new(
+                    CedarDiagnosticKind::ConflictingKeyType,
+                    format!("{op} uses condition key {key} as {key_type}, but it is declared as {declared}"),
+                ));
+            }
+        }
+
+        let mut terms = Vec::with_capacity(allowed.len());
+        let variant = match op {
+            ConditionOp::Null => {
+                let mut values = BTreeSet::new();
+                for el in allowed.iter() {
+                    if let Ok(value) = bool::from_str(&plain_value(el, variables)?) {
+                        values.insert(value);
+                    }
+                }
+
+                return Ok(match (values.contains(&true), values.contains(&false)) {
+                    (true, true) => None,
+                    (true, false) => Some(format!("!({has})")),
+                    (false, true) => Some(has),
+                    (false, false) => Some("false".to_string()),
+                });
+            }
+            ConditionOp::Binary(_) | ConditionOp::Date(..) | ConditionOp::String(StringCmp::EqualsIgnoreCase, _) => {
+                return Err(Inexact::new(
+                    CedarDiagnosticKind::UnsupportedOperator,
+                    format!("{op} has no Cedar equivalent"),
+                ));
+            }
+            ConditionOp::String(StringCmp::Equals, variant) => {
+                for el in allowed.iter() {
+                    let value = quote(&plain_value(el, true)?);
+                    terms.push(if variant.negated() {
+                        format!("{attr} != {value}")
+                    } else {
+                        format!("{attr} == {value}")
+                    });
+                }
+                variant
+            }
+            ConditionOp::String(StringCmp::Like, variant) => {
+                for el in allowed.iter() {
+                    let expr = glob_expr(&attr, &pattern_tokens(el, true, variables)?, el)?;
+                    terms.push(match (expr, variant.negated()) {
+                        (Some(expr), false) => expr,
+                        (Some(expr), true) => format!("!({expr})"),
+                        (None, false) => "true".to_string(),
+                        (None, true) => "false".to_string(),
+                    });
+                }
+                variant
+            }
+            ConditionOp::Arn(_, variant) => {
+                for el in allowed.iter() {
+                    let parts = el.splitn(6, ':').collect::<Vec<&str>>();
+                    if parts.len() != 6 || parts[0] != "arn" {
+                        continue;
+                    }
+
+                    let mut tokens = literal("arn:");
+                    for part in &parts[1..5] {
+                        if part.contains(['*', '?']) {
+                            return Err(Inexact::new(
+                                CedarDiagnosticKind::ArnSegmentWildcard,
+                                format!("{op} pattern {el} has a wildcard outside of the resource segment"),
+                            ));
+                        }
+                        tokens.extend(literal(part));
+                        tokens.push(GlobToken::Literal(':'));
+                    }
+                    tokens.extend(pattern_tokens(parts[5], true, variables)?);
+
+                    terms.push(match (glob_expr(&attr, &tokens, el)?, variant.negated()) {
+                        (Some(expr), false) => expr,
+                        (Some(expr), true) => format!("!({expr})"),
+                        (None, false) => "true".to_string(),
+                        (None, true) => "false".to_string(),
+                    });
+                }
+                variant
+            }
+            ConditionOp::Numeric(cmp, variant) => {
+                let operator = match (cmp, variant.negated()) {
+                    (NumericCmp::Equals, false) => "==",
+                    (NumericCmp::Equals, true) => "!=",
+                    (NumericCmp::LessThan, false) => "<",
+                    (NumericCmp::LessThan, true) => ">=",
+                    (NumericCmp::LessThanEquals, false) => "<=",
+                    (NumericCmp::LessThanEquals, true) => ">",
+                };
+
+                for el in allowed.iter() {
+                    if let Ok(value) = i64::from_str(&plain_value(el, variables)?) {
+                        terms.push(format!("{attr} {operator} {value}"));
+                    }
+                }
+                variant
+            }
+            ConditionOp::Bool(variant) => {
+                let mut values = BTreeSet::new();
+                for el in allowed.iter() {
+                    if let Ok(value) = bool::from_str(&plain_value(el, variables)?) {
+                        values.insert(value);
+                    }
+                }
+
+                terms.extend(values.into_iter().map(|value| format!("{attr} == {value}")));
+                variant
+            }
+            ConditionOp::IpAddress(variant) => {
+                for el in allowed.iter() {
+                    let value = plain_value(el, variables)?;
+                    let net = match value.parse::<IpNet>() {
+                        Ok(net) => net.trunc(),
+                        Err(_) => match value.parse::<IpAddr>() {
+                            Ok(addr) => IpNet::from(addr),
+                            Err(_) => continue,
+                        },
+                    };
+
+                    let expr = format!("{attr}.isInRange(ip({}))", quote(&net.to_string()));
+                    terms.push(if variant.negated() {
+                        format!("!({expr})")
+                    } else {
+                        expr
+                    });
+                }
+                variant
+            }
+        };
+
+        let body = any_of(terms);
+        Ok(Some(if variant.if_exists() {
+            format!("(!({has}) || {body})")
+        } else {
+            format!("({has} && {body})")
+        }))
+    }
+
+    /// Generates the Cedar schema for the translated policies.
+    fn schema(&self) -> Value {
+        let mut context = Map::new();
+        context.insert("action".to_string(), json!({"type": "String"}));
+        for (key, key_type) in &self.key_types {
+            context.insert(key.clone(), key_type.schema());
+        }
+
+        let principal_types = ["AwsPrincipal", "CanonicalUser", "FederatedUser", "ServicePrincipal", "Anonymous"];
+        let actions: Map<String, Value> = self
+            .actions
+            .iter()
+            .map(|action| {
+                let applies_to = json!({"appliesTo": {
+                    "principalTypes": principal_types,
+                    "resourceTypes": ["Resource", "NoResource"],
+                    "context": {"type": "Context"},
+                }});
+                (action.clone(), applies_to)
+            })
+            .collect();
+
+        json!({CEDAR_NAMESPACE: {
+            "commonTypes": {
+                "Context": {"type": "Record", "attributes": context},
+            },
+            "entityTypes": {
+                "AwsPrincipal": {"shape": {"type": "Record", "attributes": {
+                    "arn": {"type": "String"},
+                    "partition": {"type": "String"},
+                    "account": {"type": "String"},
+                    "roleName": {"type": "String", "required": false},
+                }}},
+                "CanonicalUser": {"shape": {"type": "Record", "attributes": {"id": {"type": "String"}}}},
+                "FederatedUser": {"shape": {"type": "Record", "attributes": {"name": {"type": "String"}}}},
+                "ServicePrincipal": {"shape": {"type": "Record", "attributes": {
+                    "globalName": {"type": "String"},
+                    "regionalName": {"type": "String"},
+                }}},
+                "Anonymous": {},
+                "Resource": {"shape": {"type": "Record", "attributes": {
+                    "arn": {"type": "String"},
+                    "partition": {"type": "String"},
+                    "service": {"type": "String"},
+                    "region": {"type": "String"},
+                    "account": {"type": "String"},
+                    "resourceId": {"type": "String"},
+                }}},
+                "NoResource": {},
+            },
+            "actions": actions,
+        }})
+    }
+}
+
+/// Translates a resource list into the expression matching any of its patterns.
+fn resource_constraint(resources: &ResourceList, pv: PolicyVersion) -> Constraint {
+    let mut terms = Vec::with_capacity(resources.len());
+
+    for resource in resources.iter() {
+        let arn = match resource {
+            Resource::Any => return Ok(None),
+            Resource::Arn(arn) => arn,
+        };
+
+        let segments = [
+            ("partition", arn.partition_pattern(), false),
+            ("service", arn.service_pattern(), false),
+            ("region", arn.region_pattern(), false),
+            ("account", arn.account_id_pattern(), false),
+            ("resourceId", arn.resource_pattern(), pv == PolicyVersion::V2012_10_17),
+        ];
+
+        let mut exprs = Vec::with_capacity(segments.len());
+        for (attr, pattern, variables) in segments {
+            let tokens = pattern_tokens(pattern, true, variables)?;
+            if let Some(expr) = glob_expr(&format!("resource.{attr}"), &tokens, &resource.to_string())? {
+                exprs.push(expr);
+            }
+        }
+
+        match exprs.len() {
+            0 => return Ok(Some(format!("resource is {CEDAR_NAMESPACE}::Resource"))),
+            1 => terms.push(exprs.remove(0)),
+            _ => terms.push(format!("({})", exprs.join(" && "))),
+        }
+    }
+
+    Ok(Some(format!("resource is {CEDAR_NAMESPACE}::Resource && {}", any_of(terms))))
+}
+
+/// Translates a principal into the expression matching any of its identities.
+fn principal_constraint(principal: &Principal) -> Option<String> {
+    let specified = principal.specified()?;
+    let mut terms = Vec::new();
+    let is = |entity_type: &str| format!("principal is {CEDAR_NAMESPACE}::{entity_type}");
+
+    for aws in specified.aws().into_iter().flat_map(|list| list.iter()) {
+        terms.push(match aws {
+            AwsPrincipal::Any => is("AwsPrincipal"),
+            AwsPrincipal::Account(account_id) => {
+                format!("({} && principal.account == {})", is("AwsPrincipal"), quote(account_id))
+            }
+            AwsPrincipal::Arn(arn) if arn.service() == "iam" && arn.resource() == "root" => format!(
+                "({} && principal.partition == {} && principal.account == {})",
+                is("AwsPrincipal"),
+                quote(arn.partition()),
+                quote(arn.account_id())
+            ),
+            AwsPrincipal::Arn(arn) if arn.service() == "iam" && arn.resource().starts_with("role/") => format!(
+                "({} && (principal.arn == {} || (principal has roleName && principal.partition == {} && \
+                 principal.account == {} && principal.roleName == {})))",
+                is("AwsPrincipal"),
+                quote(&arn.to_string()),
+                quote(arn.partition()),
+                quote(arn.account_id()),
+                quote(arn.resource().rsplit('/').next().unwrap_or_default())
+            ),
+            AwsPrincipal::Arn(arn) => {
+                format!("({} && principal.arn == {})", is("AwsPrincipal"), quote(&arn.to_string()))
+            }
+        });
+    }
+
+    for id in specified.canonical_user().into_iter().flat_map(|list| list.iter()) {
+        terms.push(format!("({} && principal.id == {})", is("CanonicalUser"), quote(id)));
+    }
+
+    for name in specified.federated().into_iter().flat_map(|list| list.iter()) {
+        terms.push(format!("({} && principal.name == {})", is("FederatedUser"), quote(name)));
+    }
+
+    for service in specified.service().into_iter().flat_map(|list| list.iter()) {
+        terms.push(format!(
+            "({} && (principal.globalName == {} || principal.regionalName == {}))",
+            is("ServicePrincipal"),
+            quote(service),
+            quote(service)
+        ));
+    }
+
+    Some(any_of(terms))
+}
+
+/// Converts a pattern into glob tokens, resolving the `${*}`, `${$}`, and `${?}` escapes if variables are enabled.
+///
+/// If `glob` is false, `*` and `?` are literal characters.
+fn pattern_tokens(pattern: &str, glob: bool, variables: bool) -> Result<Vec<GlobToken>, Inexact> {
+    let mut tokens = Vec::with_capacity(pattern.len());
+    let mut chars = pattern.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '$' if variables => {
+                let mut var = String::new();
+                let mut closed = false;
+                if chars.next() == Some('{') {
+                    for c in chars.by_ref() {
+                        if c == '}' {
+                            closed = true;
+                            break;
+                        }
+                        var.push(c);
+                    }
+                }
+
+                match (closed, var.as_str()) {
+                    (true, "*") => tokens.push(GlobToken::Literal('*')),
+                    (true, "$") => tokens.push(GlobToken::Literal('$')),
+                    (true, "?") => tokens.push(GlobToken::Literal('?')),
+                    (true, var) => {
+                        return Err(Inexact::new(
+                            CedarDiagnosticKind::PolicyVariable,
+                            format!("{pattern} uses the policy variable ${{{var}}}, which Cedar cannot substitute"),
+                        ))
+                    }
+                    (false, _) => {
+                        return Err(Inexact::new(
+                            CedarDiagnosticKind::PolicyVariable,
+                            format!("{pattern} has a malformed policy variable"),
+                        ))
+                    }
+                }
+            }
+            '*' if glob => tokens.push(GlobToken::AnyString),
+            '?' if glob => tokens.push(GlobToken::AnyChar),
+            c => tokens.push(GlobToken::Literal(c)),
+        }
+    }
+
+    Ok(tokens)
+}
+
+/// Resolves the escapes in a plain (non-pattern) condition value.
+fn plain_value(value: &str, variables: bool) -> Result<String, Inexact> {
+    Ok(pattern_tokens(value, false, variables)?
+        .into_iter()
+        .map(|token| match token {
+            GlobToken::Literal(c) => c,
+            _ => unreachable!("plain values have no wildcards"),
+        })
+        .collect())
+}
+
+/// Converts a string into tokens that match only that exact string.
+fn literal(value: &str) -> Vec<GlobToken> {
+    value.chars().map(GlobToken::Literal).collect()
+}
+
+/// Translates a glob match of `lhs` against `tokens`, returning `None` if the pattern matches every string.
+fn glob_expr(lhs: &str, tokens: &[GlobToken], pattern: &str) -> Result<Option<String>, Inexact> {
+    if tokens.contains(&GlobToken::AnyChar) {
+        return Err(Inexact::new(
+            CedarDiagnosticKind::SingleCharacterWildcard,
+            format!("{pattern} uses the ? wildcard, which Cedar patterns do not support"),
+        ));
+    }
+
+    if !tokens.is_empty() && tokens.iter().all(|token| *token == GlobToken::AnyString) {
+        return Ok(None);
+    }
+
+    let mut text = String::with_capacity(tokens.len() + 2);
+    let like = tokens.contains(&GlobToken::AnyString);
+    text.push('"');
+    for token in tokens {
+        match token {
+            GlobToken::AnyString => text.push('*'),
+            GlobToken::Literal('*') if like => text.push_str("\\*"),
+            GlobToken::Literal(c) => escape(*c, &mut text),
+            GlobToken::AnyChar => unreachable!(),
+        }
+    }
+    text.push('"');
+
+    Ok(Some(if like {
+        format!("{lhs} like {text}")
+    } else {
+        format!("{lhs} == {text}")
+    }))
+}
+
+/// Joins expressions with `||`, returning `false` if there are none.
+fn any_of(mut terms: Vec<String>) -> String {
+    match terms.len() {
+        0 => "false".to_string(),
+        1 => terms.remove(0),
+        _ => format!("({})", terms.join(" || ")),
+    }
+}
+
+/// Quotes a string as a Cedar string literal.
+fn quote(value: &str) -> String {
+    let mut result = String::with_capacity(value.len() + 2);
+    result.push('"');
+    for c in value.chars() {
+        escape(c, &mut result);
+    }
+    result.push('"');
+    result
+}
+
+fn escape(c: char, out: &mut String) {
+    match c {
+        '"' => out.push_str("\\\""),
+        '\\' => out.push_str("\\\\"),
+        c if c.is_control() => out.extend(c.escape_default()),
+        c => out.push(c),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::{CedarDiagnosticKind, CedarResolution, CedarTranslation},
+        crate::Policy,
+        pretty_assertions::assert_eq,
+        serde_json::json,
+        std::str::FromStr,
+    };
+
+    #[test_log::test]
+    fn test_translate() {
+        let policy = Policy::from_str(
+            r#"{"Version": "2012-10-17", "Statement": [
+                {"Sid": "Read", "Effect": "Allow", "Action": ["s3:GetObject", "s3:GetObjectAcl"],
+                 "Resource": "arn:aws:s3:::bucket/*", "Condition": {"StringEquals": {"aws:PrincipalTag/Team": "ops"}}},
+                {"Effect": "Deny", "Action": "s3:Delete*", "Resource": "*", "Principal": "*"}
+            ]}"#,
+        )
+        .unwrap();
+        let translation = CedarTranslation::from_policy(&policy);
+        assert!(translation.is_exact());
+        assert_eq!(
+            translation.policies(),
+            r#"@id("Read")
+permit (
+    principal,
+    action in [Aws::Action::"s3:GetObject", Aws::Action::"s3:GetObjectAcl"],
+    resource
+)
+when {
+    resource is Aws::Resource && (resource.partition == "aws" && resource.service == "s3" && resource.region == "" && resource.account == "" && resource.resourceId like "bucket/*") &&
+    (context has "aws:principaltag/team" && context["aws:principaltag/team"] == "ops")
+};
+
+@id("statement-1")
+forbid (
+    principal,
+    action,
+    resource
+)
+when {
+    context.action like "s3:Delete*"
+};
+"#
+        );
+
+        let schema = &translation.schema()["Aws"];
+        assert_eq!(
+            schema["commonTypes"]["Context"]["attributes"],
+            json!({
+                "action": {"type": "String"},
+                "aws:principaltag/team": {"type": "String", "required": false},
+            })
+        );
+        let actions = schema["actions"].as_object().unwrap();
+        assert_eq!(actions.keys().collect::<Vec<_>>(), vec!["s3:GetObject", "s3:GetObjectAcl"]);
+        assert_eq!(actions["s3:GetObject"]["appliesTo"]["resourceTypes"], json!(["Resource", "NoResource"]));
+        assert!(schema["entityTypes"]["AwsPrincipal"]["shape"]["attributes"]["roleName"].is_object());
+    }
+
+    #[test_log::test]
+    fn test_principals_and_conditions() {
+        let policy = Policy::from_str(
+            r#"{"Version": "2012-10-17", "Statement": [{
+                "Effect": "Allow", "NotAction": "iam:*", "NotResource": "arn:aws:s3:::secret/*",
+                "Principal": {"AWS": ["123456789012", "arn:aws:iam::123456789012:role/path/Admin"],
+                              "Service": "ec2.amazonaws.com", "CanonicalUser": "abc"},
+                "Condition": {
+                    "StringLike": {"s3:prefix": ["home/*", "a${*}b"]},
+                    "NumericLessThan": {"s3:max-keys": ["10", "ten"]},
+                    "Bool": {"aws:SecureTransport": "true"},
+                    "NotIpAddressIfExists": {"aws:SourceIp": ["10.1.2.3/8", "::1"]},
+                    "Null": {"aws:TokenIssueTime": "false"},
+                    "ArnNotLike": {"aws:SourceArn": "arn:aws:sns:us-east-1:123456789012:topic*"}
+                }
+            }]}"#,
+        )
+        .unwrap();
+        let translation = CedarTranslation::from_policy(&policy);
+        assert!(translation.is_exact(), "{:?}", translation.diagnostics());
+
+        let policies = translation.policies();
+        for expected in [
+            r#"!(context.action like "iam:*")"#,
+            r#"!(resource is Aws::Resource && (resource.partition == "aws" && resource.service == "s3" && resource.region == "" && resource.account == "" && resource.resourceId like "secret/*"))"#,
+            r#"(principal is Aws::AwsPrincipal && principal.account == "123456789012")"#,
+            r#"(principal has roleName && principal.partition == "aws" && principal.account == "123456789012" && principal.roleName == "Admin")"#,
+            r#"(principal is Aws::CanonicalUser && principal.id == "abc")"#,
+            r#"principal.regionalName == "ec2.amazonaws.com""#,
+            r#"(context has "s3:prefix" && (context["s3:prefix"] like "home/*" || context["s3:prefix"] == "a*b"))"#,
+            r#"(context has "s3:max-keys" && context["s3:max-keys"] < 10)"#,
+            r#"(context has "aws:securetransport" && context["aws:securetransport"] == true)"#,
+            r#"(!(context has "aws:sourceip") || (!(context["aws:sourceip"].isInRange(ip("10.0.0.0/8"))) || !(context["aws:sourceip"].isInRange(ip("::1/128")))))"#,
+            r#"context has "aws:tokenissuetime" &&"#,
+            r#"(context has "aws:sourcearn" && !(context["aws:sourcearn"] like "arn:aws:sns:us-east-1:123456789012:topic*"))"#,
+        ] {
+            assert!(policies.contains(expected), "{expected} not in {policies}");
+        }
+
+        let context = &translation.schema()["Aws"]["commonTypes"]["Context"]["attributes"];
+        assert_eq!(context["s3:max-keys"]["type"], "Long");
+        assert_eq!(context["aws:securetransport"]["type"], "Boolean");
+        assert_eq!(context["aws:sourceip"], json!({"type": "Extension", "name": "ipaddr", "required": false}));
+    }
+
+    #[test_log::test]
+    fn test_diagnostics() {
+        let policy = Policy::from_str(
+            r#"{"Version": "2012-10-17", "Statement": [
+                {"Sid": "Dated", "Effect": "Allow", "Action": "s3:GetObject", "Resource": "*",
+                 "Condition": {"DateLessThan": {"aws:CurrentTime": "2030-01-01T00:00:00Z"}}},
+                {"Sid": "Fence", "Effect": "Deny", "Action": "ec2:TerminateInstances",
+                 "Resource": "arn:aws:ec2:*:*:instance/i-?", "Condition": {"StringEquals": {"ec2:ResourceTag/Env": "prod"}}},
+                {"Effect": "Allow", "Action": "sns:Publish", "Resource": "*",
+                 "Condition": {"ArnLike": {"aws:SourceArn": "arn:aws:s3:::*"}, "NumericEquals": {"ec2:ResourceTag/Env": "1"}}},
+                {"Effect": "Allow", "Action": "sqs:SendMessage", "Resource": "*",
+                 "Condition": {"ArnLike": {"aws:SourceArn": "arn:aws:sns:*:123456789012:topic"}}}
+            ]}"#,
+        )
+        .unwrap();
+        let translation = CedarTranslation::from_policy(&policy);
+        let diagnostics = translation.diagnostics();
+
+        let summary = diagnostics.iter().map(|d| (d.statement_index(), d.kind(), d.resolution())).collect::<Vec<_>>();
+        assert_eq!(
+            summary,
+            vec![
+                (0, CedarDiagnosticKind::UnsupportedOperator, CedarResolution::StatementOmitted),
+                (1, CedarDiagnosticKind::SingleCharacterWildcard, CedarResolution::ConstraintDropped),
+                (2, CedarDiagnosticKind::ConflictingKeyType, CedarResolution::StatementOmitted),
+                (3, CedarDiagnosticKind::ArnSegmentWildcard, CedarResolution::StatementOmitted),
+            ]
+        );
+        assert_eq!(
+            diagnostics[0].to_string(),
+            r#"omitted[UnsupportedOperator] statement 0 "Dated": DateLessThan has no Cedar equivalent"#
+        );
+        assert_eq!(diagnostics[1].sid(), Some("Fence"));
+
+        // Only the Deny statement survives, without its resource constraint.
+        assert_eq!(
+            translation.policies(),
+            r#"@id("Fence")
+forbid (
+    principal,
+    action == Aws::Action::"ec2:TerminateInstances",
+    resource
+)
+when {
+    (context has "ec2:resourcetag/env" && context["ec2:resourcetag/env"] == "prod")
+};
+"#
+        );
+    }
+}
@@ -0,0 +1,920 @@
+use {
+    crate::{AspenError, Policy},
+    serde_json::{Map, Value},
+    std::{
+        collections::HashMap,
+        fmt::{Display, Formatter, Result as FmtResult},
+        str::FromStr,
+    },
+};
+
+/// Pseudo parameters with a well-known value that is used unless it is overridden.
+const PSEUDO_PARAMETER_DEFAULTS: [(&str, &str); 2] = [("AWS::Partition", "aws"), ("AWS::URLSuffix", "amazonaws.com")];
+
+/// The prefix of CloudFormation pseudo parameters.
+const PSEUDO_PARAMETER_PREFIX: &str = "AWS::";
+
+/// The kind of policy found in a CloudFormation template or Terraform plan.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum TemplatePolicyKind {
+    /// An inline identity policy: `AWS::IAM::Policy`, `AWS::IAM::RolePolicy`, an entry in the `Policies` property
+    /// of a role, user, or group, `aws_iam_role_policy`, or an `inline_policy` block of `aws_iam_role`.
+    Inline,
+
+    /// A customer managed policy (`AWS::IAM::ManagedPolicy` or `aws_iam_policy`).
+    Managed,
+
+    /// A role trust policy (the `AssumeRolePolicyDocument` property of `AWS::IAM::Role` or the `assume_role_policy`
+    /// of `aws_iam_role`).
+    Trust,
+
+    /// A resource policy, such as an `AWS::S3::BucketPolicy`, `AWS::SQS::QueuePolicy`, `AWS::KMS::Key` key policy,
+    /// or `aws_s3_bucket_policy`.
+    Resource,
+
+    /// A standalone policy document that is not attached to anything by itself (an `aws_iam_policy_document` data
+    /// source).
+    Document,
+}
+
+impl Display for TemplatePolicyKind {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        match self {
+            Self::Inline => f.write_str("Inline"),
+            Self::Managed => f.write_str("Managed"),
+            Self::Trust => f.write_str("Trust"),
+            Self::Resource => f.write_str("Resource"),
+            Self::Document => f.write_str("Document"),
+        }
+    }
+}
+
+/// Resource types whose policy documents are extracted, and the properties the documents are found in.
+///
+/// A property of `None` means the resource type has a `Policies` list of `{"PolicyName", "PolicyDocument"}` entries.
+const POLICY_PROPERTIES: [(&str, Option<&str>, TemplatePolicyKind); 16] = [
+    ("AWS::IAM::Role", Some("AssumeRolePolicyDocument"), TemplatePolicyKind::Trust),
+    ("AWS::IAM::Role", None, TemplatePolicyKind::Inline),
+    ("AWS::IAM::User", None, TemplatePolicyKind::Inline),
+    ("AWS::IAM::Group", None, TemplatePolicyKind::Inline),
+    ("AWS::IAM::Policy", Some("PolicyDocument"), TemplatePolicyKind::Inline),
+    ("AWS::IAM::RolePolicy", Some("PolicyDocument"), TemplatePolicyKind::Inline),
+    ("AWS::IAM::UserPolicy", Some("PolicyDocument"), TemplatePolicyKind::Inline),
+    ("AWS::IAM::GroupPolicy", Some("PolicyDocument"), TemplatePolicyKind::Inline),
+    ("AWS::IAM::ManagedPolicy", Some("PolicyDocument"), TemplatePolicyKind::Managed),
+    ("AWS::S3::BucketPolicy", Some("PolicyDocument"), TemplatePolicyKind::Resource),
+    ("AWS::SQS::QueuePolicy", Some("PolicyDocument"), TemplatePolicyKind::Resource),
+    ("AWS::SNS::TopicPolicy", Some("PolicyDocument"), TemplatePolicyKind::Resource),
+    ("AWS::KMS::Key", Some("KeyPolicy"), TemplatePolicyKind::Resource),
+    ("AWS::SecretsManager::ResourcePolicy", Some("ResourcePolicy"), TemplatePolicyKind::Resource),
+    ("AWS::ECR::Repository", Some("RepositoryPolicyText"), TemplatePolicyKind::Resource),
+    ("AWS::Events::EventBusPolicy", Some("Statement"), TemplatePolicyKind::Resource),
+];
+
+/// A policy document extracted from a CloudFormation template.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct TemplatePolicy {
+    logical_id: String,
+    resource_type: String,
+    kind: TemplatePolicyKind,
+    property: String,
+    policy_name: Option<String>,
+    policy: Policy,
+}
+
+impl TemplatePolicy {
+    /// Returns the logical id of the resource the policy was found in.
+    #[inline]
+    pub fn logical_id(&self) -> &str {
+        &self.logical_id
+    }
+
+    /// Returns the CloudFormation type of the resource the policy was found in, e.g. `AWS::IAM::Role`.
+    #[inline]
+    pub fn resource_type(&self) -> &str {
+        &self.resource_type
+    }
+
+    /// Returns the kind of policy.
+    #[inline]
+    pub fn kind(&self) -> TemplatePolicyKind {
+        self.kind
+    }
+
+    /// Returns the path of the property holding the policy document, e.g. `Policies[1].PolicyDocument`.
+    #[inline]
+    pub fn property(&self) -> &str {
+        &self.property
+    }
+
+    /// Returns the name of the policy, for resources and `Policies` entries that have a `PolicyName` or
+    /// `ManagedPolicyName`.
+    #[inline]
+    pub fn policy_name(&self) -> Option<&str> {
+        self.policy_name.as_deref()
+    }
+
+    /// Returns the policy.
+    #[inline]
+    pub fn policy(&self) -> &Policy {
+        &self.policy
+    }
+}
+
+/// A CloudFormation template in JSON form, along with parameter and condition values used to resolve intrinsic
+/// functions in its policy documents.
+///
+/// The following intrinsic functions are resolved: `Ref`, `Fn::GetAtt`, `Fn::Sub`, `Fn::Join`, `Fn::Select`,
+/// `Fn::Split`, `Fn::FindInMap`, `Fn::If`, and `Fn::ImportValue`, along with the condition functions `Fn::Equals`,
+/// `Fn::And`, `Fn::Or`, `Fn::Not`, and `Condition`.
+///
+/// A `Ref` to a parameter uses the value set with [CloudFormationTemplate::set_parameter], falling back to the
+/// parameter's `Default`. `AWS::Partition` and `AWS::URLSuffix` default to `aws` and `amazonaws.com`. Values for
+/// other pseudo parameters, resource references (`MyBucket`), attributes (`MyBucket.Arn`), and imports
+/// (`Fn::ImportValue:ExportName`) can also be set as parameters. Anything else resolves to a symbolic placeholder
+/// such as `{{MyBucket}}` or `{{AccountId}}`. An unknown `Arn` attribute resolves to an ARN built from the resource
+/// type, e.g. `arn:aws:s3:{{Region}}:{{AccountId}}:{{MyBucket.Arn}}`. Placeholders are accepted in actions and
+/// resources, but not in principal ARNs; supply values for those.
+///
+/// Resources whose `Condition` is false are skipped. Conditions are evaluated from the template unless set with
+/// [CloudFormationTemplate::set_condition]; a condition that depends on a placeholder must be set explicitly.
+///
+/// # Example
+///
+/// ```
+/// # use scratchstack_aspen::{CloudFormationTemplate, TemplatePolicyKind};
+/// let mut template = CloudFormationTemplate::from_json(r#"{
+///     "Parameters": {"Stage": {"Type": "String", "Default": "dev"}},
+///     "Resources": {
+///         "Bucket": {"Type": "AWS::S3::Bucket"},
+///         "BucketPolicy": {"Type": "AWS::S3::BucketPolicy", "Properties": {
+///             "Bucket": {"Ref": "Bucket"},
+///             "PolicyDocument": {"Statement": {"Effect": "Deny", "Principal": "*", "Action": "s3:*",
+///                 "Resource": {"Fn::Sub": "${Bucket.Arn}/${Stage}/*"},
+///                 "Condition": {"Bool": {"aws:SecureTransport": "false"}}}}}}}}"#).unwrap();
+///
+/// let policies = template.policies().unwrap();
+/// assert_eq!(policies[0].logical_id(), "BucketPolicy");
+/// assert_eq!(policies[0].kind(), TemplatePolicyKind::Resource);
+/// assert_eq!(
+///     policies[0].policy().statement()[0].resource().unwrap()[0].to_string(),
+///     "arn:aws:s3:{{Region}}:{{AccountId}}:{{Bucket.Arn}}/dev/*"
+/// );
+///
+/// template.set_parameter("Bucket.Arn", "arn:aws:s3:::logs");
+/// let policies = template.policies().unwrap();
+/// assert_eq!(policies[0].policy().statement()[0].resource().unwrap()[0].to_string(), "arn:aws:s3:::logs/dev/*");
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct CloudFormationTemplate {
+    template: Map<String, Value>,
+    parameters: HashMap<String, Value>,
+    conditions: HashMap<String, bool>,
+}
+
+impl CloudFormationTemplate {
+    /// Parses a CloudFormation template from its JSON representation.
+    ///
+    /// # Errors
+    ///
+    /// If the JSON is malformed or the template has no `Resources` object, [AspenError::InvalidTemplate] is
+    /// returned.
+    pub fn from_json(json: &str) -> Result<Self, AspenError> {
+        let value: Value = serde_json::from_str(json).map_err(|e| invalid(&e.to_string()))?;
+        let template = match value {
+            Value::Object(template) => template,
+            _ => return Err(invalid("template is not an object")),
+        };
+
+        if !template.get("Resources").map(Value::is_object).unwrap_or(false) {
+            return Err(invalid("missing Resources"));
+        }
+
+        Ok(Self {
+            template,
+            parameters: HashMap::new(),
+            conditions: HashMap::new(),
+        })
+    }
+
+    /// Sets the value of a parameter, pseudo parameter (`AWS::AccountId`), resource reference (`MyRole`), attribute
+    /// (`MyRole.Arn`), or import (`Fn::ImportValue:ExportName`).
+    ///
+    /// For `CommaDelimitedList` and `List<...>` parameters, the value is split on commas.
+    pub fn set_parameter<N: Into<String>, V: Into<String>>(&mut self, name: N, value: V) {
+        self.parameters.insert(name.into(), Value::String(value.into()));
+    }
+
+    /// Sets the value of a condition, overriding its definition in the template.
+    pub fn set_condition<N: Into<String>>(&mut self, name: N, value: bool) {
+        self.conditions.insert(name.into(), value);
+    }
+
+    /// Extracts the policy documents from the template, in resource order.
+    ///
+    /// # Errors
+    ///
+    /// If an intrinsic function cannot be resolved, a condition cannot be evaluated, or a resolved document is not
+    /// a valid policy, [AspenError::InvalidTemplate] is returned naming the logical id and property.
+    pub fn policies(&self) -> Result<Vec<TemplatePolicy>, AspenError> {
+        let mut result = Vec::new();
+        let resources = self.template["Resources"].as_object().expect("Resources is checked in from_json");
+
+        for (logical_id, resource) in resources {
+            let resource_type = match resource.get("Type").and_then(Value::as_str) {
+                Some(resource_type) => resource_type,
+                None => continue,
+            };
+
+            let kinds: Vec<_> = POLICY_PROPERTIES.iter().filter(|(rt, _, _)| *rt == resource_type).collect();
+            if kinds.is_empty() {
+                continue;
+            }
+
+            if let Some(condition) = resource.get("Condition").and_then(Value::as_str) {
+                if !self.condition(condition, &mut Vec::new()).map_err(|e| invalid(&e))? {
+                    continue;
+                }
+            }
+
+            let properties = match resource.get("Properties") {
+                Some(properties) => match self.resolve(properties, &HashMap::new()) {
+                    Ok(Some(properties)) => properties,
+                    Ok(None) => continue,
+                    Err(e) => return Err(invalid(&format!("{logical_id}: {e}"))),
+                },
+                None => continue,
+            };
+
+            let resource_name = properties
+                .get("PolicyName")
+                .or_else(|| properties.get("ManagedPolicyName"))
+                .and_then(Value::as_str)
+                .map(str::to_string);
+
+            for (_, property, kind) in kinds {
+                let mut push = |property: String, policy_name: Option<String>, document: &Value| {
+                    let policy =
+                        to_policy(document, *kind).map_err(|e| invalid(&format!("{logical_id}.{property}: {e}")))?;
+                    result.push(TemplatePolicy {
+                        logical_id: logical_id.clone(),
+                        resource_type: resource_type.to_string(),
+                        kind: *kind,
+                        property,
+                        policy_name,
+                        policy,
+                    });
+                    Ok::<_, AspenError>(())
+                };
+
+                match property {
+                    Some(property) => {
+                        if let Some(document) = properties.get(property) {
+                            push(property.to_string(), resource_name.clone(), document)?;
+                        }
+                    }
+                    None => {
+                        let entries = properties.get("Policies").and_then(Value::as_array).into_iter().flatten();
+                        for (i, entry) in entries.enumerate() {
+                            if let Some(document) = entry.get("PolicyDocument") {
+                                let policy_name = entry.get("PolicyName").and_then(Value::as_str).map(str::to_string);
+                                push(format!("Policies[{i}].PolicyDocument"), policy_name, document)?;
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        Ok(result)
+    }
+
+    /// Resolves the intrinsic functions in a value. `None` is returned for `AWS::NoValue`.
+    fn resolve(&self, value: &Value, variables: &HashMap<String, Value>) -> Result<Option<Value>, String> {
+        match value {
+            Value::Array(elements) => {
+                let mut result = Vec::with_capacity(elements.len());
+                for element in elements {
+                    if let Some(element) = self.resolve(element, variables)? {
+                        result.push(element);
+                    }
+                }
+                Ok(Some(Value::Array(result)))
+            }
+            Value::Object(map) if map.len() == 1 => {
+                let (key, arg) = map.iter().next().unwrap();
+                match key.as_str() {
+                    "Ref" => {
+                        let name = arg.as_str().ok_or("Ref argument must be a string")?;
+                        self.reference(name, variables)
+                    }
+                    "Fn::GetAtt" => {
+                        let (name, attribute) = get_att_name(arg)?;
+                        Ok(Some(self.lookup(&format!("{name}.{attribute}"), variables)?))
+                    }
+                    "Fn::Sub" => self.sub(arg, variables).map(|s| Some(Value::String(s))),
+                    "Fn::Join" => {
+                        let (delimiter, list) = pair(arg, "Fn::Join")?;
+                        let delimiter = delimiter.as_str().ok_or("Fn::Join delimiter must be a string")?;
+                        let list = self.resolve_list(list, variables, "Fn::Join")?;
+                        Ok(Some(Value::String(list.join(delimiter))))
+                    }
+                    "Fn::Select" => {
+                        let (index, list) = pair(arg, "Fn::Select")?;
+                        let index = match self.resolve(index, variables)? {
+                            Some(Value::Number(n)) => n.as_u64(),
+                            Some(Value::String(s)) => s.parse().ok(),
+                            _ => None,
+                        }
+                        .ok_or("Fn::Select index must be a non-negative integer")?;
+                        let list = self.resolve_list(list, variables, "Fn::Select")?;
+                        let element = list.get(index as usize).ok_or("Fn::Select index out of range")?;
+                        Ok(Some(Value::String(element.clone())))
+                    }
+                    "Fn::Split" => {
+                        let (delimiter, source) = pair(arg, "Fn::Split")?;
+                        let delimiter = delimiter.as_str().ok_or("Fn::Split delimiter must be a string")?;
+                        let source = self.resolve_string(source, variables, "Fn::Split")?;
+                        Ok(Some(Value::Array(source.split(delimiter).map(|s| Value::String(s.to_string())).collect())))
+                    }
+                    "Fn::FindInMap" => {
+                        let keys = match arg.as_array() {
+                            Some(keys) if keys.len() == 3 => keys,
+                            _ => return Err("Fn::FindInMap requires three arguments".to_string()),
+                        };
+                        let mut keys_resolved = Vec::with_capacity(3);
+                        for key in keys {
+                            keys_resolved.push(self.resolve_string(key, variables, "Fn::FindInMap")?);
+                        }
+                        let value = self
+                            .template
+                            .get("Mappings")
+                            .and_then(|m| m.get(&keys_resolved[0]))
+                            .and_then(|m| m.get(&keys_resolved[1]))
+                            .and_then(|m| m.get(&keys_resolved[2]))
+                            .ok_or_else(|| format!("Fn::FindInMap: no mapping for {}", keys_resolved.join(".")))?;
+                        Ok(Some(value.clone()))
+                    }
+                    "Fn::If" => {
+                        let branches = match arg.as_array() {
+                            Some(branches) if branches.len() == 3 => branches,
+                            _ => return Err("Fn::If requires three arguments".to_string()),
+                        };
+                        let condition = branches[0].as_str().ok_or("Fn::If condition must be a string")?;
+                        let branch = if self.condition(condition, &mut Vec::new())? {
+                            &branches[1]
+                        } else {
+                            &branches[2]
+                        };
+                        self.resolve(branch, variables)
+                    }
+                    "Fn::ImportValue" => {
+                        let name = self.resolve_string(arg, variables, "Fn::ImportValue")?;
+                        Ok(Some(self.lookup(&format!("Fn::ImportValue:{name}"), variables)?))
+                    }
+                    _ if key.starts_with("Fn::") => Err(format!("unsupported intrinsic function {key}")),
+                    _ => self.resolve_object(map, variables),
+                }
+            }
+            Value::Object(map) => self.resolve_object(map, variables),
+            _ => Ok(Some(value.clone())),
+        }
+    }
+
+    /// Resolves the values of an object, dropping keys whose values are `AWS::NoValue`.
+    fn resolve_object(
+        &self,
+        map: &Map<String, Value>,
+        variables: &HashMap<String, Value>,
+    ) -> Result<Option<Value>, String> {
+        let mut result = Map::new();
+        for (key, value) in map {
+            if let Some(value) = self.resolve(value, variables)? {
+                result.insert(key.clone(), value);
+            }
+        }
+        Ok(Some(Value::Object(result)))
+    }
+
+    /// Resolves a value that must be a string.
+    fn resolve_string(
+        &self,
+        value: &Value,
+        variables: &HashMap<String, Value>,
+        function: &str,
+    ) -> Result<String, String> {
+        match self.resolve(value, variables)? {
+            Some(Value::String(s)) => Ok(s),
+            _ => Err(format!("{function} argument must resolve to a string")),
+        }
+    }
+
+    /// Resolves a value that must be a list of strings.
+    fn resolve_list(
+        &self,
+        value: &Value,
+        variables: &HashMap<String, Value>,
+        function: &str,
+    ) -> Result<Vec<String>, String> {
+        match self.resolve(value, variables)? {
+            Some(Value::Array(elements)) => elements
+                .into_iter()
+                .map(|element| match element {
+                    Value::String(s) => Ok(s),
+                    _ => Err(format!("{function} list elements must resolve to strings")),
+                })
+                .collect(),
+            _ => Err(format!("{function} argument must resolve to a list")),
+        }
+    }
+
+    /// Resolves a `Ref`.
+    fn reference(&self, name: &str, variables: &HashMap<String, Value>) -> Result<Option<Value>, String> {
+        if name == "AWS::NoValue" {
+            return Ok(None);
+        }
+
+        let value = self.lookup(name, variables)?;
+
+        // List parameters are supplied as comma-separated strings.
+        let parameter_type = self.template.get("Parameters").and_then(|p| p.get(name)).and_then(|p| p.get("Type"));
+        match (parameter_type.and_then(Value::as_str), &value) {
+            (Some(t), Value::String(s)) if t == "CommaDelimitedList" || t.starts_with("List<") => {
+                Ok(Some(Value::Array(s.split(',').map(|s| Value::String(s.trim().to_string())).collect())))
+            }
+            _ => Ok(Some(value)),
+        }
+    }
+
+    /// Looks up the value of a reference, attribute, or import, returning a placeholder if it has no known value.
+    fn lookup(&self, name: &str, variables: &HashMap<String, Value>) -> Result<Value, String> {
+        if let Some(value) = variables.get(name).or_else(|| self.parameters.get(name)) {
+            return Ok(value.clone());
+        }
+
+        if let Some((_, value)) = PSEUDO_PARAMETER_DEFAULTS.iter().find(|(pseudo, _)| *pseudo == name) {
+            return Ok(Value::String(value.to_string()));
+        }
+
+        if let Some(parameter) = self.template.get("Parameters").and_then(|p| p.get(name)) {
+            if let Some(default) = parameter.get("Default") {
+                return Ok(match default {
+                    Value::Number(n) => Value::String(n.to_string()),
+                    _ => default.clone(),
+                });
+            }
+        } else if let Some(pseudo) = name.strip_prefix(PSEUDO_PARAMETER_PREFIX) {
+            return Ok(Value::String(format!("{{{{{pseudo}}}}}")));
+        } else if !name.starts_with("Fn::ImportValue:") {
+            let (logical_id, attribute) = name.split_once('.').unwrap_or((name, ""));
+            let resource = match self.template["Resources"].get(logical_id) {
+                Some(resource) => resource,
+                None => return Err(format!("unresolved reference to {name}")),
+            };
+
+            // Keep unknown ARNs well-formed so they are accepted as resources.
+            let service = resource.get("Type").and_then(Value::as_str).and_then(|t| t.split("::").nth(1));
+            if let (Some(service), "Arn") = (service, attribute) {
+                let partition = self.lookup_string("AWS::Partition", variables)?;
+                let region = self.lookup_string("AWS::Region", variables)?;
+                let account_id = self.lookup_string("AWS::AccountId", variables)?;
+                let service = service.to_lowercase();
+                return Ok(Value::String(format!("arn:{partition}:{service}:{region}:{account_id}:{{{{{name}}}}}")));
+            }
+        }
+
+        Ok(Value::String(format!("{{{{{name}}}}}")))
+    }
+
+    /// Looks up the value of a reference that must be a string.
+    fn lookup_string(&self, name: &str, variables: &HashMap<String, Value>) -> Result<String, String> {
+        match self.lookup(name, variables)? {
+            Value::String(s) => Ok(s),
+            _ => Err(format!("{name} does not resolve to a string")),
+        }
+    }
+
+    /// Resolves an `Fn::Sub` argument.
+    fn sub(&self, arg: &Value, variables: &HashMap<String, Value>) -> Result<String, String> {
+        let (source, local) = match arg {
+            Value::String(source) => (source.as_str(), variables.clone()),
+            Value::Array(args) if args.len() == 2 => {
+                let source = args[0].as_str().ok_or("Fn::Sub source must be a string")?;
+                let map = args[1].as_object().ok_or("Fn::Sub variables must be an object")?;
+                let mut local = variables.clone();
+                for (name, value) in map {
+                    local.insert(name.clone(), Value::String(self.resolve_string(value, variables, "Fn::Sub")?));
+                }
+                (source, local)
+            }
+            _ => return Err("Fn::Sub requires a string or a [string, object] pair".to_string()),
+        };
+
+        let mut result = String::with_capacity(source.len());
+        let mut rest = source;
+        while let Some(start) = rest.find("${") {
+            result.push_str(&rest[..start]);
+            let after = &rest[start + 2..];
+            let end = after.find('}').ok_or_else(|| format!("Fn::Sub: unterminated variable in {source}"))?;
+            let name = &after[..end];
+
+            if let Some(literal) = name.strip_prefix('!') {
+                // ${!Literal} is written as ${Literal}.
+                result.push_str("${");
+                result.push_str(literal);
+                result.push('}');
+            } else if name.contains(':') && !name.contains("::") {
+                // IAM policy variables such as ${aws:username} are left as-is.
+                result.push_str("${");
+                result.push_str(name);
+                result.push('}');
+            } else {
+                result.push_str(&self.lookup_string(name, &local).map_err(|e| format!("Fn::Sub: {e}"))?);
+            }
+
+            rest = &after[end + 1..];
+        }
+        result.push_str(rest);
+        Ok(result)
+    }
+
+    /// Evaluates a named condition. `visiting` holds the conditions being evaluated, to detect cycles.
+    fn condition(&self, name: &str, visiting: &mut Vec<String>) -> Result<bool, String> {
+        if let Some(value) = self.conditions.get(name) {
+            return Ok(*value);
+        }
+
+        if visiting.iter().any(|v| v == name) {
+            return Err(format!("condition {name} refers to itself"));
+        }
+
+        let definition = self
+            .template
+            .get("Conditions")
+            .and_then(|c| c.get(name))
+            .ok_or_else(|| format!("unknown condition {name}"))?;
+
+        visiting.push(name.to_string());
+        let result = self.condition_value(definition, visiting).map_err(|e| match e.starts_with("condition ") {
+            true => e,
+            false => format!("condition {name}: {e}"),
+        });
+        visiting.pop();
+        result
+    }
+
+    /// Evaluates a condition function.
+    fn condition_value(&self, value: &Value, visiting: &mut Vec<String>) -> Result<bool, String> {
+        let (function, arg) = match value.as_object() {
+            Some(map) if map.len() == 1 => map.iter().next().unwrap(),
+            _ => return Err("condition must be a condition function".to_string()),
+        };
+        let args = || arg.as_array().ok_or_else(|| format!("{function} requires a list of arguments"));
+
+        match function.as_str() {
+            "Condition" => {
+                let name = arg.as_str().ok_or("Condition argument must be a string")?;
+                self.condition(name, visiting)
+            }
+            "Fn::Equals" => {
+                let (left, right) = pair(arg, "Fn::Equals")?;
+                let left = self.resolve(left, &HashMap::new())?;
+                let right = self.resolve(right, &HashMap::new())?;
+                for side in [&left, &right] {
+                    if let Some(Value::String(s)) = side {
+                        if s.contains("{{") {
+                            return Err(format!("{s} has no known value; set it or the condition"));
+                        }
+                    }
+                }
+                Ok(left == right)
+            }
+            "Fn::And" => {
+                let mut result = true;
+                for element in args()? {
+                    result &= self.condition_value(element, visiting)?;
+                }
+                Ok(result)
+            }
+            "Fn::Or" => {
+                let mut result = false;
+                for element in args()? {
+                    result |= self.condition_value(element, visiting)?;
+                }
+                Ok(result)
+            }
+            "Fn::Not" => match args()?.as_slice() {
+                [element] => Ok(!self.condition_value(element, visiting)?),
+                _ => Err("Fn::Not requires one argument".to_string()),
+            },
+            _ => Err(format!("unsupported condition function {function}")),
+        }
+    }
+}
+
+impl FromStr for CloudFormationTemplate {
+    type Err = AspenError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::from_json(s)
+    }
+}
+
+fn invalid(message: &str) -> AspenError {
+    AspenError::InvalidTemplate(message.to_string())
+}
+
+/// Splits a two-element argument list.
+fn pair<'a>(arg: &'a Value, function: &str) -> Result<(&'a Value, &'a Value), String> {
+    match arg.as_array().map(Vec::as_slice) {
+        Some([first, second]) => Ok((first, second)),
+        _ => Err(format!("{function} requires two arguments")),
+    }
+}
+
+/// Returns the logical id and attribute name of an `Fn::GetAtt` argument, given as `"Name.Attr"` or
+/// `["Name", "Attr"]`.
+fn get_att_name(arg: &Value) -> Result<(&str, &str), String> {
+    match arg {
+        Value::String(s) => s.split_once('.'),
+        Value::Array(parts) => match parts.as_slice() {
+            [Value::String(name), Value::String(attribute)] => Some((name.as_str(), attribute.as_str())),
+            _ => None,
+        },
+        _ => None,
+    }
+    .ok_or_else(|| "Fn::GetAtt requires a logical id and attribute name".to_string())
+}
+
+/// Converts a resolved policy document to a [Policy]. The `Statement` property of `AWS::Events::EventBusPolicy`
+/// is a bare statement. Trust policy statements apply to the role itself and usually have no `Resource`; they (and
+/// standalone documents, which are often trust policies) are given a `Resource` of `*`.
+pub(crate) fn to_policy(document: &Value, kind: TemplatePolicyKind) -> Result<Policy, String> {
+    let mut document = match document.get("Statement") {
+        Some(_) => document.clone(),
+        None if document.get("Effect").is_some() => {
+            let mut wrapper = Map::new();
+            wrapper.insert("Statement".to_string(), document.clone());
+            Value::Object(wrapper)
+        }
+        None => document.clone(),
+    };
+
+    if matches!(kind, TemplatePolicyKind::Trust | TemplatePolicyKind::Document) {
+        let statements = match document.get_mut("Statement") {
+            Some(Value::Array(statements)) => statements.iter_mut().collect(),
+            Some(statement) => vec![statement],
+            None => vec![],
+        };
+        for statement in statements.into_iter().filter_map(Value::as_object_mut) {
+            if !statement.contains_key("Resource") && !statement.contains_key("NotResource") {
+                statement.insert("Resource".to_string(), Value::String("*".to_string()));
+            }
+        }
+    }
+
+    Policy::from_str(&document.to_string()).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        crate::{AspenError, CloudFormationTemplate, TemplatePolicyKind},
+        pretty_assertions::assert_eq,
+    };
+
+    const TEMPLATE: &str = r#"{
+        "AWSTemplateFormatVersion": "2010-09-09",
+        "Parameters": {
+            "Stage": {"Type": "String", "Default": "dev"},
+            "TrustedAccount": {"Type": "String"},
+            "QueueActions": {"Type": "CommaDelimitedList", "Default": "sqs:SendMessage,sqs:GetQueueUrl"}
+        },
+        "Mappings": {
+            "Services": {"Compute": {"Principal": "ec2.amazonaws.com"}}
+        },
+        "Conditions": {
+            "IsProd": {"Fn::Equals": [{"Ref": "Stage"}, "prod"]},
+            "IsNotProd": {"Fn::Not": [{"Condition": "IsProd"}]}
+        },
+        "Resources": {
+            "Bucket": {"Type": "AWS::S3::Bucket"},
+            "Queue": {"Type": "AWS::SQS::Queue"},
+            "AppRole": {
+                "Type": "AWS::IAM::Role",
+                "Properties": {
+                    "AssumeRolePolicyDocument": {
+                        "Version": "2012-10-17",
+                        "Statement": [
+                            {"Effect": "Allow", "Action": "sts:AssumeRole",
+                             "Principal": {"Service": {"Fn::FindInMap": ["Services", "Compute", "Principal"]}}},
+                            {"Effect": "Allow", "Action": "sts:AssumeRole",
+                             "Principal": {"AWS": {"Fn::Sub": "arn:${AWS::Partition}:iam::${TrustedAccount}:root"}}}
+                        ]
+                    },
+                    "Policies": [
+                        {
+                            "PolicyName": "read-bucket",
+                            "PolicyDocument": {
+                                "Version": "2012-10-17",
+                                "Statement": {
+                                    "Effect": "Allow",
+                                    "Action": ["s3:GetObject", {"Fn::If": ["IsProd", {"Ref": "AWS::NoValue"}, "s3:PutObject"]}],
+                                    "Resource": {"Fn::Join": ["", [{"Fn::GetAtt": ["Bucket", "Arn"]}, "/${aws:username}/*"]]}
+                                }
+                            }
+                        },
+                        {
+                            "Fn::If": ["IsProd", {"Ref": "AWS::NoValue"}, {
+                                "PolicyName": "debug",
+                                "PolicyDocument": {"Statement": {"Effect": "Allow", "Action": "logs:*", "Resource": "*"}}
+                            }]
+                        }
+                    ]
+                }
+            },
+            "Boundary": {
+                "Type": "AWS::IAM::ManagedPolicy",
+                "Condition": "IsNotProd",
+                "Properties": {
+                    "ManagedPolicyName": {"Fn::Sub": "${Stage}-boundary"},
+                    "PolicyDocument": {
+                        "Statement": {
+                            "Effect": "Allow", "Action": "*",
+                            "Resource": {"Fn::Sub": ["arn:aws:s3:::${Name}/*", {"Name": {"Ref": "Bucket"}}]}
+                        }
+                    }
+                }
+            },
+            "QueuePolicy": {
+                "Type": "AWS::SQS::QueuePolicy",
+                "Properties": {
+                    "Queues": [{"Ref": "Queue"}],
+                    "PolicyDocument": {
+                        "Statement": {
+                            "Effect": "Allow", "Principal": {"Service": "sns.amazonaws.com"},
+                            "Action": {"Ref": "QueueActions"},
+                            "Resource": {"Fn::Sub": "arn:aws:sqs:${AWS::Region}:${AWS::AccountId}:${Queue.QueueName}"},
+                            "Condition": {"ArnEquals": {"aws:SourceArn": {"Fn::ImportValue": {"Fn::Sub": "${Stage}-topic"}}}}
+                        }
+                    }
+                }
+            }
+        }
+    }"#;
+
+    #[test_log::test]
+    fn test_extract() {
+        let mut template = CloudFormationTemplate::from_json(TEMPLATE).unwrap();
+
+        // The trust policy has a principal account that needs a value.
+        let e = template.policies().unwrap_err();
+        assert_eq!(
+            e,
+            AspenError::InvalidTemplate(
+                "AppRole.AssumeRolePolicyDocument: Invalid principal: arn:aws:iam::{{TrustedAccount}}:root at line 1 \
+                 column 219"
+                    .to_string()
+            )
+        );
+
+        template.set_parameter("TrustedAccount", "123456789012");
+        let policies = template.policies().unwrap();
+        let summary: Vec<_> = policies
+            .iter()
+            .map(|p| (p.logical_id(), p.resource_type(), p.kind(), p.property(), p.policy_name()))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("AppRole", "AWS::IAM::Role", TemplatePolicyKind::Trust, "AssumeRolePolicyDocument", None),
+                (
+                    "AppRole",
+                    "AWS::IAM::Role",
+                    TemplatePolicyKind::Inline,
+                    "Policies[0].PolicyDocument",
+                    Some("read-bucket")
+                ),
+                ("AppRole", "AWS::IAM::Role", TemplatePolicyKind::Inline, "Policies[1].PolicyDocument", Some("debug")),
+                (
+                    "Boundary",
+                    "AWS::IAM::ManagedPolicy",
+                    TemplatePolicyKind::Managed,
+                    "PolicyDocument",
+                    Some("dev-boundary")
+                ),
+                ("QueuePolicy", "AWS::SQS::QueuePolicy", TemplatePolicyKind::Resource, "PolicyDocument", None),
+            ]
+        );
+        assert_eq!(TemplatePolicyKind::Managed.to_string(), "Managed");
+
+        let trust = policies[0].policy().statement();
+        assert_eq!(serde_json::to_string(trust[0].principal().unwrap()).unwrap(), r#"{"Service":"ec2.amazonaws.com"}"#);
+
+        let inline = &policies[1].policy().statement()[0];
+        let actions: Vec<_> = inline.action().unwrap().iter().map(|a| a.to_string()).collect();
+        assert_eq!(actions, vec!["s3:GetObject", "s3:PutObject"]);
+        assert_eq!(
+            inline.resource().unwrap()[0].to_string(),
+            "arn:aws:s3:{{Region}}:{{AccountId}}:{{Bucket.Arn}}/${aws:username}/*"
+        );
+
+        assert_eq!(policies[3].policy().statement()[0].resource().unwrap()[0].to_string(), "arn:aws:s3:::{{Bucket}}/*");
+
+        let queue = &policies[4].policy().statement()[0];
+        let actions: Vec<_> = queue.action().unwrap().iter().map(|a| a.to_string()).collect();
+        assert_eq!(actions, vec!["sqs:SendMessage", "sqs:GetQueueUrl"]);
+        assert_eq!(
+            queue.resource().unwrap()[0].to_string(),
+            "arn:aws:sqs:{{Region}}:{{AccountId}}:{{Queue.QueueName}}"
+        );
+        let condition = serde_json::to_string(queue.condition().unwrap()).unwrap();
+        assert_eq!(condition, r#"{"ArnEquals":{"aws:SourceArn":"{{Fn::ImportValue:dev-topic}}"}}"#);
+
+        // Production drops the conditional policies and action.
+        template.set_parameter("Stage", "prod");
+        template.set_parameter("Bucket.Arn", "arn:aws:s3:::prod-bucket");
+        template.set_parameter("Fn::ImportValue:prod-topic", "arn:aws:sns:us-east-1:123456789012:alerts");
+        let policies = template.policies().unwrap();
+        let ids: Vec<_> = policies.iter().map(|p| (p.logical_id(), p.property())).collect();
+        assert_eq!(
+            ids,
+            vec![
+                ("AppRole", "AssumeRolePolicyDocument"),
+                ("AppRole", "Policies[0].PolicyDocument"),
+                ("QueuePolicy", "PolicyDocument")
+            ]
+        );
+        let inline = &policies[1].policy().statement()[0];
+        assert_eq!(inline.action().unwrap().len(), 1);
+        assert_eq!(inline.resource().unwrap()[0].to_string(), "arn:aws:s3:::prod-bucket/${aws:username}/*");
+        let condition = serde_json::to_string(policies[2].policy().statement()[0].condition().unwrap()).unwrap();
+        assert_eq!(condition, r#"{"ArnEquals":{"aws:SourceArn":"arn:aws:sns:us-east-1:123456789012:alerts"}}"#);
+
+        // Explicit conditions override the template.
+        template.set_condition("IsProd", false);
+        assert_eq!(template.policies().unwrap().len(), 5);
+    }
+
+    #[test_log::test]
+    fn test_errors() {
+        let check = |json: &str, message: &str| {
+            let e = CloudFormationTemplate::from_json(json).and_then(|t| t.policies()).unwrap_err();
+            assert_eq!(e.to_string(), format!("Invalid template: {message}"));
+        };
+
+        check("[]", "template is not an object");
+        check("{}", "missing Resources");
+        check("{", "EOF while parsing an object at line 1 column 1");
+
+        let resource = |properties: &str| {
+            format!(r#"{{"Resources": {{"P": {{"Type": "AWS::IAM::ManagedPolicy", "Properties": {properties}}}}}}}"#)
+        };
+        check(&resource(r#"{"PolicyDocument": {"Ref": "Missing"}}"#), "P: unresolved reference to Missing");
+        check(&resource(r#"{"PolicyDocument": {"Fn::Base64": "x"}}"#), "P: unsupported intrinsic function Fn::Base64");
+        check(
+            &resource(r#"{"PolicyDocument": {"Fn::Join": ["", "x"]}}"#),
+            "P: Fn::Join argument must resolve to a list",
+        );
+        check(&resource(r#"{"PolicyDocument": {"Fn::Select": [3, ["a"]]}}"#), "P: Fn::Select index out of range");
+        check(&resource(r#"{"PolicyDocument": {"Fn::Sub": "${P"}}"#), "P: Fn::Sub: unterminated variable in ${P");
+        check(
+            &resource(r#"{"PolicyDocument": {"Fn::GetAtt": "P"}}"#),
+            "P: Fn::GetAtt requires a logical id and attribute name",
+        );
+        check(
+            &resource(r#"{"PolicyDocument": {"Fn::FindInMap": ["A", "B", "C"]}}"#),
+            "P: Fn::FindInMap: no mapping for A.B.C",
+        );
+        check(&resource(r#"{"PolicyDocument": {"Fn::If": ["C", 1, 2]}}"#), "P: unknown condition C");
+        check(
+            &resource(r#"{"PolicyDocument": {"Statement": 3}}"#),
+            "P.PolicyDocument: invalid type: integer `3`, expected Statement or list of Statement at line 1 column 14",
+        );
+
+        let conditional = |conditions: &str| {
+            format!(
+                r#"{{"Parameters": {{"Env": {{"Type": "String"}}}}, "Conditions": {conditions},
+                    "Resources": {{"P": {{"Type": "AWS::IAM::ManagedPolicy", "Condition": "C", "Properties": {{}}}}}}}}"#
+            )
+        };
+        check(&conditional(r#"{"C": {"Condition": "C"}}"#), "condition C refers to itself");
+        check(
+            &conditional(r#"{"C": {"Fn::Equals": [{"Ref": "Env"}, "prod"]}}"#),
+            "condition C: {{Env}} has no known value; set it or the condition",
+        );
+        check(
+            &conditional(r#"{"C": {"Fn::Contains": []}}"#),
+            "condition C: unsupported condition function Fn::Contains",
+        );
+
+        // An EventBridge bus policy holds a bare statement.
+        let template = CloudFormationTemplate::from_json(
+            r#"{"Resources": {"Bus": {"Type": "AWS::Events::EventBusPolicy", "Properties": {"StatementId": "s",
+                "Statement": {"Effect": "Allow", "Principal": {"AWS": "123456789012"}, "Action": "events:PutEvents",
+                "Resource": "*"}}}}}"#,
+        )
+        .unwrap();
+        assert_eq!(template.policies().unwrap()[0].policy().statement().len(), 1);
+    }
+}
@@ -0,0 +1,481 @@
+use {
+    crate::{
+        eval::session_role_arn, generate::EVENT_SOURCE_SUFFIX, AspenError, Context, Decision, DecisionReason, PolicySet,
+    },
+    chrono::{DateTime, Utc},
+    scratchstack_arn::Arn,
+    scratchstack_aws_principal::{
+        AssumedRole, FederatedUser, Principal as PrincipalActor, PrincipalIdentity, RootUser, Service, SessionData,
+        SessionValue, User,
+    },
+    serde_json::{Map, Value},
+    std::{
+        fmt::{Display, Formatter, Result as FmtResult},
+        net::IpAddr,
+        str::FromStr,
+    },
+};
+
+/// A CloudTrail event converted into a request [Context].
+///
+/// The event's `eventSource` and `eventName` become the service and API, `userIdentity` becomes the actor, and
+/// `resources[].ARN` become the resources. The following global condition keys are set when the corresponding
+/// fields are present:
+///
+/// | Key | Source |
+/// |-----|--------|
+/// | `aws:CurrentTime`, `aws:EpochTime` | `eventTime` |
+/// | `aws:SourceIp` | `sourceIPAddress`, if it is an IP address |
+/// | `aws:UserAgent` | `userAgent` |
+/// | `aws:RequestedRegion` | `awsRegion` |
+/// | `aws:SourceVpce` | `vpcEndpointId` |
+/// | `aws:SecureTransport` | `true` if `tlsDetails` is present |
+/// | `aws:PrincipalArn`, `aws:PrincipalAccount`, `aws:PrincipalType`, `aws:userid` | `userIdentity` |
+/// | `aws:username` | `userIdentity.userName` for IAM users |
+/// | `aws:PrincipalArn` for role sessions | `userIdentity.sessionContext.sessionIssuer.arn`, or the role ARN derived from `userIdentity.arn` |
+/// | `aws:MultiFactorAuthPresent`, `aws:TokenIssueTime` | `userIdentity.sessionContext.attributes` |
+///
+/// # Example
+///
+/// ```
+/// # use scratchstack_aspen::{CloudTrailEvent, Decision, Policy, PolicySet, PolicySource};
+/// # use std::str::FromStr;
+/// let event = CloudTrailEvent::from_str(r#"{
+///     "eventTime": "2024-03-01T12:00:00Z", "eventSource": "s3.amazonaws.com", "eventName": "GetObject",
+///     "awsRegion": "us-east-1", "sourceIPAddress": "192.0.2.1", "errorCode": "AccessDenied",
+///     "userIdentity": {"type": "IAMUser", "principalId": "AIDAEXAMPLE", "accountId": "123456789012",
+///                      "arn": "arn:aws:iam::123456789012:user/alice", "userName": "alice"},
+///     "resources": [{"type": "AWS::S3::Object", "ARN": "arn:aws:s3:::bucket/key"}]}"#).unwrap();
+/// assert!(event.is_access_denied());
+///
+/// let policy = Policy::from_str(r#"{"Statement": {"Effect": "Allow", "Action": "s3:GetObject",
+///     "Resource": "arn:aws:s3:::bucket/*", "Condition": {"IpAddress": {"aws:SourceIp": "10.0.0.0/8"}}}}"#).unwrap();
+/// let source = PolicySource::new_entity_inline("arn:aws:iam::123456789012:user/alice", "AIDAEXAMPLE", "p");
+/// let replay = event.replay(&PolicySet::from(vec![(source, policy)])).unwrap();
+/// assert_eq!(replay.decision(), Decision::DefaultDeny);
+/// assert!(replay.agrees_with_event());
+/// ```
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct CloudTrailEvent {
+    context: Context,
+    event_id: Option<String>,
+    error_code: Option<String>,
+    error_message: Option<String>,
+}
+
+impl CloudTrailEvent {
+    /// Parses a CloudTrail event from its JSON representation.
+    ///
+    /// # Errors
+    ///
+    /// If the JSON is malformed, `eventSource` or `eventName` is missing, or `userIdentity`, `eventTime`, or
+    /// `resources` cannot be converted, [AspenError::InvalidAccessRecord] is returned.
+    pub fn from_json(json: &str) -> Result<Self, AspenError> {
+        let value: Value = serde_json::from_str(json).map_err(|e| AspenError::InvalidAccessRecord(e.to_string()))?;
+        let event = value.as_object().ok_or_else(|| invalid("event is not an object"))?;
+        let string = |key: &str| event.get(key).and_then(Value::as_str);
+
+        let event_source = string("eventSource").ok_or_else(|| invalid("missing eventSource"))?;
+        let service = event_source.strip_suffix(EVENT_SOURCE_SUFFIX).unwrap_or(event_source);
+        let api = string("eventName").ok_or_else(|| invalid("missing eventName"))?;
+
+        let mut session_data = SessionData::new();
+
+        if let Some(event_time) = string("eventTime") {
+            let time = DateTime::parse_from_rfc3339(event_time)
+                .map_err(|_| invalid(&format!("invalid eventTime: {event_time}")))?
+                .with_timezone(&Utc);
+            session_data.insert("aws:CurrentTime", SessionValue::Timestamp(time));
+            session_data.insert("aws:EpochTime", SessionValue::Integer(time.timestamp()));
+        }
+
+        if let Some(source_ip) = string("sourceIPAddress").and_then(|ip| IpAddr::from_str(ip).ok()) {
+            session_data.insert("aws:SourceIp", SessionValue::IpAddr(source_ip));
+        }
+
+        for (field, key) in
+            [("userAgent", "aws:UserAgent"), ("awsRegion", "aws:RequestedRegion"), ("vpcEndpointId", "aws:SourceVpce")]
+        {
+            if let Some(value) = string(field) {
+                session_data.insert(key, SessionValue::String(value.to_string()));
+            }
+        }
+
+        if event.contains_key("tlsDetails") {
+            session_data.insert("aws:SecureTransport", SessionValue::Bool(true));
+        }
+
+        let actor = match event.get("userIdentity").and_then(Value::as_object) {
+            Some(identity) => actor_from_identity(identity, &mut session_data)?,
+            None => PrincipalActor::from(Vec::new()),
+        };
+
+        let mut resources = Vec::new();
+        if let Some(list) = event.get("resources").and_then(Value::as_array) {
+            for resource in list {
+                if let Some(arn) = resource.get("ARN").and_then(Value::as_str) {
+                    resources.push(Arn::from_str(arn).map_err(|_| invalid(&format!("invalid resource ARN: {arn}")))?);
+                }
+            }
+        }
+
+        let context = Context::builder()
+            .service(service)
+            .api(api)
+            .actor(actor)
+            .resources(resources)
+            .session_data(session_data)
+            .build()
+            .expect("all context fields are set");
+
+        Ok(Self {
+            context,
+            event_id: string("eventID").map(str::to_string),
+            error_code: string("errorCode").map(str::to_string),
+            error_message: string("errorMessage").map(str::to_string),
+        })
+    }
+
+    /// Returns the request context.
+    #[inline]
+    pub fn context(&self) -> &Context {
+        &self.context
+    }
+
+    /// Returns the event id, if present.
+    #[inline]
+    pub fn event_id(&self) -> Option<&str> {
+        self.event_id.as_deref()
+    }
+
+    /// Returns the error code recorded for the call, if any.
+    #[inline]
+    pub fn error_code(&self) -> Option<&str> {
+        self.error_code.as_deref()
+    }
+
+    /// Returns the error message recorded for the call, if any.
+    #[inline]
+    pub fn error_message(&self) -> Option<&str> {
+        self.error_message.as_deref()
+    }
+
+    /// Indicates whether the call was rejected for lack of permissions (`AccessDenied`, `AccessDeniedException`,
+    /// `UnauthorizedOperation`, and similar error codes).
+    pub fn is_access_denied(&self) -> bool {
+        self.error_code
+            .as_deref()
+            .map(|code| code.contains("AccessDenied") || code.contains("UnauthorizedOperation"))
+            .unwrap_or(false)
+    }
+
+    /// Evaluates the request against a policy set and explains the decision.
+    ///
+    /// # Errors
+    ///
+    /// If a policy fails to evaluate (for example, because of a malformed variable reference), the error is
+    /// returned.
+    pub fn replay(&self, policy_set: &PolicySet) -> Result<Replay, AspenError> {
+        let (decision, reasons) = policy_set.explain(&self.context)?;
+
+        Ok(Replay {
+            decision,
+            denied_in_event: self.is_access_denied(),
+            request: format!("{}:{}", self.context.service(), self.context.api()),
+            reasons,
+        })
+    }
+}
+
+impl FromStr for CloudTrailEvent {
+    type Err = AspenError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::from_json(s)
+    }
+}
+
+/// The result of replaying a [CloudTrailEvent] against a [PolicySet].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Replay {
+    decision: Decision,
+    denied_in_event: bool,
+    request: String,
+    reasons: Vec<DecisionReason>,
+}
+
+impl Replay {
+    /// Returns the decision of the policy set.
+    #[inline]
+    pub fn decision(&self) -> Decision {
+        self.decision
+    }
+
+    /// Returns the statements responsible for the decision: the allowing statements for [Decision::Allow], or the
+    /// denying statement or boundary for [Decision::Deny]. This is empty for [Decision::DefaultDeny].
+    #[inline]
+    pub fn reasons(&self) -> &[DecisionReason] {
+        &self.reasons
+    }
+
+    /// Indicates whether the replayed decision agrees with the outcome recorded in the event: the event recorded an
+    /// access denied error if and only if the policy set does not allow the request.
+    pub fn agrees_with_event(&self) -> bool {
+        self.denied_in_event == (self.decision != Decision::Allow)
+    }
+}
+
+impl Display for Replay {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        match self.decision {
+            Decision::Allow => write!(f, "{} is allowed", self.request)?,
+            Decision::Deny => write!(f, "{} is explicitly denied", self.request)?,
+            Decision::DefaultDeny => write!(f, "{} is implicitly denied: no statement allows it", self.request)?,
+        }
+
+        for reason in self.reasons.iter() {
+            write!(f, "\n  {reason}")?;
+        }
+
+        if !self.agrees_with_event() {
+            f.write_str(match self.denied_in_event {
+                true => "\n  (the event recorded an access denied error)",
+                false => "\n  (the event recorded the call as permitted)",
+            })?;
+        }
+
+        Ok(())
+    }
+}
+
+fn invalid(message: &str) -> AspenError {
+    AspenError::InvalidAccessRecord(message.to_string())
+}
+
+/// Converts a CloudTrail `userIdentity` element to an actor, recording principal keys in the session data.
+fn actor_from_identity(
+    identity: &Map<String, Value>,
+    session_data: &mut SessionData,
+) -> Result<PrincipalActor, AspenError> {
+    let string = |key: &str| identity.get(key).and_then(Value::as_str);
+    let identity_type = string("type").unwrap_or_default();
+    let arn = string("arn");
+    let bad_identity = || invalid(&format!("unsupported userIdentity: {}", Value::Object(identity.clone())));
+
+    if let Some(arn) = arn {
+        session_data.insert("aws:PrincipalArn", SessionValue::String(arn.to_string()));
+    }
+    if let Some(account_id) = string("accountId") {
+        session_data.insert("aws:PrincipalAccount", SessionValue::String(account_id.to_string()));
+    }
+    if let Some(principal_id) = string("principalId") {
+        session_data.insert("aws:userid", SessionValue::String(principal_id.to_string()));
+    }
+
+    if let Some(attributes) =
+        identity.get("sessionContext").and_then(|c| c.get("attributes")).and_then(Value::as_object)
+    {
+        if let Some(mfa) = attributes.get("mfaAuthenticated").and_then(Value::as_str) {
+            session_data.insert("aws:MultiFactorAuthPresent", SessionValue::Bool(mfa == "true"));
+        }
+        if let Some(created) = attributes.get("creationDate").and_then(Value::as_str) {
+            if let Ok(created) = DateTime::parse_from_rfc3339(created) {
+                session_data.insert("aws:TokenIssueTime", SessionValue::Timestamp(created.with_timezone(&Utc)));
+            }
+        }
+    }
+
+    let principal_type = |t: &str| SessionValue::String(t.to_string());
+    let identity: PrincipalIdentity = match identity_type {
+        "IAMUser" => {
+            let user = User::from_str(arn.ok_or_else(bad_identity)?).map_err(|_| bad_identity())?;
+            session_data.insert("aws:username", SessionValue::String(user.user_name().to_string()));
+            session_data.insert("aws:PrincipalType", principal_type("User"));
+            user.into()
+        }
+        "AssumedRole" => {
+            let session = AssumedRole::from_str(arn.ok_or_else(bad_identity)?).map_err(|_| bad_identity())?;
+            // aws:PrincipalArn is the role, not the session. The session issuer's ARN includes the role path.
+            let role_arn = identity
+                .get("sessionContext")
+                .and_then(|c| c.get("sessionIssuer"))
+                .filter(|issuer| issuer.get("type").and_then(Value::as_str) == Some("Role"))
+                .and_then(|issuer| issuer.get("arn"))
+                .and_then(Value::as_str)
+                .map(str::to_string)
+                .unwrap_or_else(|| session_role_arn(&session));
+            session_data.insert("aws:PrincipalArn", SessionValue::String(role_arn));
+            session_data.insert("aws:PrincipalType", principal_type("AssumedRole"));
+            session.into()
+        }
+        "FederatedUser" => {
+            let arn = Arn::from_str(arn.ok_or_else(bad_identity)?).map_err(|_| bad_identity())?;
+            let user_name = arn.resource().strip_prefix("federated-user/").ok_or_else(bad_identity)?;
+            session_data.insert("aws:PrincipalType", principal_type("FederatedUser"));
+            FederatedUser::new(arn.partition(), arn.account_id(), user_name).map_err(|_| bad_identity())?.into()
+        }
+        "Root" => {
+            let arn = Arn::from_str(arn.ok_or_else(bad_identity)?).map_err(|_| bad_identity())?;
+            session_data.insert("aws:PrincipalType", principal_type("Account"));
+            RootUser::new(arn.partition(), arn.account_id()).map_err(|_| bad_identity())?.into()
+        }
+        "AWSAccount" => {
+            let account_id = string("accountId").ok_or_else(bad_identity)?;
+            session_data.insert("aws:PrincipalType", principal_type("Account"));
+            RootUser::new("aws", account_id).map_err(|_| bad_identity())?.into()
+        }
+        "AWSService" => {
+            let invoked_by = string("invokedBy").ok_or_else(bad_identity)?;
+            let service = invoked_by.strip_suffix(EVENT_SOURCE_SUFFIX).ok_or_else(bad_identity)?;
+            Service::new(service, None, "amazonaws.com").map_err(|_| bad_identity())?.into()
+        }
+        _ => return Ok(PrincipalActor::from(Vec::new())),
+    };
+
+    Ok(PrincipalActor::from(vec![identity]))
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        crate::{CloudTrailEvent, Decision, Policy, PolicySet, PolicySource},
+        pretty_assertions::assert_eq,
+        scratchstack_aws_principal::{PrincipalIdentity, SessionValue},
+        std::str::FromStr,
+    };
+
+    const ASSUMED_ROLE_EVENT: &str = r#"{
+        "eventVersion": "1.08",
+        "userIdentity": {
+            "type": "AssumedRole",
+            "principalId": "AROAEXAMPLE:alice",
+            "arn": "arn:aws:sts::123456789012:assumed-role/deploy/alice",
+            "accountId": "123456789012",
+            "sessionContext": {
+                "sessionIssuer": {"type": "Role", "principalId": "AROAEXAMPLE",
+                                  "arn": "arn:aws:iam::123456789012:role/ops/deploy", "accountId": "123456789012",
+                                  "userName": "deploy"},
+                "attributes": {"creationDate": "2024-03-01T11:00:00Z", "mfaAuthenticated": "false"}
+            }
+        },
+        "eventTime": "2024-03-01T12:00:00Z",
+        "eventSource": "dynamodb.amazonaws.com",
+        "eventName": "DeleteTable",
+        "awsRegion": "us-west-2",
+        "sourceIPAddress": "198.51.100.7",
+        "userAgent": "aws-cli/2.15.0",
+        "errorCode": "AccessDeniedException",
+        "errorMessage": "User is not authorized to perform: dynamodb:DeleteTable",
+        "eventID": "11111111-2222-3333-4444-555555555555",
+        "tlsDetails": {"tlsVersion": "TLSv1.3"},
+        "resources": [{"accountId": "123456789012", "type": "AWS::DynamoDB::Table",
+                       "ARN": "arn:aws:dynamodb:us-west-2:123456789012:table/orders"}]
+    }"#;
+
+    #[test_log::test]
+    fn test_context() {
+        let event = CloudTrailEvent::from_str(ASSUMED_ROLE_EVENT).unwrap();
+        assert_eq!(event.event_id(), Some("11111111-2222-3333-4444-555555555555"));
+        assert_eq!(event.error_code(), Some("AccessDeniedException"));
+        assert!(event.error_message().unwrap().contains("DeleteTable"));
+        assert!(event.is_access_denied());
+
+        let context = event.context();
+        assert_eq!(context.service(), "dynamodb");
+        assert_eq!(context.api(), "DeleteTable");
+        assert_eq!(context.resources()[0].resource(), "table/orders");
+        assert!(matches!(context.actor()[0], PrincipalIdentity::AssumedRole(_)));
+
+        let session = context.session_data();
+        assert_eq!(session.get("aws:SourceIp").unwrap().to_string(), "198.51.100.7");
+        assert_eq!(session.get("aws:UserAgent").unwrap().to_string(), "aws-cli/2.15.0");
+        assert_eq!(session.get("aws:RequestedRegion").unwrap().to_string(), "us-west-2");
+        assert_eq!(session.get("aws:EpochTime"), Some(&SessionValue::Integer(1709294400)));
+        assert_eq!(session.get("aws:SecureTransport"), Some(&SessionValue::Bool(true)));
+        assert_eq!(session.get("aws:MultiFactorAuthPresent"), Some(&SessionValue::Bool(false)));
+        assert_eq!(session.get("aws:userid").unwrap().to_string(), "AROAEXAMPLE:alice");
+        assert_eq!(session.get("aws:PrincipalType").unwrap().to_string(), "AssumedRole");
+        assert_eq!(session.get("aws:PrincipalArn").unwrap().to_string(), "arn:aws:iam::123456789012:role/ops/deploy");
+
+        // Without a session issuer, the role ARN is derived from the session ARN.
+        let event = CloudTrailEvent::from_json(
+            r#"{"eventSource": "s3.amazonaws.com", "eventName": "GetObject", "userIdentity": {"type": "AssumedRole",
+                "arn": "arn:aws:sts::123456789012:assumed-role/deploy/alice", "accountId": "123456789012"}}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            event.context().session_data().get("aws:PrincipalArn").unwrap().to_string(),
+            "arn:aws:iam::123456789012:role/deploy"
+        );
+
+        let service_event = CloudTrailEvent::from_json(
+            r#"{"eventSource": "sts.amazonaws.com", "eventName": "AssumeRole", "sourceIPAddress": "lambda.amazonaws.com",
+                "userIdentity": {"type": "AWSService", "invokedBy": "lambda.amazonaws.com"}}"#,
+        )
+        .unwrap();
+        assert!(matches!(service_event.context().actor()[0], PrincipalIdentity::Service(_)));
+        assert!(service_event.context().session_data().get("aws:SourceIp").is_none());
+        assert!(!service_event.is_access_denied());
+
+        assert!(CloudTrailEvent::from_json(r#"{"eventName": "GetObject"}"#).is_err());
+        assert!(CloudTrailEvent::from_json(
+            r#"{"eventSource": "s3.amazonaws.com", "eventName": "GetObject",
+            "eventTime": "yesterday"}"#
+        )
+        .is_err());
+        assert!(CloudTrailEvent::from_json(
+            r#"{"eventSource": "s3.amazonaws.com", "eventName": "GetObject",
+            "userIdentity": {"type": "IAMUser"}}"#
+        )
+        .is_err());
+    }
+
+    #[test_log::test]
+    fn test_replay() {
+        let event = CloudTrailEvent::from_str(ASSUMED_ROLE_EVENT).unwrap();
+        let role = "arn:aws:iam::123456789012:role/ops/deploy";
+        // The Deny doesn't apply: aws:PrincipalArn is the role ARN, not the session ARN.
+        let identity = Policy::from_str(
+            r#"{"Version": "2012-10-17", "Statement": [
+                {"Sid": "OpsOnly", "Effect": "Deny", "Action": "dynamodb:Delete*", "Resource": "*",
+                 "Condition": {"ArnNotLike": {"aws:PrincipalArn": "arn:aws:iam::*:role/ops/*"}}},
+                {"Sid": "Tables", "Effect": "Allow", "Action": "dynamodb:*", "Resource": "*"}
+            ]}"#,
+        )
+        .unwrap();
+        let boundary = Policy::from_str(
+            r#"{"Statement": {"Effect": "Allow", "Action": "dynamodb:*", "Resource": "*",
+                "Condition": {"DateLessThan": {"aws:CurrentTime": "2024-02-01T00:00:00Z"}}}}"#,
+        )
+        .unwrap();
+
+        // The identity policy alone allows the call, so the replay disagrees with the event.
+        let mut policy_set = PolicySet::new();
+        policy_set.add_policy(PolicySource::new_entity_inline(role, "AROAEXAMPLE", "deploy"), identity);
+        let replay = event.replay(&policy_set).unwrap();
+        assert_eq!(replay.decision(), Decision::Allow);
+        assert!(!replay.agrees_with_event());
+        assert_eq!(replay.reasons()[0].sid(), Some("Tables"));
+        assert_eq!(
+            replay.to_string(),
+            format!(
+                "dynamodb:DeleteTable is allowed\n  statement 1 \"Tables\" of inline policy deploy of {role}\n  \
+                 (the event recorded an access denied error)"
+            )
+        );
+
+        // An expired permissions boundary explains the denial.
+        policy_set.add_policy(
+            PolicySource::new_permission_boundary("arn:aws:iam::123456789012:policy/boundary", "ANPA", "v1"),
+            boundary,
+        );
+        let replay = event.replay(&policy_set).unwrap();
+        assert_eq!(replay.decision(), Decision::Deny);
+        assert!(replay.agrees_with_event());
+        assert_eq!(replay.reasons()[0].policy_index(), 1);
+        assert_eq!(replay.reasons()[0].statement_index(), None);
+        assert!(replay.to_string().ends_with(
+            "no statement of permissions boundary arn:aws:iam::123456789012:policy/boundary (v1) allows the request"
+        ));
+    }
+}
@@ -3,10 +3,10 @@
 mod arn;
 mod binary;
 mod boolean;
-mod date;
+pub(crate) mod date;
 mod ipaddr;
 mod null;
-mod numeric;
+pub(crate) mod numeric;
 
 /// Operators for conditions.
 #[allow(non_upper_case_globals)]
@@ -14,7 +14,7 @@ pub mod op;
 
 #[cfg(test)]
 mod op_tests;
-mod string;
+pub(crate) mod string;
 mod variant;
 
 pub use {op::ConditionOp, variant::Variant};
@@ -49,6 +49,8 @@ pub struct Condition {
 }
 
 from_str_json!(Condition);
+#[cfg(feature = "yaml")]
+crate::yaml_serde!(Condition);
 
 impl<'de> Deserialize<'de> for Condition {
     fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
@@ -567,7 +569,6 @@ impl Condition {
     /// assert_eq!(condition.remove_entry(&condop::Bool), Some((condop::Bool, cmap)));
     /// assert_eq!(condition.remove(&condop::Bool), None);
     /// ```
-
     #[inline]
     pub fn remove_entry<Q>(&mut self, key: &Q) -> Option<(ConditionOp, ConditionMap)>
     where
@@ -632,7 +633,6 @@ impl Condition {
     /// assert_eq!(a.into_keys().collect::<Vec<_>>(), vec![condop::ArnLike, condop::Bool]);
     /// assert_eq!(b.into_keys().collect::<Vec<_>>(), vec![condop::DateEquals, condop::NumericEquals, condop::StringEquals]);
     /// ```
-
     #[inline]
     pub fn split_off<Q>(&mut self, key: &Q) -> Condition
     where
@@ -26,13 +26,13 @@ impl Variant {
 
     /// Indicates if this is [Variant::IfExists] or [Variant::IfExistsNegated].
     #[inline]
-    pub(super) fn if_exists(self) -> bool {
+    pub(crate) fn if_exists(self) -> bool {
         matches!(self, Self::IfExists | Self::IfExistsNegated)
     }
 
     /// Indicates if this is [Variant::Negated] or [Variant::IfExistsNegated].
     #[inline]
-    pub(super) fn negated(self) -> bool {
+    pub(crate) fn negated(self) -> bool {
         matches!(self, Self::Negated | Self::IfExistsNegated)
     }
 }
@@ -6,6 +6,9 @@ use std::{
 /// The error type used to convey Aspen errors.
 #[derive(Debug, Eq, PartialEq)]
 pub enum AspenError {
+    /// An access record (such as a CloudTrail event) could not be parsed. The string describes the problem.
+    InvalidAccessRecord(String),
+
     /// An invalid action was specified in a policy. The string is the invalid action.
     InvalidAction(String),
 
@@ -23,17 +26,45 @@ pub enum AspenError {
 
     /// An invalid variable substitution was specified in a policy. The string contains the invalid variable.
     InvalidSubstitution(String),
+
+    /// A policy file could not be loaded. The string describes the problem.
+    InvalidPolicyFile(String),
+
+    /// A policy test suite could not be loaded. The string describes the problem.
+    InvalidTestSuite(String),
+
+    /// An infrastructure template (such as a CloudFormation template or Terraform plan) could not be read, or a policy
+    /// in it could not be extracted. The string describes the problem.
+    InvalidTemplate(String),
+
+    /// A policy template was instantiated without a value for one or more of its placeholders. The string contains
+    /// the placeholder names, separated by commas.
+    MissingTemplateParameter(String),
+
+    /// A policy template was instantiated with parameters it has no placeholders for. The string contains the
+    /// parameter names, separated by commas.
+    UnexpectedTemplateParameter(String),
+
+    /// A request named a policy set that is not loaded. The string contains the name.
+    UnknownPolicySet(String),
 }
 
 impl Display for AspenError {
     fn fmt(&self, f: &mut Formatter) -> FmtResult {
         match self {
+            Self::InvalidAccessRecord(msg) => write!(f, "Invalid access record: {msg}"),
             Self::InvalidAction(action) => write!(f, "Invalid action: {action}"),
             Self::InvalidConditionOperator(operator) => write!(f, "Invalid condition operator: {operator}"),
             Self::InvalidPolicyVersion(version) => write!(f, "Invalid policy version: {version}"),
             Self::InvalidPrincipal(principal) => write!(f, "Invalid principal: {principal}"),
             Self::InvalidResource(resource) => write!(f, "Invalid resource: {resource}"),
             Self::InvalidSubstitution(element) => write!(f, "Invalid variable substitution: {element}"),
+            Self::InvalidPolicyFile(msg) => write!(f, "Invalid policy file: {msg}"),
+            Self::InvalidTestSuite(msg) => write!(f, "Invalid test suite: {msg}"),
+            Self::InvalidTemplate(msg) => write!(f, "Invalid template: {msg}"),
+            Self::MissingTemplateParameter(names) => write!(f, "Missing template parameter: {names}"),
+            Self::UnexpectedTemplateParameter(names) => write!(f, "Unexpected template parameter: {names}"),
+            Self::UnknownPolicySet(name) => write!(f, "Unknown policy set: {name}"),
         }
     }
 }
@@ -52,11 +83,26 @@ mod tests {
         let _ = format!("{:?}", AspenError::InvalidAction("foo".to_string()));
         assert_eq!(AspenError::InvalidAction("foo".to_string()).to_string(), "Invalid action: foo");
 
+        assert_eq!(AspenError::InvalidAccessRecord("foo".to_string()).to_string(), "Invalid access record: foo");
+
         let _ = format!("{:?}", AspenError::InvalidPrincipal("foo".to_string()));
         assert_eq!(AspenError::InvalidPrincipal("foo".to_string()).to_string(), "Invalid principal: foo");
 
         let _ = format!("{:?}", AspenError::InvalidResource("foo".to_string()));
         assert_eq!(AspenError::InvalidResource("foo".to_string()).to_string(), "Invalid resource: foo");
+
+        assert_eq!(AspenError::InvalidPolicyFile("foo".to_string()).to_string(), "Invalid policy file: foo");
+        assert_eq!(AspenError::InvalidTestSuite("foo".to_string()).to_string(), "Invalid test suite: foo");
+        assert_eq!(AspenError::InvalidTemplate("foo".to_string()).to_string(), "Invalid template: foo");
+        assert_eq!(
+            AspenError::MissingTemplateParameter("foo".to_string()).to_string(),
+            "Missing template parameter: foo"
+        );
+        assert_eq!(
+            AspenError::UnexpectedTemplateParameter("foo".to_string()).to_string(),
+            "Unexpected template parameter: foo"
+        );
+        assert_eq!(AspenError::UnknownPolicySet("foo".to_string()).to_string(), "Unknown policy set: foo");
     }
 
     #[test_log::test]
@@ -1,15 +1,56 @@
 use {
-    crate::{AspenError, PolicyVersion},
+    crate::{from_str_json, AspenError, Effect, PolicyVersion},
+    chrono::{DateTime, SecondsFormat, Utc},
     derive_builder::Builder,
     regex::{Regex, RegexBuilder},
-    scratchstack_arn::Arn,
-    scratchstack_aws_principal::{Principal, SessionData},
-    std::fmt::{Display, Formatter, Result as FmtResult},
+    scratchstack_arn::{utils::validate_region, Arn},
+    scratchstack_aws_principal::{
+        AssumedRole, CanonicalUser, FederatedUser, Principal, PrincipalIdentity, RootUser, Service, SessionData,
+        SessionValue, User,
+    },
+    serde::{de, Deserialize, Deserializer, Serialize, Serializer},
+    std::{
+        cell::RefCell,
+        collections::{BTreeMap, HashMap},
+        error::Error,
+        fmt::{Display, Formatter, Result as FmtResult},
+        net::IpAddr,
+        str::FromStr,
+    },
 };
 
+/// The maximum number of compiled regular expressions cached per thread before the cache is flushed.
+const REGEX_CACHE_CAPACITY: usize = 4096;
+
+thread_local! {
+    static REGEX_CACHE: RefCell<HashMap<(String, bool), Regex>> = RefCell::new(HashMap::new());
+}
+
 /// The request context used when evaluating an Aspen policy.
 ///
 /// Context structures are immutable.
+///
+/// Contexts can be serialized to and deserialized from JSON so that requests can be stored as test fixtures. The
+/// representation is:
+///
+/// ```json
+/// {
+///     "Service": "s3",
+///     "Api": "GetObject",
+///     "Actor": [{"AWS": "arn:aws:iam::123456789012:user/alice"}],
+///     "Resources": ["arn:aws:s3:::bucket/key"],
+///     "SessionData": {
+///         "aws:SourceIp": {"IpAddr": "192.0.2.1"},
+///         "aws:CurrentTime": {"Timestamp": "2024-03-01T12:00:00Z"},
+///         "aws:MultiFactorAuthPresent": {"Bool": true}
+///     }
+/// }
+/// ```
+///
+/// Actor identities are written as `{"AWS": arn}` (users, assumed roles, federated users, and root users, which may
+/// also be given as a bare account id), `{"Service": "lambda.amazonaws.com"}`, or `{"CanonicalUser": id}`. Session
+/// values are tagged with their type: `Null`, `Binary` (base64), `Bool`, `Integer`, `IpAddr`, `String`, or
+/// `Timestamp` (RFC 3339). `Actor`, `Resources`, and `SessionData` may be omitted.
 #[derive(Builder, Clone, Debug, Eq, PartialEq)]
 pub struct Context {
     /// The API being invoked.
@@ -67,6 +108,18 @@ impl Context {
         &self.service
     }
 
+    /// Replaces the service, API, and resources of this context in place.
+    ///
+    /// This is used by bulk evaluators to avoid rebuilding the actor and session data for every request.
+    pub(crate) fn set_request(&mut self, service: &str, api: &str, resources: &[Arn]) {
+        self.service.clear();
+        self.service.push_str(service);
+        self.api.clear();
+        self.api.push_str(api);
+        self.resources.clear();
+        self.resources.extend_from_slice(resources);
+    }
+
     /// Creates a [Regex] from the given string pattern and policy version.
     ///
     /// If `case_insensitive` is `true`, the returned [Regex] will be case insensitive.
@@ -142,10 +195,7 @@ impl Context {
         }
 
         pattern.push('$');
-        Ok(RegexBuilder::new(&pattern)
-            .case_insensitive(case_insensitive)
-            .build()
-            .expect("regex builds should not fail"))
+        Ok(cached_regex(pattern, case_insensitive))
     }
 
     /// Substitutes variables from the given string, returning the resulting string.
@@ -195,6 +245,188 @@ impl Context {
     }
 }
 
+from_str_json!(Context);
+
+/// The serialized form of a [Context].
+#[derive(Deserialize, Serialize)]
+#[serde(deny_unknown_fields, rename_all = "PascalCase")]
+struct ContextRepr {
+    service: String,
+    api: String,
+    #[serde(default)]
+    actor: Vec<IdentityRepr>,
+    #[serde(default)]
+    resources: Vec<String>,
+    #[serde(default)]
+    session_data: BTreeMap<String, SessionValueRepr>,
+}
+
+/// The serialized form of a [PrincipalIdentity].
+#[derive(Deserialize, Serialize)]
+enum IdentityRepr {
+    #[serde(rename = "AWS")]
+    Aws(String),
+    CanonicalUser(String),
+    Service(String),
+}
+
+/// The serialized form of a [SessionValue].
+#[derive(Deserialize, Serialize)]
+enum SessionValueRepr {
+    Null,
+    Binary(String),
+    Bool(bool),
+    Integer(i64),
+    IpAddr(IpAddr),
+    String(String),
+    Timestamp(String),
+}
+
+impl From<&PrincipalIdentity> for IdentityRepr {
+    fn from(identity: &PrincipalIdentity) -> Self {
+        match identity {
+            PrincipalIdentity::CanonicalUser(user) => Self::CanonicalUser(user.canonical_user_id().to_string()),
+            PrincipalIdentity::Service(service) => Self::Service(service.to_string()),
+            _ => Self::Aws(Arn::try_from(identity).expect("AWS identities convert to ARNs").to_string()),
+        }
+    }
+}
+
+impl TryFrom<IdentityRepr> for PrincipalIdentity {
+    type Error = AspenError;
+
+    fn try_from(repr: IdentityRepr) -> Result<Self, Self::Error> {
+        match repr {
+            IdentityRepr::Aws(aws) => parse_aws_identity(&aws).ok_or(AspenError::InvalidPrincipal(aws)),
+            IdentityRepr::CanonicalUser(id) => {
+                CanonicalUser::new(&id).map(Into::into).map_err(|_| AspenError::InvalidPrincipal(id))
+            }
+            IdentityRepr::Service(service) => {
+                parse_service(&service).map(Into::into).ok_or(AspenError::InvalidPrincipal(service))
+            }
+        }
+    }
+}
+
+/// Parses an AWS identity ARN or a bare account id.
+fn parse_aws_identity(aws: &str) -> Option<PrincipalIdentity> {
+    if aws.len() == 12 && aws.bytes().all(|b| b.is_ascii_digit()) {
+        return RootUser::new("aws", aws).ok().map(Into::into);
+    }
+
+    let arn = Arn::from_str(aws).ok()?;
+    let resource = arn.resource();
+    match arn.service() {
+        "iam" if resource == "root" => RootUser::new(arn.partition(), arn.account_id()).ok().map(Into::into),
+        "iam" if resource.starts_with("user/") => User::try_from(&arn).ok().map(Into::into),
+        "sts" if resource.starts_with("assumed-role/") => AssumedRole::try_from(&arn).ok().map(Into::into),
+        "sts" => {
+            let user_name = resource.strip_prefix("federated-user/")?;
+            FederatedUser::new(arn.partition(), arn.account_id(), user_name).ok().map(Into::into)
+        }
+        _ => None,
+    }
+}
+
+/// Parses a service principal of the form `service[.region].dns-suffix`.
+fn parse_service(service: &str) -> Option<Service> {
+    let (name, rest) = service.split_once('.')?;
+    match rest.split_once('.') {
+        Some((region, dns_suffix)) if validate_region(region).is_ok() => {
+            Service::new(name, Some(region.to_string()), dns_suffix).ok()
+        }
+        _ => Service::new(name, None, rest).ok(),
+    }
+}
+
+impl From<&SessionValue> for SessionValueRepr {
+    fn from(value: &SessionValue) -> Self {
+        match value {
+            SessionValue::Null => Self::Null,
+            SessionValue::Binary(value) => Self::Binary(base64::encode(value)),
+            SessionValue::Bool(value) => Self::Bool(*value),
+            SessionValue::Integer(value) => Self::Integer(*value),
+            SessionValue::IpAddr(value) => Self::IpAddr(*value),
+            SessionValue::String(value) => Self::String(value.clone()),
+            SessionValue::Timestamp(value) => Self::Timestamp(value.to_rfc3339_opts(SecondsFormat::AutoSi, true)),
+        }
+    }
+}
+
+impl TryFrom<SessionValueRepr> for SessionValue {
+    type Error = String;
+
+    fn try_from(repr: SessionValueRepr) -> Result<Self, Self::Error> {
+        Ok(match repr {
+            SessionValueRepr::Null => Self::Null,
+            SessionValueRepr::Binary(value) => {
+                Self::Binary(base64::decode(&value).map_err(|_| format!("invalid base64 value: {value}"))?)
+            }
+            SessionValueRepr::Bool(value) => Self::Bool(value),
+            SessionValueRepr::Integer(value) => Self::Integer(value),
+            SessionValueRepr::IpAddr(value) => Self::IpAddr(value),
+            SessionValueRepr::String(value) => Self::String(value),
+            SessionValueRepr::Timestamp(value) => Self::Timestamp(
+                DateTime::parse_from_rfc3339(&value)
+                    .map_err(|_| format!("invalid timestamp: {value}"))?
+                    .with_timezone(&Utc),
+            ),
+        })
+    }
+}
+
+impl Serialize for Context {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        ContextRepr {
+            service: self.service.clone(),
+            api: self.api.clone(),
+            actor: self.actor.iter().map(IdentityRepr::from).collect(),
+            resources: self.resources.iter().map(ToString::to_string).collect(),
+            session_data: self.session_data.iter().map(|(k, v)| (k.clone(), SessionValueRepr::from(v))).collect(),
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Context {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let repr = ContextRepr::deserialize(deserializer)?;
+
+        let mut actor = Vec::with_capacity(repr.actor.len());
+        for identity in repr.actor {
+            actor.push(PrincipalIdentity::try_from(identity).map_err(de::Error::custom)?);
+        }
+
+        let mut resources = Vec::with_capacity(repr.resources.len());
+        for resource in repr.resources {
+            match Arn::from_str(&resource) {
+                Ok(arn) => resources.push(arn),
+                Err(_) => return Err(de::Error::custom(AspenError::InvalidResource(resource))),
+            }
+        }
+
+        let mut session_data = SessionData::new();
+        for (key, value) in repr.session_data {
+            let value = SessionValue::try_from(value).map_err(|e| de::Error::custom(format!("{key}: {e}")))?;
+            session_data.insert(&key, value);
+        }
+
+        Ok(Self {
+            api: repr.api,
+            actor: Principal::from(actor),
+            resources,
+            session_data,
+            service: repr.service,
+        })
+    }
+}
+
+/// Returns the value of `aws:PrincipalArn` for an assumed-role session: the ARN of the role, not of the session. Role
+/// paths are not part of session ARNs, so the role ARN returned has no path.
+pub(crate) fn session_role_arn(session: &AssumedRole) -> String {
+    format!("arn:{}:iam::{}:role/{}", session.partition(), session.account_id(), session.role_name())
+}
+
 /// Creates a [Regex] from the given string pattern.
 ///
 /// If `case_insensitive` is `true`, the returned [Regex] will be case insensitive.
@@ -215,11 +447,33 @@ pub(crate) fn regex_from_glob(s: &str, case_insensitive: bool) -> Regex {
         }
     }
     pattern.push('$');
-    RegexBuilder::new(&pattern).case_insensitive(case_insensitive).build().expect("regex builds should not fail")
+    cached_regex(pattern, case_insensitive)
+}
+
+/// Returns a compiled [Regex] for the given (already anchored and escaped) pattern.
+///
+/// Compiled expressions are cached per thread, so repeated evaluations of the same policies do not recompile their
+/// patterns. The cache is flushed once it holds [REGEX_CACHE_CAPACITY] entries.
+fn cached_regex(pattern: String, case_insensitive: bool) -> Regex {
+    REGEX_CACHE.with(|cache| {
+        let mut cache = cache.borrow_mut();
+        let key = (pattern, case_insensitive);
+        if let Some(regex) = cache.get(&key) {
+            return regex.clone();
+        }
+
+        let regex =
+            RegexBuilder::new(&key.0).case_insensitive(case_insensitive).build().expect("regex builds should not fail");
+        if cache.len() >= REGEX_CACHE_CAPACITY {
+            cache.clear();
+        }
+        cache.insert(key, regex.clone());
+        regex
+    })
 }
 
 /// The outcome of a policy evaluation.
-#[derive(Debug, Eq, PartialEq)]
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
 pub enum Decision {
     /// Allow the request if no other statements or policies deny it.
     Allow,
@@ -245,11 +499,75 @@ impl Display for Decision {
     }
 }
 
+/// How evaluation handles a statement that fails to evaluate, e.g. because of a malformed variable reference.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub enum ErrorPolicy {
+    /// Stop at the first error and return it. This is the behavior of [Policy::evaluate](crate::Policy::evaluate).
+    #[default]
+    Abort,
+
+    /// Keep evaluating, collecting the errors. An erroring `Allow` statement is treated as not applicable and an
+    /// erroring `Deny` statement is treated as applicable, so an error never grants access.
+    FailClosed,
+}
+
+/// A statement that failed to evaluate under [ErrorPolicy::FailClosed].
+#[derive(Debug, Eq, PartialEq)]
+pub struct StatementError {
+    statement: usize,
+    effect: Effect,
+    error: AspenError,
+}
+
+impl StatementError {
+    pub(crate) fn new(statement: usize, effect: Effect, error: AspenError) -> Self {
+        Self {
+            statement,
+            effect,
+            error,
+        }
+    }
+
+    /// Returns the index of the statement within its policy.
+    #[inline]
+    pub fn statement(&self) -> usize {
+        self.statement
+    }
+
+    /// Returns the effect of the statement. An `Allow` statement was treated as not applicable; a `Deny` statement was
+    /// treated as applicable.
+    #[inline]
+    pub fn effect(&self) -> Effect {
+        self.effect
+    }
+
+    /// Returns the error raised by the statement.
+    #[inline]
+    pub fn error(&self) -> &AspenError {
+        &self.error
+    }
+}
+
+impl Display for StatementError {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        write!(f, "Statement {} ({}): {}", self.statement, self.effect, self.error)
+    }
+}
+
+impl Error for StatementError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
 #[cfg(test)]
 mod test {
     use {
         crate::{Context, Decision},
-        scratchstack_aws_principal::{Principal, PrincipalIdentity, SessionData, User},
+        indoc::indoc,
+        pretty_assertions::assert_eq,
+        scratchstack_aws_principal::{Principal, PrincipalIdentity, SessionData, SessionValue, User},
+        std::str::FromStr,
     };
 
     #[test_log::test]
@@ -279,4 +597,72 @@ mod test {
         assert_eq!(format!("{}", Decision::Deny), "Deny");
         assert_eq!(format!("{}", Decision::DefaultDeny), "DefaultDeny");
     }
+
+    #[test_log::test]
+    fn test_context_serde() {
+        let json = indoc! {r#"
+            {
+                "Service": "s3",
+                "Api": "GetObject",
+                "Actor": [
+                    {"AWS": "arn:aws:sts::123456789012:assumed-role/deploy/alice"},
+                    {"AWS": "arn:aws:iam::123456789012:user/path/bob"},
+                    {"AWS": "arn:aws:sts::123456789012:federated-user/carol"},
+                    {"AWS": "arn:aws-cn:iam::123456789012:root"},
+                    {"Service": "lambda.amazonaws.com"},
+                    {"Service": "logs.us-west-2.amazonaws.com"},
+                    {"CanonicalUser": "9da4bcba2132ad952bba3c8ecb37e668d99b310ce313da30c98aba4cdf009a7d"}
+                ],
+                "Resources": ["arn:aws:s3:::bucket/key"],
+                "SessionData": {
+                    "aws:CurrentTime": {"Timestamp": "2024-03-01T12:00:00Z"},
+                    "aws:EpochTime": {"Integer": 1709294400},
+                    "aws:MultiFactorAuthPresent": {"Bool": true},
+                    "aws:SourceIp": {"IpAddr": "192.0.2.1"},
+                    "aws:username": {"String": "bob"},
+                    "s3:x-amz-content-sha256": {"Binary": "aGVsbG8="},
+                    "aws:SourceVpc": "Null"
+                }
+            }"#};
+        let context = Context::from_str(json).unwrap();
+        assert_eq!(context.actor().len(), 7);
+        assert!(context.actor().iter().any(|identity| identity.as_assumed_role().is_some()));
+        let regions: Vec<_> = context.actor().iter().filter_map(|identity| identity.as_service()?.region()).collect();
+        assert_eq!(regions, vec!["us-west-2"]);
+        assert_eq!(context.resources()[0].resource(), "bucket/key");
+        assert_eq!(context.session_data().get("aws:EpochTime"), Some(&SessionValue::Integer(1709294400)));
+        assert_eq!(
+            context.session_data().get("s3:x-amz-content-sha256"),
+            Some(&SessionValue::Binary(b"hello".to_vec()))
+        );
+        assert_eq!(context.session_data().get("aws:SourceVpc"), Some(&SessionValue::Null));
+
+        // Session data keys are case-insensitive and are serialized in lowercase.
+        let serialized = serde_json::to_string(&context).unwrap();
+        assert!(serialized.contains(r#"{"AWS":"arn:aws-cn:iam::123456789012:root"}"#));
+        assert!(serialized.contains(r#""aws:currenttime":{"Timestamp":"2024-03-01T12:00:00Z"}"#));
+        assert_eq!(Context::from_str(&serialized).unwrap(), context);
+
+        let minimal =
+            Context::from_str(r#"{"Service": "iam", "Api": "ListUsers", "Actor": [{"AWS": "123456789012"}]}"#).unwrap();
+        assert!(minimal.actor()[0].as_root_user().is_some());
+        assert!(minimal.resources().is_empty());
+
+        assert!(Context::from_str(r#"{"Service": "s3", "Api": "GetObject", "Actor": [{"AWS": "bob"}]}"#).is_err());
+        assert!(Context::from_str(r#"{"Service": "s3", "Api": "GetObject", "Resources": ["bucket"]}"#).is_err());
+        assert!(Context::from_str(
+            r#"{"Service": "s3", "Api": "GetObject", "SessionData": {"aws:CurrentTime": {"Timestamp": "now"}}}"#
+        )
+        .is_err());
+        assert!(Context::from_str(r#"{"Service": "s3", "Api": "GetObject", "Extra": 1}"#).is_err());
+    }
+
+    #[test_log::test]
+    fn test_decision_serde() {
+        for decision in [Decision::Allow, Decision::Deny, Decision::DefaultDeny] {
+            let json = serde_json::to_string(&decision).unwrap();
+            assert_eq!(json, format!("\"{decision}\""));
+            assert_eq!(serde_json::from_str::<Decision>(&json).unwrap(), decision);
+        }
+    }
 }
@@ -0,0 +1,507 @@
+use {
+    crate::{
+        eval::{regex_from_glob, session_role_arn},
+        Action, AspenError, Context, Effect, Policy, PolicyVersion, Resource, Statement, StatementList,
+    },
+    scratchstack_arn::Arn,
+    scratchstack_aws_principal::{AssumedRole, Principal as PrincipalActor, RootUser, SessionData, SessionValue, User},
+    serde_json::Value,
+    std::{
+        collections::{BTreeMap, BTreeSet},
+        str::FromStr,
+    },
+};
+
+/// The suffix stripped from CloudTrail `eventSource` values to obtain the service name.
+pub(crate) const EVENT_SOURCE_SUFFIX: &str = ".amazonaws.com";
+
+/// A record of a single observed API call.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct AccessRecord {
+    principal: Option<String>,
+    service: String,
+    api: String,
+    resources: Vec<String>,
+}
+
+impl AccessRecord {
+    /// Create a new access record.
+    pub fn new<S: Into<String>, A: Into<String>>(
+        principal: Option<String>,
+        service: S,
+        api: A,
+        resources: Vec<String>,
+    ) -> Self {
+        Self {
+            principal,
+            service: service.into(),
+            api: api.into(),
+            resources,
+        }
+    }
+
+    /// Parses an access record from a single line of JSON.
+    ///
+    /// Two forms are accepted. The simple form has `Service`, `Api`, and optional `Principal` and `Resources` keys
+    /// (`Action` may be given in `service:api` form instead of `Service` and `Api`, and `Resource` may be given
+    /// instead of `Resources`):
+    ///
+    /// ```json
+    /// {"Principal": "arn:aws:iam::123456789012:user/alice", "Action": "s3:GetObject", "Resource": "arn:aws:s3:::b/k"}
+    /// ```
+    ///
+    /// The CloudTrail form uses the `eventSource`, `eventName`, `userIdentity.arn`, and `resources[].ARN` fields of a
+    /// CloudTrail event. The service is the `eventSource` with `.amazonaws.com` removed.
+    ///
+    /// # Errors
+    ///
+    /// If the line is not a JSON object in either form, [AspenError::InvalidAccessRecord] is returned.
+    pub fn from_json_line(line: &str) -> Result<Self, AspenError> {
+        let value: Value =
+            serde_json::from_str(line).map_err(|e| AspenError::InvalidAccessRecord(format!("{e}: {line}")))?;
+        Self::from_json_value(&value).ok_or_else(|| AspenError::InvalidAccessRecord(line.to_string()))
+    }
+
+    fn from_json_value(value: &Value) -> Option<Self> {
+        let object = value.as_object()?;
+        let string = |key: &str| object.get(key).and_then(Value::as_str).map(str::to_string);
+
+        if let Some(event_source) = string("eventSource") {
+            let service = event_source.strip_suffix(EVENT_SOURCE_SUFFIX).unwrap_or(&event_source).to_string();
+            let api = string("eventName")?;
+            let principal =
+                object.get("userIdentity").and_then(|id| id.get("arn")).and_then(Value::as_str).map(str::to_string);
+            let resources = match object.get("resources") {
+                None | Some(Value::Null) => Vec::new(),
+                Some(resources) => resources
+                    .as_array()?
+                    .iter()
+                    .map(|r| r.get("ARN").and_then(Value::as_str).map(str::to_string))
+                    .collect::<Option<Vec<_>>>()?,
+            };
+            return Some(Self::new(principal, service, api, resources));
+        }
+
+        let (service, api) = match string("Action") {
+            Some(action) => {
+                let (service, api) = action.split_once(':')?;
+                (service.to_string(), api.to_string())
+            }
+            None => (string("Service")?, string("Api")?),
+        };
+
+        let resources = match (object.get("Resources"), string("Resource")) {
+            (Some(resources), _) => {
+                resources.as_array()?.iter().map(|r| r.as_str().map(str::to_string)).collect::<Option<Vec<_>>>()?
+            }
+            (None, Some(resource)) => vec![resource],
+            (None, None) => Vec::new(),
+        };
+
+        Some(Self::new(string("Principal"), service, api, resources))
+    }
+
+    /// Returns the ARN of the principal that made the call, if known.
+    #[inline]
+    pub fn principal(&self) -> Option<&str> {
+        self.principal.as_deref()
+    }
+
+    /// Returns the service called.
+    #[inline]
+    pub fn service(&self) -> &str {
+        &self.service
+    }
+
+    /// Returns the API called.
+    #[inline]
+    pub fn api(&self) -> &str {
+        &self.api
+    }
+
+    /// Returns the ARNs of the resources accessed.
+    #[inline]
+    pub fn resources(&self) -> &[String] {
+        &self.resources
+    }
+
+    /// Create a request [Context] for this record.
+    ///
+    /// IAM user, assumed role, and account root principal ARNs are converted to the corresponding identity, and
+    /// `aws:PrincipalArn`, `aws:PrincipalAccount`, and (for users) `aws:username` are set in the session data. For an
+    /// assumed role, `aws:PrincipalArn` is the ARN of the role rather than the session. If the principal is unknown,
+    /// the request has no identities.
+    ///
+    /// # Errors
+    ///
+    /// If the principal is not a user, assumed role, or root ARN, [AspenError::InvalidPrincipal] is returned. If a
+    /// resource is not a valid ARN, [AspenError::InvalidResource] is returned.
+    pub fn to_context(&self) -> Result<Context, AspenError> {
+        let mut session_data = SessionData::new();
+        let actor = match &self.principal {
+            None => PrincipalActor::from(Vec::new()),
+            Some(principal) => {
+                let invalid = || AspenError::InvalidPrincipal(principal.clone());
+                let arn = Arn::from_str(principal).map_err(|_| invalid())?;
+                session_data.insert("aws:PrincipalAccount", SessionValue::String(arn.account_id().to_string()));
+
+                if arn.resource() == "root" {
+                    session_data.insert("aws:PrincipalArn", SessionValue::String(principal.clone()));
+                    RootUser::new(arn.partition(), arn.account_id()).map_err(|_| invalid())?.into()
+                } else if arn.resource().starts_with("assumed-role/") {
+                    let session = AssumedRole::from_str(principal).map_err(|_| invalid())?;
+                    session_data.insert("aws:PrincipalArn", SessionValue::String(session_role_arn(&session)));
+                    session.into()
+                } else {
+                    session_data.insert("aws:PrincipalArn", SessionValue::String(principal.clone()));
+                    let user = User::from_str(principal).map_err(|_| invalid())?;
+                    session_data.insert("aws:username", SessionValue::String(user.user_name().to_string()));
+                    user.into()
+                }
+            }
+        };
+
+        let resources = self
+            .resources
+            .iter()
+            .map(|r| Arn::from_str(r).map_err(|_| AspenError::InvalidResource(r.clone())))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Context::builder()
+            .service(self.service.clone())
+            .api(self.api.clone())
+            .actor(actor)
+            .resources(resources)
+            .session_data(session_data)
+            .build()
+            .expect("all context fields are set"))
+    }
+}
+
+impl FromStr for AccessRecord {
+    type Err = AspenError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::from_json_line(s)
+    }
+}
+
+/// Synthesizes a least-privilege [Policy] from observed access.
+///
+/// Each recorded call is granted exactly: one statement is generated per service and set of resources, listing the
+/// APIs called on those resources. Calls recorded without resources are granted on `*`.
+///
+/// Resource ARNs are used literally (wildcard and `$` characters are escaped) unless they match a generalization
+/// rule added with [PolicyGenerator::add_generalization], in which case the rule's pattern is granted instead.
+///
+/// # Example
+///
+/// ```
+/// # use scratchstack_aspen::PolicyGenerator;
+/// let log = r#"
+/// {"eventSource": "s3.amazonaws.com", "eventName": "GetObject", "userIdentity": {"arn": "arn:aws:iam::123456789012:user/alice"}, "resources": [{"ARN": "arn:aws:s3:::bucket/2024/01/a.csv"}]}
+/// {"eventSource": "s3.amazonaws.com", "eventName": "GetObject", "userIdentity": {"arn": "arn:aws:iam::123456789012:user/alice"}, "resources": [{"ARN": "arn:aws:s3:::bucket/2024/02/b.csv"}]}
+/// {"Principal": "arn:aws:iam::123456789012:user/alice", "Action": "sts:GetCallerIdentity"}
+/// "#;
+///
+/// let mut generator = PolicyGenerator::new();
+/// generator.add_generalization("arn:aws:s3:::bucket/2024/*").unwrap();
+/// generator.add_json_lines(log).unwrap();
+///
+/// let policy = generator.generate().unwrap();
+/// let statement = &policy.statement()[0];
+/// assert_eq!(statement.resource().unwrap()[0].to_string(), "arn:aws:s3:::bucket/2024/*");
+/// assert_eq!(policy.statement()[1].resource().unwrap()[0].to_string(), "*");
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct PolicyGenerator {
+    principal: Option<String>,
+    generalizations: Vec<String>,
+    access: BTreeMap<(String, String), BTreeSet<String>>,
+}
+
+impl PolicyGenerator {
+    /// Create a new generator that accepts records for any principal.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a new generator that only accepts records for the given principal ARN.
+    pub fn for_principal<S: Into<String>>(principal: S) -> Self {
+        Self {
+            principal: Some(principal.into()),
+            ..Self::default()
+        }
+    }
+
+    /// Add a generalization rule. Resource ARNs matching `pattern` (which may contain `*` and `?` wildcards) are
+    /// replaced by `pattern` itself. Rules are tried in the order they were added.
+    ///
+    /// # Errors
+    ///
+    /// If `pattern` is not a valid resource pattern, [AspenError::InvalidResource] is returned.
+    pub fn add_generalization(&mut self, pattern: &str) -> Result<(), AspenError> {
+        Resource::from_str(pattern)?;
+        if pattern.contains('$') {
+            return Err(AspenError::InvalidResource(pattern.to_string()));
+        }
+
+        self.generalizations.push(pattern.to_string());
+        Ok(())
+    }
+
+    /// Add an access record, returning whether it was added. Records for other principals are ignored if the
+    /// generator was created with [PolicyGenerator::for_principal].
+    ///
+    /// # Errors
+    ///
+    /// If the record's service or API is not a valid action, [AspenError::InvalidAction] is returned. If a resource is
+    /// not a valid ARN, [AspenError::InvalidResource] is returned.
+    pub fn add_record(&mut self, record: &AccessRecord) -> Result<bool, AspenError> {
+        if let Some(wanted) = &self.principal {
+            if record.principal() != Some(wanted.as_str()) {
+                return Ok(false);
+            }
+        }
+
+        Action::new(record.service(), record.api())?;
+
+        let mut patterns = BTreeSet::new();
+        for resource in record.resources() {
+            patterns.insert(self.generalize(resource)?);
+        }
+        if patterns.is_empty() {
+            patterns.insert("*".to_string());
+        }
+
+        self.access.entry((record.service().to_string(), record.api().to_string())).or_default().extend(patterns);
+        Ok(true)
+    }
+
+    /// Add access records from JSON lines (see [AccessRecord::from_json_line]), returning the number of records
+    /// added. Blank lines are skipped, as are CloudTrail events with an `errorCode`, since the call was not
+    /// permitted or did not complete, and records ignored by [PolicyGenerator::add_record] are not counted.
+    ///
+    /// # Errors
+    ///
+    /// If a line cannot be parsed or contains an invalid action or resource, the error is returned.
+    pub fn add_json_lines(&mut self, lines: &str) -> Result<usize, AspenError> {
+        let mut added = 0;
+
+        for line in lines.lines().map(str::trim).filter(|line| !line.is_empty()) {
+            let value: Value =
+                serde_json::from_str(line).map_err(|e| AspenError::InvalidAccessRecord(format!("{e}: {line}")))?;
+            if value.get("errorCode").map(|code| !code.is_null()).unwrap_or(false) {
+                continue;
+            }
+
+            let record = AccessRecord::from_json_value(&value)
+                .ok_or_else(|| AspenError::InvalidAccessRecord(line.to_string()))?;
+            if self.add_record(&record)? {
+                added += 1;
+            }
+        }
+
+        Ok(added)
+    }
+
+    /// Returns the resource pattern to grant for an observed resource ARN.
+    fn generalize(&self, resource: &str) -> Result<String, AspenError> {
+        if let Some(rule) = self.generalizations.iter().find(|rule| regex_from_glob(rule, false).is_match(resource)) {
+            return Ok(rule.clone());
+        }
+
+        let mut escaped = String::with_capacity(resource.len());
+        for c in resource.chars() {
+            match c {
+                '*' | '?' | '$' => {
+                    escaped.push_str("${");
+                    escaped.push(c);
+                    escaped.push('}');
+                }
+                _ => escaped.push(c),
+            }
+        }
+
+        Resource::from_str(&escaped).map_err(|_| AspenError::InvalidResource(resource.to_string()))?;
+        Ok(escaped)
+    }
+
+    /// Generate the policy.
+    ///
+    /// The result uses version `2012-10-17`, with statements ordered by service. Each statement has an `Sid` derived
+    /// from the service name. If no records were added, the policy has no statements.
+    ///
+    /// # Errors
+    ///
+    /// Actions and resources are validated as records are added, so this only fails if a recorded service or API
+    /// cannot form a valid [Action].
+    pub fn generate(&self) -> Result<Policy, AspenError> {
+        // service -> resource patterns -> APIs
+        let mut grouped: BTreeMap<&str, BTreeMap<BTreeSet<&str>, Vec<&str>>> = BTreeMap::new();
+        for ((service, api), resources) in self.access.iter() {
+            let resources: BTreeSet<&str> = if resources.contains("*") {
+                BTreeSet::from(["*"])
+            } else {
+                resources.iter().map(String::as_str).collect()
+            };
+            grouped.entry(service).or_default().entry(resources).or_default().push(api);
+        }
+
+        let mut statements = Vec::new();
+        for (service, groups) in grouped {
+            let base_sid = sid_for_service(service);
+
+            for (i, (resources, apis)) in groups.into_iter().enumerate() {
+                let sid = match i {
+                    0 => base_sid.clone(),
+                    _ => format!("{base_sid}{}", i + 1),
+                };
+                let actions =
+                    apis.into_iter().map(|api| Action::new(service, api)).collect::<Result<Vec<_>, AspenError>>()?;
+                let resources =
+                    resources.into_iter().map(Resource::from_str).collect::<Result<Vec<_>, AspenError>>()?;
+
+                let statement = Statement::builder()
+                    .sid(sid)
+                    .effect(Effect::Allow)
+                    .action(actions)
+                    .resource(resources)
+                    .build()
+                    .expect("all statement fields are set");
+                statements.push(statement);
+            }
+        }
+
+        Ok(Policy::builder()
+            .version(PolicyVersion::V2012_10_17)
+            .statement(StatementList::from(statements))
+            .build()
+            .expect("all policy fields are set"))
+    }
+}
+
+/// Returns a statement id for the given service, e.g. `AllowEc2InstanceConnect` for `ec2-instance-connect`.
+fn sid_for_service(service: &str) -> String {
+    let mut sid = String::from("Allow");
+    for part in service.split(|c: char| !c.is_ascii_alphanumeric()).filter(|part| !part.is_empty()) {
+        let mut chars = part.chars();
+        if let Some(first) = chars.next() {
+            sid.push(first.to_ascii_uppercase());
+            sid.extend(chars);
+        }
+    }
+    sid
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        crate::{AccessRecord, AspenError, Policy, PolicyGenerator},
+        pretty_assertions::assert_eq,
+        std::str::FromStr,
+    };
+
+    #[test_log::test]
+    fn test_parse_records() {
+        let simple = AccessRecord::from_str(
+            r#"{"Principal": "arn:aws:iam::123456789012:user/alice", "Service": "s3", "Api": "ListBucket",
+                "Resources": ["arn:aws:s3:::a", "arn:aws:s3:::b"]}"#,
+        )
+        .unwrap();
+        assert_eq!(simple.principal(), Some("arn:aws:iam::123456789012:user/alice"));
+        assert_eq!(simple.service(), "s3");
+        assert_eq!(simple.api(), "ListBucket");
+        assert_eq!(simple.resources(), &["arn:aws:s3:::a".to_string(), "arn:aws:s3:::b".to_string()]);
+
+        let trail = AccessRecord::from_json_line(
+            r#"{"eventSource": "dynamodb.amazonaws.com", "eventName": "GetItem", "userIdentity": {"type": "IAMUser"},
+                "resources": null}"#,
+        )
+        .unwrap();
+        assert_eq!(trail, AccessRecord::new(None, "dynamodb", "GetItem", vec![]));
+
+        let context = simple.to_context().unwrap();
+        assert_eq!(context.api(), "ListBucket");
+        assert_eq!(context.resources().len(), 2);
+        assert_eq!(context.session_data().get("aws:username").unwrap().to_string(), "alice");
+        assert_eq!(
+            context.session_data().get("aws:PrincipalArn").unwrap().to_string(),
+            "arn:aws:iam::123456789012:user/alice"
+        );
+        assert!(trail.to_context().unwrap().actor().is_empty());
+
+        // The principal ARN of a role session is the role.
+        let session = AccessRecord::new(
+            Some("arn:aws:sts::123456789012:assumed-role/deploy/alice".to_string()),
+            "s3",
+            "ListBucket",
+            vec![],
+        );
+        let context = session.to_context().unwrap();
+        assert_eq!(
+            context.session_data().get("aws:PrincipalArn").unwrap().to_string(),
+            "arn:aws:iam::123456789012:role/deploy"
+        );
+        assert_eq!(context.actor()[0].to_string(), "arn:aws:sts::123456789012:assumed-role/deploy/alice");
+        assert!(AccessRecord::new(Some("alice".to_string()), "s3", "ListBucket", vec![]).to_context().is_err());
+
+        assert!(matches!(AccessRecord::from_json_line("[1, 2]"), Err(AspenError::InvalidAccessRecord(_))));
+        assert!(matches!(AccessRecord::from_json_line(r#"{"Action": "s3"}"#), Err(AspenError::InvalidAccessRecord(_))));
+        assert!(matches!(AccessRecord::from_json_line("{"), Err(AspenError::InvalidAccessRecord(_))));
+    }
+
+    #[test_log::test]
+    fn test_generate() {
+        let alice = "arn:aws:iam::123456789012:user/alice";
+        let bob = "arn:aws:iam::123456789012:user/bob";
+        let log = [
+            format!(r#"{{"Principal": "{alice}", "Action": "s3:GetObject", "Resource": "arn:aws:s3:::bucket/a*b"}}"#),
+            format!(r#"{{"Principal": "{alice}", "Action": "s3:PutObject", "Resource": "arn:aws:s3:::bucket/a*b"}}"#),
+            format!(r#"{{"Principal": "{alice}", "Action": "s3:ListBucket", "Resource": "arn:aws:s3:::bucket"}}"#),
+            format!(
+                r#"{{"Principal": "{alice}", "Action": "ec2-instance-connect:SendSSHPublicKey",
+                    "Resource": "arn:aws:ec2:us-west-2:123456789012:instance/i-0123"}}"#
+            ),
+            format!(
+                r#"{{"Principal": "{alice}", "Action": "ec2-instance-connect:SendSSHPublicKey",
+                    "Resource": "arn:aws:ec2:us-west-2:123456789012:instance/i-0456"}}"#
+            ),
+            String::new(),
+            format!(r#"{{"Principal": "{bob}", "Action": "iam:DeleteUser"}}"#),
+            format!(
+                r#"{{"eventSource": "iam.amazonaws.com", "eventName": "CreateUser", "errorCode": "AccessDenied",
+                    "userIdentity": {{"arn": "{alice}"}}}}"#
+            ),
+        ]
+        .map(|line| line.replace('\n', " "))
+        .join("\n");
+
+        let mut generator = PolicyGenerator::for_principal(alice);
+        generator.add_generalization("arn:aws:ec2:*:123456789012:instance/*").unwrap();
+        assert_eq!(generator.add_json_lines(&log).unwrap(), 5);
+        let policy = generator.generate().unwrap();
+
+        let expected = Policy::from_str(
+            r#"{"Version": "2012-10-17", "Statement": [
+                {"Sid": "AllowEc2InstanceConnect", "Effect": "Allow", "Action": "ec2-instance-connect:SendSSHPublicKey",
+                 "Resource": "arn:aws:ec2:*:123456789012:instance/*"},
+                {"Sid": "AllowS3", "Effect": "Allow", "Action": "s3:ListBucket", "Resource": "arn:aws:s3:::bucket"},
+                {"Sid": "AllowS32", "Effect": "Allow", "Action": ["s3:GetObject", "s3:PutObject"],
+                 "Resource": "arn:aws:s3:::bucket/a${*}b"}
+            ]}"#,
+        )
+        .unwrap();
+        assert_eq!(policy, expected);
+
+        // The output must round-trip through the parser.
+        assert_eq!(Policy::from_str(&policy.to_string()).unwrap(), policy);
+
+        assert!(PolicyGenerator::new().generate().unwrap().statement().is_empty());
+        assert!(PolicyGenerator::new().add_generalization("bucket/*").is_err());
+        assert!(PolicyGenerator::new().add_record(&AccessRecord::new(None, "s3", "Get Object", vec![])).is_err());
+        assert!(PolicyGenerator::new()
+            .add_record(&AccessRecord::new(None, "s3", "GetObject", vec!["not-an-arn".to_string()]))
+            .is_err());
+    }
+}
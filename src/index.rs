@@ -0,0 +1,615 @@
+use {
+    crate::{AspenError, Context, Decision, PolicySet, PolicySource, Resource},
+    scratchstack_arn::Arn,
+    std::{
+        collections::{HashMap, HashSet},
+        iter::once,
+    },
+};
+
+/// A statement in an indexed policy set, as a (policy index, statement index) pair.
+type StatementRef = (usize, usize);
+
+/// Resource tries keyed by the account id segment of ARN patterns.
+type AccountMap = SegmentMap<ResourceTrie>;
+
+/// Account maps keyed by the region segment of ARN patterns.
+type RegionMap = SegmentMap<AccountMap>;
+
+/// Region maps keyed by the service segment of ARN patterns.
+type ServiceMap = SegmentMap<RegionMap>;
+
+/// Statements bucketed by the actions they can match.
+#[derive(Clone, Debug, Default)]
+struct ActionIndex {
+    /// Statements keyed by service and then by the literal prefix of an API pattern (the part before the first
+    /// wildcard).
+    services: HashMap<String, HashMap<String, Vec<StatementRef>>>,
+
+    /// Statements that can match any service: those with `NotAction`, or with `"*"` in `Action`.
+    fallback: Vec<StatementRef>,
+}
+
+impl ActionIndex {
+    fn new(policy_set: &PolicySet) -> Self {
+        let mut index = Self::default();
+
+        for (policy_index, (_, policy)) in policy_set.policies().iter().enumerate() {
+            for (statement_index, statement) in policy.statement().iter().enumerate() {
+                let statement_ref = (policy_index, statement_index);
+                let actions = match statement.action() {
+                    Some(actions) if !actions.iter().any(|action| action.is_any()) => actions,
+                    _ => {
+                        index.fallback.push(statement_ref);
+                        continue;
+                    }
+                };
+
+                for (service, api) in actions.iter().filter_map(|action| action.specific()) {
+                    let prefix = api.split(['*', '?']).next().unwrap_or_default();
+                    let prefixes = index.services.entry(service.to_string()).or_default();
+                    let statements = prefixes.entry(prefix.to_string()).or_default();
+                    if statements.last() != Some(&statement_ref) {
+                        statements.push(statement_ref);
+                    }
+                }
+            }
+        }
+
+        index
+    }
+
+    /// Returns the statements whose actions may match the service and API of the context, in order.
+    fn candidates(&self, context: &Context) -> Vec<StatementRef> {
+        let mut result = self.fallback.clone();
+
+        if let Some(prefixes) = self.services.get(context.service()) {
+            let api = context.api();
+            for end in api.char_indices().map(|(i, _)| i).chain(once(api.len())) {
+                if let Some(statements) = prefixes.get(&api[..end]) {
+                    result.extend_from_slice(statements);
+                }
+            }
+        }
+
+        result.sort_unstable();
+        result.dedup();
+        result
+    }
+}
+
+/// Values keyed by one segment of an ARN pattern: by the exact segment if it has no wildcards, or otherwise by its
+/// literal prefix (the part before the first wildcard).
+#[derive(Clone, Debug)]
+struct SegmentMap<T> {
+    exact: HashMap<String, T>,
+    prefixed: HashMap<String, T>,
+}
+
+impl<T: Default> SegmentMap<T> {
+    fn entry(&mut self, pattern: &str) -> &mut T {
+        match literal_prefix(pattern) {
+            (prefix, true) => self.prefixed.entry(prefix.to_string()).or_default(),
+            (exact, false) => self.exact.entry(exact.to_string()).or_default(),
+        }
+    }
+
+    /// Returns the values whose patterns may match the segment.
+    fn matching<'a>(&'a self, segment: &'a str) -> impl Iterator<Item = &'a T> + 'a {
+        let prefixes = segment.char_indices().map(|(i, _)| i).chain(once(segment.len()));
+        self.exact.get(segment).into_iter().chain(prefixes.filter_map(move |end| self.prefixed.get(&segment[..end])))
+    }
+}
+
+impl<T> Default for SegmentMap<T> {
+    fn default() -> Self {
+        Self {
+            exact: HashMap::new(),
+            prefixed: HashMap::new(),
+        }
+    }
+}
+
+/// A node in a [ResourceTrie].
+#[derive(Clone, Debug, Default)]
+struct TrieNode {
+    children: HashMap<u8, usize>,
+
+    /// Statements with a resource pattern whose literal prefix ends at this node and is followed by a wildcard.
+    prefixed: Vec<StatementRef>,
+
+    /// Statements with a resource pattern (without wildcards) that ends at this node.
+    exact: Vec<StatementRef>,
+}
+
+/// A byte-wise trie over the resource segment of ARN patterns.
+#[derive(Clone, Debug)]
+struct ResourceTrie {
+    nodes: Vec<TrieNode>,
+}
+
+impl ResourceTrie {
+    fn insert(&mut self, pattern: &str, statement_ref: StatementRef) {
+        let (prefix, wildcard) = literal_prefix(pattern);
+        let mut node = 0;
+        for byte in prefix.bytes() {
+            node = match self.nodes[node].children.get(&byte) {
+                Some(&child) => child,
+                None => {
+                    self.nodes.push(TrieNode::default());
+                    let child = self.nodes.len() - 1;
+                    self.nodes[node].children.insert(byte, child);
+                    child
+                }
+            };
+        }
+
+        let statements = if wildcard {
+            &mut self.nodes[node].prefixed
+        } else {
+            &mut self.nodes[node].exact
+        };
+        if statements.last() != Some(&statement_ref) {
+            statements.push(statement_ref);
+        }
+    }
+
+    /// Adds the statements with a pattern that may match the resource to `result`.
+    fn matching(&self, resource: &str, result: &mut HashSet<StatementRef>) {
+        let mut node = &self.nodes[0];
+        for byte in resource.bytes() {
+            result.extend(node.prefixed.iter());
+            node = match node.children.get(&byte) {
+                Some(&child) => &self.nodes[child],
+                None => return,
+            };
+        }
+
+        result.extend(node.prefixed.iter().chain(node.exact.iter()));
+    }
+}
+
+impl Default for ResourceTrie {
+    fn default() -> Self {
+        Self {
+            nodes: vec![TrieNode::default()],
+        }
+    }
+}
+
+/// Statements indexed by the ARN patterns in their `Resource` elements.
+#[derive(Clone, Debug, Default)]
+struct ResourceIndex {
+    /// Resource tries keyed by the partition, service, region, and account id segments of ARN patterns.
+    partitions: SegmentMap<ServiceMap>,
+
+    /// Statements that may match any resource: those with `NotResource`, or with `"*"` or a pattern containing a
+    /// variable in `Resource`.
+    fallback: HashSet<StatementRef>,
+}
+
+impl ResourceIndex {
+    fn new(policy_set: &PolicySet) -> Self {
+        let mut index = Self::default();
+
+        for (policy_index, (_, policy)) in policy_set.policies().iter().enumerate() {
+            for (statement_index, statement) in policy.statement().iter().enumerate() {
+                let statement_ref = (policy_index, statement_index);
+                let patterns: Option<Vec<_>> = statement.resource().and_then(|resources| {
+                    resources
+                        .iter()
+                        .map(|resource| match resource {
+                            Resource::Arn(pattern) if !pattern.to_string().contains('$') => Some(pattern),
+                            _ => None,
+                        })
+                        .collect()
+                });
+
+                match patterns {
+                    Some(patterns) => {
+                        for pattern in patterns {
+                            index
+                                .partitions
+                                .entry(pattern.partition_pattern())
+                                .entry(pattern.service_pattern())
+                                .entry(pattern.region_pattern())
+                                .entry(pattern.account_id_pattern())
+                                .insert(pattern.resource_pattern(), statement_ref);
+                        }
+                    }
+                    None => {
+                        index.fallback.insert(statement_ref);
+                    }
+                }
+            }
+        }
+
+        index
+    }
+
+    /// Returns the statements (other than those in the fallback bucket) with a pattern that may match the resource.
+    fn matching(&self, arn: &Arn) -> HashSet<StatementRef> {
+        let mut result = HashSet::new();
+        for services in self.partitions.matching(arn.partition()) {
+            for regions in services.matching(arn.service()) {
+                for accounts in regions.matching(arn.region()) {
+                    for trie in accounts.matching(arn.account_id()) {
+                        trie.matching(arn.resource(), &mut result);
+                    }
+                }
+            }
+        }
+        result
+    }
+
+    /// Returns the statements (other than those in the fallback bucket) with patterns that may match every resource
+    /// of the context. A statement with a `Resource` element only matches if every resource matches one of its
+    /// patterns, so this is the intersection of the statements that may match each resource.
+    fn candidates(&self, context: &Context) -> HashSet<StatementRef> {
+        let mut resources = context.resources().iter();
+        let mut result = match resources.next() {
+            Some(arn) => self.matching(arn),
+            // Without resources, only a "*" resource (in the fallback bucket) can match.
+            None => return HashSet::new(),
+        };
+
+        for arn in resources {
+            if result.is_empty() {
+                break;
+            }
+            let matching = self.matching(arn);
+            result.retain(|statement_ref| matching.contains(statement_ref));
+        }
+
+        result
+    }
+}
+
+/// A [PolicySet] with an index for evaluating requests without scanning every statement.
+///
+/// Statements are bucketed by service and by the literal prefix of their action patterns; statements using
+/// `NotAction` or the `"*"` action are kept in a fallback bucket that is checked for every request. Statements are
+/// also indexed by the ARN patterns in their `Resource` elements, keyed by the partition, service, region, and
+/// account id segments (exactly, or by literal prefix if the segment has wildcards) and then by a trie over the
+/// resource segment. Statements using `NotResource`, the `"*"` resource, or a pattern with a policy variable are
+/// kept in a resource fallback bucket.
+///
+/// Evaluation only considers the statements that are candidates for both the request's action and every one of its
+/// resources, and produces exactly the same decision and sources as [PolicySet::evaluate] and
+/// [PolicySet::evaluate_all].
+///
+/// The index is built once, when the indexed policy set is created; to change the policies, create a new indexed
+/// policy set.
+///
+/// # Example
+///
+/// ```
+/// # use scratchstack_aspen::{Context, Decision, IndexedPolicySet, PolicySet};
+/// # use std::str::FromStr;
+/// let policy_set = PolicySet::from_str(r#"[
+///     {"Source": {"Type": "EntityInline", "EntityArn": "arn:aws:iam::123456789012:user/alice",
+///                 "EntityId": "AIDAEXAMPLE", "PolicyName": "s3-read"},
+///      "Policy": {"Statement": [
+///          {"Effect": "Allow", "Action": "s3:Get*", "Resource": "*"},
+///          {"Effect": "Allow", "Action": "ec2:Describe*", "Resource": "*"}
+///      ]}}
+/// ]"#).unwrap();
+/// let indexed = IndexedPolicySet::new(policy_set);
+///
+/// let context = Context::from_str(r#"{"Service": "s3", "Api": "GetObject"}"#).unwrap();
+/// assert_eq!(indexed.candidates(&context), vec![(0, 0)]);
+/// assert_eq!(indexed.evaluate(&context).unwrap().0, Decision::Allow);
+/// ```
+#[derive(Clone, Debug)]
+pub struct IndexedPolicySet {
+    policy_set: PolicySet,
+    actions: ActionIndex,
+    resources: ResourceIndex,
+}
+
+impl IndexedPolicySet {
+    /// Creates an indexed policy set.
+    pub fn new(policy_set: PolicySet) -> Self {
+        let actions = ActionIndex::new(&policy_set);
+        let resources = ResourceIndex::new(&policy_set);
+        Self {
+            policy_set,
+            actions,
+            resources,
+        }
+    }
+
+    /// Returns the underlying policy set.
+    #[inline]
+    pub fn policy_set(&self) -> &PolicySet {
+        &self.policy_set
+    }
+
+    /// Returns the underlying policy set, discarding the index.
+    #[inline]
+    pub fn into_policy_set(self) -> PolicySet {
+        self.policy_set
+    }
+
+    /// Returns the statements that may match the request context, as (policy index, statement index) pairs in
+    /// order. Every other statement is certain not to match.
+    pub fn candidates(&self, context: &Context) -> Vec<(usize, usize)> {
+        let mut candidates = self.actions.candidates(context);
+        let resource_candidates = self.resources.candidates(context);
+        candidates.retain(|statement_ref| {
+            self.resources.fallback.contains(statement_ref) || resource_candidates.contains(statement_ref)
+        });
+        candidates
+    }
+
+    /// Evaluate the policy set; see [PolicySet::evaluate].
+    ///
+    /// # Errors
+    ///
+    /// If a candidate statement contains a malformed variable reference, the error is returned.
+    pub fn evaluate<'a>(&'a self, context: &'_ Context) -> Result<(Decision, Vec<&'a PolicySource>), AspenError> {
+        self.evaluate_core(context, false)
+    }
+
+    /// Evaluate all policies in the policy set; see [PolicySet::evaluate_all].
+    ///
+    /// # Errors
+    ///
+    /// If a candidate statement contains a malformed variable reference, the error is returned.
+    pub fn evaluate_all<'a>(&'a self, context: &'_ Context) -> Result<(Decision, Vec<&'a PolicySource>), AspenError> {
+        self.evaluate_core(context, true)
+    }
+
+    fn evaluate_core<'a>(
+        &'a self,
+        context: &'_ Context,
+        eval_all: bool,
+    ) -> Result<(Decision, Vec<&'a PolicySource>), AspenError> {
+        let (decision, indices) = self.evaluate_indices(context, eval_all)?;
+        let policies = self.policy_set.policies();
+        Ok((decision, indices.into_iter().map(|i| &policies[i].0).collect()))
+    }
+
+    /// Evaluate the policy set, returning the decision and the indices (into [PolicySet::policies]) of the policies
+    /// responsible for it.
+    pub(crate) fn evaluate_indices(
+        &self,
+        context: &Context,
+        eval_all: bool,
+    ) -> Result<(Decision, Vec<usize>), AspenError> {
+        let candidates = self.candidates(context);
+        let mut next = 0;
+
+        // Statements that are not candidates evaluate to DefaultDeny without error (their actions or resources cannot
+        // match, and patterns that could fail on a variable are always candidates), so the first matching candidate
+        // of each policy is the statement that decides it.
+        self.policy_set.combine_decisions(eval_all, |policy_index, policy| {
+            while next < candidates.len() && candidates[next].0 < policy_index {
+                next += 1;
+            }
+
+            while next < candidates.len() && candidates[next].0 == policy_index {
+                let statement = &policy.statement()[candidates[next].1];
+                next += 1;
+                match statement.evaluate(context, policy.version())? {
+                    Decision::DefaultDeny => (),
+                    decision => return Ok(decision),
+                }
+            }
+
+            Ok(Decision::DefaultDeny)
+        })
+    }
+}
+
+impl From<PolicySet> for IndexedPolicySet {
+    fn from(policy_set: PolicySet) -> Self {
+        Self::new(policy_set)
+    }
+}
+
+/// Returns the part of a glob pattern before its first wildcard, and whether it has a wildcard.
+fn literal_prefix(pattern: &str) -> (&str, bool) {
+    match pattern.find(['*', '?']) {
+        Some(end) => (&pattern[..end], true),
+        None => (pattern, false),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        crate::{Context, Decision, IndexedPolicySet, PolicySet},
+        pretty_assertions::assert_eq,
+        std::str::FromStr,
+    };
+
+    #[test_log::test]
+    fn test_matches_linear_scan() {
+        let policy_set = PolicySet::from_str(
+            r#"[
+                {"Source": {"Type": "EntityInline", "EntityArn": "arn:aws:iam::123456789012:user/alice",
+                            "EntityId": "AIDAEXAMPLE", "PolicyName": "mixed"},
+                 "Policy": {"Statement": [
+                     {"Sid": "GetObjects", "Effect": "Allow", "Action": ["s3:GetObject*", "s3:Get?ucket*"],
+                      "Resource": "arn:aws:s3:::bucket/*"},
+                     {"Sid": "NoDelete", "Effect": "Deny", "Action": "s3:Delete*", "Resource": "*"},
+                     {"Sid": "AllButIam", "Effect": "Allow", "NotAction": "iam:*", "Resource": "*",
+                      "Condition": {"Bool": {"aws:SecureTransport": "true"}}},
+                     {"Sid": "Ec2", "Effect": "Allow", "Action": ["ec2:Describe*", "ec2:RunInstances"], "Resource": "*"}
+                 ]}},
+                {"Source": {"Type": "EntityAttachedPolicy", "PolicyArn": "arn:aws:iam::aws:policy/Everything",
+                            "PolicyId": "ANPAEXAMPLE", "Version": "v1"},
+                 "Policy": {"Statement": [
+                     {"Effect": "Allow", "Action": ["sqs:SendMessage", "*"], "Resource": "*",
+                      "Condition": {"StringEquals": {"aws:RequestedRegion": "us-west-2"}}},
+                     {"Effect": "Deny", "Action": "ec2:Run*", "Resource": "*"}
+                 ]}},
+                {"Source": {"Type": "PermissionBoundary", "PolicyArn": "arn:aws:iam::123456789012:policy/b",
+                            "PolicyId": "ANPAEXAMPLE", "Version": "v1"},
+                 "Policy": {"Statement": {"Effect": "Allow", "Action": ["s3:*", "ec2:*", "sqs:*"], "Resource": "*"}}},
+                {"Source": {"Type": "Resource", "ResourceArn": "arn:aws:s3:::bucket"},
+                 "Policy": {"Statement": {"Effect": "Allow", "Principal": "*", "Action": "s3:GetObject",
+                                          "Resource": "arn:aws:s3:::bucket/*"}}}
+            ]"#,
+        )
+        .unwrap();
+        let indexed = IndexedPolicySet::new(policy_set.clone());
+
+        let requests = [
+            ("s3", "GetObject", "arn:aws:s3:::bucket/key", None),
+            ("s3", "GetObjectAcl", "arn:aws:s3:::bucket/key", None),
+            ("s3", "GetBucketPolicy", "arn:aws:s3:::bucket/key", None),
+            ("s3", "GetObject", "arn:aws:s3:::other/key", None),
+            ("s3", "DeleteObject", "arn:aws:s3:::bucket/key", None),
+            ("s3", "PutObject", "arn:aws:s3:::bucket/key", Some(("aws:SecureTransport", "true"))),
+            ("ec2", "DescribeInstances", "arn:aws:ec2:us-east-1:123456789012:instance/i-1", None),
+            ("ec2", "RunInstances", "arn:aws:ec2:us-east-1:123456789012:instance/i-1", None),
+            ("ec2", "Run", "arn:aws:ec2:us-east-1:123456789012:instance/i-1", None),
+            (
+                "sqs",
+                "SendMessage",
+                "arn:aws:sqs:us-west-2:123456789012:queue",
+                Some(("aws:RequestedRegion", "us-west-2")),
+            ),
+            ("iam", "CreateUser", "arn:aws:iam::123456789012:user/bob", Some(("aws:SecureTransport", "true"))),
+            (
+                "lambda",
+                "Invoke",
+                "arn:aws:lambda:us-east-1:123456789012:function:f",
+                Some(("aws:SecureTransport", "true")),
+            ),
+            ("S3", "GetObject", "arn:aws:s3:::bucket/key", None),
+            ("s3", "getobject", "arn:aws:s3:::bucket/key", None),
+        ];
+
+        let mut decisions = Vec::new();
+        for (service, api, resource, session) in requests {
+            let session_data = match session {
+                Some((key, value)) => format!(r#", "SessionData": {{"{key}": {{"String": "{value}"}}}}"#),
+                None => String::new(),
+            };
+            let context = Context::from_str(&format!(
+                r#"{{"Service": "{service}", "Api": "{api}", "Resources": ["{resource}"]{session_data}}}"#
+            ))
+            .unwrap();
+
+            assert_eq!(indexed.evaluate(&context).unwrap(), policy_set.evaluate(&context).unwrap(), "{service}:{api}");
+            assert_eq!(
+                indexed.evaluate_all(&context).unwrap(),
+                policy_set.evaluate_all(&context).unwrap(),
+                "{service}:{api}"
+            );
+            decisions.push(indexed.evaluate(&context).unwrap().0);
+        }
+        assert!(decisions.contains(&Decision::Allow));
+        assert!(decisions.contains(&Decision::Deny));
+        assert!(decisions.contains(&Decision::DefaultDeny));
+
+        // Only the fallback bucket (NotAction and "*") and the matching prefixes are candidates.
+        let context =
+            Context::from_str(r#"{"Service": "s3", "Api": "GetObjectAcl", "Resources": ["arn:aws:s3:::bucket/key"]}"#)
+                .unwrap();
+        assert_eq!(indexed.candidates(&context), vec![(0, 0), (0, 2), (1, 0), (2, 0), (3, 0)]);
+        let context = Context::from_str(r#"{"Service": "ec2", "Api": "RunInstances"}"#).unwrap();
+        assert_eq!(indexed.candidates(&context), vec![(0, 2), (0, 3), (1, 0), (1, 1), (2, 0)]);
+        let context = Context::from_str(r#"{"Service": "kms", "Api": "Decrypt"}"#).unwrap();
+        assert_eq!(indexed.candidates(&context), vec![(0, 2), (1, 0)]);
+
+        assert_eq!(indexed.into_policy_set(), policy_set);
+    }
+
+    #[test_log::test]
+    fn test_resource_index() {
+        let policy_set = PolicySet::from_str(
+            r#"[
+                {"Source": {"Type": "EntityInline", "EntityArn": "arn:aws:iam::123456789012:user/alice",
+                            "EntityId": "AIDAEXAMPLE", "PolicyName": "tenants"},
+                 "Policy": {"Statement": [
+                     {"Sid": "Tenant1", "Effect": "Allow", "Action": "s3:*",
+                      "Resource": ["arn:aws:s3:::tenant-1", "arn:aws:s3:::tenant-1/*"]},
+                     {"Sid": "Tenant2", "Effect": "Allow", "Action": "s3:*",
+                      "Resource": ["arn:aws:s3:::tenant-2", "arn:aws:s3:::tenant-2/*"]},
+                     {"Sid": "Tenant2Secrets", "Effect": "Deny", "Action": "s3:GetObject",
+                      "Resource": "arn:aws:s3:::tenant-2/secret?/*"},
+                     {"Sid": "Home", "Effect": "Allow", "Action": "s3:GetObject",
+                      "Resource": "arn:aws:s3:::home/${aws:username}/*"},
+                     {"Sid": "AnyRegion", "Effect": "Allow", "Action": "ec2:*",
+                      "Resource": "arn:aws:ec2:*:123456789012:instance/*"},
+                     {"Sid": "UsRegions", "Effect": "Deny", "Action": "ec2:TerminateInstances",
+                      "Resource": "arn:aws:ec2:us-*:123456789012:instance/i-0*"},
+                     {"Sid": "AnyPartition", "Effect": "Allow", "Action": "ec2:DescribeInstances",
+                      "Resource": "arn:*:ec2:us-east-1:999999999999:instance/*"},
+                     {"Sid": "NotTenant3", "Effect": "Allow", "Action": "s3:ListBucket",
+                      "NotResource": "arn:aws:s3:::tenant-3"}
+                 ]}}
+            ]"#,
+        )
+        .unwrap();
+        let indexed = IndexedPolicySet::new(policy_set.clone());
+
+        let requests: &[(&str, &str, &[&str])] = &[
+            ("s3", "GetObject", &["arn:aws:s3:::tenant-1/key"]),
+            ("s3", "GetObject", &["arn:aws:s3:::tenant-1"]),
+            ("s3", "GetObject", &["arn:aws:s3:::tenant-10/key"]),
+            ("s3", "GetObject", &["arn:aws:s3:::tenant-2/secrets/key"]),
+            ("s3", "CopyObject", &["arn:aws:s3:::tenant-1/a", "arn:aws:s3:::tenant-1/b"]),
+            ("s3", "CopyObject", &["arn:aws:s3:::tenant-1/a", "arn:aws:s3:::tenant-2/b"]),
+            ("s3", "GetObject", &["arn:aws:s3:::home/alice/key"]),
+            ("s3", "GetObject", &["arn:aws:s3:::home/bob/key"]),
+            ("s3", "GetObject", &[]),
+            ("s3", "ListBucket", &["arn:aws:s3:::tenant-3"]),
+            ("s3", "ListBucket", &["arn:aws:s3:::tenant-4"]),
+            ("ec2", "TerminateInstances", &["arn:aws:ec2:us-west-2:123456789012:instance/i-0123"]),
+            ("ec2", "TerminateInstances", &["arn:aws:ec2:eu-west-1:123456789012:instance/i-0123"]),
+            ("ec2", "TerminateInstances", &["arn:aws:ec2:us-west-2:111111111111:instance/i-0123"]),
+            ("ec2", "DescribeInstances", &["arn:aws-cn:ec2:us-east-1:999999999999:instance/i-1"]),
+        ];
+
+        for (service, api, resources) in requests {
+            let context = Context::from_str(&format!(
+                r#"{{"Service": "{service}", "Api": "{api}", "Resources": {resources:?},
+                     "SessionData": {{"aws:username": {{"String": "alice"}}}}}}"#
+            ))
+            .unwrap();
+
+            assert_eq!(
+                indexed.evaluate(&context).unwrap(),
+                policy_set.evaluate(&context).unwrap(),
+                "{api} {resources:?}"
+            );
+            assert_eq!(
+                indexed.evaluate_all(&context).unwrap(),
+                policy_set.evaluate_all(&context).unwrap(),
+                "{api} {resources:?}"
+            );
+        }
+
+        let candidates = |api: &str, resources: &[&str]| {
+            let service = resources[0].split(':').nth(2).unwrap();
+            let context = Context::from_str(&format!(
+                r#"{{"Service": "{service}", "Api": "{api}", "Resources": {resources:?}}}"#
+            ))
+            .unwrap();
+            indexed.candidates(&context).into_iter().map(|(_, statement)| statement).collect::<Vec<_>>()
+        };
+
+        // Exact patterns and prefixes only match at the right place; variables are always candidates.
+        assert_eq!(candidates("GetObject", &["arn:aws:s3:::tenant-1"]), vec![0, 3]);
+        assert_eq!(candidates("GetObject", &["arn:aws:s3:::tenant-10"]), vec![3]);
+        assert_eq!(candidates("GetObject", &["arn:aws:s3:::tenant-2/secrets/x"]), vec![1, 2, 3]);
+        assert_eq!(
+            candidates("PutObject", &["arn:aws:s3:::tenant-1/a", "arn:aws:s3:::tenant-2/b"]),
+            Vec::<usize>::new()
+        );
+        assert_eq!(candidates("ListBucket", &["arn:aws:s3:::tenant-2"]), vec![1, 7]);
+
+        // Wildcards in a segment match by the literal prefix of the segment.
+        assert_eq!(
+            candidates("TerminateInstances", &["arn:aws:ec2:us-west-2:123456789012:instance/i-0123"]),
+            vec![4, 5]
+        );
+        assert_eq!(candidates("TerminateInstances", &["arn:aws:ec2:eu-west-1:123456789012:instance/i-0123"]), vec![4]);
+        assert_eq!(candidates("DescribeInstances", &["arn:aws-cn:ec2:us-east-1:999999999999:instance/i-1"]), vec![6]);
+    }
+}
@@ -6,31 +6,75 @@
 //! AWS IAM policy document (Aspen) representation and evaluation.
 
 pub(crate) mod action;
+pub(crate) mod analysis;
+pub(crate) mod batch;
+pub(crate) mod cache;
+pub(crate) mod catalog;
+pub(crate) mod cedar;
+pub(crate) mod cloudformation;
+pub(crate) mod cloudtrail;
 pub(crate) mod condition;
 pub(crate) mod effect;
 pub(crate) mod error;
 pub(crate) mod eval;
+pub(crate) mod generate;
+pub(crate) mod index;
+pub(crate) mod parse;
 pub(crate) mod policy;
 pub(crate) mod policyset;
 pub(crate) mod principal;
+pub(crate) mod rego;
 pub(crate) mod resource;
+#[cfg(feature = "server")]
+pub(crate) mod server;
 pub(crate) mod statement;
+pub(crate) mod store;
+pub(crate) mod summary;
+pub(crate) mod template;
+pub(crate) mod terraform;
+pub(crate) mod testsuite;
 
 #[macro_use]
 pub(crate) mod serutil;
 
 pub use {
     action::{Action, ActionList},
+    analysis::{
+        AccessChange, AccessFinding, AccessRestriction, Counterexample, DecisionChange, EscalationFinding,
+        EscalationMethod, EscalationStep, Evidence, ExternalAccess, ExternalAccessKind, IamModel, LintFinding,
+        LintKind, LintSeverity, PolicyDiff, PolicyLint, StatementUsage, UsageReport, Verdict,
+    },
+    batch::{
+        EvaluationMatrix, MatrixCell, MatrixCells, MatrixIndex, MatrixRequest, MatrixRequestBuilder,
+        MatrixRequestBuilderError, MatrixSubject,
+    },
+    cache::DecisionCache,
+    catalog::ActionCatalog,
+    cedar::{CedarDiagnostic, CedarDiagnosticKind, CedarResolution, CedarTranslation, CEDAR_NAMESPACE},
+    cloudformation::{CloudFormationTemplate, TemplatePolicy, TemplatePolicyKind},
+    cloudtrail::{CloudTrailEvent, Replay},
     condition::{op as condop, Condition, ConditionMap, ConditionOp, Variant as ConditionVariant},
     effect::Effect,
     error::AspenError,
-    eval::{Context, ContextBuilder, Decision},
+    eval::{Context, ContextBuilder, Decision, ErrorPolicy, StatementError},
+    generate::{AccessRecord, PolicyGenerator},
+    index::IndexedPolicySet,
+    parse::{ParseErrorKind, PolicyParseError},
     policy::{Policy, PolicyBuilder, PolicyBuilderError, PolicyVersion},
-    policyset::{PolicySet, PolicySource},
+    policyset::{DecisionReason, EvaluationWithErrors, PolicySet, PolicySource, STANDALONE_ENTITY_ARN},
     principal::{
         AwsPrincipal, Principal, SpecifiedPrincipal, SpecifiedPrincipalBuilder, SpecifiedPrincipalBuilderError,
     },
+    rego::RegoModule,
     resource::{Resource, ResourceArn, ResourceList},
     serutil::{MapList, StringLikeList},
     statement::{Statement, StatementBuilder, StatementBuilderError, StatementList},
+    store::{ManagedPolicy, PolicySnapshot, PolicyStore, PolicyWatcher, MANAGED_POLICY_DIR},
+    summary::{ActionSummary, EffectiveAccess, Grant, PermissionsSummary, ResourceScope, ServiceSummary},
+    template::PolicyTemplate,
+    terraform::{PlanPolicy, TerraformPlan},
+    testsuite::{TestCase, TestReport, TestResult, TestSuite, TEST_SUITE_SUFFIX},
 };
+
+#[cfg(feature = "server")]
+pub use server::{DecisionRequest, DecisionResponse, DecisionService, MAX_CONNECTIONS};
@@ -0,0 +1,856 @@
+use {
+    crate::{
+        policy::POLICY_FIELDS, principal::PRINCIPAL_FIELDS, statement::STATEMENT_FIELDS, Action, AwsPrincipal,
+        ConditionOp, Policy, PolicyVersion, Resource,
+    },
+    serde::Serialize,
+    serde_json::{Map, Value},
+    std::{
+        collections::{HashMap, HashSet},
+        error::Error,
+        fmt::{Display, Formatter, Result as FmtResult},
+        str::FromStr,
+    },
+};
+
+/// The kind of problem found while parsing a policy.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, Serialize)]
+pub enum ParseErrorKind {
+    /// The document is not well-formed JSON.
+    Syntax,
+
+    /// A value has the wrong JSON type, e.g. a number where a string or list of strings is expected.
+    InvalidType,
+
+    /// A required field is missing.
+    MissingField,
+
+    /// A field appears more than once in the same object.
+    DuplicateField,
+
+    /// A field is not recognized.
+    UnknownField,
+
+    /// Two mutually exclusive fields (such as `Action` and `NotAction`) are both present.
+    ConflictingFields,
+
+    /// The policy `Version` is not recognized.
+    InvalidVersion,
+
+    /// A statement `Effect` is not `Allow` or `Deny`.
+    InvalidEffect,
+
+    /// An action is malformed.
+    InvalidAction,
+
+    /// A resource is malformed.
+    InvalidResource,
+
+    /// A principal is malformed.
+    InvalidPrincipal,
+
+    /// A condition operator is not recognized.
+    InvalidConditionOperator,
+
+    /// The policy was rejected for a reason not covered by another kind.
+    Invalid,
+}
+
+impl Display for ParseErrorKind {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        let s = match self {
+            Self::Syntax => "Syntax",
+            Self::InvalidType => "InvalidType",
+            Self::MissingField => "MissingField",
+            Self::DuplicateField => "DuplicateField",
+            Self::UnknownField => "UnknownField",
+            Self::ConflictingFields => "ConflictingFields",
+            Self::InvalidVersion => "InvalidVersion",
+            Self::InvalidEffect => "InvalidEffect",
+            Self::InvalidAction => "InvalidAction",
+            Self::InvalidResource => "InvalidResource",
+            Self::InvalidPrincipal => "InvalidPrincipal",
+            Self::InvalidConditionOperator => "InvalidConditionOperator",
+            Self::Invalid => "Invalid",
+        };
+        f.write_str(s)
+    }
+}
+
+/// A problem found while parsing a policy with [Policy::parse], located by JSON pointer and by line and column.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct PolicyParseError {
+    kind: ParseErrorKind,
+    pointer: String,
+    line: usize,
+    column: usize,
+    value: Option<String>,
+    message: String,
+}
+
+impl PolicyParseError {
+    /// Returns the kind of problem.
+    #[inline]
+    pub fn kind(&self) -> ParseErrorKind {
+        self.kind
+    }
+
+    /// Returns the [RFC 6901](https://www.rfc-editor.org/rfc/rfc6901) JSON pointer to the offending element, e.g.
+    /// `/Statement/3/Condition/StringLike/aws:username`. This is empty for the document itself and for syntax
+    /// errors.
+    #[inline]
+    pub fn pointer(&self) -> &str {
+        &self.pointer
+    }
+
+    /// Returns the 1-based line of the offending element. For a missing field, this is the line of the object that
+    /// should contain it.
+    #[inline]
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    /// Returns the 1-based column of the offending element.
+    #[inline]
+    pub fn column(&self) -> usize {
+        self.column
+    }
+
+    /// Returns the offending value: the string itself for strings, or the JSON text for other values. This is `None`
+    /// for syntax errors and missing fields.
+    #[inline]
+    pub fn value(&self) -> Option<&str> {
+        self.value.as_deref()
+    }
+
+    /// Returns a description of the problem.
+    #[inline]
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+impl Display for PolicyParseError {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        if !self.pointer.is_empty() {
+            write!(f, "{}: ", self.pointer)?;
+        }
+        write!(f, "{} at line {} column {}", self.message, self.line, self.column)
+    }
+}
+
+impl Error for PolicyParseError {}
+
+/// Parses a policy, collecting every problem found instead of stopping at the first.
+pub(crate) fn parse_policy(json: &str) -> Result<Policy, Vec<PolicyParseError>> {
+    let document: Value = serde_json::from_str(json).map_err(|e| {
+        vec![PolicyParseError {
+            kind: ParseErrorKind::Syntax,
+            pointer: String::new(),
+            line: e.line(),
+            column: e.column(),
+            value: None,
+            message: strip_location(&e),
+        }]
+    })?;
+
+    let mut checker = Checker::new(json);
+    checker.policy(&document);
+    if !checker.errors.is_empty() {
+        checker.errors.sort_by_key(|e| (e.line, e.column));
+        return Err(checker.errors);
+    }
+
+    // The checks above mirror the deserializer; this catches anything they miss.
+    Policy::from_str(json).map_err(|e| {
+        vec![PolicyParseError {
+            kind: ParseErrorKind::Invalid,
+            pointer: String::new(),
+            line: e.line(),
+            column: e.column(),
+            value: None,
+            message: strip_location(&e),
+        }]
+    })
+}
+
+/// Finds keys that appear more than once in the same object of a well-formed JSON document. Parsing into a
+/// [Value] silently keeps the last of them.
+pub(crate) fn duplicate_keys(json: &str) -> Vec<PolicyParseError> {
+    let mut scanner = Scanner::new(json);
+    scanner.value(String::new());
+    scanner.duplicates.into_iter().map(|(pointer, (line, column))| duplicate_field(pointer, line, column)).collect()
+}
+
+/// Returns a [ParseErrorKind::DuplicateField] error for the member at `pointer`.
+fn duplicate_field(pointer: String, line: usize, column: usize) -> PolicyParseError {
+    let key = pointer.rsplit('/').next().unwrap_or_default().replace("~1", "/").replace("~0", "~");
+    PolicyParseError {
+        kind: ParseErrorKind::DuplicateField,
+        pointer,
+        line,
+        column,
+        value: None,
+        message: format!("duplicate field `{key}`"),
+    }
+}
+
+/// Returns the message of a `serde_json` error without the location suffix.
+pub(crate) fn strip_location(e: &serde_json::Error) -> String {
+    let message = e.to_string();
+    match message.rfind(" at line ") {
+        Some(i) if e.line() > 0 => message[..i].to_string(),
+        _ => message,
+    }
+}
+
+/// Escapes a key for use in a JSON pointer.
+fn escape(key: &str) -> String {
+    key.replace('~', "~0").replace('/', "~1")
+}
+
+/// The text form of an offending value.
+fn value_text(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        _ => value.to_string(),
+    }
+}
+
+/// The location of an element in the document text.
+#[derive(Clone, Copy, Debug, Default)]
+struct Location {
+    /// The position of the key, for object members.
+    key: Option<(usize, usize)>,
+
+    /// The position of the value.
+    value: (usize, usize),
+}
+
+/// Validates a parsed policy document, using the document text to locate problems.
+struct Checker {
+    locations: HashMap<String, Location>,
+    duplicates: Vec<(String, (usize, usize))>,
+    errors: Vec<PolicyParseError>,
+}
+
+impl Checker {
+    fn new(json: &str) -> Self {
+        let mut scanner = Scanner::new(json);
+        scanner.value(String::new());
+        Self {
+            locations: scanner.locations,
+            duplicates: scanner.duplicates,
+            errors: Vec::new(),
+        }
+    }
+
+    /// Records an error at the value (or, for `at_key`, the key) identified by a pointer.
+    fn error(&mut self, kind: ParseErrorKind, pointer: &str, at_key: bool, value: Option<&Value>, message: String) {
+        let location = self.locations.get(pointer).copied().unwrap_or_default();
+        let (line, column) = match at_key {
+            true => location.key.unwrap_or(location.value),
+            false => location.value,
+        };
+        self.errors.push(PolicyParseError {
+            kind,
+            pointer: pointer.to_string(),
+            line,
+            column,
+            value: value.map(value_text),
+            message,
+        });
+    }
+
+    /// Checks the members of an object against the allowed fields, reporting unknown and duplicate fields.
+    fn fields(&mut self, pointer: &str, map: &Map<String, Value>, allowed: &[&str]) {
+        for (key, value) in map {
+            if !allowed.contains(&key.as_str()) {
+                let expected = allowed.iter().map(|f| format!("`{f}`")).collect::<Vec<_>>().join(", ");
+                let message = format!("unknown field `{key}`, expected one of {expected}");
+                self.error(
+                    ParseErrorKind::UnknownField,
+                    &format!("{pointer}/{}", escape(key)),
+                    true,
+                    Some(value),
+                    message,
+                );
+            }
+        }
+
+        self.duplicate_fields(pointer);
+    }
+
+    /// Reports fields that appear more than once in the object at `pointer`.
+    fn duplicate_fields(&mut self, pointer: &str) {
+        let prefix = format!("{pointer}/");
+        let duplicates: Vec<_> = self
+            .duplicates
+            .iter()
+            .filter(|(p, _)| p.strip_prefix(&prefix).map(|rest| !rest.contains('/')).unwrap_or(false))
+            .cloned()
+            .collect();
+        for (duplicate, (line, column)) in duplicates {
+            self.errors.push(duplicate_field(duplicate, line, column));
+        }
+    }
+
+    fn policy(&mut self, document: &Value) {
+        let Some(map) = document.as_object() else {
+            self.error(ParseErrorKind::InvalidType, "", false, Some(document), "expected a policy object".to_string());
+            return;
+        };
+
+        self.fields("", map, &POLICY_FIELDS);
+
+        match map.get("Version") {
+            Some(Value::String(version)) => {
+                if let Err(e) = PolicyVersion::from_str(version) {
+                    self.error(ParseErrorKind::InvalidVersion, "/Version", false, map.get("Version"), e.to_string());
+                }
+            }
+            Some(version) => self.expected_string("/Version", version),
+            None => (),
+        }
+
+        if let Some(id) = map.get("Id") {
+            if !id.is_string() {
+                self.expected_string("/Id", id);
+            }
+        }
+
+        match map.get("Statement") {
+            Some(Value::Array(statements)) => {
+                for (i, statement) in statements.iter().enumerate() {
+                    self.statement(&format!("/Statement/{i}"), statement);
+                }
+            }
+            Some(statement) => self.statement("/Statement", statement),
+            None => self.error(ParseErrorKind::MissingField, "", false, None, "missing field `Statement`".to_string()),
+        }
+    }
+
+    fn statement(&mut self, pointer: &str, statement: &Value) {
+        let Some(map) = statement.as_object() else {
+            let message = "expected a statement object".to_string();
+            self.error(ParseErrorKind::InvalidType, pointer, false, Some(statement), message);
+            return;
+        };
+
+        self.fields(pointer, map, &STATEMENT_FIELDS);
+
+        if let Some(sid) = map.get("Sid") {
+            if !sid.is_string() {
+                self.expected_string(&format!("{pointer}/Sid"), sid);
+            }
+        }
+
+        match map.get("Effect") {
+            Some(Value::String(effect)) if effect == "Allow" || effect == "Deny" => (),
+            Some(effect @ Value::String(_)) => {
+                let message = format!("invalid effect {}, expected `Allow` or `Deny`", value_text(effect));
+                self.error(ParseErrorKind::InvalidEffect, &format!("{pointer}/Effect"), false, Some(effect), message);
+            }
+            Some(effect) => self.expected_string(&format!("{pointer}/Effect"), effect),
+            None => {
+                self.error(ParseErrorKind::MissingField, pointer, false, None, "missing field `Effect`".to_string())
+            }
+        }
+
+        self.exclusive(pointer, map, "Action", "NotAction", true);
+        self.exclusive(pointer, map, "Resource", "NotResource", true);
+        self.exclusive(pointer, map, "Principal", "NotPrincipal", false);
+
+        for field in ["Action", "NotAction"] {
+            self.string_list(pointer, map, field, ParseErrorKind::InvalidAction, |s| {
+                Action::from_str(s).map(|_| ()).map_err(|e| e.to_string())
+            });
+        }
+
+        for field in ["Resource", "NotResource"] {
+            self.string_list(pointer, map, field, ParseErrorKind::InvalidResource, |s| {
+                Resource::from_str(s).map(|_| ()).map_err(|e| e.to_string())
+            });
+        }
+
+        for field in ["Principal", "NotPrincipal"] {
+            if let Some(principal) = map.get(field) {
+                self.principal(&format!("{pointer}/{field}"), principal);
+            }
+        }
+
+        if let Some(condition) = map.get("Condition") {
+            self.condition(&format!("{pointer}/Condition"), condition);
+        }
+    }
+
+    /// Checks that exactly one (if `required`) or at most one of two fields is present.
+    fn exclusive(&mut self, pointer: &str, map: &Map<String, Value>, field: &str, not_field: &str, required: bool) {
+        match (map.get(field), map.get(not_field)) {
+            (Some(_), Some(value)) => {
+                let message = format!("{field} and {not_field} cannot both be set");
+                let not_pointer = format!("{pointer}/{not_field}");
+                self.error(ParseErrorKind::ConflictingFields, &not_pointer, true, Some(value), message);
+            }
+            (None, None) if required => {
+                let message = format!("either {field} or {not_field} must be set");
+                self.error(ParseErrorKind::MissingField, pointer, false, None, message);
+            }
+            _ => (),
+        }
+    }
+
+    /// Checks a field that is a string or list of strings, validating each string.
+    fn string_list<F: Fn(&str) -> Result<(), String>>(
+        &mut self,
+        pointer: &str,
+        map: &Map<String, Value>,
+        field: &str,
+        kind: ParseErrorKind,
+        check: F,
+    ) {
+        let Some(value) = map.get(field) else {
+            return;
+        };
+        let pointer = format!("{pointer}/{}", escape(field));
+
+        let elements: Vec<(String, &Value)> = match value {
+            Value::String(_) => vec![(pointer, value)],
+            Value::Array(elements) => {
+                elements.iter().enumerate().map(|(i, element)| (format!("{pointer}/{i}"), element)).collect()
+            }
+            _ => {
+                self.expected_string_list(&pointer, value);
+                return;
+            }
+        };
+
+        for (pointer, element) in elements {
+            match element {
+                Value::String(s) => {
+                    if let Err(message) = check(s) {
+                        self.error(kind, &pointer, false, Some(element), message);
+                    }
+                }
+                _ => self.expected_string(&pointer, element),
+            }
+        }
+    }
+
+    fn principal(&mut self, pointer: &str, principal: &Value) {
+        match principal {
+            Value::String(s) if s == "*" => (),
+            Value::Object(map) => {
+                // Other principal types are ignored by the deserializer, so only duplicates are reported.
+                self.duplicate_fields(pointer);
+                self.string_list(pointer, map, "AWS", ParseErrorKind::InvalidPrincipal, |s| {
+                    AwsPrincipal::from_str(s).map(|_| ()).map_err(|e| e.to_string())
+                });
+                for field in &PRINCIPAL_FIELDS[1..] {
+                    self.string_list(pointer, map, field, ParseErrorKind::InvalidPrincipal, |_| Ok(()));
+                }
+            }
+            _ => {
+                let message = "expected a map of principal types to values or \"*\"".to_string();
+                self.error(ParseErrorKind::InvalidPrincipal, pointer, false, Some(principal), message);
+            }
+        }
+    }
+
+    fn condition(&mut self, pointer: &str, condition: &Value) {
+        let Some(operators) = condition.as_object() else {
+            let message = "expected a map of condition operators".to_string();
+            self.error(ParseErrorKind::InvalidType, pointer, false, Some(condition), message);
+            return;
+        };
+
+        for (operator, keys) in operators {
+            let operator_pointer = format!("{pointer}/{}", escape(operator));
+            if let Err(e) = ConditionOp::from_str(operator) {
+                let value = Value::String(operator.clone());
+                self.error(
+                    ParseErrorKind::InvalidConditionOperator,
+                    &operator_pointer,
+                    true,
+                    Some(&value),
+                    e.to_string(),
+                );
+            }
+
+            let Some(keys) = keys.as_object() else {
+                let message = "expected a map of condition keys to values".to_string();
+                self.error(ParseErrorKind::InvalidType, &operator_pointer, false, Some(keys), message);
+                continue;
+            };
+
+            for key in keys.keys() {
+                self.string_list(&operator_pointer, keys, key, ParseErrorKind::InvalidType, |_| Ok(()));
+            }
+        }
+    }
+
+    fn expected_string(&mut self, pointer: &str, value: &Value) {
+        let message = format!("invalid type: {}, expected a string", type_name(value));
+        self.error(ParseErrorKind::InvalidType, pointer, false, Some(value), message);
+    }
+
+    fn expected_string_list(&mut self, pointer: &str, value: &Value) {
+        let message = format!("invalid type: {}, expected a string or list of strings", type_name(value));
+        self.error(ParseErrorKind::InvalidType, pointer, false, Some(value), message);
+    }
+}
+
+/// Describes the JSON type of a value.
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "list",
+        Value::Object(_) => "map",
+    }
+}
+
+/// Records the position of every element of a well-formed JSON document by JSON pointer.
+struct Scanner<'a> {
+    text: &'a str,
+    pos: usize,
+    line: usize,
+    line_start: usize,
+    locations: HashMap<String, Location>,
+    duplicates: Vec<(String, (usize, usize))>,
+}
+
+impl<'a> Scanner<'a> {
+    fn new(text: &'a str) -> Self {
+        Self {
+            text,
+            pos: 0,
+            line: 1,
+            line_start: 0,
+            locations: HashMap::new(),
+            duplicates: Vec::new(),
+        }
+    }
+
+    /// Returns the 1-based line and column of the current position.
+    fn position(&self) -> (usize, usize) {
+        (self.line, self.pos - self.line_start + 1)
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.text.as_bytes().get(self.pos).copied()
+    }
+
+    fn advance(&mut self) {
+        if self.peek() == Some(b'\n') {
+            self.line += 1;
+            self.line_start = self.pos + 1;
+        }
+        self.pos += 1;
+    }
+
+    fn skip_whitespace(&mut self) {
+        while matches!(self.peek(), Some(b' ' | b'\t' | b'\n' | b'\r')) {
+            self.advance();
+        }
+    }
+
+    /// Scans a value, recording its position under `pointer`.
+    fn value(&mut self, pointer: String) {
+        self.skip_whitespace();
+        let position = self.position();
+        self.locations.entry(pointer.clone()).or_default().value = position;
+
+        match self.peek() {
+            Some(b'{') => {
+                self.advance();
+                let mut seen = HashSet::new();
+                loop {
+                    self.skip_whitespace();
+                    match self.peek() {
+                        Some(b'}') | None => break,
+                        Some(b',') => self.advance(),
+                        _ => {
+                            let key_position = self.position();
+                            let key = self.string();
+                            let child = format!("{pointer}/{}", escape(&key));
+                            if !seen.insert(key) {
+                                self.duplicates.push((child.clone(), key_position));
+                            }
+                            self.locations.insert(
+                                child.clone(),
+                                Location {
+                                    key: Some(key_position),
+                                    value: key_position,
+                                },
+                            );
+                            self.skip_whitespace();
+                            self.advance(); // The colon.
+                            self.value(child);
+                        }
+                    }
+                }
+                self.advance();
+            }
+            Some(b'[') => {
+                self.advance();
+                let mut index = 0;
+                loop {
+                    self.skip_whitespace();
+                    match self.peek() {
+                        Some(b']') | None => break,
+                        Some(b',') => self.advance(),
+                        _ => {
+                            self.value(format!("{pointer}/{index}"));
+                            index += 1;
+                        }
+                    }
+                }
+                self.advance();
+            }
+            Some(b'"') => {
+                self.string();
+            }
+            _ => {
+                while !matches!(self.peek(), None | Some(b',' | b'}' | b']' | b' ' | b'\t' | b'\n' | b'\r')) {
+                    self.advance();
+                }
+            }
+        }
+    }
+
+    /// Scans a string, returning its decoded value.
+    fn string(&mut self) -> String {
+        let start = self.pos;
+        self.advance();
+        while let Some(c) = self.peek() {
+            self.advance();
+            match c {
+                b'\\' => self.advance(),
+                b'"' => break,
+                _ => (),
+            }
+        }
+        serde_json::from_str(&self.text[start..self.pos]).unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        crate::{ParseErrorKind, Policy},
+        indoc::indoc,
+        pretty_assertions::assert_eq,
+        std::str::FromStr,
+    };
+
+    fn summary(json: &str) -> Vec<(ParseErrorKind, String, usize, usize, Option<String>)> {
+        Policy::parse(json)
+            .unwrap_err()
+            .into_iter()
+            .map(|e| (e.kind(), e.pointer().to_string(), e.line(), e.column(), e.value().map(str::to_string)))
+            .collect()
+    }
+
+    #[test_log::test]
+    fn test_collects_errors() {
+        let policy = indoc! { r#"
+            {
+                "Version": "2012-10-17",
+                "Statement": [
+                    {
+                        "Sid": "ok",
+                        "Effect": "Allow",
+                        "Action": "s3:GetObject",
+                        "Resource": "*"
+                    },
+                    {
+                        "Sid": "first", "Effect": "Permit",
+                        "Action": ["s3:GetObject", "s3"],
+                        "NotAction": "s3:PutObject",
+                        "Resource": "bucket",
+                        "Principal": {"AWS": ["123456789012", "alice"]},
+                        "Condition": {
+                            "StringLike": {"aws:username": 3, "aws:ResourceTag/team": "a"},
+                            "StringLikeish": {"aws:username": "bob"}
+                        },
+                        "Extra": true,
+                        "Sid": "dup"
+                    }
+                ]
+            }"# };
+
+        let errors = Policy::parse(policy).unwrap_err();
+        assert_eq!(
+            errors.iter().map(|e| e.to_string()).collect::<Vec<_>>(),
+            vec![
+                "/Statement/1/Effect: invalid effect Permit, expected `Allow` or `Deny` at line 11 column 39",
+                "/Statement/1/Action/1: Invalid action: s3 at line 12 column 40",
+                "/Statement/1/NotAction: Action and NotAction cannot both be set at line 13 column 13",
+                "/Statement/1/Resource: Invalid resource: bucket at line 14 column 25",
+                "/Statement/1/Principal/AWS/1: Invalid principal: alice at line 15 column 51",
+                "/Statement/1/Condition/StringLike/aws:username: invalid type: number, expected a string or list of strings at line 17 column 48",
+                "/Statement/1/Condition/StringLikeish: Invalid condition operator: StringLikeish at line 18 column 17",
+                "/Statement/1/Extra: unknown field `Extra`, expected one of `Sid`, `Effect`, `Action`, `NotAction`, \
+                 `Resource`, `NotResource`, `Principal`, `NotPrincipal`, `Condition` at line 20 column 13",
+                "/Statement/1/Sid: duplicate field `Sid` at line 21 column 13",
+            ]
+        );
+        assert_eq!(errors[0].kind(), ParseErrorKind::InvalidEffect);
+        assert_eq!(errors[0].value(), Some("Permit"));
+        assert_eq!(errors[0].message(), "invalid effect Permit, expected `Allow` or `Deny`");
+        assert_eq!(errors[5].value(), Some("3"));
+        assert_eq!(errors[8].kind(), ParseErrorKind::DuplicateField);
+        assert_eq!(ParseErrorKind::InvalidConditionOperator.to_string(), "InvalidConditionOperator");
+    }
+
+    #[test_log::test]
+    fn test_policy_errors() {
+        assert_eq!(summary("{\n  \"Version\": 1,\n"), vec![(ParseErrorKind::Syntax, String::new(), 3, 0, None)]);
+        assert_eq!(summary("[]"), vec![(ParseErrorKind::InvalidType, String::new(), 1, 1, Some("[]".to_string()))]);
+
+        let policy = indoc! { r#"
+            {
+                "Version": "2012-10-18",
+                "Id": 4,
+                "Version": "2012-10-17",
+                "Policy": {}
+            }"# };
+        assert_eq!(
+            summary(policy),
+            vec![
+                (ParseErrorKind::MissingField, String::new(), 1, 1, None),
+                (ParseErrorKind::InvalidType, "/Id".to_string(), 3, 11, Some("4".to_string())),
+                (ParseErrorKind::DuplicateField, "/Version".to_string(), 4, 5, None),
+                (ParseErrorKind::UnknownField, "/Policy".to_string(), 5, 5, Some("{}".to_string())),
+            ]
+        );
+    }
+
+    #[test_log::test]
+    fn test_statement_errors() {
+        let errors = summary(r#"{"Statement": [3, {"Principal": "alice", "NotPrincipal": "*", "Condition": []}]}"#);
+        assert_eq!(
+            errors,
+            vec![
+                (ParseErrorKind::InvalidType, "/Statement/0".to_string(), 1, 16, Some("3".to_string())),
+                (ParseErrorKind::MissingField, "/Statement/1".to_string(), 1, 19, None),
+                (ParseErrorKind::MissingField, "/Statement/1".to_string(), 1, 19, None),
+                (ParseErrorKind::MissingField, "/Statement/1".to_string(), 1, 19, None),
+                (
+                    ParseErrorKind::InvalidPrincipal,
+                    "/Statement/1/Principal".to_string(),
+                    1,
+                    33,
+                    Some("alice".to_string())
+                ),
+                (
+                    ParseErrorKind::ConflictingFields,
+                    "/Statement/1/NotPrincipal".to_string(),
+                    1,
+                    42,
+                    Some("*".to_string())
+                ),
+                (ParseErrorKind::InvalidType, "/Statement/1/Condition".to_string(), 1, 76, Some("[]".to_string())),
+            ]
+        );
+
+        // Keys containing `/` and `~` are escaped in pointers.
+        let errors = Policy::parse(
+            r#"{"Statement": {"Effect": "Allow", "Action": "*", "Resource": "*",
+                "Condition": {"StringEquals": {"aws:ResourceTag/a~b": {}}}}}"#,
+        )
+        .unwrap_err();
+        assert_eq!(errors[0].pointer(), "/Statement/Condition/StringEquals/aws:ResourceTag~1a~0b");
+        assert_eq!(errors[0].message(), "invalid type: map, expected a string or list of strings");
+
+        let policy =
+            Policy::parse(r#"{"Statement": {"Effect": "Deny", "NotAction": "iam:*", "NotResource": []}}"#).unwrap();
+        assert_eq!(policy.statement().len(), 1);
+    }
+
+    /// Policy::parse reimplements the deserializer's checks to locate errors. Every rule is exercised here so that a
+    /// check that drifts from the deserializer shows up as a disagreement, or as an unlocated `Invalid` error.
+    #[test_log::test]
+    fn test_agrees_with_deserializer() {
+        const STATEMENT: &str = r#""Effect": "Allow", "Action": "s3:GetObject", "Resource": "*""#;
+        let statements = [
+            STATEMENT.to_string(),
+            r#""Sid": "s", "Effect": "Deny", "NotAction": "*", "NotResource": ["*"]"#.to_string(),
+            format!(r#""Sid": 1, {STATEMENT}"#),
+            format!(r#""Sid": "a", "Sid": "b", {STATEMENT}"#),
+            format!(r#""Extra": 1, {STATEMENT}"#),
+            r#""Action": "*", "Resource": "*""#.to_string(),
+            r#""Effect": "Permit", "Action": "*", "Resource": "*""#.to_string(),
+            r#""Effect": ["Allow"], "Action": "*", "Resource": "*""#.to_string(),
+            r#""Effect": "Allow", "Resource": "*""#.to_string(),
+            r#""Effect": "Allow", "Action": "*""#.to_string(),
+            r#""Effect": "Allow", "Action": "*", "NotAction": "*", "Resource": "*""#.to_string(),
+            r#""Effect": "Allow", "Action": "*", "Resource": "*", "NotResource": "*""#.to_string(),
+            r#""Effect": "Allow", "Action": "s3", "Resource": "*""#.to_string(),
+            r#""Effect": "Allow", "Action": [], "Resource": "*""#.to_string(),
+            r#""Effect": "Allow", "Action": ["*", 1], "Resource": "*""#.to_string(),
+            r#""Effect": "Allow", "Action": {}, "Resource": "*""#.to_string(),
+            r#""Effect": "Allow", "Action": "*", "Resource": "bucket""#.to_string(),
+            r#""Effect": "Allow", "Action": "*", "Resource": "arn:aws:s3:::${oops""#.to_string(),
+            format!(r#"{STATEMENT}, "Principal": "*""#),
+            format!(r#"{STATEMENT}, "Principal": "alice""#),
+            format!(r#"{STATEMENT}, "Principal": 1"#),
+            format!(r#"{STATEMENT}, "Principal": {{"AWS": "123456789012"}}"#),
+            format!(r#"{STATEMENT}, "Principal": {{"AWS": ["arn:aws:iam::123456789012:root", "alice"]}}"#),
+            format!(r#"{STATEMENT}, "Principal": {{"Service": "ec2.amazonaws.com", "Service": "s3.amazonaws.com"}}"#),
+            format!(r#"{STATEMENT}, "Principal": {{"Federated": 1}}"#),
+            format!(r#"{STATEMENT}, "Principal": {{"CanonicalUser": ["abcd"], "Other": "x"}}"#),
+            format!(r#"{STATEMENT}, "Principal": "*", "NotPrincipal": "*""#),
+            format!(r#"{STATEMENT}, "NotPrincipal": {{"AWS": "*"}}"#),
+            format!(r#"{STATEMENT}, "Condition": {{"StringLike": {{"aws:username": ["a*", "b"]}}}}"#),
+            format!(r#"{STATEMENT}, "Condition": {{"StringLikeish": {{"aws:username": "a"}}}}"#),
+            format!(r#"{STATEMENT}, "Condition": {{"ForAnyValue:StringEquals": {{"aws:TagKeys": "a"}}}}"#),
+            format!(r#"{STATEMENT}, "Condition": {{"StringEqualsIfExists": {{"aws:username": "a"}}}}"#),
+            format!(r#"{STATEMENT}, "Condition": {{"StringLike": {{"aws:username": 3}}}}"#),
+            format!(r#"{STATEMENT}, "Condition": {{"StringLike": {{"aws:username": [true]}}}}"#),
+            format!(r#"{STATEMENT}, "Condition": {{"StringLike": []}}"#),
+            format!(r#"{STATEMENT}, "Condition": []"#),
+            format!(r#"{STATEMENT}, "Condition": {{"Bool": {{"aws:SecureTransport": "true"}}}}"#),
+            format!(r#"{STATEMENT}, "Condition": {{"NumericLessThan": {{"s3:max-keys": "10"}}}}"#),
+            format!(r#"{STATEMENT}, "Condition": {{"IpAddress": {{"aws:SourceIp": "10.0.0.0/8"}}}}"#),
+            format!(r#"{STATEMENT}, "Condition": {{"StringEquals": {{"a": "x", "a": "y"}}}}"#),
+        ];
+
+        let mut documents = vec![
+            "{}".to_string(),
+            "[]".to_string(),
+            "3".to_string(),
+            r#"{"Statement": []}"#.to_string(),
+            r#"{"Statement": 3}"#.to_string(),
+            r#"{"Statement": [3]}"#.to_string(),
+            format!(r#"{{"Version": "2012-10-17", "Statement": {{{STATEMENT}}}}}"#),
+            format!(r#"{{"Version": "2008-10-17", "Statement": {{{STATEMENT}}}}}"#),
+            format!(r#"{{"Version": "2012-10-18", "Statement": {{{STATEMENT}}}}}"#),
+            format!(r#"{{"Version": 2012, "Statement": {{{STATEMENT}}}}}"#),
+            format!(r#"{{"Id": "x", "Statement": {{{STATEMENT}}}}}"#),
+            format!(r#"{{"Id": 4, "Statement": {{{STATEMENT}}}}}"#),
+            format!(r#"{{"Id": "x", "Id": "y", "Statement": {{{STATEMENT}}}}}"#),
+            format!(r#"{{"Policy": {{}}, "Statement": {{{STATEMENT}}}}}"#),
+            format!(r#"{{"Statement": {{{STATEMENT}}}, "Statement": {{{STATEMENT}}}}}"#),
+        ];
+        for statement in statements.iter() {
+            documents.push(format!(r#"{{"Statement": {{{statement}}}}}"#));
+            documents.push(format!(r#"{{"Statement": [{{{STATEMENT}}}, {{{statement}}}]}}"#));
+        }
+
+        for document in documents.iter() {
+            match (Policy::from_str(document), Policy::parse(document)) {
+                (Ok(expected), Ok(actual)) => assert_eq!(actual, expected, "{document}"),
+                (Err(_), Err(errors)) => {
+                    assert!(errors.iter().all(|e| e.kind() != ParseErrorKind::Invalid), "{document}: {errors:?}")
+                }
+                (Ok(_), Err(errors)) => panic!("Policy::parse rejects {document}: {errors:?}"),
+                (Err(e), Ok(_)) => panic!("Policy::parse accepts {document}, rejected by Policy::from_str: {e}"),
+            }
+        }
+    }
+}
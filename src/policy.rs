@@ -1,5 +1,13 @@
 use {
-    crate::{display_json, from_str_json, AspenError, Context, Decision, StatementList},
+    crate::{
+        analysis::{
+            compare::{check, Relation},
+            space::RequestSpace,
+        },
+        display_json, from_str_json,
+        parse::parse_policy,
+        AspenError, Context, Decision, Effect, ErrorPolicy, PolicyParseError, StatementError, StatementList, Verdict,
+    },
     derive_builder::Builder,
     serde::{
         de,
@@ -13,11 +21,15 @@ use {
     },
 };
 
+/// The fields allowed in a policy document.
+pub(crate) const POLICY_FIELDS: [&str; 3] = ["Version", "Id", "Statement"];
+
 /// Aspen policy versions as represented in an Aspen policy document.
-#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
 pub enum PolicyVersion {
     /// No policy version specified. Equivalent to [PolicyVersion::V2008_10_17], but is not serialized in the policy
     /// document.
+    #[default]
     None,
 
     /// Aspen policy version 2008-10-17. This is the default version. It does not support policy variables.
@@ -41,12 +53,6 @@ impl PolicyVersion {
     }
 }
 
-impl Default for PolicyVersion {
-    fn default() -> Self {
-        Self::None
-    }
-}
-
 impl Display for PolicyVersion {
     fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
         match self {
@@ -116,6 +122,14 @@ impl Policy {
         PolicyBuilder::default()
     }
 
+    /// Parses a policy from JSON, reporting every problem found instead of stopping at the first.
+    ///
+    /// Each [PolicyParseError] identifies the offending element by JSON pointer and by line and column, along with
+    /// the offending value and a [ParseErrorKind](crate::ParseErrorKind).
+    pub fn parse(json: &str) -> Result<Self, Vec<PolicyParseError>> {
+        parse_policy(json)
+    }
+
     /// Returns the policy version.
     pub fn version(&self) -> PolicyVersion {
         self.version
@@ -154,20 +168,141 @@ impl Policy {
     /// policy.evaluate(&context);
     /// ```
     pub fn evaluate(&self, context: &Context) -> Result<Decision, crate::AspenError> {
-        for statement in self.statement.iter() {
-            match statement.evaluate(context, self.version()) {
-                Ok(Decision::Allow) => return Ok(Decision::Allow),
-                Ok(Decision::Deny) => return Ok(Decision::Deny),
-                Ok(Decision::DefaultDeny) => (),
-                Err(err) => return Err(err),
+        self.evaluate_with_statement(context).map(|(decision, _)| decision)
+    }
+
+    /// Evaluate the policy against the given request [Context], returning the decision along with the index of the
+    /// statement that produced it. The index is `None` if no statement matched ([Decision::DefaultDeny]).
+    ///
+    /// # Errors
+    ///
+    /// If a statement contains a malformed variable reference, the error is returned.
+    pub fn evaluate_with_statement(&self, context: &Context) -> Result<(Decision, Option<usize>), AspenError> {
+        self.evaluate_core(context, ErrorPolicy::Abort).map(|(decision, index, _)| (decision, index))
+    }
+
+    /// Evaluate the policy against the given request [Context], handling statements that fail to evaluate according
+    /// to `error_policy`.
+    ///
+    /// With [ErrorPolicy::FailClosed], an erroring `Allow` statement is treated as not applicable and an erroring
+    /// `Deny` statement is treated as applicable (denying the request). The decision is made from the first applicable
+    /// statement as usual, but every statement is evaluated so that all errors in the policy are returned alongside
+    /// it.
+    ///
+    /// # Errors
+    ///
+    /// With [ErrorPolicy::Abort], the first error is returned, as with [Policy::evaluate].
+    pub fn evaluate_with_errors(
+        &self,
+        context: &Context,
+        error_policy: ErrorPolicy,
+    ) -> Result<(Decision, Vec<StatementError>), AspenError> {
+        self.evaluate_core(context, error_policy).map(|(decision, _, errors)| (decision, errors))
+    }
+
+    fn evaluate_core(
+        &self,
+        context: &Context,
+        error_policy: ErrorPolicy,
+    ) -> Result<(Decision, Option<usize>, Vec<StatementError>), AspenError> {
+        let mut errors = Vec::new();
+        let mut result = None;
+
+        for (index, statement) in self.statement.iter().enumerate() {
+            let decision = match statement.evaluate(context, self.version()) {
+                Ok(decision) => decision,
+                Err(e) if error_policy == ErrorPolicy::FailClosed => {
+                    let effect = *statement.effect();
+                    errors.push(StatementError::new(index, effect, e));
+                    match effect {
+                        Effect::Allow => Decision::DefaultDeny,
+                        Effect::Deny => Decision::Deny,
+                    }
+                }
+                Err(e) => return Err(e),
+            };
+
+            // Once decided, the remaining statements are only evaluated to collect their errors.
+            if result.is_none() && decision != Decision::DefaultDeny {
+                result = Some((decision, index));
+                if error_policy == ErrorPolicy::Abort {
+                    break;
+                }
             }
         }
-        Ok(Decision::DefaultDeny)
+
+        Ok(match result {
+            Some((decision, index)) => (decision, Some(index), errors),
+            None => (Decision::DefaultDeny, None, errors),
+        })
+    }
+
+    /// Returns the index and effect of every statement that matches the given request [Context], in statement order.
+    ///
+    /// Unlike [Policy::evaluate], this does not stop at the first matching statement, so it can be used to find
+    /// every statement that grants or denies a request.
+    ///
+    /// # Errors
+    ///
+    /// If a statement contains a malformed variable reference, the error is returned.
+    pub fn matching_statements(&self, context: &Context) -> Result<Vec<(usize, Decision)>, AspenError> {
+        let mut result = Vec::new();
+        for (index, statement) in self.statement.iter().enumerate() {
+            match statement.evaluate(context, self.version())? {
+                Decision::DefaultDeny => (),
+                decision => result.push((index, decision)),
+            }
+        }
+        Ok(result)
+    }
+
+    /// Checks whether this policy allows exactly the same requests as `other`.
+    ///
+    /// The check is performed symbolically over the glob patterns in the actions, resources, principals, and
+    /// conditions of both policies. If the policies differ, a [Counterexample](crate::Counterexample) request is returned in
+    /// [Verdict::Refuted]. Requests are limited to a single resource and a single actor identity.
+    ///
+    /// # Example
+    /// ```
+    /// # use scratchstack_aspen::Policy;
+    /// # use std::str::FromStr;
+    /// let p1 = Policy::from_str(r#"{"Statement": {"Effect": "Allow", "Action": ["s3:GetObject", "s3:GetObjectAcl"], "Resource": "*"}}"#).unwrap();
+    /// let p2 = Policy::from_str(r#"{"Statement": {"Effect": "Allow", "Action": "s3:GetObject*", "Resource": "*"}}"#).unwrap();
+    /// let verdict = p1.is_equivalent(&p2).unwrap();
+    /// assert!(verdict.counterexample().unwrap().context().api().starts_with("GetObject"));
+    /// assert!(p1.is_subset_of(&p2).unwrap().holds());
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// If either policy fails to evaluate (for example, because of a malformed variable reference), the error is
+    /// returned.
+    pub fn is_equivalent(&self, other: &Policy) -> Result<Verdict, AspenError> {
+        self.compare(other, Relation::Equivalent)
+    }
+
+    /// Checks whether every request allowed by this policy is also allowed by `other`.
+    ///
+    /// See [Policy::is_equivalent] for details.
+    ///
+    /// # Errors
+    ///
+    /// If either policy fails to evaluate (for example, because of a malformed variable reference), the error is
+    /// returned.
+    pub fn is_subset_of(&self, other: &Policy) -> Result<Verdict, AspenError> {
+        self.compare(other, Relation::Subset)
+    }
+
+    fn compare(&self, other: &Policy, relation: Relation) -> Result<Verdict, AspenError> {
+        let space = RequestSpace::new([self, other]);
+        check(&space, relation, |context| self.evaluate(context), |context| other.evaluate(context))
     }
 }
 
 display_json!(Policy);
 from_str_json!(Policy);
+#[cfg(feature = "yaml")]
+crate::yaml_serde!(Policy);
 
 impl<'de> Visitor<'de> for PolicyBuilder {
     type Value = Policy;
@@ -205,7 +340,7 @@ impl<'de> Visitor<'de> for PolicyBuilder {
                     statement_seen = true;
                     builder.statement(access.next_value::<StatementList>()?);
                 }
-                _ => return Err(de::Error::unknown_field(key, &["Version", "Id", "Statement"])),
+                _ => return Err(de::Error::unknown_field(key, &POLICY_FIELDS)),
             }
         }
 
@@ -1162,6 +1297,82 @@ mod tests {
         assert_eq!(e.to_string(), "duplicate field `Statement` at line 9 column 15");
     }
 
+    #[cfg(feature = "yaml")]
+    #[test_log::test]
+    fn test_yaml() {
+        let policy_yaml = indoc! { r#"
+            Version: 2012-10-17
+            Id: PolicyId
+            Statement:
+            - Sid: '1'
+              Effect: Allow
+              Action:
+              - ec2:Get*
+              - ecs:*
+              Resource: '*'
+              Principal:
+                AWS: '123456789012'
+              Condition:
+                NumericLessThan:
+                  ec2:InstanceCount: '4'
+                StringEquals:
+                  ec2:Region:
+                  - us-west-2
+                  - us-east-1
+            - Sid: '2'
+              Effect: Deny
+              Action: '*'
+              Resource:
+              - arn:aws:s3:::my-bucket
+              - arn:aws:s3:::my-bucket/*
+              Principal: '*'
+            "# };
+        let policy = Policy::from_yaml(policy_yaml).unwrap();
+        assert_eq!(policy.version(), PolicyVersion::V2012_10_17);
+        assert_eq!(policy.id(), Some("PolicyId"));
+        assert_eq!(policy.statement().kind(), JsonRep::List);
+        assert_eq!(policy.statement()[0].action().unwrap().kind(), JsonRep::List);
+        assert_eq!(policy.statement()[1].action().unwrap().kind(), JsonRep::Single);
+        assert_eq!(policy.statement()[1].resource().unwrap().kind(), JsonRep::List);
+
+        // YAML and JSON parse to the same policy, and YAML output round-trips losslessly.
+        assert_eq!(Policy::from_str(&policy.to_string()).unwrap(), policy);
+        assert_eq!(policy.to_yaml().unwrap(), policy_yaml);
+        assert_eq!(Policy::from_yaml(&policy.to_yaml().unwrap()).unwrap(), policy);
+
+        let statement = Statement::from_yaml("Effect: Allow\nAction: s3:GetObject\nResource: '*'\n").unwrap();
+        assert_eq!(statement.to_yaml().unwrap(), "Effect: Allow\nAction: s3:GetObject\nResource: '*'\n");
+
+        // Errors carry the location of the YAML mapping being parsed.
+        let e = Policy::from_yaml(indoc! { r#"
+            Version: 2012-10-17
+            Statement:
+            - Effect: Allow
+              Action: '*'
+              Resource: '*'
+            - Effect: Deny
+              Action: '*'
+              Action: s3:*
+              Resource: '*'
+            "# })
+        .unwrap_err();
+        assert_eq!(e.to_string(), "Statement[1]: duplicate field `Action` at line 6 column 3");
+        assert_eq!(e.location().map(|l| (l.line(), l.column())), Some((6, 3)));
+
+        let e = Policy::from_yaml(indoc! { r#"
+            Statement:
+              Effect: Allow
+              Action: '*'
+              Instance: i-0123456789abcdef0
+              Resource: '*'
+            "# })
+        .unwrap_err();
+        assert_eq!(e.to_string(), "Statement: unknown field `Instance`, expected one of `Sid`, `Effect`, `Action`, `NotAction`, `Resource`, `NotResource`, `Principal`, `NotPrincipal`, `Condition` at line 2 column 3");
+
+        let e = Policy::from_yaml("Statement:\n  Effect: Allow\n  Action: s3\n  Resource: '*'\n").unwrap_err();
+        assert_eq!(e.to_string(), "Statement.Action: Invalid action: s3 at line 3 column 11");
+    }
+
     #[test_log::test]
     fn test_ec2_describe_bug() {
         let policy = Policy::from_str(indoc! {r#"
@@ -1,7 +1,33 @@
-use crate::{AspenError, Context, Decision, Policy};
+use {
+    crate::{
+        analysis::{
+            compare::{check, Relation},
+            space::RequestSpace,
+        },
+        from_str_json, AspenError, Context, Decision, ErrorPolicy, Policy, StatementError, Verdict,
+    },
+    serde::{Deserialize, Deserializer, Serialize, Serializer},
+    std::fmt::{Display, Formatter, Result as FmtResult},
+};
+
+/// The entity ARN given to standalone policies that are not attached to a known entity. The account is reserved and
+/// never issued by AWS.
+pub const STANDALONE_ENTITY_ARN: &str = "arn:aws:iam::000000000000:user/aspen-standalone";
+
+/// Pairs of (policy index, statement index) responsible for a decision.
+type DecidingStatements = Vec<(usize, Option<usize>)>;
+
+/// The decision of a policy set, the sources responsible for it, and the statements that failed to evaluate along with
+/// the sources of their policies.
+pub type EvaluationWithErrors<'a> = (Decision, Vec<&'a PolicySource>, Vec<(&'a PolicySource, StatementError)>);
 
 /// The source of a policy.
-#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+///
+/// In JSON, the variant is given by the `Type` key and the fields are in PascalCase, e.g.
+/// `{"Type": "EntityInline", "EntityArn": "arn:aws:iam::123456789012:user/alice", "EntityId": "AIDA...",
+/// "PolicyName": "s3-read"}`.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Hash, Serialize)]
+#[serde(tag = "Type", rename_all_fields = "PascalCase")]
 pub enum PolicySource {
     /// An inline policy directly attached to an IAM entity (user, role).
     EntityInline {
@@ -63,6 +89,7 @@ pub enum PolicySource {
         resource_arn: String,
 
         /// The name of the policy, if any.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
         policy_name: Option<String>,
     },
 
@@ -122,6 +149,12 @@ impl PolicySource {
         }
     }
 
+    /// Create a new [PolicySource::EntityInline] object for a policy that is not attached to a known entity, such as
+    /// a standalone policy file. The entity is given the synthetic ARN [STANDALONE_ENTITY_ARN].
+    pub fn new_standalone<S: Into<String>>(policy_name: S) -> Self {
+        Self::new_entity_inline(STANDALONE_ENTITY_ARN, "", policy_name)
+    }
+
     /// Create a new [PolicySource::EntityAttachedPolicy] object.
     pub fn new_entity_attached_policy<S1, S2, S3>(policy_arn: S1, policy_id: S2, version: S3) -> Self
     where
@@ -221,6 +254,8 @@ impl PolicySource {
 }
 
 /// A set of policies being evaluated to determine the permissions in effect.
+///
+/// In JSON, a policy set is a list of `{"Source": <PolicySource>, "Policy": <Policy>}` objects.
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub struct PolicySet {
     policies: Vec<(PolicySource, Policy)>,
@@ -271,11 +306,154 @@ impl PolicySet {
         self.evaluate_core(context, true)
     }
 
+    /// Evaluate the policy set as with [PolicySet::evaluate], handling statements that fail to evaluate according to
+    /// `error_policy`. See [Policy::evaluate_with_errors] for how erroring statements are treated.
+    ///
+    /// With [ErrorPolicy::FailClosed], every policy is evaluated so that all errors are returned alongside the
+    /// decision, each paired with the source of the policy containing the statement. The decision and sources are the
+    /// same as if evaluation had stopped at the first denial.
+    ///
+    /// # Errors
+    ///
+    /// With [ErrorPolicy::Abort], the first error is returned, as with [PolicySet::evaluate].
+    pub fn evaluate_with_errors<'a>(
+        &'a self,
+        context: &'_ Context,
+        error_policy: ErrorPolicy,
+    ) -> Result<EvaluationWithErrors<'a>, AspenError> {
+        self.evaluate_core_with_errors(context, false, error_policy)
+    }
+
+    /// Evaluate all policies in the policy set as with [PolicySet::evaluate_all], handling statements that fail to
+    /// evaluate according to `error_policy`.
+    ///
+    /// # Errors
+    ///
+    /// With [ErrorPolicy::Abort], the first error is returned, as with [PolicySet::evaluate_all].
+    pub fn evaluate_all_with_errors<'a>(
+        &'a self,
+        context: &'_ Context,
+        error_policy: ErrorPolicy,
+    ) -> Result<EvaluationWithErrors<'a>, AspenError> {
+        self.evaluate_core_with_errors(context, true, error_policy)
+    }
+
     fn evaluate_core<'a>(
         &'a self,
         context: &'_ Context,
         eval_all: bool,
     ) -> Result<(Decision, Vec<&'a PolicySource>), AspenError> {
+        let (decision, indices) = self.evaluate_indices(context, eval_all)?;
+        Ok((decision, indices.into_iter().map(|i| &self.policies[i].0).collect()))
+    }
+
+    fn evaluate_core_with_errors<'a>(
+        &'a self,
+        context: &'_ Context,
+        eval_all: bool,
+        error_policy: ErrorPolicy,
+    ) -> Result<EvaluationWithErrors<'a>, AspenError> {
+        let mut errors = Vec::new();
+        let mut evaluated = 0;
+        let (decision, indices) = self.combine_decisions(eval_all, |i, policy| {
+            let (decision, statement_errors) = policy.evaluate_with_errors(context, error_policy)?;
+            errors.extend(statement_errors.into_iter().map(|e| (&self.policies[i].0, e)));
+            evaluated = i + 1;
+            Ok(decision)
+        })?;
+
+        // The decision is final, but the remaining policies are still evaluated to collect their errors.
+        if error_policy == ErrorPolicy::FailClosed {
+            for (source, policy) in &self.policies[evaluated..] {
+                let (_, statement_errors) = policy.evaluate_with_errors(context, error_policy)?;
+                errors.extend(statement_errors.into_iter().map(|e| (source, e)));
+            }
+        }
+
+        Ok((decision, indices.into_iter().map(|i| &self.policies[i].0).collect(), errors))
+    }
+
+    /// Checks whether this policy set allows exactly the same requests as `other`.
+    ///
+    /// The check is performed symbolically over the glob patterns in the actions, resources, principals, and
+    /// conditions of every policy in both sets. If the sets differ, a [Counterexample][crate::Counterexample] request
+    /// is returned in [Verdict::Refuted]. Requests are limited to a single resource and a single actor identity.
+    ///
+    /// # Errors
+    ///
+    /// If either policy set fails to evaluate (for example, because of a malformed variable reference), the error is
+    /// returned.
+    pub fn is_equivalent(&self, other: &PolicySet) -> Result<Verdict, AspenError> {
+        self.compare(other, Relation::Equivalent)
+    }
+
+    /// Checks whether every request allowed by this policy set is also allowed by `other`.
+    ///
+    /// See [PolicySet::is_equivalent] for details.
+    ///
+    /// # Errors
+    ///
+    /// If either policy set fails to evaluate (for example, because of a malformed variable reference), the error is
+    /// returned.
+    pub fn is_subset_of(&self, other: &PolicySet) -> Result<Verdict, AspenError> {
+        self.compare(other, Relation::Subset)
+    }
+
+    fn compare(&self, other: &PolicySet, relation: Relation) -> Result<Verdict, AspenError> {
+        let space = RequestSpace::new(self.policies.iter().chain(other.policies.iter()).map(|(_, policy)| policy));
+        check(
+            &space,
+            relation,
+            |context| self.evaluate(context).map(|(decision, _)| decision),
+            |context| other.evaluate(context).map(|(decision, _)| decision),
+        )
+    }
+
+    /// Evaluate the policy set, returning the decision and the statements responsible for it: every allowing
+    /// statement for [Decision::Allow], or the denying statement or permissions boundary for [Decision::Deny]. No
+    /// statements are returned for [Decision::DefaultDeny].
+    ///
+    /// # Errors
+    ///
+    /// If a policy fails to evaluate (for example, because of a malformed variable reference), the error is
+    /// returned.
+    pub fn explain(&self, context: &Context) -> Result<(Decision, Vec<DecisionReason>), AspenError> {
+        DecisionReason::evaluate(self, context)
+    }
+
+    /// Evaluate the policy set, returning the decision and, for each policy responsible for it, the index of the
+    /// policy (into [PolicySet::policies]) and the index of the deciding statement within it. The statement index is
+    /// `None` for a permissions boundary that denies by not allowing the request.
+    pub(crate) fn evaluate_statements(&self, context: &Context) -> Result<(Decision, DecidingStatements), AspenError> {
+        let (decision, indices) = self.evaluate_indices(context, false)?;
+        let mut result = Vec::with_capacity(indices.len());
+        for index in indices {
+            let (_, statement_index) = self.policies[index].1.evaluate_with_statement(context)?;
+            result.push((index, statement_index));
+        }
+        Ok((decision, result))
+    }
+
+    /// Evaluate the policy set, returning the decision and the indices (into [PolicySet::policies]) of the policies
+    /// responsible for it.
+    pub(crate) fn evaluate_indices(
+        &self,
+        context: &Context,
+        eval_all: bool,
+    ) -> Result<(Decision, Vec<usize>), AspenError> {
+        self.combine_decisions(eval_all, |_, policy| policy.evaluate(context))
+    }
+
+    /// Combines the decisions of the individual policies, as returned by `decide` for each policy index and policy,
+    /// into the decision of the policy set and the indices of the policies responsible for it.
+    pub(crate) fn combine_decisions<F>(
+        &self,
+        eval_all: bool,
+        mut decide: F,
+    ) -> Result<(Decision, Vec<usize>), AspenError>
+    where
+        F: FnMut(usize, &Policy) -> Result<Decision, AspenError>,
+    {
         let mut allowed_sources = Vec::with_capacity(self.policies.len());
         let denied_len = if eval_all {
             self.policies.len()
@@ -284,22 +462,22 @@ impl PolicySet {
         };
         let mut denied_sources = Vec::with_capacity(denied_len);
 
-        for (source, policy) in &self.policies {
-            match policy.evaluate(context)? {
+        for (i, (source, policy)) in self.policies.iter().enumerate() {
+            match decide(i, policy)? {
                 Decision::Allow => {
                     if !source.is_boundary() {
-                        allowed_sources.push(source)
+                        allowed_sources.push(i)
                     }
                 }
                 Decision::Deny => {
-                    denied_sources.push(source);
+                    denied_sources.push(i);
                     if !eval_all {
                         return Ok((Decision::Deny, denied_sources));
                     }
                 }
                 Decision::DefaultDeny => {
                     if source.is_boundary() {
-                        denied_sources.push(source);
+                        denied_sources.push(i);
                         if !eval_all {
                             return Ok((Decision::Deny, denied_sources));
                         }
@@ -318,6 +496,52 @@ impl PolicySet {
     }
 }
 
+impl Display for PolicySource {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        match self {
+            Self::EntityInline {
+                entity_arn,
+                policy_name,
+                ..
+            } => write!(f, "inline policy {policy_name} of {entity_arn}"),
+            Self::EntityAttachedPolicy {
+                policy_arn,
+                version,
+                ..
+            } => write!(f, "{policy_arn} ({version})"),
+            Self::GroupInline {
+                group_arn,
+                policy_name,
+                ..
+            } => write!(f, "inline policy {policy_name} of {group_arn}"),
+            Self::GroupAttachedPolicy {
+                group_arn,
+                policy_arn,
+                version,
+                ..
+            } => write!(f, "{policy_arn} ({version}) via {group_arn}"),
+            Self::Resource {
+                resource_arn,
+                policy_name,
+            } => match policy_name {
+                Some(policy_name) => write!(f, "resource policy {policy_name} of {resource_arn}"),
+                None => write!(f, "resource policy of {resource_arn}"),
+            },
+            Self::PermissionBoundary {
+                policy_arn,
+                version,
+                ..
+            } => write!(f, "permissions boundary {policy_arn} ({version})"),
+            Self::OrgServiceControl {
+                policy_arn,
+                applied_arn,
+                ..
+            } => write!(f, "service control policy {policy_arn} on {applied_arn}"),
+            Self::Session => f.write_str("session policy"),
+        }
+    }
+}
+
 impl From<Vec<(PolicySource, Policy)>> for PolicySet {
     fn from(policies: Vec<(PolicySource, Policy)>) -> Self {
         Self {
@@ -326,16 +550,118 @@ impl From<Vec<(PolicySource, Policy)>> for PolicySet {
     }
 }
 
+from_str_json!(PolicySet);
+#[cfg(feature = "yaml")]
+crate::yaml_serde!(PolicySet);
+
+/// The serialized form of an entry in a [PolicySet].
+#[derive(Deserialize, Serialize)]
+#[serde(deny_unknown_fields, rename_all = "PascalCase")]
+struct PolicySetEntry<S, P> {
+    source: S,
+    policy: P,
+}
+
+impl Serialize for PolicySet {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.policies.iter().map(|(source, policy)| PolicySetEntry {
+            source,
+            policy,
+        }))
+    }
+}
+
+impl<'de> Deserialize<'de> for PolicySet {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let entries = Vec::<PolicySetEntry<PolicySource, Policy>>::deserialize(deserializer)?;
+        Ok(Self {
+            policies: entries.into_iter().map(|entry| (entry.source, entry.policy)).collect(),
+        })
+    }
+}
+
 impl Default for PolicySet {
     fn default() -> Self {
         Self::new()
     }
 }
 
+/// A policy statement (or permissions boundary) responsible for a decision, as returned by [PolicySet::explain].
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct DecisionReason {
+    source: PolicySource,
+    policy_index: usize,
+    statement_index: Option<usize>,
+    sid: Option<String>,
+}
+
+impl DecisionReason {
+    /// Evaluates a context against a policy set, returning the decision and the statements responsible for it.
+    fn evaluate(policy_set: &PolicySet, context: &Context) -> Result<(Decision, Vec<Self>), AspenError> {
+        let (decision, statements) = policy_set.evaluate_statements(context)?;
+        let policies = policy_set.policies();
+        let reasons = statements
+            .into_iter()
+            .map(|(policy_index, statement_index)| {
+                let (source, policy) = &policies[policy_index];
+                Self {
+                    source: source.clone(),
+                    policy_index,
+                    statement_index,
+                    sid: statement_index.and_then(|i| policy.statement()[i].sid().map(str::to_string)),
+                }
+            })
+            .collect();
+
+        Ok((decision, reasons))
+    }
+
+    /// Returns the source of the policy.
+    #[inline]
+    pub fn source(&self) -> &PolicySource {
+        &self.source
+    }
+
+    /// Returns the index of the policy in [PolicySet::policies].
+    #[inline]
+    pub fn policy_index(&self) -> usize {
+        self.policy_index
+    }
+
+    /// Returns the index of the deciding statement within the policy. This is `None` when a permissions boundary,
+    /// service control policy, or session policy denies the request by not allowing it.
+    #[inline]
+    pub fn statement_index(&self) -> Option<usize> {
+        self.statement_index
+    }
+
+    /// Returns the statement id of the deciding statement, if any.
+    #[inline]
+    pub fn sid(&self) -> Option<&str> {
+        self.sid.as_deref()
+    }
+}
+
+impl Display for DecisionReason {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        match self.statement_index {
+            Some(statement_index) => {
+                write!(f, "statement {statement_index}")?;
+                if let Some(sid) = &self.sid {
+                    write!(f, " {sid:?}")?;
+                }
+                write!(f, " of {}", self.source)
+            }
+            None => write!(f, "no statement of {} allows the request", self.source),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use {
-        crate::{Context, Decision, Policy, PolicySet, PolicySource},
+        crate::{AspenError, Context, Decision, Effect, ErrorPolicy, Policy, PolicySet, PolicySource},
         indoc::indoc,
         pretty_assertions::{assert_eq, assert_ne},
         scratchstack_arn::Arn,
@@ -349,7 +675,7 @@ mod tests {
 
     #[test_log::test]
     fn test_policy_source_derived() {
-        let policy_sources = vec![
+        let policy_sources = [
             PolicySource::new_entity_inline(
                 "arn:aws:iam::123456789012:user/MyUser",
                 "AIDAIXEXAMPLEID000000",
@@ -661,5 +987,164 @@ mod tests {
         assert_eq!(ps, ps2);
         assert_eq!(ps.clone(), ps);
         assert_eq!(format!("{ps:?}"), format!("{ps2:?}"));
+
+        let json = serde_json::to_string(&ps).unwrap();
+        assert_eq!(PolicySet::from_str(&json).unwrap(), ps);
+    }
+
+    #[test_log::test]
+    fn test_eval_fail_closed() {
+        let mixed_source = PolicySource::new_entity_inline("arn:aws:iam::123456789012:user/alice", "", "Mixed");
+        let mixed = Policy::from_str(indoc! {r#"
+        {
+            "Version": "2012-10-17",
+            "Statement": [
+                {"Effect": "Allow", "Action": "s3:*", "Resource": "arn:aws:s3:::${oops"},
+                {"Effect": "Allow", "Action": "s3:GetObject", "Resource": "arn:aws:s3:::bucket/*"}
+            ]
+        }"#})
+        .unwrap();
+        let guard_source = PolicySource::new_entity_inline("arn:aws:iam::123456789012:user/alice", "", "Guard");
+        let guard = Policy::from_str(
+            r#"{"Version": "2012-10-17", "Statement": {"Effect": "Deny", "Action": "s3:PutObject", "Resource": "arn:aws:s3:::${bad"}}"#,
+        )
+        .unwrap();
+        let mut ps = PolicySet::new();
+        ps.add_policy(mixed_source.clone(), mixed.clone());
+        ps.add_policy(guard_source.clone(), guard);
+
+        let actor = Principal::from(vec![User::from_str("arn:aws:iam::123456789012:user/alice").unwrap().into()]);
+        let context = |api: &str| {
+            Context::builder()
+                .service("s3")
+                .api(api)
+                .actor(actor.clone())
+                .resources(vec![Arn::from_str("arn:aws:s3:::bucket/key").unwrap()])
+                .session_data(SessionData::new())
+                .build()
+                .unwrap()
+        };
+        let get = context("GetObject");
+        let put = context("PutObject");
+
+        // By default, the first error aborts the evaluation.
+        let e = ps.evaluate(&get).unwrap_err();
+        assert_eq!(e, AspenError::InvalidSubstitution("${oops".to_string()));
+        let e = ps.evaluate_with_errors(&get, ErrorPolicy::Abort).unwrap_err();
+        assert_eq!(e, AspenError::InvalidSubstitution("${oops".to_string()));
+        assert!(mixed.evaluate(&get).is_err());
+
+        // An erroring Allow statement is skipped, so later statements can still allow the request.
+        let (decision, errors) = mixed.evaluate_with_errors(&get, ErrorPolicy::FailClosed).unwrap();
+        assert_eq!(decision, Decision::Allow);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].statement(), 0);
+        assert_eq!(errors[0].effect(), Effect::Allow);
+        assert_eq!(errors[0].to_string(), "Statement 0 (Allow): Invalid variable substitution: ${oops");
+
+        let (decision, sources, errors) = ps.evaluate_with_errors(&get, ErrorPolicy::FailClosed).unwrap();
+        assert_eq!(decision, Decision::Allow);
+        assert_eq!(sources, vec![&mixed_source]);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].0, &mixed_source);
+
+        // An erroring Deny statement denies the request.
+        let (decision, sources, errors) = ps.evaluate_all_with_errors(&put, ErrorPolicy::FailClosed).unwrap();
+        assert_eq!(decision, Decision::Deny);
+        assert_eq!(sources, vec![&guard_source]);
+        assert_eq!(errors.len(), 2);
+        assert_eq!(errors[1].0, &guard_source);
+        assert_eq!(errors[1].1.effect(), Effect::Deny);
+        assert_eq!(errors[1].1.error(), &AspenError::InvalidSubstitution("${bad".to_string()));
+
+        // Errors after the deciding statement and policy are still collected without changing the decision.
+        let late = Policy::from_str(indoc! {r#"
+        {
+            "Version": "2012-10-17",
+            "Statement": [
+                {"Effect": "Allow", "Action": "s3:GetObject", "Resource": "arn:aws:s3:::bucket/*"},
+                {"Effect": "Deny", "Action": "s3:GetObject", "Resource": "arn:aws:s3:::${late"}
+            ]
+        }"#})
+        .unwrap();
+        assert_eq!(late.evaluate(&get).unwrap(), Decision::Allow);
+        let (decision, errors) = late.evaluate_with_errors(&get, ErrorPolicy::FailClosed).unwrap();
+        assert_eq!(decision, Decision::Allow);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].statement(), 1);
+        assert_eq!(errors[0].effect(), Effect::Deny);
+
+        let late_source = PolicySource::new_entity_inline("arn:aws:iam::123456789012:user/alice", "", "Late");
+        let (decision, sources, errors) = ps.evaluate_with_errors(&put, ErrorPolicy::FailClosed).unwrap();
+        assert_eq!((decision, sources, errors.len()), (Decision::Deny, vec![&guard_source], 2));
+        let mut ps = PolicySet::from(vec![(guard_source.clone(), ps.policies()[1].1.clone())]);
+        ps.add_policy(mixed_source.clone(), mixed);
+        ps.add_policy(late_source.clone(), late);
+        let (decision, sources, errors) = ps.evaluate_with_errors(&put, ErrorPolicy::FailClosed).unwrap();
+        assert_eq!(decision, Decision::Deny);
+        assert_eq!(sources, vec![&guard_source]);
+        let sources: Vec<_> = errors.iter().map(|(source, e)| (*source, e.statement())).collect();
+        assert_eq!(sources, vec![(&guard_source, 0), (&mixed_source, 0)]);
+        let (_, _, errors) = ps.evaluate_with_errors(&get, ErrorPolicy::FailClosed).unwrap();
+        let sources: Vec<_> = errors.iter().map(|(source, e)| (*source, e.statement())).collect();
+        assert_eq!(sources, vec![(&mixed_source, 0), (&late_source, 1)]);
+    }
+
+    #[test_log::test]
+    fn test_serde() {
+        let source = PolicySource::new_resource("arn:aws:s3:::bucket", None::<String>);
+        assert_eq!(
+            serde_json::to_string(&source).unwrap(),
+            r#"{"Type":"Resource","ResourceArn":"arn:aws:s3:::bucket"}"#
+        );
+        assert_eq!(serde_json::to_string(&PolicySource::Session).unwrap(), r#"{"Type":"Session"}"#);
+
+        let ps = PolicySet::from_str(indoc! {r#"
+            [
+                {
+                    "Source": {"Type": "PermissionBoundary", "PolicyArn": "arn:aws:iam::123456789012:policy/b",
+                               "PolicyId": "ANPAEXAMPLE", "Version": "v1"},
+                    "Policy": {"Statement": {"Effect": "Allow", "Action": "s3:*", "Resource": "*"}}
+                }
+            ]"#})
+        .unwrap();
+        assert_eq!(
+            ps.policies()[0].0,
+            PolicySource::new_permission_boundary("arn:aws:iam::123456789012:policy/b", "ANPAEXAMPLE", "v1")
+        );
+        assert!(PolicySet::from_str(r#"[{"Source": {"Type": "Session"}}]"#).is_err());
+        assert!(PolicySet::from_str(r#"[{"Source": {"Type": "Unknown"}, "Policy": {"Statement": []}}]"#).is_err());
+    }
+
+    #[cfg(feature = "yaml")]
+    #[test_log::test]
+    fn test_yaml() {
+        let ps_yaml = indoc! {r#"
+            - Source:
+                Type: PermissionBoundary
+                PolicyArn: arn:aws:iam::123456789012:policy/b
+                PolicyId: ANPAEXAMPLE
+                Version: v1
+              Policy:
+                Statement:
+                  Effect: Allow
+                  Action: s3:*
+                  Resource: '*'
+            - Source:
+                Type: Session
+              Policy:
+                Statement:
+                  Effect: Allow
+                  Action: s3:GetObject
+                  Resource: '*'
+            "#};
+        let ps = PolicySet::from_yaml(ps_yaml).unwrap();
+        assert_eq!(ps.policies().len(), 2);
+        assert_eq!(ps.policies()[1].0, PolicySource::Session);
+        assert_eq!(ps.to_yaml().unwrap(), ps_yaml);
+        assert_eq!(PolicySet::from_str(&serde_json::to_string(&ps).unwrap()).unwrap(), ps);
+
+        let e = PolicySet::from_yaml("- Source:\n    Type: Session\n  Policies: []\n").unwrap_err();
+        assert_eq!(e.to_string(), ".[0]: unknown field `Policies`, expected `Source` or `Policy` at line 3 column 3");
     }
 }
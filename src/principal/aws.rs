@@ -31,6 +31,10 @@ pub enum AwsPrincipal {
 
 impl AwsPrincipal {
     /// Indicate whether this [AwsPrincipal] matches the given [PrincipalIdentity].
+    ///
+    /// As in IAM, an account root ARN (`arn:aws:iam::123456789012:root`) matches every identity in the account,
+    /// including assumed-role sessions, and a role ARN (`arn:aws:iam::123456789012:role/path/name`) matches every
+    /// session of the role (`arn:aws:sts::123456789012:assumed-role/name/*`). Any other ARN must match exactly.
     pub fn matches(&self, identity: &PrincipalIdentity) -> bool {
         if identity.source() != PrincipalSource::Aws {
             return false;
@@ -44,12 +48,18 @@ impl AwsPrincipal {
             }
             Self::Arn(arn) => {
                 let identity_arn: Arn = identity.try_into().expect("AWS principal identity must have an ARN");
-                match arn.resource() {
-                    "root" => {
-                        arn.partition() == identity_arn.partition()
-                            && arn.service() == identity_arn.service()
-                            && arn.region() == identity_arn.region()
-                            && arn.account_id() == identity_arn.account_id()
+                match (arn.resource(), identity) {
+                    // The account root matches every IAM user and role session in the account.
+                    ("root", _) if arn.service() == "iam" => {
+                        arn.partition() == identity_arn.partition() && arn.account_id() == identity_arn.account_id()
+                    }
+                    // A role ARN matches every session of the role. Role paths are not part of session ARNs.
+                    (resource, PrincipalIdentity::AssumedRole(session))
+                        if arn.service() == "iam" && resource.starts_with("role/") =>
+                    {
+                        arn.partition() == session.partition()
+                            && arn.account_id() == session.account_id()
+                            && resource.rsplit('/').next() == Some(session.role_name())
                     }
                     _ => arn == &identity_arn,
                 }
@@ -90,7 +100,7 @@ mod tests {
     use {
         crate::AwsPrincipal,
         pretty_assertions::{assert_eq, assert_ne},
-        scratchstack_aws_principal::{CanonicalUser, PrincipalIdentity, Service, User},
+        scratchstack_aws_principal::{AssumedRole, CanonicalUser, PrincipalIdentity, RootUser, Service, User},
     };
 
     #[allow(clippy::redundant_clone)]
@@ -136,4 +146,37 @@ mod tests {
         assert!(AwsPrincipal::Arn("arn:aws:iam::123456789012:root".parse().unwrap())
             .matches(&PrincipalIdentity::from(User::new("aws", "123456789012", "/", "testuser").unwrap())));
     }
+
+    #[test_log::test]
+    fn test_matches_sessions() {
+        let session = PrincipalIdentity::from(AssumedRole::new("aws", "123456789012", "admin", "session").unwrap());
+        let user = PrincipalIdentity::from(User::new("aws", "123456789012", "/", "admin").unwrap());
+        assert!(AwsPrincipal::Arn("arn:aws:iam::123456789012:root".parse().unwrap()).matches(&session));
+        assert!(AwsPrincipal::Arn("arn:aws:iam::123456789012:role/ops/admin".parse().unwrap()).matches(&session));
+        assert!(AwsPrincipal::Arn("arn:aws:sts::123456789012:assumed-role/admin/session".parse().unwrap())
+            .matches(&session));
+        assert!(!AwsPrincipal::Arn("arn:aws:iam::123456789012:role/admin2".parse().unwrap()).matches(&session));
+        assert!(!AwsPrincipal::Arn("arn:aws:iam::210987654321:role/admin".parse().unwrap()).matches(&session));
+        assert!(!AwsPrincipal::Arn("arn:aws:iam::210987654321:root".parse().unwrap()).matches(&session));
+        assert!(!AwsPrincipal::Arn("arn:aws-cn:iam::123456789012:role/admin".parse().unwrap()).matches(&session));
+        assert!(
+            !AwsPrincipal::Arn("arn:aws:sts::123456789012:assumed-role/admin/other".parse().unwrap()).matches(&session)
+        );
+
+        // A role ARN does not match a user of the same name, and a session ARN does not match the role's other
+        // sessions or the root user.
+        assert!(!AwsPrincipal::Arn("arn:aws:iam::123456789012:role/admin".parse().unwrap()).matches(&user));
+        assert!(AwsPrincipal::Arn("arn:aws:iam::123456789012:root".parse().unwrap()).matches(&user));
+        let root = PrincipalIdentity::from(RootUser::new("aws", "123456789012").unwrap());
+        assert!(AwsPrincipal::Arn("arn:aws:iam::123456789012:root".parse().unwrap()).matches(&root));
+        assert!(!AwsPrincipal::Arn("arn:aws:iam::123456789012:role/admin".parse().unwrap()).matches(&root));
+
+        // Only an IAM root ARN stands for the account.
+        for arn in ["arn:aws:sts::123456789012:root", "arn:aws:s3:::root"] {
+            let principal = AwsPrincipal::Arn(arn.parse().unwrap());
+            assert!(!principal.matches(&session), "{arn}");
+            assert!(!principal.matches(&user), "{arn}");
+            assert!(!principal.matches(&root), "{arn}");
+        }
+    }
 }
@@ -6,6 +6,8 @@ pub use {
     specified::{SpecifiedPrincipal, SpecifiedPrincipalBuilder, SpecifiedPrincipalBuilderError},
 };
 
+pub(crate) use specified::PRINCIPAL_FIELDS;
+
 use {
     crate::display_json,
     log::debug,
@@ -77,7 +79,7 @@ impl<'de> Visitor<'de> for PrincipalVisitor {
         if v == "*" {
             Ok(Principal::Any)
         } else {
-            return Err(E::invalid_value(Unexpected::Str(v), &self));
+            Err(E::invalid_value(Unexpected::Str(v), &self))
         }
     }
 
@@ -6,6 +6,9 @@ use {
     serde::{Deserialize, Serialize},
 };
 
+/// The principal types recognized in a principal map, in the order they are serialized.
+pub(crate) const PRINCIPAL_FIELDS: [&str; 4] = ["AWS", "CanonicalUser", "Federated", "Service"];
+
 /// A non-wildcard principal statement in an Aspen policy.
 ///
 /// SpecifiedPrincipal structs are immutable. To construct this programmatically, use [SpecifiedPrincipalBuilder].
@@ -125,13 +128,28 @@ impl SpecifiedPrincipal {
 #[cfg(test)]
 mod tests {
     use {
-        super::SpecifiedPrincipal,
+        super::{SpecifiedPrincipal, PRINCIPAL_FIELDS},
         scratchstack_aws_principal::{
             CanonicalUser, FederatedUser, Principal as PrincipalActor, PrincipalIdentity, Service, User,
         },
         std::str::FromStr,
     };
 
+    #[test_log::test]
+    fn test_principal_fields() {
+        // Policy::parse checks principal maps against PRINCIPAL_FIELDS, so it must name every serialized field.
+        let sp = SpecifiedPrincipal::from_str(
+            r#"{"AWS": "*", "CanonicalUser": "abcd", "Federated": "accounts.google.com", "Service": "ec2.amazonaws.com"}"#,
+        )
+        .unwrap();
+        let value = serde_json::to_value(&sp).unwrap();
+        let mut fields: Vec<_> = value.as_object().unwrap().keys().map(String::as_str).collect();
+        let mut expected = PRINCIPAL_FIELDS.to_vec();
+        fields.sort();
+        expected.sort();
+        assert_eq!(fields, expected);
+    }
+
     #[test_log::test]
     fn test_deserialize_basic1() {
         let sp = SpecifiedPrincipal::from_str(
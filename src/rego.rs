@@ -0,0 +1,978 @@
+use {
+    crate::{
+        condition::{date::DateCmp, numeric::NumericCmp, string::StringCmp},
+        AspenError, AwsPrincipal, ConditionOp, Effect, Policy, PolicySet, PolicyVersion, Principal, Resource,
+        ResourceList, Statement, StringLikeList,
+    },
+    chrono::{DateTime, Utc},
+    ipnet::IpNet,
+    std::{
+        fmt::{Display, Formatter, Result as FmtResult, Write},
+        net::IpAddr,
+        str::FromStr,
+    },
+};
+
+/// Helper rules and functions shared by every exported module.
+const REGO_RUNTIME: &str = r#"# Helpers shared by the rules above.
+
+resources := object.get(input, "Resources", [])
+
+actor := object.get(input, "Actor", [])
+
+session := {lower(k): v | some k, v in object.get(input, "SessionData", {})}
+
+session_value(key) := value if {
+	value := session[key]
+} else := "Null"
+
+var_value(name) := variable_value(value) if {
+	value := session[name]
+} else := ""
+
+variable_value("Null") := ""
+
+variable_value(value) := value.String
+
+variable_value(value) := format_int(value.Integer, 10)
+
+variable_value(value) := sprintf("%v", [value.Bool])
+
+variable_value(value) := value.IpAddr
+
+variable_value(value) := value.Binary
+
+variable_value(value) := time.format([time.parse_rfc3339_ns(value.Timestamp), "UTC", "2006-01-02T15:04:05Z"])
+
+var_regex(name) := concat("", [quote_char(c) | some c in split(var_value(name), "")])
+
+quote_char(c) := concat("", ["\\", c]) if {
+	indexof(`\.+*?()|[]{}^$#&-~`, c) >= 0
+} else := c
+
+arn_parts(s) := regex.find_all_string_submatch_n(`^arn:([^:]*):([^:]*):([^:]*):([^:]*):((?s:.*))$`, s, 1)[0]
+
+arn_like(parts, patterns) if {
+	every i, pattern in patterns {
+		j := i + 1
+		regex.match(pattern, parts[j])
+	}
+}
+
+parse_int(s) := to_number(trim_prefix(s, "+")) if {
+	regex.match(`^[+-]?[0-9]+$`, s)
+}
+
+numeric_value(value) := value.Integer
+
+numeric_value(value) := parse_int(value.String)
+
+valid_date(s) if {
+	time.parse_rfc3339_ns(s)
+}
+
+parse_date(s) := time.parse_rfc3339_ns(s)
+
+parse_date(s) := parse_int(s) * 1000000000 if {
+	not valid_date(s)
+}
+
+date_value(value) := time.parse_rfc3339_ns(value.Timestamp)
+
+date_value(value) := time.parse_rfc3339_ns(value.String)
+
+ip_cidr(s) := s if {
+	indexof(s, "/") >= 0
+}
+
+ip_cidr(s) := concat("", [s, "/32"]) if {
+	indexof(s, "/") < 0
+	indexof(s, ":") < 0
+}
+
+ip_cidr(s) := concat("", [s, "/128"]) if {
+	indexof(s, "/") < 0
+	indexof(s, ":") >= 0
+}
+
+aws_identity_arn(identity) := concat("", ["arn:aws:iam::", identity.AWS, ":root"]) if {
+	regex.match(`^[0-9]{12}$`, identity.AWS)
+}
+
+aws_identity_arn(identity) := identity.AWS if {
+	not regex.match(`^[0-9]{12}$`, identity.AWS)
+	not federated_user_name(identity)
+}
+
+federated_user_name(identity) := match[1] if {
+	match := regex.find_all_string_submatch_n(`^arn:[^:]*:sts::[^:]*:federated-user/(.*)$`, identity.AWS, 1)[0]
+}
+
+service_names(identity) := {identity.Service, concat(".", [match[1], match[3]])} if {
+	match := regex.find_all_string_submatch_n(
+		`^([^.]+)\.(local|[a-z]+(?:-[a-z]+)*-[0-9]+(?:-[a-z]+(?:-[a-z]+)*-[0-9]+)?)\.(.+)$`,
+		identity.Service,
+		1,
+	)[0]
+} else := {identity.Service}
+"#;
+
+/// A [PolicySet] exported as an [Open Policy Agent](https://www.openpolicyagent.org/) Rego module.
+///
+/// The module defines a `decision` rule that evaluates to `"Allow"`, `"Deny"`, or `"DefaultDeny"`, exactly as
+/// [PolicySet::evaluate] would for the same request. The input document is the JSON form of a
+/// [Context](crate::Context), with `Service`, `Api`, `Actor`, `Resources`, and `SessionData` members. Actions, ARNs,
+/// and condition values are matched with the same glob, variable substitution, and condition operator semantics as
+/// Aspen, with one simplification: ARN condition values only need the `arn:partition:service:region:account:resource`
+/// structure to be treated as ARNs.
+///
+/// The module uses `import rego.v1`, so it needs OPA 0.59 or later.
+///
+/// # Example
+///
+/// ```
+/// # use scratchstack_aspen::{Policy, PolicySet, PolicySource, RegoModule};
+/// # use std::str::FromStr;
+/// let policy = Policy::from_str(r#"{"Statement": {"Effect": "Allow", "Action": "s3:Get*", "Resource": "*"}}"#).unwrap();
+/// let mut policy_set = PolicySet::new();
+/// policy_set.add_policy(PolicySource::new_entity_inline("arn:aws:iam::123456789012:user/alice", "", "read"), policy);
+///
+/// let module = RegoModule::from_policy_set(&policy_set, "aspen.authz").unwrap();
+/// assert!(module.text().starts_with("package aspen.authz\n"));
+/// assert!(module.text().contains(r#"regex.match("^Get.*$", input.Api)"#));
+/// ```
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct RegoModule {
+    package: String,
+    text: String,
+}
+
+impl RegoModule {
+    /// Exports a policy set as a Rego module in the given package, such as `aspen.authz`.
+    ///
+    /// # Errors
+    ///
+    /// Aspen only reports malformed policy variables when a request reaches them; the exporter rejects them up front
+    /// with [AspenError::InvalidSubstitution], since the Rego module has no way to report an evaluation error.
+    pub fn from_policy_set(policy_set: &PolicySet, package: &str) -> Result<Self, AspenError> {
+        let mut text = format!(
+            "package {package}\n\nimport rego.v1\n\n# Generated from an Aspen policy set.\n\n\
+             default decision := \"DefaultDeny\"\n\n\
+             decision := \"Deny\" if {{\n\tdenied\n}} else := \"Allow\" if {{\n\tallowed\n}}\n\n\
+             default denied := false\n\ndefault allowed := false\n"
+        );
+
+        for (i, (source, _)) in policy_set.policies().iter().enumerate() {
+            if source.is_boundary() {
+                write!(text, "\ndenied if {{\n\tpolicy_{i} != \"Allow\"\n}}\n").unwrap();
+            } else {
+                write!(text, "\ndenied if {{\n\tpolicy_{i} == \"Deny\"\n}}\n").unwrap();
+                write!(text, "\nallowed if {{\n\tpolicy_{i} == \"Allow\"\n}}\n").unwrap();
+            }
+        }
+
+        for (i, (_, policy)) in policy_set.policies().iter().enumerate() {
+            write_policy(&mut text, &format!("policy_{i}"), policy)?;
+        }
+
+        text.push('\n');
+        text.push_str(REGO_RUNTIME);
+
+        Ok(Self {
+            package: package.to_string(),
+            text,
+        })
+    }
+
+    /// Returns the Rego package of the module.
+    #[inline]
+    pub fn package(&self) -> &str {
+        &self.package
+    }
+
+    /// Returns the Rego source of the module.
+    #[inline]
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+}
+
+impl Display for RegoModule {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        f.write_str(&self.text)
+    }
+}
+
+/// Writes the rule for a policy's decision: the effect of its first matching statement.
+fn write_policy(text: &mut String, name: &str, policy: &Policy) -> Result<(), AspenError> {
+    write!(text, "\ndefault {name} := \"DefaultDeny\"\n").unwrap();
+
+    for (j, statement) in policy.statement().iter().enumerate() {
+        let effect = match statement.effect() {
+            Effect::Allow => "Allow",
+            Effect::Deny => "Deny",
+        };
+
+        if j == 0 {
+            write!(text, "\n{name} := \"{effect}\" if {{\n\t{name}_statement_{j}\n}}").unwrap();
+        } else {
+            write!(text, " else := \"{effect}\" if {{\n\t{name}_statement_{j}\n}}").unwrap();
+        }
+    }
+
+    if !policy.statement().is_empty() {
+        text.push('\n');
+    }
+
+    for (j, statement) in policy.statement().iter().enumerate() {
+        write_statement(text, &format!("{name}_statement_{j}"), statement, policy.version())?;
+    }
+
+    Ok(())
+}
+
+/// Writes the rule matching a statement, along with the rules for its action, resource, principal, and condition
+/// clauses.
+fn write_statement(text: &mut String, name: &str, statement: &Statement, pv: PolicyVersion) -> Result<(), AspenError> {
+    let mut body = Vec::new();
+    let mut rules = Vec::new();
+
+    // Action or NotAction.
+    let (actions, negated) = match (statement.action(), statement.not_action()) {
+        (Some(actions), _) => (actions, false),
+        (None, Some(actions)) => (actions, true),
+        (None, None) => unreachable!("Statement must have either an Action or NotAction"),
+    };
+    let action = format!("{name}_action");
+    body.push(if negated {
+        format!("not {action}")
+    } else {
+        action.clone()
+    });
+    let mut alternatives = Vec::with_capacity(actions.len());
+    for action in actions.iter() {
+        match action.specific() {
+            None => alternatives.push(vec![]),
+            Some((service, api)) => alternatives.push(vec![
+                format!("input.Service == {}", quote(service)),
+                format!("regex.match({}, input.Api)", regex_term(api, false)?),
+            ]),
+        }
+    }
+    rules.push((action, alternatives));
+
+    // Resource or NotResource.
+    if let Some(resources) = statement.resource() {
+        let resource = format!("{name}_resource");
+        body.push(resource.clone());
+        let mut alternatives = Vec::with_capacity(2);
+        if resources.iter().any(Resource::is_any) {
+            alternatives.push(vec!["count(resources) == 0".to_string()]);
+        }
+        alternatives.push(vec![
+            "count(resources) > 0".to_string(),
+            format!("every r in resources {{\n\t\t{resource}_match(r)\n\t}}"),
+        ]);
+        rules.push((resource.clone(), alternatives));
+        write_resource_match(text, &format!("{resource}_match"), resources, pv)?;
+    } else if let Some(resources) = statement.not_resource() {
+        let resource = format!("{name}_resource");
+        body.push(resource.clone());
+        let mut alternatives = Vec::with_capacity(2);
+        if !resources.iter().any(Resource::is_any) {
+            alternatives.push(vec!["count(resources) == 0".to_string()]);
+        }
+        alternatives.push(vec!["count(resources) > 0".to_string(), format!("not {resource}_any")]);
+        rules.push((resource.clone(), alternatives));
+        rules.push((
+            format!("{resource}_any"),
+            vec![vec!["some r in resources".to_string(), format!("{resource}_match(r)")]],
+        ));
+        write_resource_match(text, &format!("{resource}_match"), resources, pv)?;
+    }
+
+    // Principal or NotPrincipal.
+    if let Some(principal) = statement.principal() {
+        let rule = format!("{name}_principal");
+        body.push(rule.clone());
+        rules.push((rule, principal_alternatives(principal)));
+    } else if let Some(principal) = statement.not_principal() {
+        let rule = format!("{name}_principal");
+        body.push(format!("not {rule}"));
+        rules.push((rule, principal_alternatives(principal)));
+    }
+
+    // Conditions.
+    let mut k = 0;
+    for (op, map) in statement.condition().into_iter().flat_map(|c| c.iter()) {
+        for (key, allowed) in map.iter() {
+            let rule = format!("{name}_condition_{k}");
+            body.push(rule.clone());
+            rules.push((rule, condition_alternatives(op, key, allowed, pv)?));
+            k += 1;
+        }
+    }
+
+    write_rule(text, name, &[body]);
+    for (rule, alternatives) in rules {
+        write_rule(text, &rule, &alternatives);
+    }
+
+    Ok(())
+}
+
+/// Writes a boolean rule that is true if any of the alternative bodies holds. An empty body is always true.
+fn write_rule(text: &mut String, name: &str, alternatives: &[Vec<String>]) {
+    write!(text, "\ndefault {name} := false\n").unwrap();
+    for body in alternatives {
+        if body.is_empty() {
+            write!(text, "\n{name} := true\n").unwrap();
+        } else {
+            write!(text, "\n{name} if {{\n\t{}\n}}\n", body.join("\n\t")).unwrap();
+        }
+    }
+}
+
+/// Writes a function that is true if the resource ARN `r` matches any of the resource patterns.
+fn write_resource_match(
+    text: &mut String,
+    name: &str,
+    resources: &ResourceList,
+    pv: PolicyVersion,
+) -> Result<(), AspenError> {
+    if resources.is_empty() {
+        write!(text, "\n{name}(r) := false\n").unwrap();
+    }
+
+    for resource in resources.iter() {
+        match resource {
+            Resource::Any => write!(text, "\n{name}(r) if {{\n\tis_string(r)\n}}\n").unwrap(),
+            Resource::Arn(arn) => {
+                let patterns = [
+                    regex_term(arn.partition_pattern(), false)?,
+                    regex_term(arn.service_pattern(), false)?,
+                    regex_term(arn.region_pattern(), false)?,
+                    regex_term(arn.account_id_pattern(), false)?,
+                    regex_term(arn.resource_pattern(), pv == PolicyVersion::V2012_10_17)?,
+                ];
+                write!(text, "\n{name}(r) if {{\n\tarn_like(arn_parts(r), [{}])\n}}\n", patterns.join(", ")).unwrap();
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Returns the alternative bodies matching a principal against the identities in the request actor.
+fn principal_alternatives(principal: &Principal) -> Vec<Vec<String>> {
+    let specified = match principal {
+        Principal::Any => return vec![vec![]],
+        Principal::Specified(specified) => specified,
+    };
+
+    let mut alternatives = Vec::new();
+    let mut push = |check: String| alternatives.push(vec!["some identity in actor".to_string(), check]);
+
+    for aws in specified.aws().into_iter().flat_map(|list| list.iter()) {
+        match aws {
+            AwsPrincipal::Any => push("aws_identity_arn(identity) != \"\"".to_string()),
+            AwsPrincipal::Account(account_id) => {
+                push(format!("arn_parts(aws_identity_arn(identity))[4] == {}", quote(account_id)))
+            }
+            AwsPrincipal::Arn(arn) if arn.service() == "iam" && arn.resource() == "root" => push(format!(
+                "arn_like(arn_parts(aws_identity_arn(identity)), [{}, \".*\", \".*\", {}, \".*\"])",
+                quote(&format!("^{}$", regex::escape(arn.partition()))),
+                quote(&format!("^{}$", regex::escape(arn.account_id())))
+            )),
+            AwsPrincipal::Arn(arn) => {
+                push(format!("aws_identity_arn(identity) == {}", quote(&arn.to_string())));
+                if arn.service() == "iam" && arn.resource().starts_with("role/") {
+                    let role_name = arn.resource().rsplit('/').next().unwrap_or_default();
+                    let session_prefix =
+                        format!("arn:{}:sts::{}:assumed-role/{role_name}/", arn.partition(), arn.account_id());
+                    push(format!("startswith(aws_identity_arn(identity), {})", quote(&session_prefix)));
+                }
+            }
+        }
+    }
+
+    for id in specified.canonical_user().into_iter().flat_map(|list| list.iter()) {
+        push(format!("identity.CanonicalUser == {}", quote(id)));
+    }
+
+    for user_name in specified.federated().into_iter().flat_map(|list| list.iter()) {
+        push(format!("federated_user_name(identity) == {}", quote(user_name)));
+    }
+
+    for service in specified.service().into_iter().flat_map(|list| list.iter()) {
+        push(format!("{} in service_names(identity)", quote(service)));
+    }
+
+    alternatives
+}
+
+/// Returns the alternative bodies matching one condition key against its allowed values.
+fn condition_alternatives(
+    op: &ConditionOp,
+    key: &str,
+    allowed: &StringLikeList<String>,
+    pv: PolicyVersion,
+) -> Result<Vec<Vec<String>>, AspenError> {
+    let value = format!("session_value({})", quote(&key.to_lowercase()));
+    let variables = pv == PolicyVersion::V2012_10_17;
+    let mut alternatives = Vec::new();
+    let mut push = |checks: Vec<String>| {
+        let mut body = vec![format!("value := {value}")];
+        body.extend(checks);
+        alternatives.push(body);
+    };
+
+    let variant = match op {
+        ConditionOp::Null => {
+            for el in allowed.iter() {
+                match plain_value(el, variables)? {
+                    Plain::Static(el) => match bool::from_str(&el) {
+                        Ok(true) => push(vec!["value == \"Null\"".to_string()]),
+                        Ok(false) => push(vec!["value != \"Null\"".to_string()]),
+                        Err(_) => (),
+                    },
+                    Plain::Dynamic(term) => {
+                        push(vec![format!("{term} == \"true\""), "value == \"Null\"".to_string()]);
+                        push(vec![format!("{term} == \"false\""), "value != \"Null\"".to_string()]);
+                    }
+                }
+            }
+            return Ok(alternatives);
+        }
+        ConditionOp::String(cmp, variant) => {
+            for el in allowed.iter() {
+                push(match (cmp, variant.negated()) {
+                    (StringCmp::Equals, false) => vec![format!("value.String == {}", plain_value(el, true)?.term())],
+                    (StringCmp::Equals, true) => vec![format!("value.String != {}", plain_value(el, true)?.term())],
+                    (StringCmp::EqualsIgnoreCase, false) => {
+                        vec![format!("lower(value.String) == lower({})", plain_value(el, true)?.term())]
+                    }
+                    (StringCmp::EqualsIgnoreCase, true) => {
+                        vec![format!("lower(value.String) != lower({})", plain_value(el, true)?.term())]
+                    }
+                    (StringCmp::Like, false) => {
+                        vec![format!("regex.match({}, value.String)", regex_term(el, variables)?)]
+                    }
+                    (StringCmp::Like, true) => vec![
+                        "s := value.String".to_string(),
+                        format!("not regex.match({}, s)", regex_term(el, variables)?),
+                    ],
+                });
+            }
+            variant
+        }
+        ConditionOp::Arn(_, variant) => {
+            if variant.negated() {
+                push(vec!["s := value.String".to_string(), "not arn_parts(s)".to_string()]);
+            }
+
+            for el in allowed.iter() {
+                let parts = el.splitn(6, ':').collect::<Vec<&str>>();
+                if parts.len() != 6 || parts[0] != "arn" {
+                    continue;
+                }
+
+                let patterns = [
+                    regex_term(parts[1], false)?,
+                    regex_term(parts[2], false)?,
+                    regex_term(parts[3], false)?,
+                    regex_term(parts[4], false)?,
+                    regex_term(parts[5], variables)?,
+                ];
+                let check = format!("arn_like(parts, [{}])", patterns.join(", "));
+                push(vec![
+                    "parts := arn_parts(value.String)".to_string(),
+                    if variant.negated() {
+                        format!("not {check}")
+                    } else {
+                        check
+                    },
+                ]);
+            }
+            variant
+        }
+        ConditionOp::Numeric(cmp, variant) => {
+            let operator = match (cmp, variant.negated()) {
+                (NumericCmp::Equals, false) => "==",
+                (NumericCmp::Equals, true) => "!=",
+                (NumericCmp::LessThan, false) => "<",
+                (NumericCmp::LessThan, true) => ">=",
+                (NumericCmp::LessThanEquals, false) => "<=",
+                (NumericCmp::LessThanEquals, true) => ">",
+            };
+
+            for el in allowed.iter() {
+                let term = match plain_value(el, variables)? {
+                    Plain::Static(el) => match i64::from_str(&el) {
+                        Ok(number) => number.to_string(),
+                        Err(_) => continue,
+                    },
+                    Plain::Dynamic(term) => format!("parse_int({term})"),
+                };
+                push(vec!["n := numeric_value(value)".to_string(), format!("n {operator} {term}")]);
+            }
+            variant
+        }
+        ConditionOp::Date(cmp, variant) => {
+            let operator = match (cmp, variant.negated()) {
+                (DateCmp::Equals, false) => "==",
+                (DateCmp::Equals, true) => "!=",
+                (DateCmp::LessThan, false) => "<",
+                (DateCmp::LessThan, true) => ">=",
+                (DateCmp::LessThanEquals, false) => "<=",
+                (DateCmp::LessThanEquals, true) => ">",
+            };
+
+            // Strings that are not dates never match, except that they are never equal to anything.
+            if *cmp == DateCmp::Equals && variant.negated() {
+                push(vec!["s := value.String".to_string(), "not valid_date(s)".to_string()]);
+            }
+
+            for el in allowed.iter() {
+                let term = match plain_value(el, variables)? {
+                    Plain::Static(el) => match parse_date(&el) {
+                        Some(nanos) => nanos.to_string(),
+                        None => continue,
+                    },
+                    Plain::Dynamic(term) => format!("parse_date({term})"),
+                };
+                push(vec!["t := date_value(value)".to_string(), format!("t {operator} {term}")]);
+            }
+            variant
+        }
+        ConditionOp::Bool(variant) => {
+            for el in allowed.iter() {
+                match plain_value(el, variables)? {
+                    Plain::Static(el) => {
+                        if let Ok(b) = bool::from_str(&el) {
+                            push(vec![format!("value.Bool == {b}")]);
+                        }
+                    }
+                    Plain::Dynamic(term) => {
+                        push(vec!["b := value.Bool".to_string(), format!("sprintf(\"%v\", [b]) == {term}")])
+                    }
+                }
+            }
+            variant
+        }
+        ConditionOp::IpAddress(variant) => {
+            let not = if variant.negated() {
+                "not "
+            } else {
+                ""
+            };
+
+            for el in allowed.iter() {
+                match plain_value(el, variables)? {
+                    Plain::Static(el) => {
+                        let net = match el.parse::<IpNet>() {
+                            Ok(net) => net.trunc(),
+                            Err(_) => match el.parse::<IpAddr>() {
+                                Ok(addr) => IpNet::from(addr),
+                                Err(_) => continue,
+                            },
+                        };
+                        push(vec![
+                            "ip := value.IpAddr".to_string(),
+                            format!("{not}net.cidr_contains({}, ip)", quote(&net.to_string())),
+                        ]);
+                    }
+                    Plain::Dynamic(term) => push(vec![
+                        "ip := value.IpAddr".to_string(),
+                        format!("cidr := ip_cidr({term})"),
+                        "net.cidr_is_valid(cidr)".to_string(),
+                        format!("{not}net.cidr_contains(cidr, ip)"),
+                    ]),
+                }
+            }
+            variant
+        }
+        ConditionOp::Binary(variant) => {
+            for el in allowed.iter() {
+                if let Ok(bytes) = base64::decode(el) {
+                    let encoded = quote(&base64::encode(bytes));
+                    push(vec![format!("base64.decode(value.Binary) == base64.decode({encoded})")]);
+                    push(vec![format!("value.String == base64.decode({encoded})")]);
+                }
+            }
+            variant
+        }
+    };
+
+    if variant.if_exists() {
+        push(vec!["value == \"Null\"".to_string()]);
+    }
+
+    Ok(alternatives)
+}
+
+/// Parses a date condition value the way Aspen does, returning nanoseconds since the Unix epoch.
+fn parse_date(value: &str) -> Option<i64> {
+    let date = match DateTime::parse_from_rfc3339(value) {
+        Ok(date) => date.with_timezone(&Utc),
+        Err(_) => DateTime::from_timestamp(i64::from_str(value).ok()?, 0)?,
+    };
+    date.timestamp_nanos_opt()
+}
+
+/// A piece of a condition value or pattern after parsing policy variables.
+enum Piece {
+    /// Text that is already in its final form (regex-escaped for patterns).
+    Text(String),
+
+    /// A `${name}` policy variable, with the name lowercased.
+    Variable(String),
+}
+
+/// Splits a value into text and policy variables. `${*}`, `${$}`, and `${?}` are literal characters and are passed
+/// through `literal`; if `glob` is set, `*` and `?` become the equivalent regular expressions.
+fn pieces(
+    value: &str,
+    variables: bool,
+    literal: impl Fn(char) -> String,
+    glob: bool,
+) -> Result<Vec<Piece>, AspenError> {
+    let mut result = Vec::new();
+    let mut text = String::new();
+    let mut chars = value.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '$' if variables => {
+                if chars.next() != Some('{') {
+                    return Err(AspenError::InvalidSubstitution(value.to_string()));
+                }
+
+                let mut var = String::new();
+                loop {
+                    match chars.next() {
+                        None => return Err(AspenError::InvalidSubstitution(value.to_string())),
+                        Some('}') => break,
+                        Some(c) => var.push(c),
+                    }
+                }
+
+                match var.as_str() {
+                    "*" | "$" | "?" => text.push_str(&literal(var.chars().next().unwrap())),
+                    var => {
+                        if !text.is_empty() {
+                            result.push(Piece::Text(std::mem::take(&mut text)));
+                        }
+                        result.push(Piece::Variable(var.to_lowercase()));
+                    }
+                }
+            }
+            '*' if glob => text.push_str(".*"),
+            '?' if glob => text.push('.'),
+            c => text.push_str(&literal(c)),
+        }
+    }
+
+    if !text.is_empty() {
+        result.push(Piece::Text(text));
+    }
+
+    Ok(result)
+}
+
+/// Joins pieces into a Rego string expression, calling `variable` for each policy variable.
+fn join_pieces(pieces: Vec<Piece>, variable: &str) -> String {
+    if pieces.iter().all(|piece| matches!(piece, Piece::Text(_))) {
+        let text: String = pieces
+            .into_iter()
+            .map(|piece| match piece {
+                Piece::Text(text) => text,
+                Piece::Variable(_) => unreachable!(),
+            })
+            .collect();
+        return quote(&text);
+    }
+
+    let terms = pieces
+        .into_iter()
+        .map(|piece| match piece {
+            Piece::Text(text) => quote(&text),
+            Piece::Variable(name) => format!("{variable}({})", quote(&name)),
+        })
+        .collect::<Vec<_>>();
+    format!("concat(\"\", [{}])", terms.join(", "))
+}
+
+/// Returns a Rego expression for the anchored regular expression matching a glob pattern, substituting policy
+/// variables at evaluation time if `variables` is set.
+fn regex_term(pattern: &str, variables: bool) -> Result<String, AspenError> {
+    let mut pieces = pieces(pattern, variables, |c| regex::escape(&c.to_string()), true)?;
+    pieces.insert(0, Piece::Text("^".to_string()));
+    pieces.push(Piece::Text("$".to_string()));
+
+    // Merge adjacent text so static patterns become a single literal.
+    let mut merged: Vec<Piece> = Vec::with_capacity(pieces.len());
+    for piece in pieces {
+        match (merged.last_mut(), piece) {
+            (Some(Piece::Text(last)), Piece::Text(text)) => last.push_str(&text),
+            (_, piece) => merged.push(piece),
+        }
+    }
+
+    Ok(join_pieces(merged, "var_regex"))
+}
+
+/// A plain (non-pattern) condition value after resolving policy variables.
+enum Plain {
+    /// A value without variables, with the `${*}`, `${$}`, and `${?}` escapes resolved.
+    Static(String),
+
+    /// A Rego expression that substitutes the variables at evaluation time.
+    Dynamic(String),
+}
+
+impl Plain {
+    /// Returns the value as a Rego expression.
+    fn term(self) -> String {
+        match self {
+            Self::Static(value) => quote(&value),
+            Self::Dynamic(term) => term,
+        }
+    }
+}
+
+/// Resolves the policy variables in a plain condition value if `variables` is set.
+fn plain_value(value: &str, variables: bool) -> Result<Plain, AspenError> {
+    let pieces = pieces(value, variables, |c| c.to_string(), false)?;
+    if let [Piece::Text(text)] = pieces.as_slice() {
+        return Ok(Plain::Static(text.clone()));
+    }
+    if pieces.is_empty() {
+        return Ok(Plain::Static(String::new()));
+    }
+
+    Ok(Plain::Dynamic(join_pieces(pieces, "var_value")))
+}
+
+/// Quotes a string as a Rego string literal.
+fn quote(value: &str) -> String {
+    serde_json::to_string(value).expect("strings serialize")
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::RegoModule,
+        crate::{AspenError, Context, Policy, PolicySet, PolicySource},
+        pretty_assertions::assert_eq,
+        serde_json::Value,
+        std::{env, fs, process::Command, str::FromStr},
+    };
+
+    fn policy_set() -> PolicySet {
+        let identity = Policy::from_str(
+            r#"{"Version": "2012-10-17", "Statement": [
+                {"Sid": "Home", "Effect": "Allow", "Action": ["s3:Get*", "s3:PutObject"],
+                 "Resource": "arn:aws:s3:::bucket/${aws:username}/*"},
+                {"Sid": "List", "Effect": "Allow", "Action": "s3:ListBucket", "Resource": "arn:aws:s3:::bucket",
+                 "Condition": {"StringLike": {"s3:prefix": ["${aws:username}/*", "shared/?"]}}},
+                {"Sid": "Network", "Effect": "Deny", "NotAction": "sts:*", "Resource": "*",
+                 "Condition": {"NotIpAddressIfExists": {"aws:SourceIp": ["10.0.0.0/8", "192.168.1.1"]},
+                               "Bool": {"aws:ViaAWSService": "false"}}},
+                {"Sid": "Limits", "Effect": "Allow", "Action": "ec2:RunInstances", "NotResource": "arn:aws:ec2:*:*:image/*",
+                 "Condition": {"NumericLessThanEquals": {"ec2:InstanceCount": "4"},
+                               "DateLessThan": {"aws:CurrentTime": "2030-01-01T00:00:00Z"},
+                               "Null": {"aws:MultiFactorAuthAge": "false"},
+                               "ArnLike": {"aws:SourceArn": "arn:aws:cloudformation:*:123456789012:stack/*"}}}
+            ]}"#,
+        )
+        .unwrap();
+        let resource = Policy::from_str(
+            r#"{"Statement": [
+                {"Effect": "Allow", "Action": "sqs:SendMessage", "Resource": "*",
+                 "Principal": {"AWS": ["arn:aws:iam::123456789012:role/path/Sender", "555555555555"],
+                               "Service": "sns.amazonaws.com"}},
+                {"Effect": "Deny", "Action": "sqs:*", "Resource": "*", "NotPrincipal": {"AWS": "arn:aws:iam::123456789012:root"}}
+            ]}"#,
+        )
+        .unwrap();
+        let boundary = Policy::from_str(
+            r#"{"Statement": {"Effect": "Allow", "NotAction": "iam:*", "Resource": "*",
+                "Condition": {"StringNotEqualsIgnoreCaseIfExists": {"aws:RequestedRegion": "AP-SOUTH-1"}}}}"#,
+        )
+        .unwrap();
+
+        let mut policy_set = PolicySet::new();
+        policy_set
+            .add_policy(PolicySource::new_entity_inline("arn:aws:iam::123456789012:user/alice", "", "home"), identity);
+        policy_set
+            .add_policy(PolicySource::new_resource("arn:aws:sqs:us-east-1:123456789012:queue", None::<&str>), resource);
+        policy_set.add_policy(
+            PolicySource::new_permission_boundary("arn:aws:iam::123456789012:policy/b", "", "v1"),
+            boundary,
+        );
+        policy_set
+    }
+
+    fn contexts() -> Vec<Context> {
+        [
+            r#"{"Service": "s3", "Api": "GetObject", "Actor": [{"AWS": "arn:aws:iam::123456789012:user/alice"}],
+                "Resources": ["arn:aws:s3:::bucket/alice/notes.txt"], "SessionData": {"aws:username": {"String": "alice"}}}"#,
+            r#"{"Service": "s3", "Api": "GetObject", "Actor": [{"AWS": "arn:aws:iam::123456789012:user/alice"}],
+                "Resources": ["arn:aws:s3:::bucket/bob/notes.txt"], "SessionData": {"aws:username": {"String": "alice"}}}"#,
+            r#"{"Service": "s3", "Api": "GetObject", "Actor": [{"AWS": "arn:aws:iam::123456789012:user/alice"}],
+                "Resources": ["arn:aws:s3:::bucket/alice/notes.txt"],
+                "SessionData": {"aws:username": {"String": "alice"}, "aws:SourceIp": {"IpAddr": "203.0.113.1"},
+                                "aws:ViaAWSService": {"Bool": false}}}"#,
+            r#"{"Service": "s3", "Api": "GetObject", "Actor": [{"AWS": "arn:aws:iam::123456789012:user/alice"}],
+                "Resources": ["arn:aws:s3:::bucket/bob/notes.txt"],
+                "SessionData": {"aws:username": {"String": "alice"}, "aws:SourceIp": {"IpAddr": "10.1.2.3"},
+                                "aws:ViaAWSService": {"Bool": false}}}"#,
+            r#"{"Service": "s3", "Api": "ListBucket", "Resources": ["arn:aws:s3:::bucket"],
+                "SessionData": {"aws:username": {"String": "alice"}, "s3:prefix": {"String": "alice/docs"}}}"#,
+            r#"{"Service": "s3", "Api": "ListBucket", "Resources": ["arn:aws:s3:::bucket"],
+                "SessionData": {"s3:prefix": {"String": "shared/x"}}}"#,
+            r#"{"Service": "s3", "Api": "ListBucket", "Resources": ["arn:aws:s3:::bucket"],
+                "SessionData": {"s3:prefix": {"String": "shared/xy"}}}"#,
+            r#"{"Service": "ec2", "Api": "RunInstances", "Resources": ["arn:aws:ec2:us-east-1:123456789012:instance/*"],
+                "SessionData": {"ec2:InstanceCount": {"Integer": 2}, "aws:CurrentTime": {"Timestamp": "2024-05-01T00:00:00Z"},
+                                "aws:MultiFactorAuthAge": {"Integer": 30},
+                                "aws:SourceArn": {"String": "arn:aws:cloudformation:us-west-2:123456789012:stack/app/1"}}}"#,
+            r#"{"Service": "ec2", "Api": "RunInstances", "Resources": ["arn:aws:ec2:us-east-1::image/ami-1"],
+                "SessionData": {"ec2:InstanceCount": {"Integer": 2}, "aws:CurrentTime": {"Timestamp": "2024-05-01T00:00:00Z"},
+                                "aws:MultiFactorAuthAge": {"Integer": 30},
+                                "aws:SourceArn": {"String": "arn:aws:cloudformation:us-west-2:123456789012:stack/app/1"}}}"#,
+            r#"{"Service": "ec2", "Api": "RunInstances", "Resources": ["arn:aws:ec2:us-east-1:123456789012:instance/*"],
+                "SessionData": {"ec2:InstanceCount": {"String": "8"}, "aws:CurrentTime": {"Timestamp": "2024-05-01T00:00:00Z"},
+                                "aws:MultiFactorAuthAge": {"Integer": 30},
+                                "aws:SourceArn": {"String": "arn:aws:cloudformation:us-west-2:123456789012:stack/app/1"}}}"#,
+            r#"{"Service": "sqs", "Api": "SendMessage", "Actor": [{"AWS": "arn:aws:sts::123456789012:assumed-role/Sender/s1"}],
+                "Resources": ["arn:aws:sqs:us-east-1:123456789012:queue"]}"#,
+            r#"{"Service": "sqs", "Api": "SendMessage", "Actor": [{"AWS": "555555555555"}],
+                "Resources": ["arn:aws:sqs:us-east-1:123456789012:queue"]}"#,
+            r#"{"Service": "sqs", "Api": "SendMessage", "Actor": [{"Service": "sns.us-east-1.amazonaws.com"}],
+                "Resources": ["arn:aws:sqs:us-east-1:123456789012:queue"]}"#,
+            r#"{"Service": "sqs", "Api": "SendMessage", "Actor": [{"AWS": "arn:aws:sts::123456789012:assumed-role/Other/s1"}],
+                "Resources": ["arn:aws:sqs:us-east-1:123456789012:queue"]}"#,
+            r#"{"Service": "s3", "Api": "GetObject", "Actor": [{"AWS": "arn:aws:iam::123456789012:user/alice"}],
+                "Resources": ["arn:aws:s3:::bucket/alice/notes.txt"],
+                "SessionData": {"aws:username": {"String": "alice"}, "aws:RequestedRegion": {"String": "ap-south-1"}}}"#,
+            r#"{"Service": "iam", "Api": "CreateUser", "Actor": [{"AWS": "arn:aws:iam::123456789012:user/alice"}]}"#,
+        ]
+        .iter()
+        .map(|context| Context::from_str(context).unwrap())
+        .collect()
+    }
+
+    #[test_log::test]
+    fn test_export() {
+        let module = RegoModule::from_policy_set(&policy_set(), "aspen.test").unwrap();
+        assert_eq!(module.package(), "aspen.test");
+        assert_eq!(module.to_string(), module.text());
+
+        let text = module.text();
+        for expected in [
+            "package aspen.test\n\nimport rego.v1\n",
+            "denied if {\n\tpolicy_2 != \"Allow\"\n}\n",
+            "policy_0 := \"Allow\" if {\n\tpolicy_0_statement_0\n} else := \"Allow\" if {\n\tpolicy_0_statement_1\n} \
+             else := \"Deny\" if {\n\tpolicy_0_statement_2\n}",
+            "\tnot policy_0_statement_2_action\n",
+            r#"arn_like(arn_parts(r), ["^aws$", "^s3$", "^$", "^$", concat("", ["^bucket/", var_regex("aws:username"), "/.*$"])])"#,
+            r#"regex.match("^shared/.$", value.String)"#,
+            r#"not net.cidr_contains("192.168.1.1/32", ip)"#,
+            r#"n <= 4"#,
+            r#"t < 1893456000000000000"#,
+            r#"startswith(aws_identity_arn(identity), "arn:aws:sts::123456789012:assumed-role/Sender/")"#,
+            r#""sns.amazonaws.com" in service_names(identity)"#,
+            "\tnot policy_1_statement_1_principal\n",
+            r#"lower(value.String) != lower("AP-SOUTH-1")"#,
+        ] {
+            assert!(text.contains(expected), "{expected:?} not in {text}");
+        }
+
+        let mut bad = PolicySet::new();
+        bad.add_policy(
+            PolicySource::new_entity_inline("arn:aws:iam::123456789012:user/alice", "", "bad"),
+            Policy::from_str(
+                r#"{"Version": "2012-10-17", "Statement": {"Effect": "Allow", "Action": "s3:*", "Resource": "arn:aws:s3:::${oops"}}"#,
+            )
+            .unwrap(),
+        );
+        let e = RegoModule::from_policy_set(&bad, "aspen.test").unwrap_err();
+        assert_eq!(e, AspenError::InvalidSubstitution("${oops".to_string()));
+    }
+
+    /// Runs the same contexts through Aspen and OPA and checks that the decisions agree. This needs an `opa` binary,
+    /// so it is ignored by default; run it with `cargo test --all-features -- --ignored test_opa_conformance`, setting
+    /// `OPA` to the binary's path if it is not on the `PATH`. CI runs it against the OPA version pinned in
+    /// `.github/workflows/rust.yml`.
+    #[test_log::test]
+    #[ignore = "requires the opa binary"]
+    fn test_opa_conformance() {
+        let opa = env::var("OPA").unwrap_or_else(|_| "opa".to_string());
+        match Command::new(&opa).arg("version").output() {
+            Ok(output) if output.status.success() => (),
+            Ok(_) => panic!("{opa} version failed"),
+            Err(e) => panic!("Failed to run {opa}: {e}; set OPA to the path of the opa binary"),
+        }
+
+        let dir = env::temp_dir().join(format!("aspen-rego-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+
+        let policy_set = policy_set();
+        let module = RegoModule::from_policy_set(&policy_set, "aspen.test").unwrap();
+        let module_path = dir.join("aspen.rego");
+        fs::write(&module_path, module.text()).unwrap();
+
+        for (i, context) in contexts().iter().enumerate() {
+            let (expected, _) = policy_set.evaluate(context).unwrap();
+            let input_path = dir.join(format!("input-{i}.json"));
+            fs::write(&input_path, serde_json::to_string(context).unwrap()).unwrap();
+
+            let output = Command::new(&opa)
+                .args(["eval", "--format", "json", "--data"])
+                .arg(&module_path)
+                .arg("--input")
+                .arg(&input_path)
+                .arg("data.aspen.test.decision")
+                .output()
+                .unwrap();
+            assert!(output.status.success(), "opa eval failed: {}", String::from_utf8_lossy(&output.stderr));
+
+            let result: Value = serde_json::from_slice(&output.stdout).unwrap();
+            let actual = &result["result"][0]["expressions"][0]["value"];
+            assert_eq!(actual, &Value::String(expected.to_string()), "context {i}: {context:?}");
+        }
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test_log::test]
+    fn test_contexts_cover_every_decision() {
+        let policy_set = policy_set();
+        let decisions =
+            contexts().iter().map(|context| policy_set.evaluate(context).unwrap().0.to_string()).collect::<Vec<_>>();
+        assert_eq!(
+            decisions,
+            vec![
+                "Allow",
+                "DefaultDeny",
+                "Allow",
+                "Deny",
+                "Allow",
+                "Allow",
+                "DefaultDeny",
+                "Allow",
+                "DefaultDeny",
+                "DefaultDeny",
+                "Allow",
+                "Allow",
+                "Allow",
+                "DefaultDeny",
+                "Deny",
+                "Deny",
+            ]
+        );
+    }
+}
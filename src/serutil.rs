@@ -92,6 +92,35 @@ macro_rules! from_str_json {
     };
 }
 
+/// Implement YAML parsing and serialization for a given class.
+///
+/// YAML is parsed with the same `Deserialize` implementation used for JSON, so single-value vs. list forms,
+/// duplicate fields, and unknown fields are handled identically. Parse errors report the YAML line and column of
+/// the value being parsed.
+#[cfg(feature = "yaml")]
+#[macro_export]
+macro_rules! yaml_serde {
+    ($cls:ident) => {
+        impl $cls {
+            #[doc = concat!("Parse a [", stringify!($cls), "] from a YAML document.")]
+            pub fn from_yaml(s: &str) -> Result<Self, ::serde_yaml::Error> {
+                match ::serde_yaml::from_str::<Self>(s) {
+                    Ok(result) => Ok(result),
+                    Err(e) => {
+                        ::log::debug!("Failed to parse YAML: {}: {:?}", s, e);
+                        Err(e)
+                    }
+                }
+            }
+
+            #[doc = concat!("Serialize this [", stringify!($cls), "] to a YAML document.")]
+            pub fn to_yaml(&self) -> Result<String, ::serde_yaml::Error> {
+                ::serde_yaml::to_string(self)
+            }
+        }
+    };
+}
+
 /// The JSON representation of a list-like type.
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub enum JsonRep {
@@ -414,7 +443,6 @@ where
 mod tests {
     use {
         super::{simple_type_name, JsonRep, MapList},
-        crate::display_json,
         indoc::indoc,
         serde::{ser::Serializer, Deserialize, Serialize},
         std::panic::catch_unwind,
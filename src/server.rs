@@ -0,0 +1,664 @@
+use {
+    crate::{display_json, from_str_json, AspenError, Context, Decision, DecisionReason, PolicySource, PolicyStore},
+    log::{debug, warn},
+    serde::{Deserialize, Serialize},
+    serde_json::json,
+    std::{
+        fmt::Display,
+        io::{BufRead, BufReader, Error as IoError, ErrorKind, Read, Result as IoResult, Write},
+        net::{TcpListener, TcpStream},
+        path::Path,
+        str::FromStr,
+        sync::{mpsc::sync_channel, Arc, Mutex},
+        thread,
+        time::{Duration, Instant},
+    },
+};
+
+/// The largest request body the server accepts.
+const MAX_BODY_SIZE: usize = 1 << 20;
+
+/// The largest request line or header line the server accepts.
+const MAX_LINE_SIZE: usize = 8192;
+
+/// The largest number of headers the server accepts.
+const MAX_HEADERS: usize = 100;
+
+/// How long the server waits for a client to send its entire request.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How long the server pauses accepting connections when it runs out of file descriptors.
+const ACCEPT_BACKOFF: Duration = Duration::from_millis(100);
+
+/// The number of connections [DecisionService::serve] handles at once.
+pub const MAX_CONNECTIONS: usize = 64;
+
+/// A request for an authorization decision.
+///
+/// The serialized form is a JSON object with a `Context` (a serialized [Context]), an optional `PolicySet` naming the
+/// policy set to evaluate it against, and an optional `Explain` flag requesting the statements responsible for the
+/// decision:
+///
+/// ```json
+/// {
+///     "PolicySet": "s3-readers",
+///     "Context": {"Service": "s3", "Api": "GetObject", "Resources": ["arn:aws:s3:::bucket/key"]},
+///     "Explain": true
+/// }
+/// ```
+///
+/// The policy set may be omitted if the service has exactly one.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(deny_unknown_fields, rename_all = "PascalCase")]
+pub struct DecisionRequest {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    policy_set: Option<String>,
+    context: Context,
+    #[serde(default)]
+    explain: bool,
+}
+
+impl DecisionRequest {
+    /// Creates a new decision request.
+    pub fn new(policy_set: Option<String>, context: Context, explain: bool) -> Self {
+        Self {
+            policy_set,
+            context,
+            explain,
+        }
+    }
+
+    /// Returns the name of the policy set to evaluate the request against, if specified.
+    #[inline]
+    pub fn policy_set(&self) -> Option<&str> {
+        self.policy_set.as_deref()
+    }
+
+    /// Returns the request context.
+    #[inline]
+    pub fn context(&self) -> &Context {
+        &self.context
+    }
+
+    /// Indicates whether the statements responsible for the decision should be returned.
+    #[inline]
+    pub fn explain(&self) -> bool {
+        self.explain
+    }
+}
+
+from_str_json!(DecisionRequest);
+
+/// The response to a [DecisionRequest].
+///
+/// `Sources` lists the policies responsible for the decision (see [PolicySet::evaluate](crate::PolicySet::evaluate)). `Reasons` is present only
+/// if an explanation was requested, and lists the responsible statements (see [PolicySet::explain](crate::PolicySet::explain)).
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct DecisionResponse {
+    decision: Decision,
+    sources: Vec<PolicySource>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reasons: Option<Vec<DecisionReason>>,
+}
+
+impl DecisionResponse {
+    /// Returns the decision.
+    #[inline]
+    pub fn decision(&self) -> Decision {
+        self.decision
+    }
+
+    /// Returns the sources of the policies responsible for the decision.
+    #[inline]
+    pub fn sources(&self) -> &[PolicySource] {
+        &self.sources
+    }
+
+    /// Returns the statements responsible for the decision, if an explanation was requested.
+    #[inline]
+    pub fn reasons(&self) -> Option<&[DecisionReason]> {
+        self.reasons.as_deref()
+    }
+}
+
+display_json!(DecisionResponse);
+
+/// An authorization decision service backed by the policy sets in a [PolicyStore].
+///
+/// Policies can be reloaded at any time with [DecisionService::reload] or by watching the store (see
+/// [PolicyStore::watch]). A reload replaces the policy sets atomically, and only if every file loads; requests that
+/// are already being evaluated finish against the policy sets they started with.
+///
+/// The service can be exposed over HTTP with [DecisionService::serve], which handles up to [MAX_CONNECTIONS]
+/// connections at once, or embedded in another server through [DecisionService::handle]. The HTTP interface has these endpoints:
+///
+/// * `GET /health` returns `{"Status": "Ok", "Generation": n, "PolicySets": [...]}`, where the generation counts
+///   successful loads.
+/// * `POST /v1/decide` takes a serialized [DecisionRequest] and returns a serialized [DecisionResponse].
+/// * `POST /v1/reload` reloads the policy directory and returns the new generation and policy set names.
+///
+/// Errors are returned as `{"Error": "..."}` with a 4xx or 5xx status.
+#[derive(Debug)]
+pub struct DecisionService {
+    store: Arc<PolicyStore>,
+}
+
+impl DecisionService {
+    /// Creates a decision service backed by a policy store.
+    pub fn new(store: Arc<PolicyStore>) -> Self {
+        Self {
+            store,
+        }
+    }
+
+    /// Creates a decision service from a policy store directory.
+    ///
+    /// # Errors
+    ///
+    /// See [PolicyStore::load].
+    pub fn load<P: AsRef<Path>>(dir: P) -> Result<Self, AspenError> {
+        Ok(Self::new(Arc::new(PolicyStore::load(dir)?)))
+    }
+
+    /// Returns the policy store.
+    #[inline]
+    pub fn store(&self) -> &Arc<PolicyStore> {
+        &self.store
+    }
+
+    /// Returns the names of the loaded policy sets, in sorted order.
+    pub fn policy_set_names(&self) -> Vec<String> {
+        self.store.snapshot().policy_sets().keys().cloned().collect()
+    }
+
+    /// Reloads the policy store, returning the new generation.
+    ///
+    /// # Errors
+    ///
+    /// See [PolicyStore::reload]. If the store fails to load, the previously loaded policy sets remain in use.
+    pub fn reload(&self) -> Result<u64, AspenError> {
+        self.store.reload()
+    }
+
+    /// Evaluates a decision request.
+    ///
+    /// # Errors
+    ///
+    /// If the request names a policy set that is not loaded (or names none and the service does not have exactly
+    /// one), [AspenError::UnknownPolicySet] is returned. If the policy set fails to evaluate, the evaluation error is
+    /// returned.
+    pub fn decide(&self, request: &DecisionRequest) -> Result<DecisionResponse, AspenError> {
+        // Hold a reference to the current snapshot so a concurrent reload cannot change the policies mid-request.
+        let snapshot = self.store.snapshot();
+        let policy_set = match request.policy_set() {
+            Some(name) => snapshot.policy_set(name),
+            None if snapshot.policy_sets().len() == 1 => snapshot.policy_sets().values().next(),
+            None => None,
+        }
+        .ok_or_else(|| AspenError::UnknownPolicySet(request.policy_set().unwrap_or_default().to_string()))?;
+
+        let (decision, sources) = policy_set.evaluate(request.context())?;
+        let reasons = if request.explain() {
+            Some(policy_set.explain(request.context())?.1)
+        } else {
+            None
+        };
+
+        Ok(DecisionResponse {
+            decision,
+            sources: sources.into_iter().cloned().collect(),
+            reasons,
+        })
+    }
+
+    /// Handles an HTTP request, returning the status code and JSON response body.
+    ///
+    /// `target` is the request target (path and optional query string) and `body` is the request body.
+    pub fn handle(&self, method: &str, target: &str, body: &[u8]) -> (u16, String) {
+        let path = target.split_once('?').map(|(path, _)| path).unwrap_or(target);
+
+        match (method, path) {
+            ("GET" | "HEAD", "/health") => {
+                let snapshot = self.store.snapshot();
+                let names: Vec<&String> = snapshot.policy_sets().keys().collect();
+                (200, json!({"Status": "Ok", "Generation": snapshot.generation(), "PolicySets": names}).to_string())
+            }
+            ("POST", "/v1/decide") => {
+                let request = match std::str::from_utf8(body)
+                    .map_err(|e| e.to_string())
+                    .and_then(|body| DecisionRequest::from_str(body).map_err(|e| e.to_string()))
+                {
+                    Ok(request) => request,
+                    Err(e) => return error(400, e),
+                };
+
+                match self.decide(&request) {
+                    Ok(response) => (200, serde_json::to_string(&response).expect("responses serialize")),
+                    Err(e @ AspenError::UnknownPolicySet(_)) => error(404, e),
+                    Err(e) => error(400, e),
+                }
+            }
+            ("POST", "/v1/reload") => match self.reload() {
+                Ok(generation) => {
+                    (200, json!({"Generation": generation, "PolicySets": self.policy_set_names()}).to_string())
+                }
+                Err(e) => error(500, e),
+            },
+            (_, "/health" | "/v1/decide" | "/v1/reload") => error(405, format!("Method not allowed: {method}")),
+            _ => error(404, format!("Not found: {path}")),
+        }
+    }
+
+    /// Serves HTTP requests from a listener on a fixed pool of [MAX_CONNECTIONS] worker threads.
+    ///
+    /// Each connection carries a single request, which the client must send in full within 30 seconds. When every
+    /// worker is busy, the server stops accepting connections until one frees up; pending clients wait in the
+    /// listener's backlog.
+    ///
+    /// Connections that fail while being accepted are skipped, and accepting pauses briefly if the process runs out of
+    /// file descriptors. This only returns if the listener itself fails.
+    pub fn serve(self: &Arc<Self>, listener: TcpListener) -> IoResult<()> {
+        let (sender, receiver) = sync_channel::<TcpStream>(0);
+        let receiver = Arc::new(Mutex::new(receiver));
+
+        for _ in 0..MAX_CONNECTIONS {
+            let service = Arc::clone(self);
+            let receiver = Arc::clone(&receiver);
+            thread::spawn(move || loop {
+                // The lock is released before the connection is handled. A closed channel means serve has returned.
+                let stream = match receiver.lock().unwrap_or_else(|e| e.into_inner()).recv() {
+                    Ok(stream) => stream,
+                    Err(_) => return,
+                };
+                let peer = stream.peer_addr().map(|a| a.to_string()).unwrap_or_else(|_| "unknown peer".to_string());
+                if let Err(e) = service.handle_connection(stream, REQUEST_TIMEOUT) {
+                    debug!("Connection from {peer} failed: {e}");
+                }
+            });
+        }
+
+        loop {
+            let stream = match listener.accept() {
+                Ok((stream, _)) => stream,
+                Err(e) if is_connection_error(&e) => {
+                    debug!("Failed to accept a connection: {e}");
+                    continue;
+                }
+                Err(e) if is_fd_exhausted(&e) => {
+                    warn!("Failed to accept a connection: {e}");
+                    thread::sleep(ACCEPT_BACKOFF);
+                    continue;
+                }
+                Err(e) => return Err(e),
+            };
+
+            // Blocks until a worker is free. Workers only exit once the sender is dropped.
+            sender.send(stream).expect("connection workers exited");
+        }
+    }
+
+    /// Reads a request from the stream and writes the response. The connection is abandoned if the request has not
+    /// been read within `timeout`.
+    fn handle_connection(&self, stream: TcpStream, timeout: Duration) -> IoResult<()> {
+        let mut writer = stream.try_clone()?;
+        let mut reader = BufReader::new(DeadlineReader {
+            stream,
+            deadline: Instant::now() + timeout,
+        });
+
+        let (status, body, head) = match read_request(&mut reader, &mut writer)? {
+            Ok(request) => {
+                let (status, body) = self.handle(&request.method, &request.target, &request.body);
+                (status, body, request.method == "HEAD")
+            }
+            Err((status, message)) => {
+                let (status, body) = error(status, message);
+                (status, body, false)
+            }
+        };
+
+        if status >= 500 {
+            warn!("{status}: {body}");
+        }
+
+        write!(
+            writer,
+            "HTTP/1.1 {status} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+            reason_phrase(status),
+            body.len()
+        )?;
+        if !head {
+            writer.write_all(body.as_bytes())?;
+        }
+        writer.flush()
+    }
+}
+
+/// A stream whose reads fail once a deadline passes, so a client cannot hold a worker by trickling its request.
+struct DeadlineReader {
+    stream: TcpStream,
+    deadline: Instant,
+}
+
+impl Read for DeadlineReader {
+    fn read(&mut self, buf: &mut [u8]) -> IoResult<usize> {
+        let remaining = self.deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return Err(ErrorKind::TimedOut.into());
+        }
+
+        self.stream.set_read_timeout(Some(remaining))?;
+        self.stream.read(buf)
+    }
+}
+
+/// Indicates whether an accept error only affects the connection being accepted.
+fn is_connection_error(e: &IoError) -> bool {
+    matches!(
+        e.kind(),
+        ErrorKind::ConnectionAborted | ErrorKind::ConnectionReset | ErrorKind::Interrupted | ErrorKind::TimedOut
+    )
+}
+
+/// Indicates whether an accept error is `EMFILE` or `ENFILE`: the process or the system is out of file descriptors.
+fn is_fd_exhausted(e: &IoError) -> bool {
+    #[cfg(unix)]
+    const CODES: &[i32] = &[23, 24];
+    #[cfg(windows)]
+    const CODES: &[i32] = &[10024];
+    #[cfg(not(any(unix, windows)))]
+    const CODES: &[i32] = &[];
+
+    e.raw_os_error().is_some_and(|code| CODES.contains(&code))
+}
+
+/// A parsed HTTP request.
+struct HttpRequest {
+    method: String,
+    target: String,
+    body: Vec<u8>,
+}
+
+/// Reads an HTTP/1.x request. Malformed requests are returned as the status code and message to respond with; I/O
+/// errors abandon the connection.
+fn read_request<R: BufRead, W: Write>(reader: &mut R, writer: &mut W) -> IoResult<Result<HttpRequest, (u16, String)>> {
+    let request_line = match read_line(reader)? {
+        Some(line) => line,
+        None => return Ok(Err((400, "Request line too long".to_string()))),
+    };
+
+    let mut parts = request_line.split_ascii_whitespace();
+    let (method, target) = match (parts.next(), parts.next(), parts.next(), parts.next()) {
+        (Some(method), Some(target), Some(version), None) if version.starts_with("HTTP/1.") => {
+            (method.to_string(), target.to_string())
+        }
+        _ => return Ok(Err((400, format!("Malformed request line: {request_line}")))),
+    };
+
+    let mut content_length = 0;
+    let mut expect_continue = false;
+    for _ in 0..=MAX_HEADERS {
+        let line = match read_line(reader)? {
+            Some(line) => line,
+            None => return Ok(Err((400, "Header line too long".to_string()))),
+        };
+        if line.is_empty() {
+            if expect_continue {
+                writer.write_all(b"HTTP/1.1 100 Continue\r\n\r\n")?;
+                writer.flush()?;
+            }
+
+            let mut body = vec![0; content_length];
+            reader.read_exact(&mut body)?;
+            return Ok(Ok(HttpRequest {
+                method,
+                target,
+                body,
+            }));
+        }
+
+        let (name, value) = match line.split_once(':') {
+            Some((name, value)) => (name.trim(), value.trim()),
+            None => return Ok(Err((400, format!("Malformed header: {line}")))),
+        };
+
+        if name.eq_ignore_ascii_case("content-length") {
+            content_length = match value.parse() {
+                Ok(length) if length <= MAX_BODY_SIZE => length,
+                Ok(_) => return Ok(Err((413, format!("Request body exceeds {MAX_BODY_SIZE} bytes")))),
+                Err(_) => return Ok(Err((400, format!("Invalid Content-Length: {value}")))),
+            };
+        } else if name.eq_ignore_ascii_case("transfer-encoding") {
+            return Ok(Err((411, "Chunked requests are not supported; send a Content-Length".to_string())));
+        } else if name.eq_ignore_ascii_case("expect") && value.eq_ignore_ascii_case("100-continue") {
+            expect_continue = true;
+        }
+    }
+
+    Ok(Err((400, "Too many headers".to_string())))
+}
+
+/// Reads a CRLF- or LF-terminated line, returning `None` if it exceeds [MAX_LINE_SIZE].
+fn read_line<R: BufRead>(reader: &mut R) -> IoResult<Option<String>> {
+    let mut line = Vec::new();
+    reader.by_ref().take(MAX_LINE_SIZE as u64 + 1).read_until(b'\n', &mut line)?;
+    if line.len() > MAX_LINE_SIZE {
+        return Ok(None);
+    }
+
+    if line.last() != Some(&b'\n') {
+        return Err(ErrorKind::UnexpectedEof.into());
+    }
+
+    Ok(Some(String::from_utf8_lossy(&line).trim_end_matches(['\r', '\n']).to_string()))
+}
+
+fn reason_phrase(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        400 => "Bad Request",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        411 => "Length Required",
+        413 => "Payload Too Large",
+        _ => "Internal Server Error",
+    }
+}
+
+fn error<E: Display>(status: u16, e: E) -> (u16, String) {
+    (status, json!({ "Error": e.to_string() }).to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::{is_connection_error, is_fd_exhausted},
+        crate::{AspenError, Context, Decision, DecisionRequest, DecisionService, MAX_CONNECTIONS},
+        pretty_assertions::assert_eq,
+        serde_json::Value,
+        std::{
+            env, fs,
+            io::{Error as IoError, ErrorKind, Read, Write},
+            net::{TcpListener, TcpStream},
+            path::PathBuf,
+            str::FromStr,
+            sync::Arc,
+            thread,
+            time::{Duration, Instant},
+        },
+    };
+
+    const READ_POLICY: &str = r#"{"Statement": {"Sid": "Read", "Effect": "Allow", "Action": "s3:Get*", "Resource": "arn:aws:s3:::bucket/*"}}"#;
+
+    const GET_OBJECT: &str = r#"{"Service": "s3", "Api": "GetObject", "Resources": ["arn:aws:s3:::bucket/key"]}"#;
+
+    fn policy_dir(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("aspen-server-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("teams")).unwrap();
+        fs::write(dir.join("teams").join("reader.json"), READ_POLICY).unwrap();
+        fs::write(
+            dir.join("boundary.json"),
+            r#"[{"Source": {"Type": "PermissionBoundary", "PolicyArn": "arn:aws:iam::123456789012:policy/b",
+                            "PolicyId": "ANPAEXAMPLE", "Version": "v1"},
+                 "Policy": {"Statement": {"Effect": "Allow", "Action": "ec2:*", "Resource": "*"}}}]"#,
+        )
+        .unwrap();
+        fs::write(dir.join("reader.test.json"), "not a policy").unwrap();
+        dir
+    }
+
+    fn body(response: &(u16, String)) -> Value {
+        serde_json::from_str(&response.1).unwrap()
+    }
+
+    #[test_log::test]
+    fn test_handle() {
+        let dir = policy_dir("handle");
+        let service = DecisionService::load(&dir).unwrap();
+        assert_eq!(service.store().dir(), dir.as_path());
+        assert_eq!(service.policy_set_names(), vec!["boundary".to_string(), "teams/reader".to_string()]);
+
+        let health = service.handle("GET", "/health", b"");
+        assert_eq!(health.0, 200);
+        assert_eq!(body(&health)["Generation"], 1);
+
+        let request = format!(r#"{{"PolicySet": "teams/reader", "Context": {GET_OBJECT}, "Explain": true}}"#);
+        let response = service.handle("POST", "/v1/decide", request.as_bytes());
+        assert_eq!(response.0, 200);
+        let response = body(&response);
+        assert_eq!(response["Decision"], "Allow");
+        assert_eq!(response["Sources"][0]["PolicyName"], "teams/reader");
+        assert_eq!(response["Reasons"][0]["Sid"], "Read");
+
+        let request = DecisionRequest::new(Some("boundary".to_string()), Context::from_str(GET_OBJECT).unwrap(), false);
+        let response = service.decide(&request).unwrap();
+        assert_eq!(response.decision(), Decision::Deny);
+        assert_eq!(response.sources()[0].to_string(), "permissions boundary arn:aws:iam::123456789012:policy/b (v1)");
+        assert!(response.reasons().is_none());
+
+        let request = DecisionRequest::new(None, Context::from_str(GET_OBJECT).unwrap(), false);
+        assert_eq!(service.decide(&request).unwrap_err(), AspenError::UnknownPolicySet("".to_string()));
+
+        let request = format!(r#"{{"PolicySet": "missing", "Context": {GET_OBJECT}}}"#);
+        assert_eq!(service.handle("POST", "/v1/decide", request.as_bytes()).0, 404);
+        assert_eq!(service.handle("POST", "/v1/decide", b"{").0, 400);
+        assert_eq!(service.handle("GET", "/v1/decide", b"").0, 405);
+        assert_eq!(service.handle("GET", "/", b"").0, 404);
+
+        // A failed reload keeps the old policies.
+        fs::write(dir.join("broken.json"), "{").unwrap();
+        let response = service.handle("POST", "/v1/reload", b"");
+        assert_eq!(response.0, 500);
+        assert!(body(&response)["Error"].as_str().unwrap().starts_with("Invalid policy file: "));
+        assert_eq!(service.store().generation(), 1);
+
+        fs::remove_file(dir.join("broken.json")).unwrap();
+        fs::remove_file(dir.join("boundary.json")).unwrap();
+        let response = service.handle("POST", "/v1/reload", b"");
+        assert_eq!(response.0, 200);
+        assert_eq!(body(&response)["Generation"], 2);
+        assert_eq!(body(&response)["PolicySets"], serde_json::json!(["teams/reader"]));
+
+        // With a single policy set, the name may be omitted.
+        let request = DecisionRequest::new(None, Context::from_str(GET_OBJECT).unwrap(), false);
+        assert_eq!(service.decide(&request).unwrap().decision(), Decision::Allow);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test_log::test]
+    fn test_serve() {
+        let dir = policy_dir("serve");
+        let service = Arc::new(DecisionService::load(&dir).unwrap());
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = Arc::clone(&service);
+        thread::spawn(move || server.serve(listener));
+
+        let send = |request: &str| {
+            let mut stream = TcpStream::connect(addr).unwrap();
+            stream.write_all(request.as_bytes()).unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).unwrap();
+            response
+        };
+
+        let body = format!(r#"{{"PolicySet": "teams/reader", "Context": {GET_OBJECT}}}"#);
+        let response = send(&format!(
+            "POST /v1/decide HTTP/1.1\r\nHost: localhost\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{body}",
+            body.len()
+        ));
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{response}");
+        assert!(response.ends_with(r#"{"Decision":"Allow","Sources":[{"Type":"EntityInline","EntityArn":"arn:aws:iam::000000000000:user/aspen-standalone","EntityId":"","PolicyName":"teams/reader"}]}"#), "{response}");
+
+        let response = send("GET /health HTTP/1.1\r\nHost: localhost\r\n\r\n");
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{response}");
+        assert!(response.contains(r#""Status":"Ok""#), "{response}");
+
+        let response = send("POST /v1/decide HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n");
+        assert!(response.starts_with("HTTP/1.1 411 Length Required\r\n"), "{response}");
+
+        let response = send("garbage\r\n\r\n");
+        assert!(response.starts_with("HTTP/1.1 400 Bad Request\r\n"), "{response}");
+
+        // Idle connections occupy every worker, so a further request waits until one of them closes.
+        let mut idle: Vec<_> = (0..MAX_CONNECTIONS).map(|_| TcpStream::connect(addr).unwrap()).collect();
+        let mut stream = TcpStream::connect(addr).unwrap();
+        stream.write_all(b"GET /health HTTP/1.1\r\n\r\n").unwrap();
+        stream.set_read_timeout(Some(Duration::from_millis(200))).unwrap();
+        assert!(stream.read(&mut [0; 1]).is_err());
+
+        idle.pop();
+        stream.set_read_timeout(None).unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{response}");
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test_log::test]
+    fn test_request_deadline() {
+        let dir = policy_dir("deadline");
+        let service = DecisionService::load(&dir).unwrap();
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        // Each byte arrives well within the timeout, but the request never completes.
+        let client = thread::spawn(move || {
+            let mut stream = TcpStream::connect(addr).unwrap();
+            stream.write_all(b"GET /health HTTP/1.1\r\n").unwrap();
+            for _ in 0..500 {
+                if stream.write_all(b"X").is_err() {
+                    break;
+                }
+                thread::sleep(Duration::from_millis(10));
+            }
+        });
+
+        let (stream, _) = listener.accept().unwrap();
+        let start = Instant::now();
+        let e = service.handle_connection(stream, Duration::from_millis(200)).unwrap_err();
+        assert!(matches!(e.kind(), ErrorKind::TimedOut | ErrorKind::WouldBlock), "{e}");
+        assert!(start.elapsed() < Duration::from_secs(2));
+        client.join().unwrap();
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test_log::test]
+    fn test_accept_errors() {
+        assert!(is_connection_error(&IoError::from(ErrorKind::ConnectionAborted)));
+        assert!(!is_connection_error(&IoError::from(ErrorKind::InvalidInput)));
+        assert!(!is_fd_exhausted(&IoError::from(ErrorKind::ConnectionAborted)));
+
+        #[cfg(unix)]
+        {
+            assert!(is_fd_exhausted(&IoError::from_raw_os_error(24)));
+            assert!(is_fd_exhausted(&IoError::from_raw_os_error(23)));
+            assert!(!is_fd_exhausted(&IoError::from_raw_os_error(22)));
+        }
+    }
+}
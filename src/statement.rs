@@ -11,6 +11,10 @@ use {
     std::fmt::{Formatter, Result as FmtResult},
 };
 
+/// The fields allowed in a statement.
+pub(crate) const STATEMENT_FIELDS: [&str; 9] =
+    ["Sid", "Effect", "Action", "NotAction", "Resource", "NotResource", "Principal", "NotPrincipal", "Condition"];
+
 /// An Aspen policy statement.
 ///
 /// Statement structs are immutable after creation. They can be created using the [StatementBuilder].
@@ -257,6 +261,8 @@ impl Statement {
 
 display_json!(Statement);
 from_str_json!(Statement);
+#[cfg(feature = "yaml")]
+crate::yaml_serde!(Statement);
 
 impl<'de> Deserialize<'de> for Statement {
     fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
@@ -359,27 +365,14 @@ impl<'de> Visitor<'de> for StatementVisitor {
                     builder.condition(access.next_value::<Condition>()?);
                 }
                 _ => {
-                    return Err(serde::de::Error::unknown_field(
-                        key,
-                        &[
-                            "Sid",
-                            "Effect",
-                            "Action",
-                            "NotAction",
-                            "Resource",
-                            "NotResource",
-                            "Principal",
-                            "NotPrincipal",
-                            "Condition",
-                        ],
-                    ));
+                    return Err(serde::de::Error::unknown_field(key, &STATEMENT_FIELDS));
                 }
             }
         }
 
         builder.build().map_err(|e| match e {
             StatementBuilderError::ValidationError(s) => {
-                let msg2 = s.replace('.', ";").trim_end_matches(|c| c == ';').to_string();
+                let msg2 = s.replace('.', ";").trim_end_matches(';').to_string();
                 serde::de::Error::custom(StatementBuilderError::ValidationError(msg2))
             }
             _ => serde::de::Error::custom(e),
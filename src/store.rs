@@ -0,0 +1,671 @@
+use {
+    crate::{AspenError, LintSeverity, Policy, PolicyLint, PolicySet, PolicySource, TEST_SUITE_SUFFIX},
+    arc_swap::ArcSwap,
+    log::{debug, warn},
+    serde::{Deserialize, Serialize},
+    serde_json::Value,
+    std::{
+        collections::BTreeMap,
+        fmt::Display,
+        fs,
+        path::{Path, PathBuf},
+        str::FromStr,
+        sync::{
+            mpsc::{channel, RecvTimeoutError, Sender},
+            Arc, Mutex, Weak,
+        },
+        thread::{self, JoinHandle},
+        time::{Duration, SystemTime},
+    },
+};
+
+/// The subdirectory of a policy store directory that holds managed policies.
+pub const MANAGED_POLICY_DIR: &str = "policies";
+
+/// A managed policy with one or more versions, one of which is the default.
+///
+/// This mirrors an IAM managed policy: the policy is identified by its ARN and IAM ID, and the version used when the
+/// policy is attached to an entity is recorded in [PolicySource::EntityAttachedPolicy] (and the other attached
+/// policy sources).
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ManagedPolicy {
+    policy_arn: String,
+    policy_id: String,
+    default_version: String,
+    versions: BTreeMap<String, Policy>,
+}
+
+/// The serialized form of a [ManagedPolicy].
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "PascalCase")]
+struct ManagedPolicyRepr {
+    #[serde(default)]
+    policy_arn: Option<String>,
+    #[serde(default)]
+    policy_id: String,
+    #[serde(default)]
+    default_version: Option<String>,
+    versions: BTreeMap<String, Policy>,
+}
+
+impl ManagedPolicy {
+    /// Creates a new managed policy.
+    ///
+    /// # Errors
+    ///
+    /// If `default_version` is not one of the versions, [AspenError::InvalidPolicyVersion] is returned.
+    pub fn new<S1, S2, S3>(
+        policy_arn: S1,
+        policy_id: S2,
+        default_version: S3,
+        versions: BTreeMap<String, Policy>,
+    ) -> Result<Self, AspenError>
+    where
+        S1: Into<String>,
+        S2: Into<String>,
+        S3: Into<String>,
+    {
+        let default_version = default_version.into();
+        if !versions.contains_key(&default_version) {
+            return Err(AspenError::InvalidPolicyVersion(default_version));
+        }
+
+        Ok(Self {
+            policy_arn: policy_arn.into(),
+            policy_id: policy_id.into(),
+            default_version,
+            versions,
+        })
+    }
+
+    /// Returns the ARN of the policy.
+    #[inline]
+    pub fn policy_arn(&self) -> &str {
+        &self.policy_arn
+    }
+
+    /// Returns the IAM ID of the policy.
+    #[inline]
+    pub fn policy_id(&self) -> &str {
+        &self.policy_id
+    }
+
+    /// Returns the default version of the policy.
+    #[inline]
+    pub fn default_version(&self) -> &str {
+        &self.default_version
+    }
+
+    /// Returns the versions of the policy, keyed by version id.
+    #[inline]
+    pub fn versions(&self) -> &BTreeMap<String, Policy> {
+        &self.versions
+    }
+
+    /// Returns the default version's policy document.
+    pub fn policy(&self) -> &Policy {
+        &self.versions[&self.default_version]
+    }
+
+    /// Returns the policy document of the specified version, if it exists.
+    pub fn version(&self, version: &str) -> Option<&Policy> {
+        self.versions.get(version)
+    }
+
+    /// Returns the source of this policy when attached to an IAM entity, using the specified version (or the default
+    /// version if `None`). `None` is returned if the version does not exist.
+    pub fn entity_attached_source(&self, version: Option<&str>) -> Option<PolicySource> {
+        let version = version.unwrap_or(&self.default_version);
+        self.versions.contains_key(version).then(|| {
+            PolicySource::new_entity_attached_policy(self.policy_arn.as_str(), self.policy_id.as_str(), version)
+        })
+    }
+}
+
+/// An entry in a policy set file. The policy may be omitted if the source refers to a managed policy.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "PascalCase")]
+struct PolicySetFileEntry {
+    source: PolicySource,
+    #[serde(default)]
+    policy: Option<Policy>,
+}
+
+/// An immutable set of named managed policies and policy sets published by a [PolicyStore].
+#[derive(Debug, Default)]
+pub struct PolicySnapshot {
+    generation: u64,
+    policies: BTreeMap<String, ManagedPolicy>,
+    policy_sets: BTreeMap<String, PolicySet>,
+}
+
+impl PolicySnapshot {
+    /// Loads and validates the managed policies and policy sets in a directory without publishing them.
+    ///
+    /// See [PolicyStore] for the directory layout. The returned snapshot has a generation of 0.
+    ///
+    /// # Errors
+    ///
+    /// If the directory cannot be read, a file cannot be parsed, a policy has lint errors (see [PolicyLint]), or a
+    /// policy set refers to a managed policy or version that does not exist, [AspenError::InvalidPolicyFile] is
+    /// returned.
+    pub fn load<P: AsRef<Path>>(dir: P) -> Result<Self, AspenError> {
+        let dir = dir.as_ref();
+        let managed_dir = dir.join(MANAGED_POLICY_DIR);
+
+        let mut policies: BTreeMap<String, ManagedPolicy> = BTreeMap::new();
+        if managed_dir.is_dir() {
+            for path in find_json_files(&managed_dir, None)? {
+                let name = entry_name(&managed_dir, &path);
+                let policy = load_managed_policy(&name, &path)?;
+                if let Some((other, _)) = policies.iter().find(|(_, p)| p.policy_arn == policy.policy_arn) {
+                    return Err(invalid(
+                        &path,
+                        format!("duplicate policy ARN {} (also used by {other})", policy.policy_arn),
+                    ));
+                }
+                policies.insert(name, policy);
+            }
+        }
+
+        let mut policy_sets = BTreeMap::new();
+        for path in find_json_files(dir, Some(&managed_dir))? {
+            let name = entry_name(dir, &path);
+            let policy_set = load_policy_set(&name, &path, &policies)?;
+            policy_sets.insert(name, policy_set);
+        }
+
+        Ok(Self {
+            generation: 0,
+            policies,
+            policy_sets,
+        })
+    }
+
+    /// Returns the number of times the store had published a snapshot when this one was published.
+    #[inline]
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// Returns the managed policies, keyed by name.
+    #[inline]
+    pub fn policies(&self) -> &BTreeMap<String, ManagedPolicy> {
+        &self.policies
+    }
+
+    /// Returns the named managed policy, if it exists.
+    pub fn policy(&self, name: &str) -> Option<&ManagedPolicy> {
+        self.policies.get(name)
+    }
+
+    /// Returns the managed policy with the specified ARN, if it exists.
+    pub fn policy_by_arn(&self, policy_arn: &str) -> Option<&ManagedPolicy> {
+        self.policies.values().find(|policy| policy.policy_arn == policy_arn)
+    }
+
+    /// Returns the policy sets, keyed by name.
+    #[inline]
+    pub fn policy_sets(&self) -> &BTreeMap<String, PolicySet> {
+        &self.policy_sets
+    }
+
+    /// Returns the named policy set, if it exists.
+    pub fn policy_set(&self, name: &str) -> Option<&PolicySet> {
+        self.policy_sets.get(name)
+    }
+}
+
+/// A store of named managed policies and policy sets loaded from a directory.
+///
+/// The store publishes immutable [PolicySnapshot]s. Readers obtain the current snapshot with
+/// [PolicyStore::snapshot] without taking a lock, and keep using it for as long as they hold it, even if the store is
+/// reloaded in the meantime. A reload validates the entire directory first and publishes a new snapshot only if
+/// every file loads.
+///
+/// The directory is laid out as follows:
+///
+/// * Files under the `policies` subdirectory are managed policies, named by their path relative to that
+///   subdirectory without the `.json` suffix. A file is either a plain policy document (loaded as version `v1`,
+///   with the name as its ARN), or a descriptor with the policy's ARN, IAM ID, versions, and default version (which
+///   defaults to the highest-numbered version):
+///
+///   ```json
+///   {
+///       "PolicyArn": "arn:aws:iam::123456789012:policy/s3-read",
+///       "PolicyId": "ANPAEXAMPLE",
+///       "DefaultVersion": "v2",
+///       "Versions": {"v1": {"Statement": []}, "v2": {"Statement": []}}
+///   }
+///   ```
+///
+/// * Every other file ending in `.json` (except policy test suites ending in [`.test.json`][TEST_SUITE_SUFFIX]) is
+///   a policy set, named by its path relative to the directory without the `.json` suffix. A file whose top-level
+///   JSON value is a list is a serialized [PolicySet], except that an entry may omit its `Policy` if its `Source`
+///   refers to a managed policy by `PolicyArn`. The referenced `Version` is used, or the default version if the
+///   version is empty. Any other file is a single policy document, which is loaded as an inline identity policy
+///   attributed to the policy set name.
+///
+/// Path components are joined with `/` in names, and files and directories beginning with `.` are ignored.
+#[derive(Debug)]
+pub struct PolicyStore {
+    dir: PathBuf,
+    snapshot: ArcSwap<PolicySnapshot>,
+    reload_lock: Mutex<()>,
+}
+
+impl PolicyStore {
+    /// Creates a policy store from a directory and publishes its first snapshot (generation 1).
+    ///
+    /// # Errors
+    ///
+    /// See [PolicySnapshot::load].
+    pub fn load<P: AsRef<Path>>(dir: P) -> Result<Self, AspenError> {
+        let dir = dir.as_ref().to_path_buf();
+        let mut snapshot = PolicySnapshot::load(&dir)?;
+        snapshot.generation = 1;
+
+        Ok(Self {
+            dir,
+            snapshot: ArcSwap::from_pointee(snapshot),
+            reload_lock: Mutex::new(()),
+        })
+    }
+
+    /// Returns the directory the store is loaded from.
+    #[inline]
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Returns the current snapshot.
+    pub fn snapshot(&self) -> Arc<PolicySnapshot> {
+        self.snapshot.load_full()
+    }
+
+    /// Returns the generation of the current snapshot.
+    pub fn generation(&self) -> u64 {
+        self.snapshot.load().generation
+    }
+
+    /// Reloads the directory and publishes a new snapshot, returning its generation.
+    ///
+    /// # Errors
+    ///
+    /// See [PolicySnapshot::load]. If the directory fails to load, the current snapshot remains published.
+    pub fn reload(&self) -> Result<u64, AspenError> {
+        let _guard = self.reload_lock.lock().unwrap_or_else(|e| e.into_inner());
+        let mut snapshot = PolicySnapshot::load(&self.dir)?;
+        snapshot.generation = self.generation() + 1;
+        let generation = snapshot.generation;
+        self.snapshot.store(Arc::new(snapshot));
+        Ok(generation)
+    }
+
+    /// Watches the directory for changes, reloading the store when a file is added, removed, or modified.
+    ///
+    /// The directory is polled every `interval`. Failed reloads are logged and leave the current snapshot published
+    /// until the next change. Watching stops when the returned [PolicyWatcher] is dropped or the store is dropped.
+    pub fn watch(self: &Arc<Self>, interval: Duration) -> PolicyWatcher {
+        let store = Arc::downgrade(self);
+        let (stop, stopped) = channel::<()>();
+        let mut last = fingerprint(&self.dir);
+
+        let thread = thread::spawn(move || {
+            while let Err(RecvTimeoutError::Timeout) = stopped.recv_timeout(interval) {
+                let store = match Weak::upgrade(&store) {
+                    Some(store) => store,
+                    None => break,
+                };
+
+                let current = fingerprint(&store.dir);
+                if current != last {
+                    last = current;
+                    match store.reload() {
+                        Ok(generation) => debug!("Reloaded {} (generation {generation})", store.dir.display()),
+                        Err(e) => warn!("Failed to reload {}: {e}", store.dir.display()),
+                    }
+                }
+            }
+        });
+
+        PolicyWatcher {
+            stop: Some(stop),
+            thread: Some(thread),
+        }
+    }
+}
+
+/// Watches a [PolicyStore] directory for changes; see [PolicyStore::watch]. Watching stops when this is dropped.
+#[derive(Debug)]
+pub struct PolicyWatcher {
+    stop: Option<Sender<()>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Drop for PolicyWatcher {
+    fn drop(&mut self) {
+        // Dropping the sender wakes the watcher thread.
+        drop(self.stop.take());
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// The modification times and sizes of the files in a directory, used to detect changes.
+fn fingerprint(dir: &Path) -> Vec<(PathBuf, Option<SystemTime>, u64)> {
+    let mut files = Vec::new();
+    let _ = find_json_files(dir, None).map(|paths| {
+        for path in paths {
+            let metadata = fs::metadata(&path).ok();
+            let modified = metadata.as_ref().and_then(|m| m.modified().ok());
+            files.push((path, modified, metadata.map(|m| m.len()).unwrap_or_default()));
+        }
+    });
+    files
+}
+
+fn load_managed_policy(name: &str, path: &Path) -> Result<ManagedPolicy, AspenError> {
+    let json = fs::read_to_string(path).map_err(|e| invalid(path, e))?;
+    let value: Value = serde_json::from_str(&json).map_err(|e| invalid(path, e))?;
+
+    let policy = if value.get("Versions").is_some() {
+        let repr: ManagedPolicyRepr = serde_json::from_str(&json).map_err(|e| invalid(path, e))?;
+        let default_version = match repr.default_version {
+            Some(version) => version,
+            None => repr
+                .versions
+                .keys()
+                .max_by_key(|version| version_number(version))
+                .cloned()
+                .ok_or_else(|| invalid(path, "no policy versions"))?,
+        };
+        ManagedPolicy::new(
+            repr.policy_arn.unwrap_or_else(|| name.to_string()),
+            repr.policy_id,
+            default_version,
+            repr.versions,
+        )
+        .map_err(|e| invalid(path, e))?
+    } else {
+        let policy = Policy::from_str(&json).map_err(|e| invalid(path, e))?;
+        ManagedPolicy::new(name, "", "v1", BTreeMap::from([("v1".to_string(), policy)]))
+            .map_err(|e| invalid(path, e))?
+    };
+
+    for (version, document) in policy.versions.iter() {
+        check_lint(document).map_err(|e| invalid(path, format!("version {version}: {e}")))?;
+    }
+
+    Ok(policy)
+}
+
+fn load_policy_set(
+    name: &str,
+    path: &Path,
+    policies: &BTreeMap<String, ManagedPolicy>,
+) -> Result<PolicySet, AspenError> {
+    let json = fs::read_to_string(path).map_err(|e| invalid(path, e))?;
+    let value: Value = serde_json::from_str(&json).map_err(|e| invalid(path, e))?;
+
+    if !value.is_array() {
+        let policy = Policy::from_str(&json).map_err(|e| invalid(path, e))?;
+        check_lint(&policy).map_err(|e| invalid(path, e))?;
+        return Ok(PolicySet::from(vec![(PolicySource::new_standalone(name), policy)]));
+    }
+
+    let entries: Vec<PolicySetFileEntry> = serde_json::from_str(&json).map_err(|e| invalid(path, e))?;
+    let mut policy_set = PolicySet::new();
+    for (i, entry) in entries.into_iter().enumerate() {
+        let (source, policy) = match entry.policy {
+            Some(policy) => (entry.source, policy),
+            None => resolve(entry.source, policies).map_err(|e| invalid(path, format!("entry {i}: {e}")))?,
+        };
+        check_lint(&policy).map_err(|e| invalid(path, format!("entry {i}: {e}")))?;
+        policy_set.add_policy(source, policy);
+    }
+
+    Ok(policy_set)
+}
+
+/// Resolves a reference to a managed policy, filling in the default version if the source's version is empty.
+fn resolve(
+    mut source: PolicySource,
+    policies: &BTreeMap<String, ManagedPolicy>,
+) -> Result<(PolicySource, Policy), String> {
+    let (policy_arn, version) = match &mut source {
+        PolicySource::EntityAttachedPolicy {
+            policy_arn,
+            version,
+            ..
+        }
+        | PolicySource::GroupAttachedPolicy {
+            policy_arn,
+            version,
+            ..
+        }
+        | PolicySource::PermissionBoundary {
+            policy_arn,
+            version,
+            ..
+        } => (policy_arn, version),
+        _ => return Err("missing Policy".to_string()),
+    };
+
+    let managed = policies
+        .values()
+        .find(|policy| &policy.policy_arn == policy_arn)
+        .ok_or_else(|| format!("unknown managed policy {policy_arn}"))?;
+    if version.is_empty() {
+        *version = managed.default_version.clone();
+    }
+    let policy = managed.version(version).ok_or_else(|| format!("unknown version {version} of {policy_arn}"))?.clone();
+
+    Ok((source, policy))
+}
+
+fn check_lint(policy: &Policy) -> Result<(), String> {
+    let lint = PolicyLint::check(policy);
+    if lint.has_errors() {
+        let messages: Vec<String> =
+            lint.findings().iter().filter(|f| f.severity() == LintSeverity::Error).map(ToString::to_string).collect();
+        return Err(messages.join("; "));
+    }
+    Ok(())
+}
+
+/// Returns the numeric part of a version id such as `v3`, for ordering versions.
+fn version_number(version: &str) -> Option<u64> {
+    version.strip_prefix('v').and_then(|n| n.parse().ok())
+}
+
+/// Returns the name of a file relative to a directory, without the `.json` suffix.
+fn entry_name(dir: &Path, path: &Path) -> String {
+    let relative = path.strip_prefix(dir).unwrap_or(path);
+    let name: Vec<String> = relative.iter().map(|c| c.to_string_lossy().into_owned()).collect();
+    let name = name.join("/");
+    name.strip_suffix(".json").unwrap_or(&name).to_string()
+}
+
+/// Finds the JSON files (other than test suites) under `dir`, skipping hidden files and the `exclude` directory.
+fn find_json_files(dir: &Path, exclude: Option<&Path>) -> Result<Vec<PathBuf>, AspenError> {
+    let mut paths = Vec::new();
+    let mut dirs = vec![dir.to_path_buf()];
+
+    while let Some(dir) = dirs.pop() {
+        for entry in fs::read_dir(&dir).map_err(|e| invalid(&dir, e))? {
+            let path = entry.map_err(|e| invalid(&dir, e))?.path();
+            let file_name = path.file_name().and_then(|name| name.to_str()).unwrap_or_default();
+            if file_name.starts_with('.') || Some(path.as_path()) == exclude {
+                continue;
+            }
+
+            if path.is_dir() {
+                dirs.push(path);
+            } else if file_name.ends_with(".json") && !file_name.ends_with(TEST_SUITE_SUFFIX) {
+                paths.push(path);
+            }
+        }
+    }
+
+    paths.sort();
+    Ok(paths)
+}
+
+fn invalid<E: Display>(path: &Path, e: E) -> AspenError {
+    AspenError::InvalidPolicyFile(format!("{}: {e}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        crate::{AspenError, Context, Decision, PolicySnapshot, PolicySource, PolicyStore},
+        pretty_assertions::assert_eq,
+        std::{
+            env, fs,
+            path::PathBuf,
+            str::FromStr,
+            sync::Arc,
+            thread,
+            time::{Duration, Instant},
+        },
+    };
+
+    const GET_OBJECT: &str = r#"{"Service": "s3", "Api": "GetObject", "Resources": ["arn:aws:s3:::bucket/key"]}"#;
+
+    fn store_dir(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("aspen-store-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("policies")).unwrap();
+        fs::write(
+            dir.join("policies").join("s3.json"),
+            r#"{"PolicyArn": "arn:aws:iam::123456789012:policy/s3", "PolicyId": "ANPAEXAMPLE",
+                "Versions": {
+                    "v2": {"Statement": {"Effect": "Allow", "Action": "s3:GetObject", "Resource": "*"}},
+                    "v10": {"Statement": {"Effect": "Allow", "Action": "s3:PutObject", "Resource": "*"}}
+                }}"#,
+        )
+        .unwrap();
+        fs::write(
+            dir.join("policies").join("ec2.json"),
+            r#"{"Statement": {"Effect": "Allow", "Action": "ec2:*", "Resource": "*"}}"#,
+        )
+        .unwrap();
+        fs::write(
+            dir.join("alice.json"),
+            r#"[{"Source": {"Type": "EntityAttachedPolicy", "PolicyArn": "arn:aws:iam::123456789012:policy/s3",
+                            "PolicyId": "ANPAEXAMPLE", "Version": "v2"}},
+                {"Source": {"Type": "EntityAttachedPolicy", "PolicyArn": "ec2", "PolicyId": "", "Version": ""}}]"#,
+        )
+        .unwrap();
+        dir
+    }
+
+    fn decide(snapshot: &PolicySnapshot, name: &str) -> Decision {
+        snapshot.policy_set(name).unwrap().evaluate(&Context::from_str(GET_OBJECT).unwrap()).unwrap().0
+    }
+
+    #[test_log::test]
+    fn test_load() {
+        let dir = store_dir("load");
+        let snapshot = PolicySnapshot::load(&dir).unwrap();
+        assert_eq!(snapshot.generation(), 0);
+
+        let s3 = snapshot.policy("s3").unwrap();
+        assert_eq!(s3.default_version(), "v10");
+        assert_eq!(s3.versions().len(), 2);
+        assert!(s3.version("v3").is_none());
+        assert_eq!(snapshot.policy_by_arn("arn:aws:iam::123456789012:policy/s3"), Some(s3));
+        assert_eq!(
+            s3.entity_attached_source(Some("v2")),
+            Some(PolicySource::new_entity_attached_policy("arn:aws:iam::123456789012:policy/s3", "ANPAEXAMPLE", "v2"))
+        );
+        assert_eq!(s3.entity_attached_source(Some("v3")), None);
+
+        let ec2 = snapshot.policy("ec2").unwrap();
+        assert_eq!(ec2.policy_arn(), "ec2");
+        assert_eq!(ec2.default_version(), "v1");
+
+        // Policy sets pin the referenced version, filling in the default version if none is given.
+        let alice = snapshot.policy_set("alice").unwrap();
+        assert_eq!(alice.policies()[0].1, *s3.version("v2").unwrap());
+        assert_eq!(alice.policies()[1].0, PolicySource::new_entity_attached_policy("ec2", "", "v1"));
+        assert_eq!(decide(&snapshot, "alice"), Decision::Allow);
+        assert_eq!(snapshot.policy_sets().len(), 1);
+
+        fs::write(
+            dir.join("bob.json"),
+            r#"[{"Source": {"Type": "EntityAttachedPolicy", "PolicyArn": "ec2", "PolicyId": "", "Version": "v2"}}]"#,
+        )
+        .unwrap();
+        match PolicySnapshot::load(&dir) {
+            Err(AspenError::InvalidPolicyFile(msg)) => {
+                assert!(msg.ends_with("entry 0: unknown version v2 of ec2"), "{msg}")
+            }
+            other => panic!("unexpected result: {other:?}"),
+        }
+
+        fs::write(
+            dir.join("bob.json"),
+            r#"{"Statement": [{"Sid": "A", "Effect": "Allow", "Action": "s3:*", "Resource": "*"},
+                              {"Sid": "A", "Effect": "Allow", "Action": "ec2:*", "Resource": "*"}]}"#,
+        )
+        .unwrap();
+        match PolicySnapshot::load(&dir) {
+            Err(AspenError::InvalidPolicyFile(msg)) => assert!(msg.contains("bob.json"), "{msg}"),
+            other => panic!("unexpected result: {other:?}"),
+        }
+        fs::remove_file(dir.join("bob.json")).unwrap();
+
+        fs::write(dir.join("policies").join("s3-copy.json"), fs::read(dir.join("policies").join("s3.json")).unwrap())
+            .unwrap();
+        match PolicySnapshot::load(&dir) {
+            Err(AspenError::InvalidPolicyFile(msg)) => assert!(msg.contains("duplicate policy ARN"), "{msg}"),
+            other => panic!("unexpected result: {other:?}"),
+        }
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test_log::test]
+    fn test_reload() {
+        let dir = store_dir("reload");
+        let store = Arc::new(PolicyStore::load(&dir).unwrap());
+        let before = store.snapshot();
+        assert_eq!(before.generation(), 1);
+
+        // Drop the ec2 policy from alice's policy set.
+        fs::write(
+            dir.join("alice.json"),
+            r#"[{"Source": {"Type": "EntityAttachedPolicy", "PolicyArn": "arn:aws:iam::123456789012:policy/s3",
+                            "PolicyId": "ANPAEXAMPLE", "Version": ""}}]"#,
+        )
+        .unwrap();
+        assert_eq!(store.reload().unwrap(), 2);
+
+        // Readers holding the old snapshot are unaffected.
+        assert_eq!(decide(&before, "alice"), Decision::Allow);
+        assert_eq!(decide(&store.snapshot(), "alice"), Decision::DefaultDeny);
+
+        // Invalid changes are not published.
+        fs::write(dir.join("broken.json"), "[").unwrap();
+        assert!(store.reload().is_err());
+        assert_eq!(store.generation(), 2);
+        fs::remove_file(dir.join("broken.json")).unwrap();
+
+        let watcher = store.watch(Duration::from_millis(10));
+        fs::write(dir.join("carol.json"), r#"{"Statement": {"Effect": "Allow", "Action": "s3:*", "Resource": "*"}}"#)
+            .unwrap();
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while store.snapshot().policy_set("carol").is_none() && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(decide(&store.snapshot(), "carol"), Decision::Allow);
+        assert_eq!(store.generation(), 3);
+        drop(watcher);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
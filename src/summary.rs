@@ -0,0 +1,496 @@
+use {
+    crate::{display_json, Action, ActionCatalog, Condition, Effect, PolicySet, Resource, ResourceList, Statement},
+    serde::Serialize,
+    std::{
+        collections::{BTreeMap, BTreeSet},
+        fmt::{Display, Formatter, Result as FmtResult},
+    },
+};
+
+/// The effective access a [PolicySet] grants for a single action.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
+pub enum EffectiveAccess {
+    /// The action is allowed on all resources without conditions, and nothing denies it.
+    Allowed,
+
+    /// The action is allowed, but only on some resources, under some conditions, or subject to a Deny statement
+    /// that applies only on some resources or under some conditions.
+    Conditional,
+
+    /// The action is denied on all resources without conditions.
+    Denied,
+
+    /// No identity policy allows the action, or a permissions boundary does not allow it.
+    NotAllowed,
+}
+
+impl Display for EffectiveAccess {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        match self {
+            Self::Allowed => f.write_str("Allowed"),
+            Self::Conditional => f.write_str("Conditional"),
+            Self::Denied => f.write_str("Denied"),
+            Self::NotAllowed => f.write_str("NotAllowed"),
+        }
+    }
+}
+
+/// The resources a statement applies to.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub enum ResourceScope {
+    /// The statement applies to resources matching any of these patterns.
+    Resource(Vec<String>),
+
+    /// The statement applies to resources matching none of these patterns.
+    NotResource(Vec<String>),
+
+    /// The statement does not specify resources (e.g. a resource-based policy).
+    Unspecified,
+}
+
+impl ResourceScope {
+    fn from_statement(statement: &Statement) -> Self {
+        let to_strings = |rl: &ResourceList| rl.iter().map(Resource::to_string).collect();
+
+        if let Some(resources) = statement.resource() {
+            Self::Resource(to_strings(resources))
+        } else if let Some(resources) = statement.not_resource() {
+            Self::NotResource(to_strings(resources))
+        } else {
+            Self::Unspecified
+        }
+    }
+
+    /// Indicates whether this scope covers every resource.
+    pub fn is_any(&self) -> bool {
+        match self {
+            Self::Resource(patterns) => patterns.iter().any(|p| p == "*"),
+            Self::NotResource(_) => false,
+            Self::Unspecified => true,
+        }
+    }
+}
+
+impl Display for ResourceScope {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        match self {
+            Self::Resource(patterns) => f.write_str(&patterns.join(", ")),
+            Self::NotResource(patterns) => write!(f, "all except {}", patterns.join(", ")),
+            Self::Unspecified => f.write_str("(unspecified)"),
+        }
+    }
+}
+
+/// A statement that applies to an action in a [PermissionsSummary].
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct Grant {
+    /// The index of the policy in [PolicySet::policies].
+    policy_index: usize,
+
+    /// The index of the statement within the policy.
+    statement_index: usize,
+
+    /// The statement id, if any.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sid: Option<String>,
+
+    /// Whether the statement comes from a permissions boundary.
+    boundary: bool,
+
+    /// The resources the statement applies to.
+    resources: ResourceScope,
+
+    /// The conditions under which the statement applies.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    condition: Option<Condition>,
+}
+
+impl Grant {
+    /// Returns the index of the policy in [PolicySet::policies].
+    #[inline]
+    pub fn policy_index(&self) -> usize {
+        self.policy_index
+    }
+
+    /// Returns the index of the statement within the policy.
+    #[inline]
+    pub fn statement_index(&self) -> usize {
+        self.statement_index
+    }
+
+    /// Returns the statement id, if any.
+    #[inline]
+    pub fn sid(&self) -> Option<&str> {
+        self.sid.as_deref()
+    }
+
+    /// Indicates whether the statement comes from a permissions boundary.
+    #[inline]
+    pub fn is_boundary(&self) -> bool {
+        self.boundary
+    }
+
+    /// Returns the resources the statement applies to.
+    #[inline]
+    pub fn resources(&self) -> &ResourceScope {
+        &self.resources
+    }
+
+    /// Returns the conditions under which the statement applies.
+    #[inline]
+    pub fn condition(&self) -> Option<&Condition> {
+        self.condition.as_ref()
+    }
+
+    /// Indicates whether the statement applies to every resource without conditions.
+    pub fn is_unrestricted(&self) -> bool {
+        self.resources.is_any() && self.condition.as_ref().map(Condition::is_empty).unwrap_or(true)
+    }
+}
+
+impl Display for Grant {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        write!(f, "{} (policy {}, statement {}", self.resources, self.policy_index, self.statement_index)?;
+        if let Some(sid) = &self.sid {
+            write!(f, " {sid:?}")?;
+        }
+        if self.boundary {
+            f.write_str(", boundary")?;
+        }
+        f.write_str(")")?;
+        if let Some(condition) = &self.condition {
+            let condition = serde_json::to_string(condition).map_err(|_| std::fmt::Error)?;
+            write!(f, " when {condition}")?;
+        }
+        Ok(())
+    }
+}
+
+/// The effective permissions for a single action.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ActionSummary {
+    access: EffectiveAccess,
+    allowed: Vec<Grant>,
+    denied: Vec<Grant>,
+}
+
+impl ActionSummary {
+    /// Returns the overall effective access for the action.
+    #[inline]
+    pub fn access(&self) -> EffectiveAccess {
+        self.access
+    }
+
+    /// Returns the Allow statements that apply to the action, including those from permissions boundaries.
+    #[inline]
+    pub fn allowed(&self) -> &[Grant] {
+        &self.allowed
+    }
+
+    /// Returns the Deny statements that apply to the action.
+    #[inline]
+    pub fn denied(&self) -> &[Grant] {
+        &self.denied
+    }
+}
+
+/// The effective permissions for the actions of a single service.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize)]
+#[serde(transparent)]
+pub struct ServiceSummary {
+    actions: BTreeMap<String, ActionSummary>,
+}
+
+impl ServiceSummary {
+    /// Returns the summary for the given API, if any statement applies to it.
+    #[inline]
+    pub fn get(&self, api: &str) -> Option<&ActionSummary> {
+        self.actions.get(api)
+    }
+
+    /// Returns an iterator over the APIs and their summaries, in sorted order.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &ActionSummary)> {
+        self.actions.iter().map(|(api, summary)| (api.as_str(), summary))
+    }
+
+    /// Returns the APIs with the given effective access, in sorted order.
+    pub fn with_access(&self, access: EffectiveAccess) -> Vec<&str> {
+        self.iter().filter(|(_, summary)| summary.access == access).map(|(api, _)| api).collect()
+    }
+}
+
+/// A summary of the effective permissions granted by a [PolicySet].
+///
+/// Wildcard actions and `NotAction` statements are expanded against an [ActionCatalog]; actions named literally in
+/// a statement are always included, even if the catalog does not list them. Only actions that at least one
+/// statement applies to are summarized.
+///
+/// The summary is available in machine-readable form through [Serialize] (or [Display], which pretty-prints it as
+/// JSON) and in human-readable form through [PermissionsSummary::to_text].
+///
+/// # Example
+///
+/// ```
+/// # use scratchstack_aspen::{ActionCatalog, EffectiveAccess, PermissionsSummary, Policy, PolicySet, PolicySource};
+/// # use std::str::FromStr;
+/// let policy = Policy::from_str(r#"{"Statement": [
+///     {"Effect": "Allow", "Action": "s3:*", "Resource": "*"},
+///     {"Effect": "Deny", "Action": "s3:Delete*", "Resource": "*"}
+/// ]}"#).unwrap();
+/// let policy_set = PolicySet::from(vec![(PolicySource::new_session(), policy.clone()),
+///     (PolicySource::new_entity_inline("arn:aws:iam::123456789012:user/bob", "AIDABOB", "p"), policy)]);
+/// let catalog = ActionCatalog::from_iter([("s3", "GetObject"), ("s3", "DeleteObject")]);
+/// let summary = PermissionsSummary::new(&policy_set, &catalog);
+/// let s3 = summary.service("s3").unwrap();
+/// assert_eq!(s3.get("GetObject").unwrap().access(), EffectiveAccess::Allowed);
+/// assert_eq!(s3.get("DeleteObject").unwrap().access(), EffectiveAccess::Denied);
+/// ```
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize)]
+#[serde(transparent)]
+pub struct PermissionsSummary {
+    services: BTreeMap<String, ServiceSummary>,
+}
+
+display_json!(PermissionsSummary);
+
+impl PermissionsSummary {
+    /// Compute the effective permissions summary for a [PolicySet], expanding wildcards against `catalog`.
+    pub fn new(policy_set: &PolicySet, catalog: &ActionCatalog) -> Self {
+        let policies = policy_set.policies();
+
+        // The universe of actions: the catalog plus any literal actions named in the policies.
+        let mut universe: BTreeSet<(String, String)> =
+            catalog.iter().map(|(service, api)| (service.to_string(), api.to_string())).collect();
+        for (_, policy) in policies {
+            for statement in policy.statement().iter() {
+                for action in statement.action().iter().flat_map(|actions| actions.iter()) {
+                    if let Some((service, api)) = action.specific() {
+                        if !api.contains(['*', '?']) {
+                            universe.insert((service.to_string(), api.to_string()));
+                        }
+                    }
+                }
+            }
+        }
+
+        let boundaries: BTreeSet<usize> =
+            policies.iter().enumerate().filter(|(_, (source, _))| source.is_boundary()).map(|(i, _)| i).collect();
+
+        let mut services: BTreeMap<String, ServiceSummary> = BTreeMap::new();
+
+        for (service, api) in universe {
+            let mut allowed = Vec::new();
+            let mut denied = Vec::new();
+
+            for (policy_index, (source, policy)) in policies.iter().enumerate() {
+                for (statement_index, statement) in policy.statement().iter().enumerate() {
+                    if !statement_applies_to_action(statement, &service, &api) {
+                        continue;
+                    }
+
+                    let grant = Grant {
+                        policy_index,
+                        statement_index,
+                        sid: statement.sid().map(str::to_string),
+                        boundary: source.is_boundary(),
+                        resources: ResourceScope::from_statement(statement),
+                        condition: statement.condition().cloned(),
+                    };
+
+                    match statement.effect() {
+                        Effect::Allow => allowed.push(grant),
+                        Effect::Deny => denied.push(grant),
+                    }
+                }
+            }
+
+            if allowed.is_empty() && denied.is_empty() {
+                continue;
+            }
+
+            let access = effective_access(&allowed, &denied, &boundaries);
+            services.entry(service).or_default().actions.insert(
+                api,
+                ActionSummary {
+                    access,
+                    allowed,
+                    denied,
+                },
+            );
+        }
+
+        Self {
+            services,
+        }
+    }
+
+    /// Returns the summary for the given service, if any statement applies to one of its actions.
+    #[inline]
+    pub fn service(&self, service: &str) -> Option<&ServiceSummary> {
+        self.services.get(service)
+    }
+
+    /// Returns an iterator over the services and their summaries, in sorted order.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &ServiceSummary)> {
+        self.services.iter().map(|(service, summary)| (service.as_str(), summary))
+    }
+
+    /// Returns the summary for the given action, if any statement applies to it.
+    pub fn action(&self, service: &str, api: &str) -> Option<&ActionSummary> {
+        self.service(service).and_then(|s| s.get(api))
+    }
+
+    /// Render the summary as human-readable text.
+    pub fn to_text(&self) -> String {
+        let mut result = String::new();
+        for (service, summary) in self.iter() {
+            result.push_str(service);
+            result.push_str(":\n");
+            for (api, action) in summary.iter() {
+                result.push_str(&format!("  {api}: {}\n", action.access));
+                for grant in &action.allowed {
+                    result.push_str(&format!("    Allow {grant}\n"));
+                }
+                for grant in &action.denied {
+                    result.push_str(&format!("    Deny {grant}\n"));
+                }
+            }
+        }
+        result
+    }
+}
+
+/// Indicates whether the statement's `Action` or `NotAction` element covers the given action.
+fn statement_applies_to_action(statement: &Statement, service: &str, api: &str) -> bool {
+    if let Some(actions) = statement.action() {
+        actions.iter().any(|a: &Action| a.matches(service, api))
+    } else if let Some(actions) = statement.not_action() {
+        !actions.iter().any(|a: &Action| a.matches(service, api))
+    } else {
+        false
+    }
+}
+
+fn effective_access(allowed: &[Grant], denied: &[Grant], boundaries: &BTreeSet<usize>) -> EffectiveAccess {
+    if denied.iter().any(Grant::is_unrestricted) {
+        return EffectiveAccess::Denied;
+    }
+
+    let identity_allows: Vec<&Grant> = allowed.iter().filter(|g| !g.boundary).collect();
+    if identity_allows.is_empty() {
+        return EffectiveAccess::NotAllowed;
+    }
+
+    // Every permissions boundary must also allow the action.
+    let mut unrestricted = identity_allows.iter().any(|g| g.is_unrestricted());
+    for boundary in boundaries {
+        let boundary_allows: Vec<&Grant> = allowed.iter().filter(|g| g.policy_index == *boundary).collect();
+        if boundary_allows.is_empty() {
+            return EffectiveAccess::NotAllowed;
+        }
+        unrestricted &= boundary_allows.iter().any(|g| g.is_unrestricted());
+    }
+
+    if unrestricted && denied.is_empty() {
+        EffectiveAccess::Allowed
+    } else {
+        EffectiveAccess::Conditional
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        crate::{ActionCatalog, EffectiveAccess, PermissionsSummary, Policy, PolicySet, PolicySource, ResourceScope},
+        indoc::indoc,
+        pretty_assertions::assert_eq,
+        std::str::FromStr,
+    };
+
+    fn catalog() -> ActionCatalog {
+        ActionCatalog::from_iter([
+            ("s3", "GetObject"),
+            ("s3", "PutObject"),
+            ("s3", "DeleteBucket"),
+            ("iam", "CreateUser"),
+            ("iam", "GetUser"),
+            ("ec2", "RunInstances"),
+        ])
+    }
+
+    fn user_source(name: &str) -> PolicySource {
+        PolicySource::new_entity_inline("arn:aws:iam::123456789012:user/alice", "AIDAALICE", name)
+    }
+
+    #[test_log::test]
+    fn test_summary() {
+        let identity = Policy::from_str(indoc! {r#"
+        {
+            "Version": "2012-10-17",
+            "Statement": [
+                {"Sid": "S3Read", "Effect": "Allow", "Action": "s3:Get*", "Resource": "arn:aws:s3:::bucket/*"},
+                {"Effect": "Allow", "Action": "s3:PutObject", "Resource": "*",
+                 "Condition": {"Bool": {"aws:SecureTransport": "true"}}},
+                {"Effect": "Allow", "NotAction": ["s3:*", "ec2:*"], "Resource": "*"},
+                {"Effect": "Allow", "Action": "ec2:RunInstances", "Resource": "*"},
+                {"Effect": "Allow", "Action": "sqs:SendMessage", "Resource": "*"},
+                {"Effect": "Deny", "Action": "s3:DeleteBucket", "Resource": "*"},
+                {"Effect": "Deny", "Action": "iam:Get*", "NotResource": "arn:aws:iam::123456789012:user/alice"}
+            ]
+        }"#})
+        .unwrap();
+        let boundary = Policy::from_str(indoc! {r#"
+        {
+            "Statement": {"Effect": "Allow", "NotAction": "ec2:*", "Resource": "*"}
+        }"#})
+        .unwrap();
+
+        let ps = PolicySet::from(vec![
+            (user_source("Identity"), identity),
+            (PolicySource::new_permission_boundary("arn:aws:iam::123456789012:policy/B", "ANPAB", "v1"), boundary),
+        ]);
+        let summary = PermissionsSummary::new(&ps, &catalog());
+
+        assert_eq!(summary.iter().map(|(s, _)| s).collect::<Vec<_>>(), vec!["ec2", "iam", "s3", "sqs"]);
+
+        let get_object = summary.action("s3", "GetObject").unwrap();
+        assert_eq!(get_object.access(), EffectiveAccess::Conditional);
+        assert_eq!(get_object.allowed().len(), 2);
+        assert_eq!(get_object.allowed()[0].sid(), Some("S3Read"));
+        assert_eq!(get_object.allowed()[0].resources(), &ResourceScope::Resource(vec!["arn:aws:s3:::bucket/*".into()]));
+        assert!(get_object.allowed()[1].is_boundary());
+
+        assert_eq!(summary.action("s3", "PutObject").unwrap().access(), EffectiveAccess::Conditional);
+        assert_eq!(summary.action("s3", "DeleteBucket").unwrap().access(), EffectiveAccess::Denied);
+        assert_eq!(summary.action("iam", "CreateUser").unwrap().access(), EffectiveAccess::Allowed);
+        assert_eq!(summary.action("iam", "GetUser").unwrap().access(), EffectiveAccess::Conditional);
+        assert_eq!(summary.action("ec2", "RunInstances").unwrap().access(), EffectiveAccess::NotAllowed);
+        assert_eq!(summary.action("sqs", "SendMessage").unwrap().access(), EffectiveAccess::Allowed);
+        assert_eq!(summary.service("s3").unwrap().with_access(EffectiveAccess::Denied), vec!["DeleteBucket"]);
+        assert!(summary.action("s3", "ListBucket").is_none());
+
+        let text = summary.to_text();
+        assert!(text.contains(
+            "  GetObject: Conditional\n    Allow arn:aws:s3:::bucket/* (policy 0, statement 0 \"S3Read\")\n"
+        ));
+        assert!(
+            text.contains("    Allow * (policy 0, statement 1) when {\"Bool\":{\"aws:SecureTransport\":\"true\"}}\n")
+        );
+        assert!(text.contains("    Deny all except arn:aws:iam::123456789012:user/alice (policy 0, statement 6)\n"));
+
+        let json: serde_json::Value = serde_json::from_str(&summary.to_string()).unwrap();
+        assert_eq!(json["s3"]["DeleteBucket"]["Access"], "Denied");
+        assert_eq!(json["s3"]["GetObject"]["Allowed"][0]["Sid"], "S3Read");
+        assert_eq!(json["s3"]["GetObject"]["Allowed"][0]["Resources"]["Resource"][0], "arn:aws:s3:::bucket/*");
+    }
+
+    #[test_log::test]
+    fn test_empty() {
+        let summary = PermissionsSummary::new(&PolicySet::new(), &catalog());
+        assert_eq!(summary.iter().count(), 0);
+        assert_eq!(summary.to_text(), "");
+        assert_eq!(summary.to_string(), "{}");
+    }
+}
@@ -0,0 +1,447 @@
+use {
+    crate::{
+        parse::{duplicate_keys, strip_location},
+        AspenError, Policy,
+    },
+    serde_json::{Map, Value},
+    std::{
+        collections::{BTreeSet, HashMap, HashSet},
+        fmt::{Display, Formatter, Result as FmtResult},
+        str::FromStr,
+    },
+};
+
+/// The value substituted for a placeholder that makes up an entire string when validating a template.
+const WHOLE_VALUE_STAND_IN: &str = "*";
+
+/// The value substituted for a placeholder embedded in a string when validating a template. This is accepted as an
+/// account id, ARN segment, service, or action name.
+const EMBEDDED_STAND_IN: &str = "000000000000";
+
+/// A policy with named `{{placeholder}}` parameters, instantiated into a [Policy] for each set of parameter values.
+///
+/// Placeholders may appear in any string in the policy, including actions, ARN segments, principals, condition keys,
+/// and condition values. They are distinct from IAM policy variables such as `${aws:username}`, which are left in
+/// place for evaluation. Whitespace around the name is ignored, so `{{ account }}` and `{{account}}` are the same
+/// placeholder.
+///
+/// The template is validated when it is parsed: placeholders must be well-formed, and the document must be a valid
+/// policy once placeholders are filled in.
+///
+/// # Example
+///
+/// ```
+/// # use scratchstack_aspen::{AspenError, PolicyTemplate};
+/// # use std::{collections::HashMap, str::FromStr};
+/// let template = PolicyTemplate::from_str(r#"{
+///     "Version": "2012-10-17",
+///     "Statement": {
+///         "Effect": "Allow",
+///         "Action": "s3:GetObject",
+///         "Resource": "arn:aws:s3:::{{bucket}}/${aws:username}/*",
+///         "Principal": {"AWS": "{{account}}"}
+///     }
+/// }"#).unwrap();
+/// assert_eq!(template.parameters().iter().collect::<Vec<_>>(), vec!["account", "bucket"]);
+///
+/// let mut parameters = HashMap::new();
+/// parameters.insert("bucket".to_string(), "tenant-a".to_string());
+/// assert_eq!(template.instantiate(&parameters).unwrap_err(), AspenError::MissingTemplateParameter("account".into()));
+///
+/// parameters.insert("account".to_string(), "123456789012".to_string());
+/// let policy = template.instantiate(&parameters).unwrap();
+/// assert_eq!(policy.statement()[0].resource().unwrap()[0].to_string(), "arn:aws:s3:::tenant-a/${aws:username}/*");
+/// ```
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PolicyTemplate {
+    document: Value,
+    parameters: BTreeSet<String>,
+}
+
+impl PolicyTemplate {
+    /// Parses and validates a policy template from its JSON representation.
+    ///
+    /// # Errors
+    ///
+    /// If the JSON is malformed or repeats a key within an object, a placeholder is unterminated or empty, or the
+    /// document is not a valid policy with its placeholders filled in, [AspenError::InvalidTemplate] is returned.
+    pub fn from_json(json: &str) -> Result<Self, AspenError> {
+        let document: Value = serde_json::from_str(json).map_err(|e| invalid(&e.to_string()))?;
+        if let Some(duplicate) = duplicate_keys(json).into_iter().next() {
+            return Err(invalid(&duplicate.to_string()));
+        }
+
+        let mut parameters = BTreeSet::new();
+        collect_parameters(&document, &mut parameters)?;
+
+        to_policy(&substitute(&document, &|_| None)?)
+            .map_err(|e| invalid(&format!("template is not a valid policy: {e}")))?;
+
+        Ok(Self {
+            document,
+            parameters,
+        })
+    }
+
+    /// Returns the names of the placeholders in the template.
+    #[inline]
+    pub fn parameters(&self) -> &BTreeSet<String> {
+        &self.parameters
+    }
+
+    /// Creates a policy by replacing each placeholder with its value in `parameters`.
+    ///
+    /// # Errors
+    ///
+    /// If a placeholder has no value, [AspenError::MissingTemplateParameter] is returned; if a parameter has no
+    /// placeholder, [AspenError::UnexpectedTemplateParameter] is returned. If a value makes the policy invalid (for
+    /// example, an account id that is not 12 digits in a principal ARN) or makes two keys of the same object equal
+    /// (for example, two condition keys), [AspenError::InvalidTemplate] is returned.
+    pub fn instantiate(&self, parameters: &HashMap<String, String>) -> Result<Policy, AspenError> {
+        let missing: Vec<_> = self.parameters.iter().filter(|p| !parameters.contains_key(*p)).cloned().collect();
+        if !missing.is_empty() {
+            return Err(AspenError::MissingTemplateParameter(missing.join(", ")));
+        }
+
+        let mut unexpected: Vec<_> = parameters.keys().filter(|p| !self.parameters.contains(*p)).cloned().collect();
+        if !unexpected.is_empty() {
+            unexpected.sort();
+            return Err(AspenError::UnexpectedTemplateParameter(unexpected.join(", ")));
+        }
+
+        let document = substitute(&self.document, &|name| parameters.get(name).map(String::as_str))?;
+        to_policy(&document).map_err(|e| invalid(&e))
+    }
+}
+
+impl Display for PolicyTemplate {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        write!(f, "{:#}", self.document)
+    }
+}
+
+impl FromStr for PolicyTemplate {
+    type Err = AspenError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::from_json(s)
+    }
+}
+
+fn invalid(message: &str) -> AspenError {
+    AspenError::InvalidTemplate(message.to_string())
+}
+
+/// Parses a substituted document as a policy. The error location refers to the serialized document rather than the
+/// template text, so it is omitted.
+fn to_policy(document: &Value) -> Result<Policy, String> {
+    Policy::from_str(&document.to_string()).map_err(|e| strip_location(&e))
+}
+
+/// A piece of a template string: literal text or a placeholder name.
+enum Piece<'a> {
+    Literal(&'a str),
+    Placeholder(&'a str),
+}
+
+/// Splits a template string into literal text and placeholders.
+fn pieces(s: &str) -> Result<Vec<Piece<'_>>, AspenError> {
+    let mut result = Vec::new();
+    let mut rest = s;
+
+    while let Some(start) = rest.find("{{") {
+        if start > 0 {
+            result.push(Piece::Literal(&rest[..start]));
+        }
+
+        let after = &rest[start + 2..];
+        let end = after.find("}}").ok_or_else(|| invalid(&format!("unterminated placeholder in {s:?}")))?;
+        let name = after[..end].trim();
+        if name.is_empty() || name.contains('{') {
+            return Err(invalid(&format!("invalid placeholder in {s:?}")));
+        }
+
+        result.push(Piece::Placeholder(name));
+        rest = &after[end + 2..];
+    }
+
+    if !rest.is_empty() {
+        result.push(Piece::Literal(rest));
+    }
+
+    Ok(result)
+}
+
+/// Collects the placeholder names in every string and object key in a document.
+fn collect_parameters(value: &Value, parameters: &mut BTreeSet<String>) -> Result<(), AspenError> {
+    match value {
+        Value::String(s) => collect_string_parameters(s, parameters),
+        Value::Array(elements) => elements.iter().try_for_each(|element| collect_parameters(element, parameters)),
+        Value::Object(map) => {
+            for (key, value) in map {
+                collect_string_parameters(key, parameters)?;
+                collect_parameters(value, parameters)?;
+            }
+            Ok(())
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Collects the placeholder names in a string.
+fn collect_string_parameters(s: &str, parameters: &mut BTreeSet<String>) -> Result<(), AspenError> {
+    for piece in pieces(s)? {
+        if let Piece::Placeholder(name) = piece {
+            parameters.insert(name.to_string());
+        }
+    }
+    Ok(())
+}
+
+/// Replaces the placeholders in every string and object key in a document. Placeholders that `lookup` has no value
+/// for are replaced with a stand-in that keeps the policy valid.
+///
+/// Object keys that become equal after substitution would silently drop an element (such as a condition key), so
+/// this is an error unless a stand-in was involved.
+fn substitute<'a, F: Fn(&str) -> Option<&'a str>>(value: &Value, lookup: &F) -> Result<Value, AspenError> {
+    // Returns the substituted string and whether every placeholder had a value.
+    let replace = |s: &str| -> Result<(String, bool), AspenError> {
+        let pieces = pieces(s)?;
+        let whole = pieces.len() == 1;
+        let mut result = String::with_capacity(s.len());
+        let mut complete = true;
+        for piece in pieces {
+            match piece {
+                Piece::Literal(literal) => result.push_str(literal),
+                Piece::Placeholder(name) => match lookup(name) {
+                    Some(value) => result.push_str(value),
+                    None => {
+                        complete = false;
+                        result.push_str(match whole {
+                            true => WHOLE_VALUE_STAND_IN,
+                            false => EMBEDDED_STAND_IN,
+                        });
+                    }
+                },
+            }
+        }
+        Ok((result, complete))
+    };
+
+    Ok(match value {
+        Value::String(s) => Value::String(replace(s)?.0),
+        Value::Array(elements) => {
+            Value::Array(elements.iter().map(|element| substitute(element, lookup)).collect::<Result<_, _>>()?)
+        }
+        Value::Object(map) => {
+            let mut result = Map::new();
+            let mut stand_ins = HashSet::new();
+            for (key, value) in map {
+                let (key, complete) = replace(key)?;
+                if result.contains_key(&key) && complete && !stand_ins.contains(&key) {
+                    return Err(invalid(&format!("duplicate key {key:?} after substitution")));
+                }
+                if !complete {
+                    stand_ins.insert(key.clone());
+                }
+                result.insert(key, substitute(value, lookup)?);
+            }
+            Value::Object(result)
+        }
+        _ => value.clone(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        crate::{AspenError, Policy, PolicyTemplate},
+        indoc::indoc,
+        pretty_assertions::assert_eq,
+        std::{collections::HashMap, str::FromStr},
+    };
+
+    const TEMPLATE: &str = indoc! { r#"
+        {
+            "Version": "2012-10-17",
+            "Statement": [
+                {
+                    "Effect": "Allow",
+                    "Action": ["s3:GetObject", "{{ write_action }}"],
+                    "Resource": "arn:aws:s3:::{{bucket}}/*",
+                    "Principal": {"AWS": "arn:aws:iam::{{account}}:role/{{role}}"},
+                    "Condition": {
+                        "StringEquals": {"aws:ResourceTag/{{tag_key}}": "{{tag_value}}"},
+                        "StringLike": {"s3:prefix": "${aws:username}/*"}
+                    }
+                },
+                {
+                    "Effect": "Deny",
+                    "Action": "s3:DeleteBucket",
+                    "Resource": "{{bucket_arn}}"
+                }
+            ]
+        }"# };
+
+    fn parameters(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+    }
+
+    #[test_log::test]
+    fn test_instantiate() {
+        let template = PolicyTemplate::from_str(TEMPLATE).unwrap();
+        assert_eq!(
+            template.parameters().iter().map(String::as_str).collect::<Vec<_>>(),
+            vec!["account", "bucket", "bucket_arn", "role", "tag_key", "tag_value", "write_action"]
+        );
+        assert_eq!(PolicyTemplate::from_str(&template.to_string()).unwrap(), template);
+
+        let values = parameters(&[
+            ("account", "123456789012"),
+            ("bucket", "tenant-a"),
+            ("bucket_arn", "arn:aws:s3:::tenant-a"),
+            ("role", "reader"),
+            ("tag_key", "tenant"),
+            ("tag_value", "a"),
+            ("write_action", "s3:PutObject"),
+        ]);
+        let policy = template.instantiate(&values).unwrap();
+        let expected = Policy::from_str(indoc! { r#"
+            {
+                "Version": "2012-10-17",
+                "Statement": [
+                    {
+                        "Effect": "Allow",
+                        "Action": ["s3:GetObject", "s3:PutObject"],
+                        "Resource": "arn:aws:s3:::tenant-a/*",
+                        "Principal": {"AWS": "arn:aws:iam::123456789012:role/reader"},
+                        "Condition": {
+                            "StringEquals": {"aws:ResourceTag/tenant": "a"},
+                            "StringLike": {"s3:prefix": "${aws:username}/*"}
+                        }
+                    },
+                    {
+                        "Effect": "Deny",
+                        "Action": "s3:DeleteBucket",
+                        "Resource": "arn:aws:s3:::tenant-a"
+                    }
+                ]
+            }"# })
+        .unwrap();
+        assert_eq!(policy, expected);
+
+        // Values are substituted once; placeholders in values are not expanded.
+        let mut braces = values.clone();
+        braces.insert("tag_value".to_string(), "{{account}}".to_string());
+        let policy = template.instantiate(&braces).unwrap();
+        let condition = serde_json::to_string(policy.statement()[0].condition().unwrap()).unwrap();
+        assert!(condition.contains(r#""aws:ResourceTag/tenant":"{{account}}""#));
+
+        let mut missing = values.clone();
+        missing.remove("role");
+        missing.remove("account");
+        assert_eq!(
+            template.instantiate(&missing).unwrap_err(),
+            AspenError::MissingTemplateParameter("account, role".to_string())
+        );
+
+        let mut extra = values.clone();
+        extra.insert("region".to_string(), "us-east-1".to_string());
+        extra.insert("partition".to_string(), "aws".to_string());
+        assert_eq!(
+            template.instantiate(&extra).unwrap_err(),
+            AspenError::UnexpectedTemplateParameter("partition, region".to_string())
+        );
+
+        let mut bad_account = values;
+        bad_account.insert("account".to_string(), "tenant-a".to_string());
+        assert_eq!(
+            template.instantiate(&bad_account).unwrap_err().to_string(),
+            "Invalid template: Invalid principal: arn:aws:iam::tenant-a:role/reader"
+        );
+    }
+
+    #[test_log::test]
+    fn test_key_collision() {
+        let template = PolicyTemplate::from_str(
+            r#"{"Statement": {"Effect": "Allow", "Action": "s3:GetObject", "Resource": "*", "Condition": {
+                "StringEquals": {"aws:ResourceTag/{{k1}}": "{{v1}}", "aws:ResourceTag/{{k2}}": "{{v2}}",
+                                 "aws:ResourceTag/team": "storage"}}}}"#,
+        )
+        .unwrap();
+        let values = |k1: &str, k2: &str| {
+            HashMap::from([
+                ("k1".to_string(), k1.to_string()),
+                ("k2".to_string(), k2.to_string()),
+                ("v1".to_string(), "a".to_string()),
+                ("v2".to_string(), "b".to_string()),
+            ])
+        };
+
+        let policy = template.instantiate(&values("tenant", "stage")).unwrap();
+        assert_eq!(policy.statement()[0].condition().unwrap().iter().next().unwrap().1.len(), 3);
+
+        // Dropping either restriction would grant more access than intended.
+        assert_eq!(
+            template.instantiate(&values("tenant", "tenant")).unwrap_err(),
+            AspenError::InvalidTemplate(r#"duplicate key "aws:ResourceTag/tenant" after substitution"#.to_string())
+        );
+        assert_eq!(
+            template.instantiate(&values("team", "stage")).unwrap_err(),
+            AspenError::InvalidTemplate(r#"duplicate key "aws:ResourceTag/team" after substitution"#.to_string())
+        );
+    }
+
+    #[test_log::test]
+    fn test_duplicate_keys() {
+        // Policy::from_str rejects repeated fields, so templates must too rather than keeping the last one.
+        let json =
+            r#"{"Statement": {"Effect": "Allow", "Action": "s3:GetObject", "Action": "{{action}}", "Resource": "*"}}"#;
+        assert!(Policy::from_str(&json.replace("{{action}}", "s3:PutObject")).is_err());
+        assert_eq!(
+            PolicyTemplate::from_str(json).unwrap_err(),
+            AspenError::InvalidTemplate("/Statement/Action: duplicate field `Action` at line 1 column 61".to_string())
+        );
+
+        let json = r#"{"Statement": {"Effect": "Allow", "Action": "*", "Resource": "*", "Condition": {
+            "StringEquals": {"aws:username": "{{user}}", "aws:username": "admin"}}}}"#;
+        assert_eq!(
+            PolicyTemplate::from_str(json).unwrap_err(),
+            AspenError::InvalidTemplate(
+                "/Statement/Condition/StringEquals/aws:username: duplicate field `aws:username` at line 2 column 58"
+                    .to_string()
+            )
+        );
+    }
+
+    #[test_log::test]
+    fn test_invalid() {
+        let check = |json: &str, message: &str| {
+            assert_eq!(PolicyTemplate::from_str(json).unwrap_err(), AspenError::InvalidTemplate(message.to_string()));
+        };
+
+        check("{", "EOF while parsing an object at line 1 column 1");
+        check(
+            r#"{"Statement": {"Effect": "Allow", "Action": "s3:{{action", "Resource": "*"}}"#,
+            r#"unterminated placeholder in "s3:{{action""#,
+        );
+        check(
+            r#"{"Statement": {"Effect": "Allow", "Action": "{{ }}", "Resource": "*"}}"#,
+            r#"invalid placeholder in "{{ }}""#,
+        );
+        check(
+            r#"{"Statement": {"Effect": "{{effect}}", "Action": "*", "Resource": "*"}}"#,
+            "template is not a valid policy: unknown variant `*`, expected `Allow` or `Deny`",
+        );
+        check(
+            r#"{"Statement": {"Effect": "Allow", "Action": "*", "Resource": "*"}, "{{key}}": 1}"#,
+            "template is not a valid policy: unknown field `*`, expected one of `Version`, `Id`, `Statement`",
+        );
+
+        // A template without placeholders is just a policy.
+        let template =
+            PolicyTemplate::from_str(r#"{"Statement": {"Effect": "Allow", "Action": "*", "Resource": "*"}}"#).unwrap();
+        assert!(template.parameters().is_empty());
+        assert_eq!(template.instantiate(&HashMap::new()).unwrap().statement().len(), 1);
+    }
+}
@@ -0,0 +1,438 @@
+use {
+    crate::{cloudformation::to_policy, AspenError, Policy, TemplatePolicyKind},
+    serde_json::Value,
+    std::{collections::HashMap, fmt::Display, fs, path::Path, str::FromStr},
+};
+
+/// Resource and data source types whose policy documents are extracted: (type, mode, attribute, kind).
+///
+/// The `inline_policy` attribute of `aws_iam_role` is a list of `{"name", "policy"}` blocks.
+const POLICY_ATTRIBUTES: [(&str, &str, &str, TemplatePolicyKind); 14] = [
+    ("aws_iam_policy", "managed", "policy", TemplatePolicyKind::Managed),
+    ("aws_iam_role", "managed", "assume_role_policy", TemplatePolicyKind::Trust),
+    ("aws_iam_role", "managed", "inline_policy", TemplatePolicyKind::Inline),
+    ("aws_iam_role_policy", "managed", "policy", TemplatePolicyKind::Inline),
+    ("aws_iam_user_policy", "managed", "policy", TemplatePolicyKind::Inline),
+    ("aws_iam_group_policy", "managed", "policy", TemplatePolicyKind::Inline),
+    ("aws_s3_bucket_policy", "managed", "policy", TemplatePolicyKind::Resource),
+    ("aws_sqs_queue_policy", "managed", "policy", TemplatePolicyKind::Resource),
+    ("aws_sns_topic_policy", "managed", "policy", TemplatePolicyKind::Resource),
+    ("aws_kms_key", "managed", "policy", TemplatePolicyKind::Resource),
+    ("aws_ecr_repository_policy", "managed", "policy", TemplatePolicyKind::Resource),
+    ("aws_secretsmanager_secret_policy", "managed", "policy", TemplatePolicyKind::Resource),
+    ("aws_lambda_layer_version_permission", "managed", "policy", TemplatePolicyKind::Resource),
+    ("aws_iam_policy_document", "data", "json", TemplatePolicyKind::Document),
+];
+
+/// A policy document extracted from a Terraform plan.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PlanPolicy {
+    address: String,
+    resource_type: String,
+    kind: TemplatePolicyKind,
+    attribute: String,
+    policy_name: Option<String>,
+    policy: Policy,
+}
+
+impl PlanPolicy {
+    /// Returns the address of the resource or data source the policy was found in, e.g.
+    /// `module.app.aws_iam_role.worker` or `data.aws_iam_policy_document.read`.
+    #[inline]
+    pub fn address(&self) -> &str {
+        &self.address
+    }
+
+    /// Returns the Terraform type of the resource or data source, e.g. `aws_iam_role`.
+    #[inline]
+    pub fn resource_type(&self) -> &str {
+        &self.resource_type
+    }
+
+    /// Returns the kind of policy.
+    #[inline]
+    pub fn kind(&self) -> TemplatePolicyKind {
+        self.kind
+    }
+
+    /// Returns the path of the attribute holding the policy document, e.g. `assume_role_policy` or
+    /// `inline_policy[0].policy`.
+    #[inline]
+    pub fn attribute(&self) -> &str {
+        &self.attribute
+    }
+
+    /// Returns the `name` of a managed or inline policy, if set.
+    #[inline]
+    pub fn policy_name(&self) -> Option<&str> {
+        self.policy_name.as_deref()
+    }
+
+    /// Returns the policy.
+    #[inline]
+    pub fn policy(&self) -> &Policy {
+        &self.policy
+    }
+}
+
+/// The policies in the output of `terraform show -json` for a saved plan.
+///
+/// Resources are read from `planned_values`, and data sources read during planning from `prior_state`, including
+/// those in child modules. Policy attributes that will only be known after apply are listed by
+/// [TerraformPlan::unknown] rather than extracted.
+///
+/// # Example
+///
+/// ```
+/// # use scratchstack_aspen::{TemplatePolicyKind, TerraformPlan};
+/// let plan = TerraformPlan::from_json(r#"{
+///     "format_version": "1.2",
+///     "planned_values": {"root_module": {"resources": [{
+///         "address": "aws_iam_policy.read", "mode": "managed", "type": "aws_iam_policy", "name": "read",
+///         "values": {"name": "read", "policy": "{\"Version\":\"2012-10-17\",\"Statement\":{\"Effect\":\"Allow\",\"Action\":\"s3:GetObject\",\"Resource\":\"*\"}}"}
+///     }]}}}"#).unwrap();
+///
+/// assert_eq!(plan.policies()[0].address(), "aws_iam_policy.read");
+/// assert_eq!(plan.policies()[0].kind(), TemplatePolicyKind::Managed);
+/// assert_eq!(plan.policies()[0].policy().statement().len(), 1);
+/// assert!(plan.unknown().is_empty());
+/// ```
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct TerraformPlan {
+    policies: Vec<PlanPolicy>,
+    unknown: Vec<String>,
+}
+
+impl TerraformPlan {
+    /// Parses a plan from the JSON output of `terraform show -json`.
+    ///
+    /// # Errors
+    ///
+    /// If the JSON is malformed or a policy attribute is not a valid policy, [AspenError::InvalidTemplate] is
+    /// returned naming the address and attribute.
+    pub fn from_json(json: &str) -> Result<Self, AspenError> {
+        let plan: Value = serde_json::from_str(json).map_err(|e| invalid(&e.to_string()))?;
+        if !plan.is_object() {
+            return Err(invalid("plan is not an object"));
+        }
+
+        // Data sources read during planning are in the prior state; resources (and data sources whose reads are
+        // deferred until apply) are in the planned values. Later entries replace earlier ones with the same address.
+        let mut resources: Vec<&Value> = Vec::new();
+        let mut index: HashMap<&str, usize> = HashMap::new();
+        // Managed resources in the prior state are as they were before the plan (including those being destroyed),
+        // so only data sources are taken from it.
+        let prior_state = &plan["prior_state"]["values"]["root_module"];
+        collect_resources(prior_state, Some("data"), &mut resources, &mut index);
+        collect_resources(&plan["planned_values"]["root_module"], None, &mut resources, &mut index);
+
+        let mut after_unknown = HashMap::new();
+        for change in plan["resource_changes"].as_array().into_iter().flatten() {
+            if let Some(address) = change["address"].as_str() {
+                after_unknown.insert(address, &change["change"]["after_unknown"]);
+            }
+        }
+
+        let mut result = Self {
+            policies: Vec::new(),
+            unknown: Vec::new(),
+        };
+
+        for resource in resources {
+            let (Some(address), Some(resource_type), Some(mode)) =
+                (resource["address"].as_str(), resource["type"].as_str(), resource["mode"].as_str())
+            else {
+                continue;
+            };
+            let values = &resource["values"];
+            let resource = (address, resource_type);
+            let unknown = after_unknown.get(address).copied().unwrap_or(&Value::Null);
+
+            for (_, _, attribute, kind) in
+                POLICY_ATTRIBUTES.iter().filter(|(t, m, _, _)| *t == resource_type && *m == mode)
+            {
+                if *attribute == "inline_policy" {
+                    for (i, block) in values[attribute].as_array().into_iter().flatten().enumerate() {
+                        let block_unknown = match &unknown[attribute] {
+                            Value::Bool(true) => &Value::Bool(true),
+                            block_unknown => &block_unknown[i]["policy"],
+                        };
+                        let attribute = format!("{attribute}[{i}].policy");
+                        result.add(resource, *kind, attribute, block.get("name"), &block["policy"], block_unknown)?;
+                    }
+                } else {
+                    let (document, unknown) = (&values[attribute], &unknown[attribute]);
+                    result.add(resource, *kind, attribute.to_string(), values.get("name"), document, unknown)?;
+                }
+            }
+        }
+
+        Ok(result)
+    }
+
+    /// Adds the policy in an attribute of a resource, or records the attribute as unknown if it has no value and
+    /// `unknown` (from the resource change's `after_unknown`) is `true`.
+    fn add(
+        &mut self,
+        (address, resource_type): (&str, &str),
+        kind: TemplatePolicyKind,
+        attribute: String,
+        policy_name: Option<&Value>,
+        document: &Value,
+        unknown: &Value,
+    ) -> Result<(), AspenError> {
+        let document = match document.as_str() {
+            Some("") => return Ok(()),
+            Some(document) => document,
+            None => {
+                if unknown == &Value::Bool(true) {
+                    self.unknown.push(format!("{address}.{attribute}"));
+                }
+                return Ok(());
+            }
+        };
+
+        let document: Value =
+            serde_json::from_str(document).map_err(|e| invalid(&format!("{address}.{attribute}: {e}")))?;
+        let policy = to_policy(&document, kind).map_err(|e| invalid(&format!("{address}.{attribute}: {e}")))?;
+        let policy_name = match kind {
+            TemplatePolicyKind::Managed | TemplatePolicyKind::Inline => policy_name.and_then(Value::as_str),
+            _ => None,
+        };
+
+        self.policies.push(PlanPolicy {
+            address: address.to_string(),
+            resource_type: resource_type.to_string(),
+            kind,
+            attribute,
+            policy_name: policy_name.map(str::to_string),
+            policy,
+        });
+        Ok(())
+    }
+
+    /// Loads a plan from a file containing the JSON output of `terraform show -json`.
+    ///
+    /// # Errors
+    ///
+    /// If the file cannot be read or parsed, [AspenError::InvalidTemplate] is returned.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, AspenError> {
+        let path = path.as_ref();
+        let json = fs::read_to_string(path).map_err(|e| invalid_file(path, e))?;
+        Self::from_json(&json).map_err(|e| match e {
+            AspenError::InvalidTemplate(msg) => invalid_file(path, msg),
+            e => e,
+        })
+    }
+
+    /// Returns the policies in the plan, in module and resource order.
+    #[inline]
+    pub fn policies(&self) -> &[PlanPolicy] {
+        &self.policies
+    }
+
+    /// Returns the policy attributes whose values will only be known after apply, as `address.attribute`, e.g.
+    /// `aws_s3_bucket_policy.logs.policy`.
+    #[inline]
+    pub fn unknown(&self) -> &[String] {
+        &self.unknown
+    }
+}
+
+impl FromStr for TerraformPlan {
+    type Err = AspenError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::from_json(s)
+    }
+}
+
+/// Collects the resources in a module and its child modules, replacing earlier resources with the same address.
+fn collect_resources<'a>(
+    module: &'a Value,
+    mode: Option<&str>,
+    resources: &mut Vec<&'a Value>,
+    index: &mut HashMap<&'a str, usize>,
+) {
+    for resource in module["resources"].as_array().into_iter().flatten() {
+        let Some(address) = resource["address"].as_str() else {
+            continue;
+        };
+        if mode.is_some() && resource["mode"].as_str() != mode {
+            continue;
+        }
+
+        match index.get(address) {
+            Some(&i) => resources[i] = resource,
+            None => {
+                index.insert(address, resources.len());
+                resources.push(resource);
+            }
+        }
+    }
+
+    for child in module["child_modules"].as_array().into_iter().flatten() {
+        collect_resources(child, mode, resources, index);
+    }
+}
+
+fn invalid(message: &str) -> AspenError {
+    AspenError::InvalidTemplate(message.to_string())
+}
+
+fn invalid_file<E: Display>(path: &Path, e: E) -> AspenError {
+    AspenError::InvalidTemplate(format!("{}: {e}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        crate::{AspenError, TemplatePolicyKind, TerraformPlan},
+        pretty_assertions::assert_eq,
+        std::{env, fs, process, str::FromStr},
+    };
+
+    const PLAN: &str = r#"{
+        "format_version": "1.2",
+        "terraform_version": "1.7.0",
+        "prior_state": {"values": {"root_module": {"resources": [
+            {"address": "data.aws_iam_policy_document.trust", "mode": "data", "type": "aws_iam_policy_document",
+             "name": "trust", "values": {"json": "{\"Version\":\"2012-10-17\",\"Statement\":[{\"Effect\":\"Allow\",\"Action\":\"sts:AssumeRole\",\"Principal\":{\"Service\":\"lambda.amazonaws.com\"}}]}"}},
+            {"address": "data.aws_iam_policy_document.deferred", "mode": "data", "type": "aws_iam_policy_document",
+             "name": "deferred", "values": {}},
+            {"address": "aws_iam_policy.old", "mode": "managed", "type": "aws_iam_policy", "name": "old",
+             "values": {"name": "old", "policy": "{\"Statement\":{\"Effect\":\"Allow\",\"Action\":\"*\",\"Resource\":\"*\"}}"}}
+        ]}}},
+        "planned_values": {"root_module": {
+            "resources": [
+                {"address": "aws_iam_role.worker", "mode": "managed", "type": "aws_iam_role", "name": "worker",
+                 "values": {
+                     "name": "worker",
+                     "assume_role_policy": "{\"Version\":\"2012-10-17\",\"Statement\":[{\"Effect\":\"Allow\",\"Action\":\"sts:AssumeRole\",\"Principal\":{\"Service\":\"lambda.amazonaws.com\"}}]}",
+                     "inline_policy": [
+                         {"name": "logs", "policy": "{\"Statement\":{\"Effect\":\"Allow\",\"Action\":\"logs:PutLogEvents\",\"Resource\":\"*\"}}"},
+                         {"name": "queue"}
+                     ]
+                 }},
+                {"address": "aws_s3_bucket_policy.logs", "mode": "managed", "type": "aws_s3_bucket_policy",
+                 "name": "logs", "values": {"bucket": "logs"}},
+                {"address": "aws_s3_bucket.logs", "mode": "managed", "type": "aws_s3_bucket", "name": "logs",
+                 "values": {"bucket": "logs"}}
+            ],
+            "child_modules": [{
+                "address": "module.app",
+                "resources": [
+                    {"address": "module.app.aws_iam_policy.read", "mode": "managed", "type": "aws_iam_policy",
+                     "name": "read", "values": {"name": "app-read", "policy": "{\"Statement\":{\"Effect\":\"Allow\",\"Action\":[\"s3:GetObject\",\"s3:ListBucket\"],\"Resource\":\"*\"}}"}}
+                ]
+            }]
+        }},
+        "resource_changes": [
+            {"address": "aws_iam_policy.old", "change": {"actions": ["delete"], "after": null,
+             "after_unknown": {}}},
+            {"address": "aws_iam_role.worker", "change": {"actions": ["create"],
+             "after_unknown": {"arn": true, "inline_policy": [{}, {"policy": true}]}}},
+            {"address": "aws_s3_bucket_policy.logs", "change": {"actions": ["create"],
+             "after_unknown": {"id": true, "policy": true}}},
+            {"address": "data.aws_iam_policy_document.deferred", "change": {"actions": ["read"],
+             "after_unknown": {"id": true, "json": true}}}
+        ]
+    }"#;
+
+    #[test_log::test]
+    fn test_plan() {
+        let plan = TerraformPlan::from_str(PLAN).unwrap();
+        let summary: Vec<_> = plan
+            .policies()
+            .iter()
+            .map(|p| (p.address(), p.resource_type(), p.kind(), p.attribute(), p.policy_name()))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (
+                    "data.aws_iam_policy_document.trust",
+                    "aws_iam_policy_document",
+                    TemplatePolicyKind::Document,
+                    "json",
+                    None
+                ),
+                ("aws_iam_role.worker", "aws_iam_role", TemplatePolicyKind::Trust, "assume_role_policy", None),
+                (
+                    "aws_iam_role.worker",
+                    "aws_iam_role",
+                    TemplatePolicyKind::Inline,
+                    "inline_policy[0].policy",
+                    Some("logs")
+                ),
+                (
+                    "module.app.aws_iam_policy.read",
+                    "aws_iam_policy",
+                    TemplatePolicyKind::Managed,
+                    "policy",
+                    Some("app-read")
+                ),
+            ]
+        );
+        assert_eq!(TemplatePolicyKind::Document.to_string(), "Document");
+
+        // Resources being destroyed are only in the prior state and are not reported.
+        assert!(plan.policies().iter().all(|p| p.address() != "aws_iam_policy.old"));
+
+        // Trust policies are given a wildcard resource.
+        assert_eq!(plan.policies()[1].policy().statement()[0].resource().unwrap()[0].to_string(), "*");
+        assert_eq!(plan.policies()[3].policy().statement()[0].action().unwrap().len(), 2);
+
+        assert_eq!(
+            plan.unknown(),
+            &[
+                "data.aws_iam_policy_document.deferred.json".to_string(),
+                "aws_iam_role.worker.inline_policy[1].policy".to_string(),
+                "aws_s3_bucket_policy.logs.policy".to_string(),
+            ]
+        );
+
+        let dir = env::temp_dir().join(format!("aspen-terraform-{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("plan.json");
+        fs::write(&path, PLAN).unwrap();
+        assert_eq!(TerraformPlan::from_file(&path).unwrap(), plan);
+
+        let missing = dir.join("missing.json");
+        let e = TerraformPlan::from_file(&missing).unwrap_err();
+        assert!(e.to_string().starts_with(&format!("Invalid template: {}: ", missing.display())));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test_log::test]
+    fn test_errors() {
+        assert_eq!(
+            TerraformPlan::from_json("[]").unwrap_err(),
+            AspenError::InvalidTemplate("plan is not an object".to_string())
+        );
+        assert_eq!(
+            TerraformPlan::from_json("{").unwrap_err(),
+            AspenError::InvalidTemplate("EOF while parsing an object at line 1 column 1".to_string())
+        );
+
+        let plan = |policy: &str| {
+            let values = serde_json::json!({"policy": policy});
+            format!(
+                r#"{{"planned_values": {{"root_module": {{"resources": [{{"address": "aws_iam_policy.p",
+                    "mode": "managed", "type": "aws_iam_policy", "name": "p", "values": {values}}}]}}}}}}"#
+            )
+        };
+        assert_eq!(TerraformPlan::from_json(&plan("")).unwrap().policies().len(), 0);
+        assert_eq!(
+            TerraformPlan::from_json(&plan("{")).unwrap_err(),
+            AspenError::InvalidTemplate(
+                "aws_iam_policy.p.policy: EOF while parsing an object at line 1 column 1".to_string()
+            )
+        );
+        assert_eq!(
+            TerraformPlan::from_json(&plan(r#"{"Statement": {"Effect": "Allow", "Action": "s3", "Resource": "*"}}"#))
+                .unwrap_err(),
+            AspenError::InvalidTemplate("aws_iam_policy.p.policy: Invalid action: s3 at line 1 column 27".to_string())
+        );
+    }
+}
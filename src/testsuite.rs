@@ -0,0 +1,470 @@
+use {
+    crate::{AspenError, Context, Decision, DecisionReason, Policy, PolicySet, PolicySource},
+    serde::{Deserialize, Serialize},
+    std::{
+        fmt::{Display, Formatter, Result as FmtResult},
+        fs,
+        path::{Path, PathBuf},
+    },
+};
+
+/// The file name suffix used to recognize test suites when loading a directory.
+pub const TEST_SUITE_SUFFIX: &str = ".test.json";
+
+/// A request context paired with the decision a policy set is expected to make for it.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(deny_unknown_fields, rename_all = "PascalCase")]
+pub struct TestCase {
+    name: String,
+    context: Context,
+    expect: Decision,
+}
+
+impl TestCase {
+    /// Creates a new test case.
+    pub fn new<S: Into<String>>(name: S, context: Context, expect: Decision) -> Self {
+        Self {
+            name: name.into(),
+            context,
+            expect,
+        }
+    }
+
+    /// Returns the name of the test case.
+    #[inline]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the request context.
+    #[inline]
+    pub fn context(&self) -> &Context {
+        &self.context
+    }
+
+    /// Returns the expected decision.
+    #[inline]
+    pub fn expect(&self) -> Decision {
+        self.expect
+    }
+}
+
+/// The serialized form of a [TestSuite].
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "PascalCase")]
+struct TestSuiteRepr {
+    #[serde(default)]
+    name: Option<String>,
+    #[serde(default)]
+    policy: Option<Policy>,
+    #[serde(default)]
+    policy_file: Option<PathBuf>,
+    #[serde(default)]
+    policy_set: Option<PolicySet>,
+    #[serde(default)]
+    source: Option<PolicySource>,
+    cases: Vec<TestCase>,
+}
+
+/// A declarative set of test cases for a policy or policy set.
+///
+/// Test suites are written in JSON. The policies under test are given by exactly one of `Policy` (an inline policy
+/// document), `PolicyFile` (a path to a policy document, relative to the suite file), or `PolicySet` (a serialized
+/// [PolicySet]). A single policy is evaluated as an inline identity policy (attributed to the suite) unless `Source`
+/// specifies another [PolicySource]. Each case in `Cases` has a `Name`, a serialized [Context], and the expected [Decision]:
+///
+/// ```json
+/// {
+///     "Name": "s3-read",
+///     "PolicyFile": "s3-read.json",
+///     "Cases": [
+///         {
+///             "Name": "reads from the bucket",
+///             "Context": {"Service": "s3", "Api": "GetObject", "Resources": ["arn:aws:s3:::bucket/key"]},
+///             "Expect": "Allow"
+///         }
+///     ]
+/// }
+/// ```
+///
+/// # Example
+///
+/// ```
+/// # use scratchstack_aspen::{Decision, TestSuite};
+/// let suite = TestSuite::from_json(r#"{
+///     "Name": "s3-read",
+///     "Policy": {"Statement": {"Effect": "Allow", "Action": "s3:Get*", "Resource": "arn:aws:s3:::bucket/*"}},
+///     "Cases": [
+///         {"Name": "get", "Context": {"Service": "s3", "Api": "GetObject", "Resources": ["arn:aws:s3:::bucket/k"]},
+///          "Expect": "Allow"},
+///         {"Name": "put", "Context": {"Service": "s3", "Api": "PutObject", "Resources": ["arn:aws:s3:::bucket/k"]},
+///          "Expect": "DefaultDeny"}
+///     ]
+/// }"#).unwrap();
+/// let report = suite.run();
+/// assert!(report.passed());
+/// assert_eq!(report.to_string(), "s3-read: 2 passed, 0 failed\n  PASS get\n  PASS put");
+/// ```
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct TestSuite {
+    name: String,
+    policy_set: PolicySet,
+    cases: Vec<TestCase>,
+}
+
+impl TestSuite {
+    /// Creates a new test suite.
+    pub fn new<S: Into<String>>(name: S, policy_set: PolicySet, cases: Vec<TestCase>) -> Self {
+        Self {
+            name: name.into(),
+            policy_set,
+            cases,
+        }
+    }
+
+    /// Parses a test suite from JSON. A `PolicyFile` is resolved relative to the current directory.
+    ///
+    /// # Errors
+    ///
+    /// If the JSON is malformed, or the policies are missing or cannot be loaded, [AspenError::InvalidTestSuite] is
+    /// returned.
+    pub fn from_json(json: &str) -> Result<Self, AspenError> {
+        Self::parse(json, "test suite", Path::new(""))
+    }
+
+    /// Loads a test suite from a file. A `PolicyFile` is resolved relative to the directory containing the suite,
+    /// and the suite is named after the file if it has no `Name`.
+    ///
+    /// # Errors
+    ///
+    /// If the file cannot be read or parsed, or the policies are missing or cannot be loaded,
+    /// [AspenError::InvalidTestSuite] is returned.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, AspenError> {
+        let path = path.as_ref();
+        let json = fs::read_to_string(path).map_err(|e| invalid(path, e))?;
+        let base_dir = path.parent().unwrap_or_else(|| Path::new(""));
+        Self::parse(&json, &path.display().to_string(), base_dir)
+            .map_err(|e| AspenError::InvalidTestSuite(format!("{}: {}", path.display(), invalid_message(e))))
+    }
+
+    /// Loads every test suite (files ending in [TEST_SUITE_SUFFIX]) found under a directory, recursively, in path
+    /// order.
+    ///
+    /// # Errors
+    ///
+    /// If the directory cannot be read or any suite fails to load, [AspenError::InvalidTestSuite] is returned.
+    pub fn load_dir<P: AsRef<Path>>(dir: P) -> Result<Vec<Self>, AspenError> {
+        let mut paths = Vec::new();
+        find_suites(dir.as_ref(), &mut paths)?;
+        paths.sort();
+        paths.iter().map(Self::from_file).collect()
+    }
+
+    fn parse(json: &str, default_name: &str, base_dir: &Path) -> Result<Self, AspenError> {
+        let repr: TestSuiteRepr =
+            serde_json::from_str(json).map_err(|e| AspenError::InvalidTestSuite(e.to_string()))?;
+        let name = repr.name.unwrap_or_else(|| default_name.to_string());
+
+        let policy = match (repr.policy, repr.policy_file) {
+            (Some(_), Some(_)) => return Err(AspenError::InvalidTestSuite("both Policy and PolicyFile given".into())),
+            (Some(policy), None) => Some((policy, "Policy".to_string())),
+            (None, Some(policy_file)) => {
+                let path = base_dir.join(&policy_file);
+                let json = fs::read_to_string(&path).map_err(|e| invalid(&path, e))?;
+                Some((serde_json::from_str(&json).map_err(|e| invalid(&path, e))?, policy_file.display().to_string()))
+            }
+            (None, None) => None,
+        };
+
+        let policy_set = match (policy, repr.policy_set) {
+            (Some((policy, policy_name)), None) => {
+                let source = repr.source.unwrap_or_else(|| PolicySource::new_standalone(policy_name));
+                PolicySet::from(vec![(source, policy)])
+            }
+            (None, Some(policy_set)) if repr.source.is_none() => policy_set,
+            (None, Some(_)) => return Err(AspenError::InvalidTestSuite("Source given with PolicySet".into())),
+            (Some(_), Some(_)) => return Err(AspenError::InvalidTestSuite("both Policy and PolicySet given".into())),
+            (None, None) => {
+                return Err(AspenError::InvalidTestSuite("missing Policy, PolicyFile, or PolicySet".into()))
+            }
+        };
+
+        Ok(Self {
+            name,
+            policy_set,
+            cases: repr.cases,
+        })
+    }
+
+    /// Returns the name of the test suite.
+    #[inline]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the policies under test.
+    #[inline]
+    pub fn policy_set(&self) -> &PolicySet {
+        &self.policy_set
+    }
+
+    /// Returns the test cases.
+    #[inline]
+    pub fn cases(&self) -> &[TestCase] {
+        &self.cases
+    }
+
+    /// Evaluates every test case against the policy set.
+    pub fn run(&self) -> TestReport {
+        let results = self
+            .cases
+            .iter()
+            .map(|case| {
+                let (actual, reasons) = match self.policy_set.explain(case.context()) {
+                    Ok((decision, reasons)) => (Ok(decision), reasons),
+                    Err(e) => (Err(e), Vec::new()),
+                };
+
+                TestResult {
+                    name: case.name.clone(),
+                    expected: case.expect,
+                    actual,
+                    reasons,
+                }
+            })
+            .collect();
+
+        TestReport {
+            suite: self.name.clone(),
+            results,
+        }
+    }
+}
+
+/// The outcome of a single [TestCase].
+#[derive(Debug, Eq, PartialEq)]
+pub struct TestResult {
+    name: String,
+    expected: Decision,
+    actual: Result<Decision, AspenError>,
+    reasons: Vec<DecisionReason>,
+}
+
+impl TestResult {
+    /// Returns the name of the test case.
+    #[inline]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the expected decision.
+    #[inline]
+    pub fn expected(&self) -> Decision {
+        self.expected
+    }
+
+    /// Returns the decision made by the policy set, or the error encountered while evaluating it.
+    #[inline]
+    pub fn actual(&self) -> &Result<Decision, AspenError> {
+        &self.actual
+    }
+
+    /// Returns the statements responsible for the actual decision.
+    #[inline]
+    pub fn reasons(&self) -> &[DecisionReason] {
+        &self.reasons
+    }
+
+    /// Indicates whether the actual decision matches the expected decision.
+    #[inline]
+    pub fn passed(&self) -> bool {
+        self.actual == Ok(self.expected)
+    }
+}
+
+impl Display for TestResult {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        if self.passed() {
+            return write!(f, "PASS {}", self.name);
+        }
+
+        match &self.actual {
+            Ok(actual) => write!(f, "FAIL {}: expected {}, got {actual}", self.name, self.expected)?,
+            Err(e) => write!(f, "FAIL {}: expected {}, got error: {e}", self.name, self.expected)?,
+        }
+
+        if self.actual == Ok(Decision::DefaultDeny) {
+            f.write_str("\n    no statement allows the request")?;
+        }
+
+        for reason in self.reasons.iter() {
+            write!(f, "\n    {reason}")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// The results of running a [TestSuite].
+#[derive(Debug, Eq, PartialEq)]
+pub struct TestReport {
+    suite: String,
+    results: Vec<TestResult>,
+}
+
+impl TestReport {
+    /// Returns the name of the test suite.
+    #[inline]
+    pub fn suite(&self) -> &str {
+        &self.suite
+    }
+
+    /// Returns the result of each test case, in order.
+    #[inline]
+    pub fn results(&self) -> &[TestResult] {
+        &self.results
+    }
+
+    /// Returns the test cases that failed.
+    pub fn failures(&self) -> impl Iterator<Item = &TestResult> {
+        self.results.iter().filter(|result| !result.passed())
+    }
+
+    /// Indicates whether every test case passed.
+    pub fn passed(&self) -> bool {
+        self.results.iter().all(TestResult::passed)
+    }
+}
+
+impl Display for TestReport {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        let failed = self.failures().count();
+        write!(f, "{}: {} passed, {failed} failed", self.suite, self.results.len() - failed)?;
+        for result in self.results.iter() {
+            write!(f, "\n  {result}")?;
+        }
+        Ok(())
+    }
+}
+
+fn find_suites(dir: &Path, paths: &mut Vec<PathBuf>) -> Result<(), AspenError> {
+    for entry in fs::read_dir(dir).map_err(|e| invalid(dir, e))? {
+        let path = entry.map_err(|e| invalid(dir, e))?.path();
+        if path.is_dir() {
+            find_suites(&path, paths)?;
+        } else if path.to_str().map(|p| p.ends_with(TEST_SUITE_SUFFIX)).unwrap_or(false) {
+            paths.push(path);
+        }
+    }
+
+    Ok(())
+}
+
+fn invalid<E: Display>(path: &Path, e: E) -> AspenError {
+    AspenError::InvalidTestSuite(format!("{}: {e}", path.display()))
+}
+
+/// Returns the message of an [AspenError::InvalidTestSuite] error, or the display form of any other error.
+fn invalid_message(e: AspenError) -> String {
+    match e {
+        AspenError::InvalidTestSuite(msg) => msg,
+        e => e.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        crate::{AspenError, Context, Decision, PolicySet, TestCase, TestSuite},
+        pretty_assertions::assert_eq,
+        std::{env, fs, str::FromStr},
+    };
+
+    #[test_log::test]
+    fn test_run() {
+        let suite = TestSuite::from_json(
+            r#"{
+                "Name": "boundary",
+                "PolicySet": [
+                    {"Source": {"Type": "EntityInline", "EntityArn": "arn:aws:iam::123456789012:user/alice",
+                                "EntityId": "AIDAEXAMPLE", "PolicyName": "admin"},
+                     "Policy": {"Statement": {"Sid": "Admin", "Effect": "Allow", "Action": "*", "Resource": "*"}}},
+                    {"Source": {"Type": "PermissionBoundary", "PolicyArn": "arn:aws:iam::123456789012:policy/b",
+                                "PolicyId": "ANPAEXAMPLE", "Version": "v1"},
+                     "Policy": {"Statement": {"Effect": "Allow", "Action": "s3:*", "Resource": "*"}}}
+                ],
+                "Cases": [
+                    {"Name": "s3 is allowed", "Context": {"Service": "s3", "Api": "ListBuckets"}, "Expect": "Allow"},
+                    {"Name": "iam is allowed", "Context": {"Service": "iam", "Api": "CreateUser"}, "Expect": "Allow"}
+                ]
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(suite.name(), "boundary");
+        assert_eq!(suite.cases()[1].expect(), Decision::Allow);
+
+        let report = suite.run();
+        assert!(!report.passed());
+        assert_eq!(report.failures().count(), 1);
+        assert_eq!(report.results()[1].actual(), &Ok(Decision::Deny));
+        assert_eq!(
+            report.to_string(),
+            "boundary: 1 passed, 1 failed\n  PASS s3 is allowed\n  FAIL iam is allowed: expected Allow, got Deny\n    \
+             no statement of permissions boundary arn:aws:iam::123456789012:policy/b (v1) allows the request"
+        );
+
+        let context = Context::from_str(r#"{"Service": "s3", "Api": "GetObject"}"#).unwrap();
+        let suite =
+            TestSuite::new("empty", PolicySet::new(), vec![TestCase::new("nothing allowed", context, Decision::Allow)]);
+        assert_eq!(
+            suite.run().to_string(),
+            "empty: 0 passed, 1 failed\n  FAIL nothing allowed: expected Allow, got DefaultDeny\n    \
+             no statement allows the request"
+        );
+
+        assert!(TestSuite::from_json(r#"{"Cases": []}"#).is_err());
+        assert!(TestSuite::from_json(r#"{"Policy": {"Statement": []}, "PolicySet": [], "Cases": []}"#).is_err());
+    }
+
+    #[test_log::test]
+    fn test_load_dir() {
+        let dir = env::temp_dir().join(format!("aspen-testsuite-{}", std::process::id()));
+        let nested = dir.join("s3");
+        fs::create_dir_all(&nested).unwrap();
+        fs::write(
+            nested.join("read.json"),
+            r#"{"Statement": {"Effect": "Allow", "Action": "s3:Get*", "Resource": "arn:aws:s3:::bucket/*"}}"#,
+        )
+        .unwrap();
+        fs::write(
+            nested.join("read.test.json"),
+            r#"{"PolicyFile": "read.json", "Cases": [{"Name": "get",
+                "Context": {"Service": "s3", "Api": "GetObject", "Resources": ["arn:aws:s3:::bucket/key"]},
+                "Expect": "Allow"}]}"#,
+        )
+        .unwrap();
+        fs::write(
+            dir.join("deny.test.json"),
+            r#"{"Name": "deny", "Policy": {"Statement": {"Effect": "Deny", "Action": "*", "Resource": "*"}},
+                "Cases": [{"Name": "denied", "Context": {"Service": "ec2", "Api": "RunInstances"}, "Expect": "Deny"}]}"#,
+        )
+        .unwrap();
+
+        let suites = TestSuite::load_dir(&dir).unwrap();
+        assert_eq!(suites.len(), 2);
+        assert_eq!(suites[0].name(), "deny");
+        assert!(suites[1].name().ends_with("read.test.json"));
+        assert!(suites.iter().all(|suite| suite.run().passed()));
+
+        fs::write(nested.join("broken.test.json"), r#"{"PolicyFile": "missing.json", "Cases": []}"#).unwrap();
+        match TestSuite::load_dir(&dir) {
+            Err(AspenError::InvalidTestSuite(msg)) => {
+                assert!(msg.starts_with(&nested.join("broken.test.json").display().to_string()));
+                assert!(msg.contains("missing.json"));
+            }
+            other => panic!("unexpected result: {other:?}"),
+        }
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,157 @@
+//! Tests for the `aspen` and `aspen-test` command-line tools: exit statuses and the shape of `--json` output.
+
+use {
+    pretty_assertions::assert_eq,
+    serde_json::{json, Value},
+    std::{
+        env, fs,
+        path::{Path, PathBuf},
+        process::{Command, Output},
+    },
+};
+
+/// The exit status when a check fails.
+const EXIT_CHECK_FAILED: i32 = 1;
+
+/// The exit status on usage or I/O errors.
+const EXIT_ERROR: i32 = 2;
+
+const READ_POLICY: &str =
+    r#"{"Version": "2012-10-17", "Statement": {"Effect": "Allow", "Action": "s3:GetObject", "Resource": "*"}}"#;
+
+const BAD_POLICY: &str = r#"{
+    "Version": "2012-10-17",
+    "Statement": [{"Effect": "Maybe", "Action": "s3", "Resource": "*"}],
+    "Bogus": 1
+}"#;
+
+const GET_OBJECT: &str = r#"{"Service": "s3", "Api": "GetObject", "Resources": ["arn:aws:s3:::bucket/key"]}"#;
+
+const PUT_OBJECT: &str = r#"{"Service": "s3", "Api": "PutObject", "Resources": ["arn:aws:s3:::bucket/key"]}"#;
+
+/// Creates an empty scratch directory for a test.
+fn scratch_dir(name: &str) -> PathBuf {
+    let dir = env::temp_dir().join(format!("aspen-cli-{name}-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn run(program: &str, dir: &Path, args: &[&str]) -> Output {
+    Command::new(program).current_dir(dir).args(args).output().unwrap()
+}
+
+fn aspen(dir: &Path, args: &[&str]) -> Output {
+    run(env!("CARGO_BIN_EXE_aspen"), dir, args)
+}
+
+fn aspen_test(dir: &Path, args: &[&str]) -> Output {
+    run(env!("CARGO_BIN_EXE_aspen-test"), dir, args)
+}
+
+fn stdout_json(output: &Output) -> Value {
+    serde_json::from_slice(&output.stdout)
+        .unwrap_or_else(|e| panic!("{e}: {}", String::from_utf8_lossy(&output.stdout)))
+}
+
+#[test]
+fn test_validate() {
+    let dir = scratch_dir("validate");
+    fs::write(dir.join("read.json"), READ_POLICY).unwrap();
+    fs::write(dir.join("bad.json"), BAD_POLICY).unwrap();
+
+    let output = aspen(&dir, &["--json", "validate", "read.json"]);
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(stdout_json(&output), json!([{"File": "read.json", "Valid": true, "Findings": []}]));
+
+    // Every parse error is reported with its location.
+    let output = aspen(&dir, &["--json", "validate", "read.json", "bad.json"]);
+    assert_eq!(output.status.code(), Some(EXIT_CHECK_FAILED));
+    let results = stdout_json(&output);
+    assert_eq!(results[0]["Valid"], json!(true));
+    assert_eq!(results[1]["File"], json!("bad.json"));
+    assert_eq!(results[1]["Valid"], json!(false));
+    let errors = results[1]["Errors"].as_array().unwrap();
+    assert_eq!(
+        errors.iter().map(|e| (e["Kind"].clone(), e["Pointer"].clone(), e["Line"].clone())).collect::<Vec<_>>(),
+        vec![
+            (json!("InvalidEffect"), json!("/Statement/0/Effect"), json!(3)),
+            (json!("InvalidAction"), json!("/Statement/0/Action"), json!(3)),
+            (json!("UnknownField"), json!("/Bogus"), json!(4)),
+        ]
+    );
+    assert_eq!(errors[0]["Value"], json!("Maybe"));
+    assert_eq!(errors[0]["Column"], json!(30));
+    assert!(errors[0]["Message"].as_str().unwrap().contains("Maybe"));
+
+    let output = aspen(&dir, &["validate", "bad.json"]);
+    assert_eq!(output.status.code(), Some(EXIT_CHECK_FAILED));
+    let text = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(text.lines().count(), 3, "{text}");
+    assert!(text.starts_with("bad.json: invalid policy: /Statement/0/Effect: "), "{text}");
+
+    let output = aspen(&dir, &["--json", "validate", "missing.json"]);
+    assert_eq!(output.status.code(), Some(EXIT_ERROR));
+    assert!(stdout_json(&output)["Error"].as_str().unwrap().starts_with("missing.json: "));
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_eval() {
+    let dir = scratch_dir("eval");
+    fs::write(dir.join("read.json"), READ_POLICY).unwrap();
+    fs::write(dir.join("get.json"), GET_OBJECT).unwrap();
+    fs::write(dir.join("put.json"), PUT_OBJECT).unwrap();
+
+    // Standalone policies are attributed to the same entity as in test suites.
+    let output = aspen(&dir, &["--json", "explain", "--context", "get.json", "read.json"]);
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(
+        stdout_json(&output),
+        json!({
+            "Decision": "Allow",
+            "Reasons": [{
+                "PolicyIndex": 0,
+                "StatementIndex": 0,
+                "Sid": null,
+                "Source": {
+                    "Type": "EntityInline",
+                    "EntityArn": scratchstack_aspen::STANDALONE_ENTITY_ARN,
+                    "EntityId": "",
+                    "PolicyName": "read.json",
+                },
+            }],
+        })
+    );
+
+    let output = aspen(&dir, &["--json", "eval", "--context", "put.json", "read.json"]);
+    assert_eq!(output.status.code(), Some(EXIT_CHECK_FAILED));
+    assert_eq!(stdout_json(&output), json!({"Decision": "DefaultDeny"}));
+
+    let output = aspen(&dir, &["eval", "read.json"]);
+    assert_eq!(output.status.code(), Some(EXIT_ERROR));
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_aspen_test() {
+    let dir = scratch_dir("aspen-test");
+    fs::write(dir.join("read.json"), READ_POLICY).unwrap();
+    let suite = |expect: &str| {
+        format!(
+            r#"{{"PolicyFile": "read.json", "Cases": [{{"Name": "get", "Context": {GET_OBJECT}, "Expect": "{expect}"}}]}}"#
+        )
+    };
+    fs::write(dir.join("pass.test.json"), suite("Allow")).unwrap();
+    fs::write(dir.join("fail.test.json"), suite("Deny")).unwrap();
+
+    assert_eq!(aspen_test(&dir, &["pass.test.json"]).status.code(), Some(0));
+    assert_eq!(aspen_test(&dir, &["fail.test.json"]).status.code(), Some(EXIT_CHECK_FAILED));
+    assert_eq!(aspen_test(&dir, &["."]).status.code(), Some(EXIT_CHECK_FAILED));
+    assert_eq!(aspen_test(&dir, &["missing.test.json"]).status.code(), Some(EXIT_ERROR));
+    assert_eq!(aspen_test(&dir, &[]).status.code(), Some(EXIT_ERROR));
+
+    fs::remove_dir_all(&dir).unwrap();
+}